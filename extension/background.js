@@ -16,8 +16,8 @@ function createContextMenu() {
 }
 
 function createContextMenuIfLoggedIn() {
-  chrome.storage.local.get(["user_token"], (result) => {
-    if (result.user_token) {
+  chrome.storage.local.get(["api_key"], (result) => {
+    if (result.api_key) {
       createContextMenu();
     }
   });
@@ -25,8 +25,8 @@ function createContextMenuIfLoggedIn() {
 
 chrome.contextMenus.onClicked.addListener((info, tab) => {
   if (info.menuItemId === "find-sticker" && info.selectionText) {
-    chrome.storage.local.get(["user_token", "username"], (result) => {
-      const { user_token: token, username } = result;
+    chrome.storage.local.get(["api_key", "username"], (result) => {
+      const { api_key: token, username } = result;
 
       if (!token || !username) {
         chrome.notifications.create({
@@ -44,7 +44,7 @@ chrome.contextMenus.onClicked.addListener((info, tab) => {
           "Content-Type": "application/json",
           "Authorization": `Bearer ${token}`
         },
-        body: JSON.stringify({ input_text: info.selectionText, username })
+        body: JSON.stringify({ input_text: info.selectionText })
       })
         .then((res) => {
           if (!res.ok) {
@@ -84,9 +84,9 @@ chrome.contextMenus.onClicked.addListener((info, tab) => {
 });
 
 chrome.storage.onChanged.addListener((changes, namespace) => {
-  if (namespace === "local" && changes.user_token) {
+  if (namespace === "local" && changes.api_key) {
     chrome.contextMenus.removeAll(() => {
-      if (changes.user_token.newValue) {
+      if (changes.api_key.newValue) {
         createContextMenu();
       }
     });
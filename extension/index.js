@@ -5,11 +5,65 @@ document.addEventListener("DOMContentLoaded", () => {
   const signupForm = document.getElementById("signup-form");
   const loginMsg = document.getElementById("login-msg");
   const logoutBtn = document.getElementById("logout-btn");
+  const mfaForm = document.getElementById("mfa-form");
+  let mfaToken = null; // set while a 2FA login waits for its code
+
+  // Every install signs in with the same key name, so a new login replaces the old key
+  // instead of piling up keys until the per-user limit is hit
+  const EXTENSION_KEY_NAME = "Browser extension";
+
+  async function revokeOldExtensionKeys(token) {
+    const res = await fetch(`${API_BASE_URL}/v1/user/api-keys`, {
+      headers: { "Authorization": `Bearer ${token}` },
+    });
+    if (!res.ok) return;
+    const data = await res.json();
+    const stale = data.api_keys.filter((key) => key.name === EXTENSION_KEY_NAME && !key.revoked_at);
+    await Promise.all(
+      stale.map((key) =>
+        fetch(`${API_BASE_URL}/v1/user/api-keys/${key.id}`, {
+          method: "DELETE",
+          headers: { "Authorization": `Bearer ${token}` },
+        })
+      )
+    );
+  }
+
+  // Swap the login JWT for a key that can only find stickers, then end the login session,
+  // so the extension is left holding nothing but the narrow key
+  async function storeApiKey(token, username) {
+    try {
+      await revokeOldExtensionKeys(token);
+      const res = await fetch(`${API_BASE_URL}/v1/user/api-keys`, {
+        method: "POST",
+        headers: {
+          "Content-Type": "application/json",
+          "Authorization": `Bearer ${token}`,
+        },
+        body: JSON.stringify({ name: EXTENSION_KEY_NAME, scopes: ["sticker:find"] }),
+      });
+      const data = await res.json();
+      if (!res.ok) {
+        throw new Error(data.error || "Could not create API key");
+      }
+      chrome.storage.local.set({ api_key: data.key, api_key_id: data.api_key.id, username }, () => {
+        updateUI(data.key, username);
+        chrome.runtime.sendMessage({ action: "updateMenu" });
+      });
+    } finally {
+      // Best effort: the session expires on its own if this fails
+      fetch(`${API_BASE_URL}/v1/user/logout`, {
+        method: "POST",
+        headers: { "Authorization": `Bearer ${token}` },
+      }).catch((err) => console.warn("Failed to end login session", err));
+    }
+  }
 
   function updateUI(token, username) {
     if (token) {
       if (loginForm) loginForm.style.display = "none";
       if (signupForm) signupForm.style.display = "none";
+      if (mfaForm) mfaForm.style.display = "none";
       if (loginMsg) loginMsg.innerText = `✅ Logged in as ${username || "User"}`;
       if (logoutBtn) logoutBtn.style.display = "block";
     } else {
@@ -20,8 +74,8 @@ document.addEventListener("DOMContentLoaded", () => {
     }
   }
 
-  chrome.storage.local.get(["user_token", "username"], (result) => {
-    updateUI(result.user_token, result.username);
+  chrome.storage.local.get(["api_key", "username"], (result) => {
+    updateUI(result.api_key, result.username);
   });
 
   if (loginForm) {
@@ -38,11 +92,14 @@ document.addEventListener("DOMContentLoaded", () => {
           body: JSON.stringify({ username, password }),
         });
         const data = await res.json();
-        if (res.ok) {
-          chrome.storage.local.set({ user_token: data.token, username: data.username }, () => {
-            updateUI(data.token, data.username);
-            chrome.runtime.sendMessage({ action: "updateMenu" });
-          });
+        if (res.ok && data.mfa_required) {
+          // Second step: the password was right, now the 2FA code
+          mfaToken = data.mfa_token;
+          loginForm.style.display = "none";
+          if (mfaForm) mfaForm.style.display = "block";
+          if (loginMsg) loginMsg.innerText = "Enter the code from your authenticator app or a recovery code";
+        } else if (res.ok) {
+          await storeApiKey(data.token, data.username);
         } else {
           if (loginMsg) loginMsg.innerText = `❌ ${data.error || "Invalid credentials"}`;
         }
@@ -52,6 +109,37 @@ document.addEventListener("DOMContentLoaded", () => {
     });
   }
 
+  if (mfaForm) {
+    mfaForm.addEventListener("submit", async (e) => {
+      e.preventDefault();
+      const code = document.getElementById("mfa-code").value.trim();
+      if (loginMsg) loginMsg.innerText = "Verifying...";
+
+      try {
+        const res = await fetch(`${API_BASE_URL}/v1/auth/login/mfa`, {
+          method: "POST",
+          headers: { "Content-Type": "application/json" },
+          body: JSON.stringify({ mfa_token: mfaToken, code }),
+        });
+        const data = await res.json();
+        if (res.ok) {
+          mfaToken = null;
+          await storeApiKey(data.token, data.username);
+        } else if (res.status === 401 && data.error === "Invalid code") {
+          if (loginMsg) loginMsg.innerText = "❌ Invalid code, try again";
+        } else {
+          // The login expired or ran out of attempts: start over from the password
+          mfaToken = null;
+          mfaForm.style.display = "none";
+          loginForm.style.display = "block";
+          if (loginMsg) loginMsg.innerText = `❌ ${data.error || "Login failed"}`;
+        }
+      } catch (err) {
+        if (loginMsg) loginMsg.innerText = `❌ Network error: ${err.message}`;
+      }
+    });
+  }
+
   if (signupForm) {
     signupForm.addEventListener("submit", async (e) => {
       e.preventDefault();
@@ -67,10 +155,7 @@ document.addEventListener("DOMContentLoaded", () => {
         });
         const data = await res.json();
         if (res.ok) {
-          chrome.storage.local.set({ user_token: data.token, username: data.username }, () => {
-            updateUI(data.token, data.username);
-            chrome.runtime.sendMessage({ action: "updateMenu" });
-          });
+          await storeApiKey(data.token, data.username);
         } else {
           if (loginMsg) loginMsg.innerText = `❌ ${data.error || "Signup failed"}`;
         }
@@ -82,9 +167,22 @@ document.addEventListener("DOMContentLoaded", () => {
 
   if (logoutBtn) {
     logoutBtn.addEventListener("click", () => {
-      chrome.storage.local.remove(["user_token", "username"], () => {
-        updateUI(null, null);
-        chrome.runtime.sendMessage({ action: "updateMenu" });
+      chrome.storage.local.get(["api_key", "api_key_id"], async (result) => {
+        // Best effort: a key may always revoke itself
+        if (result.api_key && result.api_key_id) {
+          try {
+            await fetch(`${API_BASE_URL}/v1/user/api-keys/${result.api_key_id}`, {
+              method: "DELETE",
+              headers: { "Authorization": `Bearer ${result.api_key}` },
+            });
+          } catch (err) {
+            console.warn("Failed to revoke API key", err);
+          }
+        }
+        chrome.storage.local.remove(["api_key", "api_key_id", "username"], () => {
+          updateUI(null, null);
+          chrome.runtime.sendMessage({ action: "updateMenu" });
+        });
       });
     });
   }
@@ -0,0 +1,61 @@
+use crate::client::StickerClient;
+use crate::error::Error;
+use reqwest::Method;
+use serde::Deserialize;
+use sticker_types::{LoginRequest, MfaChallengeResponse, MfaLoginRequest, RegisterRequest, SessionResponse};
+
+// Answer of POST /v1/auth/login/user
+#[derive(Debug, Clone, Deserialize)]
+#[serde(untagged)]
+pub enum LoginOutcome {
+    LoggedIn(SessionResponse),
+    MfaRequired(MfaChallengeResponse), // finish with `login_mfa`
+}
+
+impl StickerClient {
+    // Signs in and keeps the session token for the following calls
+    pub async fn login(&self, username: &str, password: &str) -> Result<LoginOutcome, Error> {
+        let body = LoginRequest {
+            username: username.to_string(),
+            password: password.to_string(),
+        };
+        let outcome: LoginOutcome = self
+            .send_json(self.request(Method::POST, "/v1/auth/login/user").json(&body))
+            .await?;
+        if let LoginOutcome::LoggedIn(session) = &outcome {
+            self.set_token(Some(session.token.clone()));
+        }
+        Ok(outcome)
+    }
+
+    // Second step of a 2FA login; `code` is a TOTP code or a recovery code
+    pub async fn login_mfa(&self, mfa_token: &str, code: &str) -> Result<SessionResponse, Error> {
+        let body = MfaLoginRequest {
+            mfa_token: mfa_token.to_string(),
+            code: code.to_string(),
+        };
+        let session: SessionResponse = self
+            .send_json(self.request(Method::POST, "/v1/auth/login/mfa").json(&body))
+            .await?;
+        self.set_token(Some(session.token.clone()));
+        Ok(session)
+    }
+
+    // Creates the account and signs in as it
+    pub async fn register(&self, request: &RegisterRequest) -> Result<SessionResponse, Error> {
+        let session: SessionResponse = self
+            .send_json(self.request(Method::POST, "/v1/auth/register/user").json(request))
+            .await?;
+        self.set_token(Some(session.token.clone()));
+        Ok(session)
+    }
+
+    // Forgets the token locally; the server-side session stays valid until it expires
+    pub fn logout(&self) {
+        self.set_token(None);
+    }
+
+    pub async fn health(&self) -> Result<(), Error> {
+        self.send(self.request(Method::GET, "/v1/health")).await.map(|_| ())
+    }
+}
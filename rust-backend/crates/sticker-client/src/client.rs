@@ -0,0 +1,319 @@
+use crate::error::{ApiError, Error};
+use crate::retry::RetryPolicy;
+use reqwest::header::RETRY_AFTER;
+use reqwest::{Method, RequestBuilder, Response, StatusCode, Url};
+use serde::de::DeserializeOwned;
+use std::sync::{Arc, RwLock};
+use std::time::Duration;
+
+const DEFAULT_TIMEOUT: Duration = Duration::from_secs(30);
+
+// Cheap to clone; clones share the connection pool and the token, so a login through one
+// is seen by all of them
+#[derive(Clone)]
+pub struct StickerClient {
+    http: reqwest::Client,
+    base_url: String,
+    token: Arc<RwLock<Option<String>>>,
+    retry: RetryPolicy,
+}
+
+impl StickerClient {
+    // `base_url` is where the server is mounted, e.g. "https://stickers.example.com"
+    pub fn new(base_url: &str) -> Result<Self, Error> {
+        let invalid = || Error::InvalidBaseUrl(base_url.to_string());
+        let url = Url::parse(base_url).map_err(|_| invalid())?;
+        if !matches!(url.scheme(), "http" | "https") || url.query().is_some() {
+            return Err(invalid());
+        }
+        Ok(Self {
+            http: reqwest::Client::builder().timeout(DEFAULT_TIMEOUT).build()?,
+            base_url: base_url.trim_end_matches('/').to_string(),
+            token: Arc::new(RwLock::new(None)),
+            retry: RetryPolicy::default(),
+        })
+    }
+
+    // For custom timeouts, proxies or TLS roots
+    pub fn with_http_client(mut self, http: reqwest::Client) -> Self {
+        self.http = http;
+        self
+    }
+
+    pub fn with_retry(mut self, retry: RetryPolicy) -> Self {
+        self.retry = retry;
+        self
+    }
+
+    // A session token or an API key; both are sent as bearer tokens
+    pub fn with_token(self, token: impl Into<String>) -> Self {
+        self.set_token(Some(token.into()));
+        self
+    }
+
+    pub fn set_token(&self, token: Option<String>) {
+        *self.token.write().unwrap_or_else(|e| e.into_inner()) = token;
+    }
+
+    pub fn token(&self) -> Option<String> {
+        self.token.read().unwrap_or_else(|e| e.into_inner()).clone()
+    }
+
+    pub(crate) fn request(&self, method: Method, path: &str) -> RequestBuilder {
+        let request = self.http.request(method, format!("{}{}", self.base_url, path));
+        match self.token() {
+            Some(token) => request.bearer_auth(token),
+            None => request,
+        }
+    }
+
+    pub(crate) async fn send_json<T: DeserializeOwned>(&self, request: RequestBuilder) -> Result<T, Error> {
+        Ok(self.send(request).await?.json().await?)
+    }
+
+    // Sends the request, retrying as the policy allows; non-2xx answers become Error::Api
+    pub(crate) async fn send(&self, request: RequestBuilder) -> Result<Response, Error> {
+        let request = request.build()?;
+        let repeatable = request.method() != Method::POST;
+        let mut attempt = 0;
+        loop {
+            // Bodies are always buffered JSON, so the request can be cloned
+            let this_try = request.try_clone().expect("request body is buffered");
+            let delay = match self.http.execute(this_try).await {
+                Ok(response) if response.status().is_success() => return Ok(response),
+                Ok(response) => {
+                    let status = response.status();
+                    let retry_after = retry_after(&response);
+                    let delay = match status {
+                        StatusCode::TOO_MANY_REQUESTS => Some(retry_after.unwrap_or_else(|| self.retry.backoff(attempt))),
+                        StatusCode::BAD_GATEWAY | StatusCode::SERVICE_UNAVAILABLE | StatusCode::GATEWAY_TIMEOUT
+                            if repeatable =>
+                        {
+                            Some(self.retry.backoff(attempt))
+                        }
+                        _ => None,
+                    };
+                    match delay.filter(|delay| attempt < self.retry.max_retries && *delay <= self.retry.max_delay) {
+                        Some(delay) => delay,
+                        None => {
+                            let body = response.text().await.unwrap_or_default();
+                            return Err(Error::Api(ApiError::new(status, retry_after, &body)));
+                        }
+                    }
+                }
+                // A failed connect never reached the server; a timeout might have
+                Err(e) if attempt < self.retry.max_retries && (e.is_connect() || (repeatable && e.is_timeout())) => {
+                    self.retry.backoff(attempt)
+                }
+                Err(e) => return Err(e.into()),
+            };
+            attempt += 1;
+            log::debug!("Retrying {} {} in {:?} (retry {})", request.method(), request.url().path(), delay, attempt);
+            tokio::time::sleep(delay).await;
+        }
+    }
+}
+
+// The server sends Retry-After in seconds
+fn retry_after(response: &Response) -> Option<Duration> {
+    let seconds = response.headers().get(RETRY_AFTER)?.to_str().ok()?.trim().parse().ok()?;
+    Some(Duration::from_secs(seconds))
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use serde_json::json;
+    use wiremock::matchers::{header, method, path};
+    use wiremock::{Mock, MockServer, ResponseTemplate};
+
+    fn fast_retries() -> RetryPolicy {
+        RetryPolicy {
+            max_retries: 2,
+            base_delay: Duration::from_millis(1),
+            max_delay: Duration::from_secs(1),
+        }
+    }
+
+    async fn client(server: &MockServer) -> StickerClient {
+        StickerClient::new(&server.uri()).expect("mock server URL").with_retry(fast_retries())
+    }
+
+    fn favorites() -> ResponseTemplate {
+        ResponseTemplate::new(200).set_body_json(json!({ "favorites": [] }))
+    }
+
+    async fn requests(server: &MockServer) -> usize {
+        server.received_requests().await.map(|r| r.len()).unwrap_or_default()
+    }
+
+    #[tokio::test]
+    async fn sends_the_token_as_a_bearer_header() {
+        let server = MockServer::start().await;
+        Mock::given(method("POST"))
+            .and(path("/v1/auth/login/user"))
+            .respond_with(ResponseTemplate::new(200).set_body_json(json!({ "token": "session-1", "username": "alice" })))
+            .mount(&server)
+            .await;
+        Mock::given(method("GET"))
+            .and(path("/v1/user/favorites"))
+            .and(header("authorization", "Bearer session-1"))
+            .respond_with(favorites())
+            .expect(1)
+            .mount(&server)
+            .await;
+        Mock::given(method("GET"))
+            .and(path("/v1/user/favorites"))
+            .and(header("authorization", "Bearer stk_key"))
+            .respond_with(favorites())
+            .expect(1)
+            .mount(&server)
+            .await;
+
+        let client = client(&server).await;
+        assert_eq!(client.favorites().await.unwrap_err().status(), Some(StatusCode::NOT_FOUND));
+        client.login("alice", "password").await.expect("login");
+        client.clone().favorites().await.expect("token shared by clones");
+        client.clone().with_token("stk_key").favorites().await.expect("API key");
+        client.logout();
+        assert!(client.favorites().await.is_err());
+    }
+
+    #[tokio::test]
+    async fn waits_out_429s_for_any_method() {
+        let server = MockServer::start().await;
+        Mock::given(method("POST"))
+            .and(path("/v1/sticker/find"))
+            .respond_with(ResponseTemplate::new(429).insert_header("retry-after", "0"))
+            .up_to_n_times(2)
+            .mount(&server)
+            .await;
+        Mock::given(method("POST"))
+            .and(path("/v1/sticker/find"))
+            .respond_with(ResponseTemplate::new(200).set_body_json(json!({
+                "recommendation_id": "00000000-0000-0000-0000-000000000001",
+                "detected_emotion": "joy",
+                "sticker_urls": ["https://stickers.example/1.gif"],
+            })))
+            .mount(&server)
+            .await;
+
+        let found = client(&server).await.find("finally friday").await.expect("third try succeeds");
+        assert_eq!(found.detected_emotion, "joy");
+        assert_eq!(requests(&server).await, 3);
+    }
+
+    #[tokio::test]
+    async fn backs_off_on_gateway_errors_for_repeatable_requests() {
+        let server = MockServer::start().await;
+        Mock::given(method("GET"))
+            .and(path("/v1/user/favorites"))
+            .respond_with(ResponseTemplate::new(503))
+            .up_to_n_times(1)
+            .mount(&server)
+            .await;
+        Mock::given(method("GET"))
+            .and(path("/v1/user/favorites"))
+            .respond_with(ResponseTemplate::new(502))
+            .up_to_n_times(1)
+            .mount(&server)
+            .await;
+        Mock::given(method("GET")).and(path("/v1/user/favorites")).respond_with(favorites()).mount(&server).await;
+        Mock::given(method("POST"))
+            .and(path("/v1/sticker/find"))
+            .respond_with(ResponseTemplate::new(503))
+            .mount(&server)
+            .await;
+
+        let client = client(&server).await;
+        client.favorites().await.expect("third try succeeds");
+        assert_eq!(requests(&server).await, 3);
+
+        // The server may have acted on a POST before the gateway gave up
+        let err = client.find("hello").await.unwrap_err();
+        assert_eq!(err.status(), Some(StatusCode::SERVICE_UNAVAILABLE));
+        assert_eq!(requests(&server).await, 4);
+    }
+
+    #[tokio::test]
+    async fn gives_up_after_the_last_retry() {
+        let server = MockServer::start().await;
+        Mock::given(method("GET"))
+            .and(path("/v1/user/favorites"))
+            .respond_with(ResponseTemplate::new(504))
+            .mount(&server)
+            .await;
+
+        let err = client(&server).await.favorites().await.unwrap_err();
+        assert_eq!(err.status(), Some(StatusCode::GATEWAY_TIMEOUT));
+        assert_eq!(requests(&server).await, 3);
+    }
+
+    #[test]
+    fn backoff_doubles_up_to_the_cap() {
+        let policy = RetryPolicy {
+            max_retries: 5,
+            base_delay: Duration::from_millis(100),
+            max_delay: Duration::from_millis(500),
+        };
+        let delays: Vec<_> = (0..5).map(|attempt| policy.backoff(attempt).as_millis()).collect();
+        assert_eq!(delays, [100, 200, 400, 500, 500]);
+    }
+
+    #[tokio::test]
+    async fn client_errors_are_not_retried() {
+        for status in [400, 401, 403, 404, 409, 500] {
+            let server = MockServer::start().await;
+            Mock::given(method("GET"))
+                .and(path("/v1/user/favorites"))
+                .respond_with(ResponseTemplate::new(status))
+                .mount(&server)
+                .await;
+
+            let err = client(&server).await.favorites().await.unwrap_err();
+            assert_eq!(err.status().map(|s| s.as_u16()), Some(status));
+            assert_eq!(requests(&server).await, 1, "{} was retried", status);
+        }
+    }
+
+    #[tokio::test]
+    async fn a_long_retry_after_is_returned_instead_of_waited() {
+        let server = MockServer::start().await;
+        Mock::given(method("POST"))
+            .and(path("/v1/sticker/find"))
+            .respond_with(
+                ResponseTemplate::new(429)
+                    .insert_header("retry-after", "3600")
+                    .set_body_json(json!({ "error": "Daily quota exceeded", "retry_after": 3600 })),
+            )
+            .mount(&server)
+            .await;
+
+        let Error::Api(err) = client(&server).await.find("hello").await.unwrap_err() else {
+            panic!("expected an API error");
+        };
+        assert_eq!(err.message, "Daily quota exceeded");
+        assert_eq!(err.retry_after, Some(Duration::from_secs(3600)));
+        assert_eq!(requests(&server).await, 1);
+    }
+
+    #[tokio::test]
+    async fn error_bodies_are_decoded() {
+        let server = MockServer::start().await;
+        Mock::given(method("POST"))
+            .and(path("/v1/user/favorites"))
+            .respond_with(ResponseTemplate::new(400).set_body_json(json!({
+                "error": "Invalid request",
+                "fields": { "sticker_url": ["Must be 1-2048 characters"] },
+            })))
+            .mount(&server)
+            .await;
+
+        let Error::Api(err) = client(&server).await.add_favorite("").await.unwrap_err() else {
+            panic!("expected an API error");
+        };
+        assert_eq!(err.status, StatusCode::BAD_REQUEST);
+        assert_eq!(err.message, "Invalid request");
+        assert_eq!(err.fields["sticker_url"], ["Must be 1-2048 characters"]);
+    }
+}
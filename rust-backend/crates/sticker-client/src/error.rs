@@ -0,0 +1,121 @@
+use reqwest::StatusCode;
+use sticker_types::{ErrorResponse, ValidationErrorResponse};
+use std::collections::HashMap;
+use std::fmt;
+use std::time::Duration;
+
+#[derive(Debug)]
+pub enum Error {
+    InvalidBaseUrl(String),
+    Http(reqwest::Error), // connection failures, timeouts and bodies that don't match the types
+    Api(ApiError),
+}
+
+// A non-2xx answer from the server
+#[derive(Debug, Clone)]
+pub struct ApiError {
+    pub status: StatusCode,
+    pub message: String,
+    pub fields: HashMap<String, Vec<String>>, // per-field messages of a 400 validation error
+    pub retry_after: Option<Duration>,        // set on 429s
+}
+
+impl Error {
+    pub fn status(&self) -> Option<StatusCode> {
+        match self {
+            Error::Api(e) => Some(e.status),
+            Error::Http(e) => e.status(),
+            Error::InvalidBaseUrl(_) => None,
+        }
+    }
+
+    // The token or API key is missing, expired or revoked
+    pub fn is_unauthorized(&self) -> bool {
+        self.status() == Some(StatusCode::UNAUTHORIZED)
+    }
+
+    pub fn is_rate_limited(&self) -> bool {
+        self.status() == Some(StatusCode::TOO_MANY_REQUESTS)
+    }
+}
+
+impl fmt::Display for Error {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            Error::InvalidBaseUrl(url) => write!(f, "invalid base URL: {}", url),
+            Error::Http(e) => write!(f, "request failed: {}", e),
+            Error::Api(e) => write!(f, "{}: {}", e.status, e.message),
+        }
+    }
+}
+
+impl std::error::Error for Error {
+    fn source(&self) -> Option<&(dyn std::error::Error + 'static)> {
+        match self {
+            Error::Http(e) => Some(e),
+            _ => None,
+        }
+    }
+}
+
+impl From<reqwest::Error> for Error {
+    fn from(e: reqwest::Error) -> Self {
+        Error::Http(e)
+    }
+}
+
+impl ApiError {
+    // Error bodies are sticker-types' ValidationErrorResponse or ErrorResponse. Anything else,
+    // such as a proxy's HTML page, is kept as text.
+    pub(crate) fn new(status: StatusCode, retry_after: Option<Duration>, body: &str) -> Self {
+        let (message, fields) = if let Ok(invalid) = serde_json::from_str::<ValidationErrorResponse>(body) {
+            (invalid.error, invalid.fields)
+        } else if let Ok(error) = serde_json::from_str::<ErrorResponse>(body) {
+            let message = match error.details {
+                Some(details) => format!("{} ({})", error.error, details),
+                None => error.error,
+            };
+            (message, HashMap::new())
+        } else {
+            let message = serde_json::from_str::<String>(body).unwrap_or_else(|_| body.trim().to_string());
+            (message, HashMap::new())
+        };
+        let message = if message.is_empty() {
+            status.canonical_reason().unwrap_or("request failed").to_string()
+        } else {
+            message
+        };
+        ApiError { status, message, fields, retry_after }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn decode(body: &str) -> ApiError {
+        ApiError::new(StatusCode::BAD_REQUEST, None, body)
+    }
+
+    #[test]
+    fn decodes_the_sticker_types_error_bodies() {
+        let invalid = decode(r#"{"error": "Invalid request", "fields": {"username": ["Must be 3-32 characters"]}}"#);
+        assert_eq!(invalid.message, "Invalid request");
+        assert_eq!(invalid.fields["username"], ["Must be 3-32 characters"]);
+
+        let error = decode(r#"{"error": "Collection already exists"}"#);
+        assert_eq!(error.message, "Collection already exists");
+        assert!(error.fields.is_empty());
+
+        let unparsed = decode(r#"{"error": "Invalid request body", "details": "missing field `password`"}"#);
+        assert_eq!(unparsed.message, "Invalid request body (missing field `password`)");
+    }
+
+    #[test]
+    fn keeps_other_bodies_as_text() {
+        assert_eq!(decode("\"Invalid API key\"").message, "Invalid API key");
+        assert_eq!(decode("<html>Bad gateway</html>\n").message, "<html>Bad gateway</html>");
+        assert_eq!(decode(r#"{"message": "not ours"}"#).message, r#"{"message": "not ours"}"#);
+        assert_eq!(decode("").message, "Bad Request");
+    }
+}
@@ -0,0 +1,27 @@
+//! Typed async client for the sticker recommender API.
+//!
+//! ```no_run
+//! use sticker_client::{LoginOutcome, StickerClient};
+//!
+//! # async fn run() -> Result<(), sticker_client::Error> {
+//! let client = StickerClient::new("http://localhost:8080")?;
+//! if let LoginOutcome::MfaRequired(challenge) = client.login("alice", "hunter2hunter2").await? {
+//!     client.login_mfa(&challenge.mfa_token, "123456").await?;
+//! }
+//! let found = client.find("finally friday").await?;
+//! println!("{}: {:?}", found.detected_emotion, found.sticker_urls);
+//! # Ok(())
+//! # }
+//! ```
+mod auth;
+mod client;
+mod error;
+mod retry;
+mod sticker;
+mod user;
+
+pub use auth::LoginOutcome;
+pub use client::StickerClient;
+pub use error::{ApiError, Error};
+pub use retry::RetryPolicy;
+pub use sticker_types as types;
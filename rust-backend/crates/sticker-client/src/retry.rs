@@ -0,0 +1,31 @@
+use std::time::Duration;
+
+// 429s are retried for every request, since the server didn't act on them. 502-504s,
+// connection failures and timeouts are only retried for requests that are safe to repeat
+// (everything but POST).
+#[derive(Debug, Clone)]
+pub struct RetryPolicy {
+    pub max_retries: u32,
+    pub base_delay: Duration, // doubled after every attempt
+    pub max_delay: Duration,  // a longer Retry-After (e.g. a spent daily quota) is returned as the error
+}
+
+impl Default for RetryPolicy {
+    fn default() -> Self {
+        Self {
+            max_retries: 3,
+            base_delay: Duration::from_millis(250),
+            max_delay: Duration::from_secs(10),
+        }
+    }
+}
+
+impl RetryPolicy {
+    pub fn none() -> Self {
+        Self { max_retries: 0, ..Self::default() }
+    }
+
+    pub(crate) fn backoff(&self, attempt: u32) -> Duration {
+        self.base_delay.saturating_mul(2u32.saturating_pow(attempt)).min(self.max_delay)
+    }
+}
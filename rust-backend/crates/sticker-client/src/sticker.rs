@@ -0,0 +1,54 @@
+use crate::client::StickerClient;
+use crate::error::Error;
+use futures::stream::{self, Stream, TryStreamExt};
+use reqwest::Method;
+use sticker_types::{FeedbackRequest, RecommendRequest, RecommendResponse, TrendingRequest, TrendingResponse};
+
+impl StickerClient {
+    // POST /v1/sticker/find with personalization left on
+    pub async fn find(&self, input_text: &str) -> Result<RecommendResponse, Error> {
+        self.recommend(&RecommendRequest {
+            input_text: input_text.to_string(),
+            personalize: None,
+        })
+        .await
+    }
+
+    pub async fn recommend(&self, request: &RecommendRequest) -> Result<RecommendResponse, Error> {
+        self.send_json(self.request(Method::POST, "/v1/sticker/find").json(request)).await
+    }
+
+    pub async fn dashboard_find(&self, request: &RecommendRequest) -> Result<RecommendResponse, Error> {
+        self.send_json(self.request(Method::POST, "/v1/sticker/dashboard-find").json(request))
+            .await
+    }
+
+    pub async fn trending(&self, request: &TrendingRequest) -> Result<TrendingResponse, Error> {
+        self.send_json(self.request(Method::GET, "/v1/sticker/dashboard-trending").query(request))
+            .await
+    }
+
+    // Every trending sticker URL from `request.offset` on, fetched `request.limit` at a time
+    pub fn trending_stream(&self, request: TrendingRequest) -> impl Stream<Item = Result<String, Error>> + '_ {
+        stream::try_unfold(Some(request), move |next| async move {
+            let Some(request) = next else {
+                return Ok::<_, Error>(None);
+            };
+            let page = self.trending(&request).await?;
+            let offset = page.offset + page.sticker_urls.len();
+            let next = (!page.sticker_urls.is_empty() && offset < page.total).then_some(TrendingRequest {
+                offset: Some(offset),
+                ..request
+            });
+            Ok(Some((page.sticker_urls, next)))
+        })
+        .map_ok(|urls| stream::iter(urls.into_iter().map(Ok)))
+        .try_flatten()
+    }
+
+    pub async fn feedback(&self, request: &FeedbackRequest) -> Result<(), Error> {
+        self.send(self.request(Method::POST, "/v1/sticker/feedback").json(request))
+            .await
+            .map(|_| ())
+    }
+}
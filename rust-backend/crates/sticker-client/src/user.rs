@@ -0,0 +1,107 @@
+use crate::client::StickerClient;
+use crate::error::Error;
+use futures::stream::{self, Stream, TryStreamExt};
+use reqwest::Method;
+use serde::Deserialize;
+use sticker_types::{
+    ClearHistoryRequest, Favorite, FavoriteRequest, HistoryItem, HistoryRequest, HistoryResponse, TopSticker,
+    TopStickerRequest, UpdatePreferencesRequest, UserAnalytics, UserPreferences,
+};
+use uuid::Uuid;
+
+#[derive(Deserialize)]
+struct FavoritesResponse {
+    favorites: Vec<Favorite>,
+}
+
+#[derive(Deserialize)]
+struct ClearHistoryResponse {
+    deleted: u64,
+}
+
+impl StickerClient {
+    // One page; pass `next_cursor` back as `cursor` for the next
+    pub async fn history(&self, request: &HistoryRequest) -> Result<HistoryResponse, Error> {
+        self.send_json(self.request(Method::GET, "/v1/user/history").query(request))
+            .await
+    }
+
+    // Every entry matching the filters, newest first, following the cursor page by page
+    pub fn history_stream(&self, request: HistoryRequest) -> impl Stream<Item = Result<HistoryItem, Error>> + '_ {
+        stream::try_unfold(Some(request), move |next| async move {
+            let Some(request) = next else {
+                return Ok::<_, Error>(None);
+            };
+            let page = self.history(&request).await?;
+            let next = page.next_cursor.map(|cursor| HistoryRequest {
+                cursor: Some(cursor),
+                ..request
+            });
+            Ok(Some((page.history, next)))
+        })
+        .map_ok(|items| stream::iter(items.into_iter().map(Ok)))
+        .try_flatten()
+    }
+
+    pub async fn history_all(&self, request: HistoryRequest) -> Result<Vec<HistoryItem>, Error> {
+        self.history_stream(request).try_collect().await
+    }
+
+    pub async fn delete_history_entry(&self, id: Uuid) -> Result<(), Error> {
+        self.send(self.request(Method::DELETE, &format!("/v1/user/history/{}", id)))
+            .await
+            .map(|_| ())
+    }
+
+    // Returns how many entries were deleted. Use `ClearHistoryRequest::all()` to delete every entry;
+    // a request with neither filters nor `all` is rejected.
+    pub async fn clear_history(&self, request: &ClearHistoryRequest) -> Result<u64, Error> {
+        let cleared: ClearHistoryResponse = self
+            .send_json(self.request(Method::DELETE, "/v1/user/history").json(request))
+            .await?;
+        Ok(cleared.deleted)
+    }
+
+    pub async fn top_stickers(&self, request: &TopStickerRequest) -> Result<Vec<TopSticker>, Error> {
+        self.send_json(self.request(Method::GET, "/v1/user/top-stickers").query(request))
+            .await
+    }
+
+    pub async fn analytics(&self, request: &TopStickerRequest) -> Result<UserAnalytics, Error> {
+        self.send_json(self.request(Method::GET, "/v1/user/analytics").query(request))
+            .await
+    }
+
+    // Newest first
+    pub async fn favorites(&self) -> Result<Vec<Favorite>, Error> {
+        let response: FavoritesResponse = self.send_json(self.request(Method::GET, "/v1/user/favorites")).await?;
+        Ok(response.favorites)
+    }
+
+    pub async fn add_favorite(&self, sticker_url: &str) -> Result<(), Error> {
+        let body = FavoriteRequest {
+            sticker_url: sticker_url.to_string(),
+        };
+        self.send(self.request(Method::POST, "/v1/user/favorites").json(&body))
+            .await
+            .map(|_| ())
+    }
+
+    pub async fn remove_favorite(&self, sticker_url: &str) -> Result<(), Error> {
+        let query = FavoriteRequest {
+            sticker_url: sticker_url.to_string(),
+        };
+        self.send(self.request(Method::DELETE, "/v1/user/favorites").query(&query))
+            .await
+            .map(|_| ())
+    }
+
+    pub async fn preferences(&self) -> Result<UserPreferences, Error> {
+        self.send_json(self.request(Method::GET, "/v1/user/preferences")).await
+    }
+
+    pub async fn update_preferences(&self, request: &UpdatePreferencesRequest) -> Result<UserPreferences, Error> {
+        self.send_json(self.request(Method::PUT, "/v1/user/preferences").json(request))
+            .await
+    }
+}
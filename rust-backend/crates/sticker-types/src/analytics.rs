@@ -0,0 +1,102 @@
+use chrono::{DateTime, NaiveDate, NaiveDateTime, NaiveTime, Utc};
+use serde::{Deserialize, Serialize};
+
+// Counted per sticker across all searches; unlike the original endpoint there is no
+// input_text, since one sticker is picked for many different texts
+#[derive(Debug, Clone, Serialize, Deserialize)]
+#[cfg_attr(feature = "openapi", derive(utoipa::ToSchema))]
+pub struct TopSticker {
+    pub sticker_url: String,
+    pub usage_count: u32,
+    pub last_used: DateTime<Utc>,
+}
+
+// Query string of GET /user/top-stickers and /user/analytics
+#[derive(Debug, Clone, Default, Serialize, Deserialize)]
+#[cfg_attr(feature = "openapi", derive(utoipa::IntoParams))]
+#[cfg_attr(feature = "openapi", into_params(parameter_in = Query))]
+pub struct TopStickerRequest {
+    pub limit: Option<i64>,
+    #[serde(default)]
+    pub window: AnalyticsWindow,
+}
+
+#[derive(Deserialize, Serialize, Debug, Clone, Copy, Default, PartialEq)]
+#[cfg_attr(feature = "openapi", derive(utoipa::ToSchema))]
+pub enum AnalyticsWindow {
+    #[serde(rename = "7d")]
+    SevenDays,
+    #[serde(rename = "30d")]
+    ThirtyDays,
+    #[default]
+    #[serde(rename = "all")]
+    All,
+}
+
+impl AnalyticsWindow {
+    // Start of the window, or None for all-time
+    pub fn since(&self) -> Option<NaiveDateTime> {
+        self.since_day(Utc::now().date_naive())
+            .map(|day| day.and_time(NaiveTime::MIN))
+    }
+
+    // First UTC day of the window ending today. Windows cover whole days, today included,
+    // so the first day of the daily series is never a partial one.
+    pub fn since_day(&self, today: NaiveDate) -> Option<NaiveDate> {
+        let days = match self {
+            AnalyticsWindow::SevenDays => 7,
+            AnalyticsWindow::ThirtyDays => 30,
+            AnalyticsWindow::All => return None,
+        };
+        Some(today - chrono::Duration::days(days - 1))
+    }
+}
+
+#[derive(Debug, Clone, Serialize, Deserialize)]
+#[cfg_attr(feature = "sqlx", derive(sqlx::FromRow))]
+#[cfg_attr(feature = "openapi", derive(utoipa::ToSchema))]
+pub struct EmotionCount {
+    pub emotion: String,
+    pub count: i64,
+}
+
+#[derive(Debug, Clone, Serialize, Deserialize)]
+#[cfg_attr(feature = "sqlx", derive(sqlx::FromRow))]
+#[cfg_attr(feature = "openapi", derive(utoipa::ToSchema))]
+pub struct DailyActivity {
+    pub day: NaiveDate,
+    pub count: i64,
+}
+
+#[derive(Debug, Clone, Serialize, Deserialize)]
+#[cfg_attr(feature = "openapi", derive(utoipa::ToSchema))]
+pub struct UserAnalytics {
+    pub window: AnalyticsWindow,
+    pub total_interactions: i64,
+    pub top_stickers: Vec<TopSticker>,
+    pub top_emotions: Vec<EmotionCount>,
+    pub daily_activity: Vec<DailyActivity>, // oldest first, days without activity included as 0
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn windows_start_at_midnight_and_include_today() {
+        let today = NaiveDate::from_ymd_opt(2025, 3, 10).unwrap();
+        assert_eq!(AnalyticsWindow::SevenDays.since_day(today), NaiveDate::from_ymd_opt(2025, 3, 4));
+        assert_eq!(AnalyticsWindow::ThirtyDays.since_day(today), NaiveDate::from_ymd_opt(2025, 2, 9));
+        assert_eq!(AnalyticsWindow::All.since_day(today), None);
+
+        assert_eq!(AnalyticsWindow::SevenDays.since().unwrap().time(), NaiveTime::MIN);
+    }
+
+    #[test]
+    fn window_query_values() {
+        for (value, window) in [("7d", AnalyticsWindow::SevenDays), ("30d", AnalyticsWindow::ThirtyDays), ("all", AnalyticsWindow::All)] {
+            assert_eq!(serde_json::from_value::<AnalyticsWindow>(serde_json::json!(value)).unwrap(), window);
+        }
+        assert!(serde_json::from_value::<AnalyticsWindow>(serde_json::json!("90d")).is_err());
+    }
+}
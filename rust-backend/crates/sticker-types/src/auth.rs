@@ -0,0 +1,62 @@
+use crate::validation::{normalized_username, trimmed};
+#[cfg(feature = "validate")]
+use crate::validation::{username_charset, USERNAME_MAX_CHARS, USERNAME_MIN_CHARS};
+use serde::{Deserialize, Serialize};
+
+// Only bounded here: accounts created before usernames were normalized must still log in
+#[derive(Clone, Serialize, Deserialize)]
+#[cfg_attr(feature = "validate", derive(validator::Validate))]
+#[cfg_attr(feature = "openapi", derive(utoipa::ToSchema))]
+pub struct LoginRequest {
+    #[serde(deserialize_with = "trimmed")]
+    #[cfg_attr(feature = "validate", validate(length(min = 1, max = 64, message = "Must be 1-64 characters")))]
+    #[cfg_attr(feature = "openapi", schema(min_length = 1, max_length = 64))]
+    pub username: String,
+    #[cfg_attr(feature = "validate", validate(length(min = 1, max = 1024, message = "Must be 1-1024 characters")))]
+    #[cfg_attr(feature = "openapi", schema(min_length = 1, max_length = 1024))]
+    pub password: String,
+}
+
+// Strength rules live in the password policy, which reports all problems at once
+#[derive(Debug, Clone, Serialize, Deserialize)]
+#[cfg_attr(feature = "validate", derive(validator::Validate))]
+#[cfg_attr(feature = "openapi", derive(utoipa::ToSchema))]
+pub struct RegisterRequest {
+    #[serde(deserialize_with = "normalized_username")]
+    #[cfg_attr(
+        feature = "validate",
+        validate(
+            length(min = USERNAME_MIN_CHARS, max = USERNAME_MAX_CHARS, message = "Must be 3-32 characters"),
+            custom(function = "username_charset")
+        )
+    )]
+    #[cfg_attr(feature = "openapi", schema(min_length = 3, max_length = 32))]
+    pub username: String,
+    #[cfg_attr(feature = "validate", validate(length(max = 1024, message = "Must be at most 1024 characters")))]
+    #[cfg_attr(feature = "openapi", schema(max_length = 1024))]
+    pub password: String,
+    #[serde(default)]
+    pub email: Option<String>, // needed for password reset
+}
+
+#[derive(Clone, Serialize, Deserialize)]
+#[cfg_attr(feature = "openapi", derive(utoipa::ToSchema))]
+pub struct MfaLoginRequest {
+    pub mfa_token: String,
+    pub code: String, // TOTP code or recovery code
+}
+
+#[derive(Debug, Clone, Serialize, Deserialize)]
+#[cfg_attr(feature = "openapi", derive(utoipa::ToSchema))]
+pub struct SessionResponse {
+    pub token: String,
+    pub username: String,
+}
+
+// Login answer when the account has 2FA; finish with POST /v1/auth/login/mfa
+#[derive(Debug, Clone, Serialize, Deserialize)]
+#[cfg_attr(feature = "openapi", derive(utoipa::ToSchema))]
+pub struct MfaChallengeResponse {
+    pub mfa_required: bool,
+    pub mfa_token: String,
+}
@@ -0,0 +1,19 @@
+use serde::{Deserialize, Serialize};
+use std::collections::HashMap;
+
+// Body of most 4xx/5xx answers
+#[derive(Debug, Clone, Serialize, Deserialize)]
+#[cfg_attr(feature = "openapi", derive(utoipa::ToSchema))]
+pub struct ErrorResponse {
+    pub error: String,
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub details: Option<String>, // why a JSON body couldn't be parsed
+}
+
+// 400 for a body that parsed but failed validation
+#[derive(Debug, Clone, Serialize, Deserialize)]
+#[cfg_attr(feature = "openapi", derive(utoipa::ToSchema))]
+pub struct ValidationErrorResponse {
+    pub error: String,
+    pub fields: HashMap<String, Vec<String>>, // field name -> messages
+}
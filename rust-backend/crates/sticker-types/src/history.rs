@@ -0,0 +1,54 @@
+use chrono::{DateTime, Utc};
+use serde::{Deserialize, Serialize};
+use uuid::Uuid;
+
+#[derive(Debug, Clone, Serialize, Deserialize)]
+#[cfg_attr(feature = "openapi", derive(utoipa::ToSchema))]
+pub struct HistoryItem {
+    pub id: Uuid,
+    pub input_text: String,
+    pub detected_emotion: String,
+    pub sticker_url: Vec<String>,
+    pub created_at: DateTime<Utc>,
+}
+
+#[derive(Debug, Clone, Serialize, Deserialize)]
+#[cfg_attr(feature = "openapi", derive(utoipa::ToSchema))]
+pub struct HistoryResponse {
+    pub history: Vec<HistoryItem>,
+    pub next_cursor: Option<String>, // pass back as `cursor` to fetch the next page
+}
+
+// Query string of GET /user/history
+#[derive(Debug, Clone, Default, Serialize, Deserialize)]
+#[cfg_attr(feature = "openapi", derive(utoipa::IntoParams))]
+#[cfg_attr(feature = "openapi", into_params(parameter_in = Query))]
+pub struct HistoryRequest {
+    pub cursor: Option<String>,
+    pub limit: Option<i64>,
+    pub emotion: Option<String>,
+    pub from: Option<DateTime<Utc>>,
+    pub to: Option<DateTime<Utc>>,
+    pub q: Option<String>, // case-insensitive search over input_text
+}
+
+// Body of DELETE /v1/user/history: some filters, or `all` to delete every entry
+#[derive(Debug, Clone, Default, Serialize, Deserialize)]
+#[cfg_attr(feature = "openapi", derive(utoipa::ToSchema))]
+pub struct ClearHistoryRequest {
+    pub ids: Option<Vec<Uuid>>,
+    pub from: Option<DateTime<Utc>>,
+    pub to: Option<DateTime<Utc>>,
+    #[serde(default)]
+    pub all: bool,
+}
+
+impl ClearHistoryRequest {
+    pub fn all() -> Self {
+        Self { all: true, ..Self::default() }
+    }
+
+    pub fn has_filters(&self) -> bool {
+        self.ids.is_some() || self.from.is_some() || self.to.is_some()
+    }
+}
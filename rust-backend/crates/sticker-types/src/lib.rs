@@ -0,0 +1,17 @@
+// Request and response bodies of the sticker recommender API, shared by the server and the
+// clients. The derives only the server needs (validation, sqlx rows, OpenAPI schemas) sit
+// behind features, so clients only pull in serde.
+mod analytics;
+mod auth;
+mod errors;
+mod history;
+mod sticker;
+mod user;
+pub mod validation;
+
+pub use analytics::*;
+pub use auth::*;
+pub use errors::*;
+pub use history::*;
+pub use sticker::*;
+pub use user::*;
@@ -0,0 +1,105 @@
+#[cfg(feature = "validate")]
+use crate::validation::{MAX_INPUT_TEXT_CHARS, MAX_STICKER_URL_CHARS};
+use crate::validation::trimmed;
+use chrono::{DateTime, Utc};
+use serde::{Deserialize, Serialize};
+use uuid::Uuid;
+
+// The user always comes from the token; a `username` sent by older clients is ignored
+#[derive(Debug, Clone, Serialize, Deserialize)]
+#[cfg_attr(feature = "validate", derive(validator::Validate))]
+#[cfg_attr(feature = "openapi", derive(utoipa::ToSchema))]
+pub struct RecommendRequest {
+    #[serde(deserialize_with = "trimmed")]
+    #[cfg_attr(
+        feature = "validate",
+        validate(length(min = 1, max = MAX_INPUT_TEXT_CHARS, message = "Must be 1-1000 characters"))
+    )]
+    #[cfg_attr(feature = "openapi", schema(min_length = 1, max_length = 1000))]
+    pub input_text: String,
+    pub personalize: Option<bool>, // defaults to true; false returns the unranked results
+}
+
+#[derive(Debug, Clone, Serialize, Deserialize)]
+#[cfg_attr(feature = "openapi", derive(utoipa::ToSchema))]
+pub struct RecommendResponse {
+    pub recommendation_id: Uuid, // echoed back in feedback events
+    pub detected_emotion: String,
+    pub sticker_urls: Vec<String>, // Changed from sticker_url: String
+}
+
+// Query string of GET /sticker/dashboard-trending
+#[derive(Debug, Clone, Default, Serialize, Deserialize)]
+#[cfg_attr(feature = "openapi", derive(utoipa::IntoParams))]
+#[cfg_attr(feature = "openapi", into_params(parameter_in = Query))]
+pub struct TrendingRequest {
+    pub limit: Option<usize>,
+    pub offset: Option<usize>,
+    pub rating: Option<String>,
+}
+
+#[derive(Debug, Clone, Serialize, Deserialize)]
+#[cfg_attr(feature = "openapi", derive(utoipa::ToSchema))]
+pub struct TrendingResponse {
+    pub sticker_urls: Vec<String>, // Changed from sticker_url: String
+    pub provider: String,
+    pub fetched_at: DateTime<Utc>,
+    pub stale: bool, // true when served from cache after a failed or pending refresh
+    pub rating: String,
+    pub limit: usize,
+    pub offset: usize,
+    pub total: usize,
+}
+
+#[derive(Deserialize, Serialize, Debug, Clone, Copy, PartialEq)]
+#[cfg_attr(feature = "openapi", derive(utoipa::ToSchema))]
+#[serde(rename_all = "lowercase")]
+pub enum FeedbackAction {
+    Shown,
+    Clicked,
+    Copied,
+    Sent,
+    Liked,
+    Disliked,
+}
+
+impl FeedbackAction {
+    pub fn as_str(&self) -> &'static str {
+        match self {
+            FeedbackAction::Shown => "shown",
+            FeedbackAction::Clicked => "clicked",
+            FeedbackAction::Copied => "copied",
+            FeedbackAction::Sent => "sent",
+            FeedbackAction::Liked => "liked",
+            FeedbackAction::Disliked => "disliked",
+        }
+    }
+
+    // Actions that mean the user actually picked this sticker
+    pub fn is_pick(&self) -> bool {
+        matches!(self, FeedbackAction::Clicked | FeedbackAction::Copied | FeedbackAction::Sent)
+    }
+
+    // Rating stored for explicit likes and dislikes, which the ranking adds to the score
+    pub fn rating(&self) -> Option<i16> {
+        match self {
+            FeedbackAction::Liked => Some(1),
+            FeedbackAction::Disliked => Some(-1),
+            _ => None,
+        }
+    }
+}
+
+#[derive(Debug, Clone, Serialize, Deserialize)]
+#[cfg_attr(feature = "validate", derive(validator::Validate))]
+#[cfg_attr(feature = "openapi", derive(utoipa::ToSchema))]
+pub struct FeedbackRequest {
+    pub recommendation_id: Uuid,
+    #[cfg_attr(
+        feature = "validate",
+        validate(length(min = 1, max = MAX_STICKER_URL_CHARS, message = "Must be 1-2048 characters"))
+    )]
+    #[cfg_attr(feature = "openapi", schema(min_length = 1, max_length = 2048))]
+    pub sticker_url: String,
+    pub action: FeedbackAction,
+}
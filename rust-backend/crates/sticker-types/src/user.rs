@@ -0,0 +1,39 @@
+#[cfg(feature = "validate")]
+use crate::validation::MAX_STICKER_URL_CHARS;
+use chrono::NaiveDateTime;
+use serde::{Deserialize, Serialize};
+
+// Body of POST /user/favorites, query string of the DELETEs
+#[derive(Debug, Clone, Serialize, Deserialize)]
+#[cfg_attr(feature = "validate", derive(validator::Validate))]
+#[cfg_attr(feature = "openapi", derive(utoipa::ToSchema, utoipa::IntoParams))]
+#[cfg_attr(feature = "openapi", into_params(parameter_in = Query))]
+pub struct FavoriteRequest {
+    #[cfg_attr(
+        feature = "validate",
+        validate(length(min = 1, max = MAX_STICKER_URL_CHARS, message = "Must be 1-2048 characters"))
+    )]
+    #[cfg_attr(feature = "openapi", schema(min_length = 1, max_length = 2048))]
+    pub sticker_url: String,
+}
+
+#[derive(Debug, Clone, Serialize, Deserialize)]
+#[cfg_attr(feature = "sqlx", derive(sqlx::FromRow))]
+#[cfg_attr(feature = "openapi", derive(utoipa::ToSchema))]
+pub struct Favorite {
+    pub sticker_url: String,
+    pub created_at: NaiveDateTime,
+}
+
+#[derive(Debug, Clone, Serialize, Deserialize)]
+#[cfg_attr(feature = "sqlx", derive(sqlx::FromRow))]
+#[cfg_attr(feature = "openapi", derive(utoipa::ToSchema))]
+pub struct UserPreferences {
+    pub store_input_text: bool,
+}
+
+#[derive(Debug, Clone, Serialize, Deserialize)]
+#[cfg_attr(feature = "openapi", derive(utoipa::ToSchema))]
+pub struct UpdatePreferencesRequest {
+    pub store_input_text: bool,
+}
@@ -0,0 +1,55 @@
+use serde::{Deserialize, Deserializer};
+
+pub const USERNAME_MIN_CHARS: u64 = 3;
+pub const USERNAME_MAX_CHARS: u64 = 32;
+pub const MAX_INPUT_TEXT_CHARS: u64 = 1000; // forwarded to sticker-api, so kept small
+pub const MAX_STICKER_URL_CHARS: u64 = 2048;
+
+// Usernames are stored trimmed and lowercased so "Alice" and "alice " are the same account
+pub fn normalized_username<'de, D: Deserializer<'de>>(deserializer: D) -> Result<String, D::Error> {
+    Ok(String::deserialize(deserializer)?.trim().to_lowercase())
+}
+
+pub fn trimmed<'de, D: Deserializer<'de>>(deserializer: D) -> Result<String, D::Error> {
+    Ok(String::deserialize(deserializer)?.trim().to_string())
+}
+
+// Runs after normalization, so only lowercase letters are allowed
+#[cfg(feature = "validate")]
+pub fn username_charset(username: &str) -> Result<(), validator::ValidationError> {
+    let valid_chars = username
+        .chars()
+        .all(|c| c.is_ascii_lowercase() || c.is_ascii_digit() || matches!(c, '_' | '-' | '.'));
+    let starts_alphanumeric = username.chars().next().is_some_and(|c| c.is_ascii_alphanumeric());
+    if valid_chars && starts_alphanumeric {
+        Ok(())
+    } else {
+        Err(validator::ValidationError::new("username_charset").with_message(
+            "May only contain letters, digits, '_', '-' and '.', and must start with a letter or digit".into(),
+        ))
+    }
+}
+
+// Applies the sticker URL cap to every element of a list
+#[cfg(feature = "validate")]
+pub fn sticker_url_lengths(urls: &[String]) -> Result<(), validator::ValidationError> {
+    if urls.iter().all(|url| !url.is_empty() && url.chars().count() as u64 <= MAX_STICKER_URL_CHARS) {
+        Ok(())
+    } else {
+        Err(validator::ValidationError::new("length").with_message("Every URL must be 1-2048 characters".into()))
+    }
+}
+
+#[cfg(all(test, feature = "validate"))]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn every_sticker_url_is_bounded() {
+        let url = |len: usize| "u".repeat(len);
+        assert!(sticker_url_lengths(&[]).is_ok());
+        assert!(sticker_url_lengths(&[url(1), url(2048)]).is_ok());
+        assert!(sticker_url_lengths(&[url(1), url(2049)]).is_err());
+        assert!(sticker_url_lengths(&[url(0)]).is_err());
+    }
+}
@@ -0,0 +1,44 @@
+use dotenv::dotenv;
+use std::env;
+
+#[derive(Clone)]
+pub struct AccountConfig {
+    pub restore_window_days: i64, // soft-deleted users can be restored for this long, then they are erased
+    pub purge_interval_secs: u64,
+    pub impersonation_ttl_mins: i64,
+    pub bootstrap_admin: Option<(String, String)>, // created at startup if the username is free
+}
+
+impl AccountConfig {
+    pub fn new() -> Self {
+        dotenv().ok();
+        let restore_window_days = env::var("ACCOUNT_RESTORE_WINDOW_DAYS")
+            .ok()
+            .and_then(|v| v.parse::<i64>().ok())
+            .unwrap_or(30)
+            .max(0);
+        let purge_interval_secs = env::var("ACCOUNT_PURGE_INTERVAL_SECS")
+            .ok()
+            .and_then(|v| v.parse::<u64>().ok())
+            .unwrap_or(3600)
+            .max(60);
+        let impersonation_ttl_mins = env::var("IMPERSONATION_TTL_MINS")
+            .ok()
+            .and_then(|v| v.parse::<i64>().ok())
+            .unwrap_or(30)
+            .clamp(1, 240);
+        let bootstrap_admin = match (env::var("ADMIN_USERNAME"), env::var("ADMIN_PASSWORD")) {
+            (Ok(username), Ok(password)) if !username.is_empty() && !password.is_empty() => {
+                Some((username, password))
+            }
+            _ => None,
+        };
+
+        Self {
+            restore_window_days,
+            purge_interval_secs,
+            impersonation_ttl_mins,
+            bootstrap_admin,
+        }
+    }
+}
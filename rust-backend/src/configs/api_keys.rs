@@ -0,0 +1,22 @@
+use dotenv::dotenv;
+use std::env;
+
+#[derive(Clone)]
+pub struct ApiKeyConfig {
+    pub max_keys_per_user: i64,
+}
+
+impl ApiKeyConfig {
+    pub fn new() -> Self {
+        dotenv().ok();
+        let max_keys_per_user = env::var("API_KEY_MAX_PER_USER")
+            .ok()
+            .and_then(|v| v.parse::<i64>().ok())
+            .unwrap_or(10)
+            .max(1);
+
+        Self {
+            max_keys_per_user,
+        }
+    }
+}
@@ -0,0 +1,221 @@
+use dotenv::dotenv;
+use std::env;
+
+// One entry of an origin allowlist
+#[derive(Clone, Debug, PartialEq)]
+pub enum OriginRule {
+    Any,
+    Exact(String), // "https://app.example.com", "chrome-extension://<id>"
+    // "https://*.example.com" matches any subdomain, but not example.com itself
+    Subdomain { scheme: String, suffix: String, port: Option<String> },
+}
+
+// Splits "host:port", dropping the scheme's default port since browsers never send it
+fn split_port<'a>(scheme: &str, host: &'a str) -> (&'a str, Option<&'a str>) {
+    match host.rsplit_once(':') {
+        Some((host, "443")) if scheme == "https" => (host, None),
+        Some((host, "80")) if scheme == "http" => (host, None),
+        Some((host, port)) => (host, Some(port)),
+        None => (host, None),
+    }
+}
+
+impl OriginRule {
+    pub fn parse(raw: &str) -> Result<Self, String> {
+        let raw = raw.trim().trim_end_matches('/').to_lowercase();
+        if raw == "*" {
+            return Ok(OriginRule::Any);
+        }
+        let (scheme, host) = raw
+            .split_once("://")
+            .ok_or_else(|| format!("CORS origin {} must include a scheme", raw))?;
+        if host.is_empty() || host.contains('/') {
+            return Err(format!("CORS origin {} must not have a path", raw));
+        }
+        if scheme == "chrome-extension" && !(host.len() == 32 && host.chars().all(|c| ('a'..='p').contains(&c))) {
+            return Err(format!("{} is not a Chrome extension ID", host));
+        }
+        let (host, port) = split_port(scheme, host);
+        if port.is_some_and(|port| port.is_empty() || !port.bytes().all(|b| b.is_ascii_digit())) {
+            return Err(format!("CORS origin {} has an invalid port", raw));
+        }
+        match host.strip_prefix("*.") {
+            Some(rest) if !rest.is_empty() && !rest.contains('*') => Ok(OriginRule::Subdomain {
+                scheme: scheme.to_string(),
+                suffix: format!(".{}", rest),
+                port: port.map(str::to_string),
+            }),
+            Some(_) => Err(format!("Invalid wildcard CORS origin {}", raw)),
+            None if host.contains('*') => Err(format!("Wildcards are only allowed as the first label: {}", raw)),
+            None => Ok(OriginRule::Exact(match port {
+                Some(port) => format!("{}://{}:{}", scheme, host, port),
+                None => format!("{}://{}", scheme, host),
+            })),
+        }
+    }
+
+    pub fn matches(&self, origin: &str) -> bool {
+        let origin = origin.to_lowercase();
+        let Some((origin_scheme, host)) = origin.split_once("://") else {
+            return matches!(self, OriginRule::Any);
+        };
+        let (host, origin_port) = split_port(origin_scheme, host);
+        match self {
+            OriginRule::Any => true,
+            OriginRule::Exact(allowed) => match origin_port {
+                Some(port) => *allowed == format!("{}://{}:{}", origin_scheme, host, port),
+                None => *allowed == format!("{}://{}", origin_scheme, host),
+            },
+            OriginRule::Subdomain { scheme, suffix, port } => {
+                origin_scheme == scheme
+                    && origin_port == port.as_deref()
+                    && host.len() > suffix.len()
+                    && host.ends_with(suffix.as_str())
+            }
+        }
+    }
+}
+
+#[derive(Clone)]
+pub struct CorsConfig {
+    pub origins: Vec<OriginRule>,
+    pub route_overrides: Vec<(String, Vec<OriginRule>)>, // path prefix, longest first
+    pub max_age_secs: usize,
+}
+
+fn parse_list(raw: &str) -> Vec<OriginRule> {
+    raw.split(',')
+        .filter(|entry| !entry.trim().is_empty())
+        .map(|entry| OriginRule::parse(entry).unwrap_or_else(|e| panic!("{}", e)))
+        .collect()
+}
+
+impl CorsConfig {
+    pub fn new() -> Self {
+        dotenv().ok();
+        // CORS_ALLOWED_ORIGINS wins; FRONTEND_URL alone still works for single-origin setups
+        let origins = match env::var("CORS_ALLOWED_ORIGINS") {
+            Ok(raw) => parse_list(&raw),
+            Err(_) => env::var("FRONTEND_URL").map(|url| parse_list(&url)).unwrap_or_default(),
+        };
+        if origins.is_empty() {
+            log::warn!("Neither CORS_ALLOWED_ORIGINS nor FRONTEND_URL is set; browsers can only call the API from the same origin");
+        }
+
+        // "/v1/admin=https://admin.example.com;/v1/sticker=https://app.example.com,chrome-extension://..."
+        let mut route_overrides: Vec<(String, Vec<OriginRule>)> = vec![("/.well-known/".to_string(), vec![OriginRule::Any])];
+        if let Ok(raw) = env::var("CORS_ROUTE_OVERRIDES") {
+            for entry in raw.split(';').filter(|e| !e.trim().is_empty()) {
+                let (prefix, list) = entry
+                    .split_once('=')
+                    .unwrap_or_else(|| panic!("CORS_ROUTE_OVERRIDES entry {} must be <path prefix>=<origins>", entry));
+                let prefix = prefix.trim().to_string();
+                route_overrides.retain(|(existing, _)| *existing != prefix);
+                route_overrides.push((prefix, parse_list(list)));
+            }
+        }
+        route_overrides.sort_by_key(|(prefix, _)| std::cmp::Reverse(prefix.len()));
+
+        let max_age_secs = env::var("CORS_MAX_AGE_SECS")
+            .ok()
+            .and_then(|v| v.parse::<usize>().ok())
+            .unwrap_or(3600);
+
+        Self { origins, route_overrides, max_age_secs }
+    }
+
+    // The allowlist for a path: the longest matching override, else the global list
+    pub fn rules_for(&self, path: &str) -> &[OriginRule] {
+        self.route_overrides
+            .iter()
+            .find(|(prefix, _)| path.starts_with(prefix.as_str()))
+            .map(|(_, rules)| rules.as_slice())
+            .unwrap_or(&self.origins)
+    }
+
+    pub fn is_allowed(&self, origin: &str, path: &str) -> bool {
+        self.rules_for(path).iter().any(|rule| rule.matches(origin))
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn rule(raw: &str) -> OriginRule {
+        OriginRule::parse(raw).unwrap_or_else(|e| panic!("{}", e))
+    }
+
+    #[test]
+    fn parses_exact_origins_loosely() {
+        assert_eq!(rule(" HTTPS://App.Example.com/ "), OriginRule::Exact("https://app.example.com".to_string()));
+        assert_eq!(rule("http://localhost:5173"), OriginRule::Exact("http://localhost:5173".to_string()));
+        assert_eq!(rule("*"), OriginRule::Any);
+        assert!(rule("chrome-extension://abcdefghijklmnopabcdefghijklmnop").matches("chrome-extension://abcdefghijklmnopabcdefghijklmnop"));
+    }
+
+    #[test]
+    fn rejects_malformed_origins() {
+        for raw in [
+            "app.example.com",
+            "https://",
+            "https://app.example.com/path",
+            "https://*.",
+            "https://*.*.example.com",
+            "https://app.*.example.com",
+            "https://*example.com",
+            "https://app.example.com:",
+            "https://app.example.com:https",
+            "https://*.example.com:80a",
+            "chrome-extension://not-an-extension-id",
+        ] {
+            assert!(OriginRule::parse(raw).is_err(), "{} should be rejected", raw);
+        }
+    }
+
+    #[test]
+    fn wildcards_match_subdomains_only() {
+        let wildcard = rule("https://*.example.com");
+        assert!(wildcard.matches("https://app.example.com"));
+        assert!(wildcard.matches("https://a.b.example.com"));
+        assert!(wildcard.matches("HTTPS://App.Example.COM"));
+        assert!(!wildcard.matches("https://example.com"));
+        assert!(!wildcard.matches("https://badexample.com"));
+        assert!(!wildcard.matches("https://app.example.com.evil.com"));
+        assert!(!wildcard.matches("http://app.example.com"));
+        assert!(!wildcard.matches("app.example.com"));
+    }
+
+    #[test]
+    fn ports_must_match_except_defaults() {
+        let wildcard = rule("https://*.example.com:8443");
+        assert!(wildcard.matches("https://app.example.com:8443"));
+        assert!(!wildcard.matches("https://app.example.com"));
+        assert!(!wildcard.matches("https://app.example.com:9443"));
+        assert!(!rule("https://*.example.com").matches("https://app.example.com:8443"));
+
+        // Browsers leave out default ports, so rules that spell them out still match
+        assert!(rule("https://*.example.com:443").matches("https://app.example.com"));
+        assert!(rule("https://app.example.com:443").matches("https://app.example.com"));
+        assert!(rule("http://app.example.com:80").matches("http://app.example.com"));
+        assert!(!rule("http://app.example.com:443").matches("http://app.example.com"));
+        assert!(!rule("http://localhost:5173").matches("http://localhost:5174"));
+        assert!(!rule("http://localhost:5173").matches("http://localhost"));
+    }
+
+    #[test]
+    fn route_overrides_win_for_their_prefix() {
+        let config = CorsConfig {
+            origins: vec![rule("https://app.example.com")],
+            route_overrides: vec![
+                ("/v1/admin".to_string(), vec![rule("https://admin.example.com")]),
+                ("/.well-known/".to_string(), vec![OriginRule::Any]),
+            ],
+            max_age_secs: 3600,
+        };
+        assert!(config.is_allowed("https://app.example.com", "/v1/sticker/find"));
+        assert!(!config.is_allowed("https://app.example.com", "/v1/admin/users"));
+        assert!(config.is_allowed("https://admin.example.com", "/v1/admin/users"));
+        assert!(config.is_allowed("https://anywhere.example.org", "/.well-known/jwks.json"));
+    }
+}
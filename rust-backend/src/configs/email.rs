@@ -0,0 +1,47 @@
+use dotenv::dotenv;
+use std::env;
+
+#[derive(Clone, Debug, PartialEq)]
+pub enum EmailTransport {
+    Log,  // writes messages to the application log
+    File, // writes each message to EMAIL_OUTBOX_DIR
+}
+
+#[derive(Clone)]
+pub struct EmailConfig {
+    pub transport: EmailTransport,
+    pub outbox_dir: String,
+    pub from: String,
+    pub password_reset_url: String, // the reset token is appended as ?token=...
+    pub password_reset_ttl_mins: i64,
+}
+
+impl EmailConfig {
+    pub fn new() -> Self {
+        dotenv().ok();
+        let transport = match env::var("EMAIL_SENDER").unwrap_or_default().as_str() {
+            "" | "log" => EmailTransport::Log,
+            "file" => EmailTransport::File,
+            other => panic!("Unsupported EMAIL_SENDER {}; use log or file", other),
+        };
+        let outbox_dir = env::var("EMAIL_OUTBOX_DIR").unwrap_or_else(|_| "outbox".to_string());
+        let from = env::var("EMAIL_FROM").unwrap_or_else(|_| "no-reply@localhost".to_string());
+        let password_reset_url = env::var("PASSWORD_RESET_URL").unwrap_or_else(|_| {
+            let frontend = env::var("FRONTEND_URL").unwrap_or_else(|_| "http://localhost:3000".to_string());
+            format!("{}/reset-password", frontend.trim_end_matches('/'))
+        });
+        let password_reset_ttl_mins = env::var("PASSWORD_RESET_TTL_MINS")
+            .ok()
+            .and_then(|v| v.parse::<i64>().ok())
+            .unwrap_or(30)
+            .clamp(5, 1440);
+
+        Self {
+            transport,
+            outbox_dir,
+            from,
+            password_reset_url,
+            password_reset_ttl_mins,
+        }
+    }
+}
@@ -0,0 +1,20 @@
+use dotenv::dotenv;
+use std::env;
+
+#[derive(Clone)]
+pub struct RequestLimits {
+    pub json_payload_bytes: usize, // bodies above this get 413 before deserializing
+}
+
+impl RequestLimits {
+    pub fn new() -> Self {
+        dotenv().ok();
+        let json_payload_bytes = env::var("MAX_JSON_PAYLOAD_BYTES")
+            .ok()
+            .and_then(|v| v.parse::<usize>().ok())
+            .unwrap_or(64 * 1024)
+            .clamp(1024, 10 * 1024 * 1024);
+
+        Self { json_payload_bytes }
+    }
+}
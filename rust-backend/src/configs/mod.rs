@@ -1 +1,14 @@
 pub mod env_load;
+pub mod trending;
+pub mod retention;
+pub mod accounts;
+pub mod api_keys;
+pub mod oidc;
+pub mod totp;
+pub mod password;
+pub mod email;
+pub mod limits;
+pub mod rate_limit;
+pub mod cors;
+pub mod tls;
+pub mod security_headers;
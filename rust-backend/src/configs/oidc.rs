@@ -0,0 +1,61 @@
+use dotenv::dotenv;
+use std::env;
+
+#[derive(Clone, Debug)]
+pub struct OidcProvider {
+    pub name: String,
+    pub issuer: String,
+    pub client_id: String,
+    pub client_secret: Option<String>, // None for public clients relying on PKCE alone
+    pub redirect_uri: String,          // must point at /v1/auth/oidc/<name>/callback
+    pub scopes: String,
+    pub auto_provision: bool,          // create a user on first login instead of refusing
+}
+
+#[derive(Clone)]
+pub struct OidcConfig {
+    pub providers: Vec<OidcProvider>,
+    pub success_redirect: Option<String>, // frontend URL that receives #token=...; JSON response if unset
+}
+
+// Providers are listed in OIDC_PROVIDERS and configured with OIDC_<NAME>_* variables, e.g.
+// OIDC_PROVIDERS=google with OIDC_GOOGLE_ISSUER, OIDC_GOOGLE_CLIENT_ID and OIDC_GOOGLE_REDIRECT_URI.
+impl OidcConfig {
+    pub fn new() -> Self {
+        dotenv().ok();
+        let providers = env::var("OIDC_PROVIDERS")
+            .unwrap_or_default()
+            .split(',')
+            .map(|name| name.trim().to_lowercase())
+            .filter(|name| !name.is_empty())
+            .filter_map(|name| {
+                let var = |key: &str| env::var(format!("OIDC_{}_{}", name.to_uppercase(), key)).ok();
+                let (Some(issuer), Some(client_id), Some(redirect_uri)) =
+                    (var("ISSUER"), var("CLIENT_ID"), var("REDIRECT_URI"))
+                else {
+                    log::warn!("OIDC provider {} is missing ISSUER, CLIENT_ID or REDIRECT_URI; skipping", name);
+                    return None;
+                };
+                Some(OidcProvider {
+                    issuer: issuer.trim_end_matches('/').to_string(),
+                    client_id,
+                    client_secret: var("CLIENT_SECRET").filter(|s| !s.is_empty()),
+                    redirect_uri,
+                    scopes: var("SCOPES").unwrap_or_else(|| "openid profile email".to_string()),
+                    auto_provision: var("AUTO_PROVISION").map(|v| v != "false").unwrap_or(true),
+                    name,
+                })
+            })
+            .collect();
+        let success_redirect = env::var("OIDC_SUCCESS_REDIRECT").ok().filter(|s| !s.is_empty());
+
+        Self {
+            providers,
+            success_redirect,
+        }
+    }
+
+    pub fn provider(&self, name: &str) -> Option<&OidcProvider> {
+        self.providers.iter().find(|p| p.name == name)
+    }
+}
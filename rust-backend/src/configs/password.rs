@@ -0,0 +1,175 @@
+use dotenv::dotenv;
+use sha1::{Digest, Sha1};
+use std::collections::HashSet;
+use std::env;
+use std::sync::Arc;
+
+// Used when PASSWORD_BREACHED_LIST is not set
+const BUNDLED_LIST: &str = include_str!("../../data/common-passwords.txt");
+const BCRYPT_MAX_BYTES: usize = 72; // bcrypt ignores anything past this
+
+#[derive(Clone)]
+pub struct PasswordPolicy {
+    pub min_length: usize,
+    pub min_classes: usize, // of lowercase, uppercase, digits and symbols
+    breached: Arc<HashSet<String>>, // lowercased passwords and uppercase SHA-1 hex
+}
+
+impl PasswordPolicy {
+    pub fn new() -> Self {
+        dotenv().ok();
+        let min_length = env::var("PASSWORD_MIN_LENGTH")
+            .ok()
+            .and_then(|v| v.parse::<usize>().ok())
+            .unwrap_or(10)
+            .clamp(6, BCRYPT_MAX_BYTES);
+        let min_classes = env::var("PASSWORD_MIN_CLASSES")
+            .ok()
+            .and_then(|v| v.parse::<usize>().ok())
+            .unwrap_or(2)
+            .min(4);
+        let list = match env::var("PASSWORD_BREACHED_LIST") {
+            Ok(path) if !path.is_empty() => std::fs::read_to_string(&path)
+                .unwrap_or_else(|e| panic!("Failed to read PASSWORD_BREACHED_LIST {}: {}", path, e)),
+            _ => BUNDLED_LIST.to_string(),
+        };
+        let breached: HashSet<String> = list.lines().filter_map(parse_list_line).collect();
+        log::info!("Loaded {} breached passwords", breached.len());
+
+        Self {
+            min_length,
+            min_classes,
+            breached: Arc::new(breached),
+        }
+    }
+
+    // Every rule the password breaks, as messages for the client. Empty means acceptable.
+    pub fn violations(&self, password: &str, username: &str) -> Vec<String> {
+        let mut problems = Vec::new();
+        if password.chars().count() < self.min_length {
+            problems.push(format!("Must be at least {} characters", self.min_length));
+        }
+        if password.len() > BCRYPT_MAX_BYTES {
+            problems.push(format!("Must be at most {} bytes", BCRYPT_MAX_BYTES));
+        }
+        let classes = [
+            password.chars().any(|c| c.is_lowercase()),
+            password.chars().any(|c| c.is_uppercase()),
+            password.chars().any(|c| c.is_ascii_digit()),
+            password.chars().any(|c| !c.is_alphanumeric()),
+        ]
+        .iter()
+        .filter(|present| **present)
+        .count();
+        if classes < self.min_classes {
+            problems.push(format!(
+                "Must mix at least {} of lowercase, uppercase, digits and symbols",
+                self.min_classes
+            ));
+        }
+        let lowered = password.to_lowercase();
+        let username = username.trim().to_lowercase();
+        if username.len() >= 3 && lowered.contains(&username) {
+            problems.push("Must not contain the username".to_string());
+        }
+        if self.is_breached(password) {
+            problems.push("Appears in a list of breached passwords".to_string());
+        }
+        problems
+    }
+
+    fn is_breached(&self, password: &str) -> bool {
+        let sha1 = hex::encode_upper(Sha1::digest(password.as_bytes()));
+        self.breached.contains(&password.to_lowercase()) || self.breached.contains(&sha1)
+    }
+}
+
+// Accepts plain passwords or SHA-1 hashes, optionally with a ":count" suffix as in
+// the Have I Been Pwned downloads. Lines starting with # are comments.
+fn parse_list_line(line: &str) -> Option<String> {
+    let line = line.trim();
+    if line.is_empty() || line.starts_with('#') {
+        return None;
+    }
+    let hash = line.split(':').next().unwrap_or(line);
+    if hash.len() == 40 && hash.bytes().all(|b| b.is_ascii_hexdigit()) {
+        Some(hash.to_ascii_uppercase())
+    } else {
+        Some(line.to_lowercase())
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn policy(list: &str) -> PasswordPolicy {
+        PasswordPolicy {
+            min_length: 10,
+            min_classes: 2,
+            breached: Arc::new(list.lines().filter_map(parse_list_line).collect()),
+        }
+    }
+
+    #[test]
+    fn list_lines_are_plain_passwords_or_sha1_hashes() {
+        assert_eq!(parse_list_line("  Hunter2  "), Some("hunter2".to_string()));
+        assert_eq!(parse_list_line(""), None);
+        assert_eq!(parse_list_line("   "), None);
+        assert_eq!(parse_list_line("# comment"), None);
+        // Have I Been Pwned lines: hash and count
+        assert_eq!(
+            parse_list_line("5baa61e4c9b93f3f0682250b6cf8331b7ee68fd8:3861493"),
+            Some("5BAA61E4C9B93F3F0682250B6CF8331B7EE68FD8".to_string())
+        );
+        // Not a 40 character hash, so kept whole as a password
+        assert_eq!(parse_list_line("abc:def"), Some("abc:def".to_string()));
+    }
+
+    #[test]
+    fn breached_passwords_match_by_text_or_hash() {
+        // SHA-1 of "password"
+        let policy = policy("Summer2024!\n5BAA61E4C9B93F3F0682250B6CF8331B7EE68FD8:10\n");
+        assert!(policy.is_breached("summer2024!"));
+        assert!(policy.is_breached("SUMMER2024!"));
+        assert!(policy.is_breached("password"));
+        assert!(!policy.is_breached("Password"));
+        assert!(!policy.is_breached("correct horse battery"));
+    }
+
+    #[test]
+    fn violations_list_every_broken_rule() {
+        let policy = policy("Summer2024!\n");
+        assert!(policy.violations("Tr1cky-Passphrase", "alice").is_empty());
+
+        assert_eq!(
+            policy.violations("short", "alice"),
+            vec![
+                "Must be at least 10 characters".to_string(),
+                "Must mix at least 2 of lowercase, uppercase, digits and symbols".to_string(),
+            ]
+        );
+        assert_eq!(
+            policy.violations("xxALICExx-99", "Alice"),
+            vec!["Must not contain the username".to_string()]
+        );
+        // Usernames under three characters would match too much to be useful
+        assert!(policy.violations("xxbox-99xx", "bo").is_empty());
+        assert_eq!(
+            policy.violations("Summer2024!", "alice"),
+            vec!["Appears in a list of breached passwords".to_string()]
+        );
+        let long = "aA1".repeat(25);
+        assert_eq!(policy.violations(&long, "alice"), vec![format!("Must be at most {} bytes", BCRYPT_MAX_BYTES)]);
+        // Length is counted in characters, not bytes
+        assert!(policy.violations("éééééééé1", "alice").iter().any(|p| p.starts_with("Must be at least")));
+        assert!(policy.violations("ééééééééé1", "alice").is_empty());
+    }
+
+    #[test]
+    fn the_bundled_list_loads() {
+        let breached: HashSet<String> = BUNDLED_LIST.lines().filter_map(parse_list_line).collect();
+        assert!(!breached.is_empty());
+        assert!(breached.iter().all(|entry| !entry.starts_with('#') && !entry.is_empty()));
+    }
+}
@@ -0,0 +1,104 @@
+use actix_web::http::Method;
+use dotenv::dotenv;
+use std::env;
+
+// What a policy counts requests against
+#[derive(Clone, Copy, PartialEq, Debug)]
+pub enum LimitKey {
+    Ip,      // unauthenticated routes
+    Account, // the user, API key or admin from the token
+}
+
+#[derive(Clone, Debug)]
+pub struct RatePolicy {
+    pub name: &'static str,
+    pub key: LimitKey,
+    pub limit: u64,
+    pub window_secs: u64,
+    pub daily_quota: Option<u64>, // per user and UTC day, on top of the window
+}
+
+#[derive(Clone)]
+pub struct RateLimitConfig {
+    pub enabled: bool,
+    pub trust_proxy: bool, // take the client IP from X-Forwarded-For / Forwarded
+    auth: RatePolicy,
+    recommend: RatePolicy,
+    export: RatePolicy,
+    default: RatePolicy,
+    api_key: RatePolicy, // replaces the route's window for requests made with an API key
+}
+
+fn env_u64(name: &str, default: u64) -> u64 {
+    env::var(name).ok().and_then(|v| v.parse::<u64>().ok()).unwrap_or(default)
+}
+
+impl RateLimitConfig {
+    pub fn new() -> Self {
+        dotenv().ok();
+        let enabled = env::var("RATE_LIMIT_ENABLED").map(|v| v != "false").unwrap_or(true);
+        let trust_proxy = env::var("TRUST_PROXY_HEADERS").map(|v| v == "true").unwrap_or(false);
+        let daily_quota = env_u64("RECOMMEND_DAILY_QUOTA", 500);
+
+        Self {
+            enabled,
+            trust_proxy,
+            auth: RatePolicy {
+                name: "auth",
+                key: LimitKey::Ip,
+                limit: env_u64("RATE_LIMIT_AUTH_PER_MIN", 10).max(1),
+                window_secs: 60,
+                daily_quota: None,
+            },
+            recommend: RatePolicy {
+                name: "recommend",
+                key: LimitKey::Account,
+                limit: env_u64("RATE_LIMIT_RECOMMEND_PER_MIN", 30).max(1),
+                window_secs: 60,
+                daily_quota: (daily_quota > 0).then_some(daily_quota), // 0 turns the quota off
+            },
+            export: RatePolicy {
+                name: "export",
+                key: LimitKey::Account,
+                limit: env_u64("RATE_LIMIT_EXPORT_PER_HOUR", 5).max(1),
+                window_secs: 3600,
+                daily_quota: None,
+            },
+            default: RatePolicy {
+                name: "default",
+                key: LimitKey::Account,
+                limit: env_u64("RATE_LIMIT_DEFAULT_PER_MIN", 120).max(1),
+                window_secs: 60,
+                daily_quota: None,
+            },
+            api_key: RatePolicy {
+                name: "api_key",
+                key: LimitKey::Account,
+                limit: env_u64("API_KEY_RATE_LIMIT_PER_MIN", 60).max(1),
+                window_secs: 60,
+                daily_quota: None,
+            },
+        }
+    }
+
+    pub fn api_key_policy(&self) -> &RatePolicy {
+        &self.api_key
+    }
+
+    // The policy for a route, or None for routes that are never limited (health, JWKS, preflight)
+    pub fn policy_for(&self, method: &Method, path: &str) -> Option<&RatePolicy> {
+        if *method == Method::OPTIONS {
+            return None;
+        }
+        let path = path.trim_end_matches('/');
+        match path {
+            "/v1/sticker/find" | "/v1/sticker/dashboard-find" => Some(&self.recommend),
+            "/v1/user/export" => Some(&self.export),
+            _ if path.starts_with("/v1/auth/") => Some(&self.auth),
+            _ if ["/v1/sticker/", "/v1/user/", "/v1/admin/"].iter().any(|p| path.starts_with(p)) => {
+                Some(&self.default)
+            }
+            _ => None,
+        }
+    }
+}
@@ -0,0 +1,40 @@
+use dotenv::dotenv;
+use std::env;
+
+#[derive(Clone, Copy, Debug, PartialEq)]
+pub enum RetentionMode {
+    Purge,     // delete expired interactions and their recommendations and feedback outright
+    Anonymize, // keep emotion/sticker for aggregates, drop the user link and text
+}
+
+#[derive(Clone)]
+pub struct RetentionConfig {
+    pub retention_days: Option<i64>, // None keeps history forever
+    pub mode: RetentionMode,
+    pub interval_secs: u64,
+}
+
+impl RetentionConfig {
+    pub fn new() -> Self {
+        dotenv().ok();
+        let retention_days = env::var("HISTORY_RETENTION_DAYS")
+            .ok()
+            .and_then(|v| v.parse::<i64>().ok())
+            .filter(|days| *days > 0);
+        let mode = match env::var("HISTORY_RETENTION_MODE").as_deref() {
+            Ok("purge") => RetentionMode::Purge,
+            _ => RetentionMode::Anonymize,
+        };
+        let interval_secs = env::var("HISTORY_PURGE_INTERVAL_SECS")
+            .ok()
+            .and_then(|v| v.parse::<u64>().ok())
+            .unwrap_or(3600)
+            .max(60);
+
+        Self {
+            retention_days,
+            mode,
+            interval_secs,
+        }
+    }
+}
@@ -0,0 +1,67 @@
+use actix_web::http::header::{self, HeaderName, HeaderValue};
+use dotenv::dotenv;
+use std::env;
+
+#[derive(Clone)]
+pub struct SecurityHeadersConfig {
+    pub headers: Vec<(HeaderName, HeaderValue)>, // added to every response that doesn't set its own
+    pub no_store_prefixes: Vec<String>,          // paths whose responses must never be cached
+}
+
+// An empty value leaves the header off
+fn env_or(name: &str, default: &str) -> Option<String> {
+    let value = env::var(name).unwrap_or_else(|_| default.to_string());
+    let value = value.trim();
+    (!value.is_empty()).then(|| value.to_string())
+}
+
+fn header_value(name: &str, value: &str) -> HeaderValue {
+    HeaderValue::from_str(value).unwrap_or_else(|_| panic!("{} is not a valid header value: {}", name, value))
+}
+
+impl SecurityHeadersConfig {
+    pub fn new() -> Self {
+        dotenv().ok();
+        if env::var("SECURITY_HEADERS_ENABLED").map(|v| v == "false").unwrap_or(false) {
+            return Self { headers: Vec::new(), no_store_prefixes: Vec::new() };
+        }
+
+        let mut headers = vec![(header::X_CONTENT_TYPE_OPTIONS, HeaderValue::from_static("nosniff"))];
+
+        // The API only serves JSON, so by default nothing may load from it or frame it
+        let frame_ancestors = env_or("CSP_FRAME_ANCESTORS", "'none'");
+        let mut csp: Vec<String> = env_or("CONTENT_SECURITY_POLICY", "default-src 'none'").into_iter().collect();
+        if let Some(ancestors) = &frame_ancestors {
+            if !csp.iter().any(|policy| policy.contains("frame-ancestors")) {
+                csp.push(format!("frame-ancestors {}", ancestors));
+            }
+        }
+        if !csp.is_empty() {
+            headers.push((header::CONTENT_SECURITY_POLICY, header_value("CONTENT_SECURITY_POLICY", &csp.join("; "))));
+        }
+        // For browsers that predate frame-ancestors
+        match frame_ancestors.as_deref() {
+            Some("'none'") => headers.push((header::X_FRAME_OPTIONS, HeaderValue::from_static("DENY"))),
+            Some("'self'") => headers.push((header::X_FRAME_OPTIONS, HeaderValue::from_static("SAMEORIGIN"))),
+            _ => {}
+        }
+
+        if let Some(policy) = env_or("REFERRER_POLICY", "no-referrer") {
+            headers.push((header::REFERRER_POLICY, header_value("REFERRER_POLICY", &policy)));
+        }
+        if let Some(policy) = env_or("PERMISSIONS_POLICY", "camera=(), microphone=(), geolocation=(), payment=(), usb=()") {
+            headers.push((HeaderName::from_static("permissions-policy"), header_value("PERMISSIONS_POLICY", &policy)));
+        }
+
+        // Token-bearing responses (login, refresh) and everything behind auth
+        let no_store_prefixes = env_or("NO_STORE_PATHS", "/v1/auth/,/v1/user/,/v1/sticker/,/v1/admin/")
+            .map(|raw| raw.split(',').map(str::trim).filter(|p| !p.is_empty()).map(str::to_string).collect())
+            .unwrap_or_default();
+
+        Self { headers, no_store_prefixes }
+    }
+
+    pub fn is_no_store(&self, path: &str) -> bool {
+        self.no_store_prefixes.iter().any(|prefix| path.starts_with(prefix.as_str()))
+    }
+}
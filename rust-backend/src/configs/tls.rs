@@ -0,0 +1,51 @@
+use dotenv::dotenv;
+use std::env;
+use std::path::PathBuf;
+
+#[derive(Clone)]
+pub struct TlsConfig {
+    pub cert_path: Option<PathBuf>, // PEM chain, leaf first
+    pub key_path: Option<PathBuf>,  // PEM private key (PKCS#8, PKCS#1 or SEC1)
+    pub hsts_max_age_secs: u64,     // 0 leaves the header off
+    pub hsts_include_subdomains: bool,
+    pub hsts_preload: bool,
+}
+
+impl TlsConfig {
+    pub fn new() -> Self {
+        dotenv().ok();
+        let path = |name: &str| env::var(name).ok().filter(|v| !v.trim().is_empty()).map(PathBuf::from);
+        let cert_path = path("TLS_CERT_PATH");
+        let key_path = path("TLS_KEY_PATH");
+        if cert_path.is_some() != key_path.is_some() {
+            panic!("TLS_CERT_PATH and TLS_KEY_PATH must be set together");
+        }
+        let hsts_max_age_secs = env::var("HSTS_MAX_AGE_SECS")
+            .ok()
+            .and_then(|v| v.parse::<u64>().ok())
+            .unwrap_or(31_536_000);
+        let hsts_include_subdomains = env::var("HSTS_INCLUDE_SUBDOMAINS").map(|v| v == "true").unwrap_or(false);
+        let hsts_preload = env::var("HSTS_PRELOAD").map(|v| v == "true").unwrap_or(false);
+
+        Self { cert_path, key_path, hsts_max_age_secs, hsts_include_subdomains, hsts_preload }
+    }
+
+    pub fn enabled(&self) -> bool {
+        self.cert_path.is_some()
+    }
+
+    // Strict-Transport-Security value; only sent when we terminate TLS ourselves
+    pub fn hsts_header(&self) -> Option<String> {
+        if !self.enabled() || self.hsts_max_age_secs == 0 {
+            return None;
+        }
+        let mut value = format!("max-age={}", self.hsts_max_age_secs);
+        if self.hsts_include_subdomains {
+            value.push_str("; includeSubDomains");
+        }
+        if self.hsts_preload {
+            value.push_str("; preload");
+        }
+        Some(value)
+    }
+}
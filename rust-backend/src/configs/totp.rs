@@ -0,0 +1,35 @@
+use dotenv::dotenv;
+use std::env;
+
+#[derive(Clone)]
+pub struct TotpConfig {
+    pub issuer: String, // shown as the account name in authenticator apps
+    pub challenge_ttl_secs: u64,
+    pub max_attempts: i64, // wrong codes allowed per login before it must restart
+}
+
+impl TotpConfig {
+    pub fn new() -> Self {
+        dotenv().ok();
+        let issuer = env::var("TOTP_ISSUER")
+            .ok()
+            .filter(|v| !v.trim().is_empty())
+            .unwrap_or_else(|| "Sticker Recommender".to_string());
+        let challenge_ttl_secs = env::var("MFA_CHALLENGE_TTL_SECS")
+            .ok()
+            .and_then(|v| v.parse::<u64>().ok())
+            .unwrap_or(300)
+            .clamp(30, 1800);
+        let max_attempts = env::var("MFA_MAX_ATTEMPTS")
+            .ok()
+            .and_then(|v| v.parse::<i64>().ok())
+            .unwrap_or(5)
+            .max(1);
+
+        Self {
+            issuer,
+            challenge_ttl_secs,
+            max_attempts,
+        }
+    }
+}
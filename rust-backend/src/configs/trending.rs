@@ -0,0 +1,52 @@
+use dotenv::dotenv;
+use std::env;
+
+pub const TRENDING_PROVIDER: &str = "giphy";
+pub const TRENDING_RATINGS: [&str; 4] = ["g", "pg", "pg-13", "r"];
+
+#[derive(Clone)]
+pub struct TrendingConfig {
+    pub giphy_api_key: Option<String>,
+    pub refresh_after_secs: i64, // age after which a cached list is refreshed in the background
+    pub stale_ttl_secs: u64,     // how long a cached list may still be served when Giphy is down
+    pub cache_size: usize,       // number of stickers fetched and cached per rating
+    pub prewarm_ratings: Vec<String>,
+}
+
+impl TrendingConfig {
+    pub fn new() -> Self {
+        dotenv().ok();
+        let giphy_api_key = env::var("GIPHY_API_KEY").ok().filter(|key| !key.is_empty());
+        if giphy_api_key.is_none() {
+            log::warn!("GIPHY_API_KEY is not set; trending stickers will only be served from cache");
+        }
+
+        let refresh_after_secs = env::var("TRENDING_REFRESH_SECS")
+            .ok()
+            .and_then(|v| v.parse::<i64>().ok())
+            .unwrap_or(300);
+        let stale_ttl_secs = env::var("TRENDING_STALE_TTL_SECS")
+            .ok()
+            .and_then(|v| v.parse::<u64>().ok())
+            .unwrap_or(86400);
+        let cache_size = env::var("TRENDING_CACHE_SIZE")
+            .ok()
+            .and_then(|v| v.parse::<usize>().ok())
+            .unwrap_or(50)
+            .clamp(1, 50); // Giphy caps trending pages at 50
+        let prewarm_ratings = env::var("TRENDING_PREWARM_RATINGS")
+            .unwrap_or_else(|_| "g".to_string())
+            .split(',')
+            .map(|r| r.trim().to_lowercase())
+            .filter(|r| TRENDING_RATINGS.contains(&r.as_str()))
+            .collect();
+
+        Self {
+            giphy_api_key,
+            refresh_after_secs,
+            stale_ttl_secs,
+            cache_size,
+            prewarm_ratings,
+        }
+    }
+}
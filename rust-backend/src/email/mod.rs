@@ -0,0 +1,76 @@
+use crate::configs::email::{EmailConfig, EmailTransport};
+use chrono::Utc;
+use futures::future::BoxFuture;
+use std::sync::Arc;
+use uuid::Uuid;
+
+pub struct EmailMessage {
+    pub to: String,
+    pub subject: String,
+    pub body: String,
+}
+
+// Delivery is behind a trait so an SMTP or API sender can be added without touching
+// the routes that send mail.
+pub trait EmailSender: Send + Sync {
+    fn send<'a>(&'a self, message: &'a EmailMessage) -> BoxFuture<'a, Result<(), String>>;
+}
+
+// For local development: the message, including any links, goes to the log
+pub struct LogSender {
+    from: String,
+}
+
+impl EmailSender for LogSender {
+    fn send<'a>(&'a self, message: &'a EmailMessage) -> BoxFuture<'a, Result<(), String>> {
+        Box::pin(async move {
+            log::info!(
+                "Email from {} to {}: {}\n{}",
+                self.from,
+                message.to,
+                message.subject,
+                message.body
+            );
+            Ok(())
+        })
+    }
+}
+
+// Writes one .eml file per message, for local use or a mail relay that picks them up
+pub struct FileSender {
+    from: String,
+    outbox_dir: String,
+}
+
+impl EmailSender for FileSender {
+    fn send<'a>(&'a self, message: &'a EmailMessage) -> BoxFuture<'a, Result<(), String>> {
+        Box::pin(async move {
+            tokio::fs::create_dir_all(&self.outbox_dir)
+                .await
+                .map_err(|e| format!("Failed to create {}: {}", self.outbox_dir, e))?;
+            let now = Utc::now();
+            let path = format!("{}/{}-{}.eml", self.outbox_dir, now.format("%Y%m%dT%H%M%S"), Uuid::new_v4());
+            let contents = format!(
+                "From: {}\r\nTo: {}\r\nSubject: {}\r\nDate: {}\r\nContent-Type: text/plain; charset=utf-8\r\n\r\n{}\r\n",
+                self.from,
+                message.to,
+                message.subject,
+                now.to_rfc2822(),
+                message.body
+            );
+            tokio::fs::write(&path, contents)
+                .await
+                .map_err(|e| format!("Failed to write {}: {}", path, e))
+        })
+    }
+}
+
+pub fn sender_from_config(config: &EmailConfig) -> Arc<dyn EmailSender> {
+    match config.transport {
+        EmailTransport::Log => Arc::new(LogSender { from: config.from.clone() }),
+        EmailTransport::File => Arc::new(FileSender {
+            from: config.from.clone(),
+            outbox_dir: config.outbox_dir.clone(),
+        }),
+    }
+}
@@ -1,22 +1,29 @@
+use crate::configs::accounts::AccountConfig;
+use crate::configs::api_keys::ApiKeyConfig;
 use crate::configs::env_load::_load_envs as load_envs;
+use crate::configs::cors::CorsConfig;
+use crate::configs::email::EmailConfig;
+use crate::configs::limits::RequestLimits;
+use crate::configs::oidc::OidcConfig;
+use crate::configs::password::PasswordPolicy;
+use crate::configs::rate_limit::RateLimitConfig;
+use crate::configs::retention::RetentionConfig;
+use crate::configs::security_headers::SecurityHeadersConfig;
+use crate::configs::tls::TlsConfig;
+use crate::configs::totp::TotpConfig;
+use crate::configs::trending::TrendingConfig;
+use crate::email;
 use crate::middleware;
 use crate::middleware::auth::AuthConfig;
 use crate::routes;
 use crate::structs::database_structs::DatabaseConnection;
-use actix_governor::{Governor, GovernorConfigBuilder};
+use crate::tls;
+use actix_web::middleware::from_fn;
 use actix_web::web;
 use actix_web::{self, middleware::Logger, web::Data, App, HttpServer};
-use actix_web_httpauth::middleware::HttpAuthentication;
 
 #[actix_web::main]
 pub async fn init_app() -> std::io::Result<()> {
-    // Setting up the Governor
-    let governor = GovernorConfigBuilder::default()
-        .seconds_per_request(30)
-        .burst_size(30)
-        .finish()
-        .unwrap();
-
     let db = DatabaseConnection::new()
         .await
         .expect("Failed to connect to database");
@@ -25,44 +32,146 @@ pub async fn init_app() -> std::io::Result<()> {
         .expect("Failed to initialize database schema");
     let redis = db.redis.clone();
     let auth_config = AuthConfig::new();
+    let trending_config = TrendingConfig::new();
+    let retention_config = RetentionConfig::new();
+    let account_config = AccountConfig::new();
+    let api_key_config = ApiKeyConfig::new();
+    let oidc_config = OidcConfig::new();
+    let totp_config = TotpConfig::new();
+    let password_policy = PasswordPolicy::new();
+    let email_config = EmailConfig::new();
+    let email_sender = email::sender_from_config(&email_config);
+    let request_limits = RequestLimits::new();
+    let rate_limit_config = RateLimitConfig::new();
+    let cors_config = std::sync::Arc::new(CorsConfig::new());
+    let security_headers_config = SecurityHeadersConfig::new();
+    let tls_config = TlsConfig::new();
+    let hsts = tls_config.hsts_header();
+
+    // Asymmetric signing: load (or create) keys before serving, then rotate on schedule
+    if auth_config.uses_signing_keys() {
+        auth_config
+            .refresh_keys(&db)
+            .await
+            .expect("Failed to load JWT signing keys");
+        let db = db.clone();
+        let auth_config = auth_config.clone();
+        actix_web::rt::spawn(async move {
+            let mut interval = tokio::time::interval(std::time::Duration::from_secs(auth_config.refresh_secs));
+            loop {
+                interval.tick().await;
+                if let Err(e) = auth_config.refresh_keys(&db).await {
+                    log::error!("JWT signing key refresh failed: {}", e);
+                }
+            }
+        });
+    }
+
+    if let Some((username, password)) = &account_config.bootstrap_admin {
+        match db.ensure_admin(username, password).await {
+            Ok(true) => log::info!("Created bootstrap admin {}", username),
+            Ok(false) => {}
+            Err(e) => log::error!("Failed to create bootstrap admin {}: {}", username, e),
+        }
+    }
+
+    // Pre-warm the trending cache and keep it refreshed ahead of expiry
+    if trending_config.giphy_api_key.is_some() {
+        let db = db.clone();
+        let trending_config = trending_config.clone();
+        actix_web::rt::spawn(async move {
+            let mut interval = tokio::time::interval(std::time::Duration::from_secs(
+                trending_config.refresh_after_secs.max(30) as u64,
+            ));
+            loop {
+                interval.tick().await;
+                for rating in &trending_config.prewarm_ratings {
+                    if let Err(e) = routes::recommend::refresh_trending(&db, &trending_config, rating).await {
+                        log::warn!("Trending pre-warm for rating {} failed: {}", rating, e);
+                    }
+                }
+            }
+        });
+    }
+
+    // Purge or anonymize interactions past the retention period
+    if let Some(retention_days) = retention_config.retention_days {
+        let db = db.clone();
+        let retention_config = retention_config.clone();
+        actix_web::rt::spawn(async move {
+            let mut interval =
+                tokio::time::interval(std::time::Duration::from_secs(retention_config.interval_secs));
+            loop {
+                interval.tick().await;
+                let cutoff = (chrono::Utc::now() - chrono::Duration::days(retention_days)).naive_utc();
+                match db.apply_history_retention(cutoff, retention_config.mode).await {
+                    Ok(0) => {}
+                    Ok(count) => log::info!(
+                        "History retention ({:?}) applied to {} interactions older than {}",
+                        retention_config.mode,
+                        count,
+                        cutoff
+                    ),
+                    Err(e) => log::error!("History retention job failed: {}", e),
+                }
+            }
+        });
+    }
+
+    // Erase soft-deleted users once their restore window has passed
+    {
+        let db = db.clone();
+        let account_config = account_config.clone();
+        actix_web::rt::spawn(async move {
+            let mut interval =
+                tokio::time::interval(std::time::Duration::from_secs(account_config.purge_interval_secs));
+            loop {
+                interval.tick().await;
+                let cutoff = (chrono::Utc::now() - chrono::Duration::days(account_config.restore_window_days)).naive_utc();
+                match db.purge_deleted_users(cutoff).await {
+                    Ok(0) => {}
+                    Ok(count) => log::info!("Erased {} users deleted before {}", count, cutoff),
+                    Err(e) => log::error!("Deleted user purge failed: {}", e),
+                }
+            }
+        });
+    }
 
     // Start the server
-    HttpServer::new(move || {
-        let auth = HttpAuthentication::bearer(middleware::validate::jwt_middleware);
+    let server = HttpServer::new(move || {
         App::new()
             .wrap(Logger::default())
-            .wrap(Governor::new(&governor))
-            .wrap(middleware::cors_mgt::handle_cors())
+            .wrap(middleware::security_headers::hsts(hsts.clone()))
+            .wrap(from_fn(middleware::rate_limit::by_ip))
+            .wrap(middleware::cors_mgt::handle_cors(cors_config.clone()))
+            // Outermost, so CORS and rate limit rejections carry the headers as well
+            .wrap(from_fn(middleware::security_headers::set_headers))
+            .app_data(middleware::validation::json_config(request_limits.json_payload_bytes))
             .app_data(Data::new(db.clone()))
             .app_data(Data::new(redis.clone()))
             .app_data(web::Data::new(auth_config.clone()))
-            .service(
-                web::scope("/v1")
-                    .configure(routes::health::init_routes)
-                    .configure(routes::auth::init_routes)
-                    
-                    .service(
-                        web::scope("/sticker")
-                            .wrap(auth.clone())
-                            .configure(routes::recommend::init_routes), // Route becomes /v1/sticker/find
-                    )
-                    .service(
-                        web::scope("/user")
-                            .wrap(auth.clone())
-                            .configure(routes::user::init_routes)
-                            .configure(routes::history::init_routes)
-                            .configure(routes::top_stickers::init_routes),
-
-                    )
-                    .service(
-                        web::scope("/admin")
-                            .wrap(auth)
-                            .configure(routes::admin::init_routes),
-                    ),
-            )
+            .app_data(web::Data::new(trending_config.clone()))
+            .app_data(web::Data::new(account_config.clone()))
+            .app_data(web::Data::new(api_key_config.clone()))
+            .app_data(web::Data::new(oidc_config.clone()))
+            .app_data(web::Data::new(totp_config.clone()))
+            .app_data(web::Data::new(rate_limit_config.clone()))
+            .app_data(web::Data::new(security_headers_config.clone()))
+            .app_data(web::Data::new(password_policy.clone()))
+            .app_data(web::Data::new(email_config.clone()))
+            .app_data(web::Data::from(email_sender.clone()))
+            .configure(routes::init_api)
     })
-    .workers(4)
-    .bind((load_envs().0, load_envs().1))?
-    .run()
-    .await
+    .workers(4);
+
+    let (host, port) = load_envs();
+    let server = if tls_config.enabled() {
+        // HTTPS with HTTP/2 negotiated over ALPN; plain HTTP is not served alongside it
+        let server_config = tls::server_config(&tls_config).map_err(std::io::Error::other)?;
+        log::info!("Serving HTTPS on {}:{}", host, port);
+        server.bind_rustls_0_23((host, port), server_config)?
+    } else {
+        server.bind((host, port))?
+    };
+    server.run().await
 }
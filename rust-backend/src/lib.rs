@@ -0,0 +1,12 @@
+// The server as a library, so the integration tests in tests/ can mount the real routes
+// Config types read the environment in new(); a Default that did the same would hide that
+#![allow(clippy::new_without_default)]
+
+pub mod configs;
+pub mod email;
+pub mod init;
+pub mod middleware;
+pub mod models;
+pub mod routes;
+pub mod structs;
+pub mod tls;
@@ -1,11 +1,5 @@
-mod configs;
-mod init;
-mod middleware;
-mod models;
-mod routes;
-mod structs;
-
 use log::{error, info};
+use rust_backend::init;
 use std::env;
 
 fn main() {
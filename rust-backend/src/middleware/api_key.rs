@@ -0,0 +1,111 @@
+use actix_web::http::Method;
+use rand::rngs::OsRng;
+use rand::RngCore;
+use sha2::{Digest, Sha256};
+
+// Bearer tokens starting with this are API keys rather than JWTs
+pub const API_KEY_PREFIX: &str = "stk_";
+const DISPLAY_PREFIX_LEN: usize = 12; // "stk_" plus 8 hex chars, safe to show in listings
+
+pub const SCOPES: [&str; 7] = [
+    "sticker:find",
+    "sticker:feedback",
+    "history:read",
+    "history:write",
+    "favorites:read",
+    "favorites:write",
+    "analytics:read",
+];
+
+// Returns (plaintext key, display prefix, hash). Only the hash and prefix are stored.
+pub fn generate_api_key() -> (String, String, String) {
+    let mut bytes = [0u8; 32];
+    OsRng.fill_bytes(&mut bytes);
+    let key = format!("{}{}", API_KEY_PREFIX, hex::encode(bytes));
+    let prefix = key[..DISPLAY_PREFIX_LEN].to_string();
+    let hash = hash_api_key(&key);
+    (key, prefix, hash)
+}
+
+// Keys are 256 random bits, so a plain SHA-256 is enough; no salt or slow hash needed
+pub fn hash_api_key(key: &str) -> String {
+    hex::encode(Sha256::digest(key.as_bytes()))
+}
+
+// The scope an API key needs for a route, or None if API keys can't call it at all
+// (account settings, key management, exports and the admin scope).
+pub fn required_scope(method: &Method, path: &str) -> Option<&'static str> {
+    let path = path.trim_end_matches('/');
+    let is_read = *method == Method::GET;
+    match path {
+        "/v1/sticker/find" | "/v1/sticker/dashboard-find" | "/v1/sticker/dashboard-trending" => {
+            Some("sticker:find")
+        }
+        "/v1/sticker/feedback" => Some("sticker:feedback"),
+        "/v1/user/top-stickers" | "/v1/user/analytics" => Some("analytics:read"),
+        "/v1/user/history" if is_read => Some("history:read"),
+        _ if path == "/v1/user/history" || path.starts_with("/v1/user/history/") => {
+            (*method == Method::DELETE).then_some("history:write")
+        }
+        _ if path == "/v1/user/favorites"
+            || path == "/v1/user/collections"
+            || path.starts_with("/v1/user/collections/") =>
+        {
+            Some(if is_read { "favorites:read" } else { "favorites:write" })
+        }
+        _ => None,
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn routes_map_to_their_scopes() {
+        for (method, path, scope) in [
+            (Method::POST, "/v1/sticker/find", Some("sticker:find")),
+            (Method::POST, "/v1/sticker/dashboard-find/", Some("sticker:find")),
+            (Method::GET, "/v1/sticker/dashboard-trending", Some("sticker:find")),
+            (Method::POST, "/v1/sticker/feedback", Some("sticker:feedback")),
+            (Method::GET, "/v1/user/top-stickers", Some("analytics:read")),
+            (Method::GET, "/v1/user/analytics", Some("analytics:read")),
+            (Method::GET, "/v1/user/history", Some("history:read")),
+            (Method::DELETE, "/v1/user/history", Some("history:write")),
+            (Method::DELETE, "/v1/user/history/42", Some("history:write")),
+            (Method::POST, "/v1/user/history", None),
+            (Method::GET, "/v1/user/favorites", Some("favorites:read")),
+            (Method::POST, "/v1/user/favorites", Some("favorites:write")),
+            (Method::GET, "/v1/user/collections/7", Some("favorites:read")),
+            (Method::DELETE, "/v1/user/collections/7/items", Some("favorites:write")),
+        ] {
+            assert_eq!(required_scope(&method, path), scope, "{} {}", method, path);
+        }
+    }
+
+    #[test]
+    fn account_and_admin_routes_need_a_login() {
+        for (method, path) in [
+            (Method::GET, "/v1/user/api-keys"),
+            (Method::POST, "/v1/user/api-keys"),
+            (Method::DELETE, "/v1/user/delete"),
+            (Method::GET, "/v1/user/export"),
+            (Method::PUT, "/v1/user/preferences"),
+            (Method::POST, "/v1/user/logout"),
+            (Method::GET, "/v1/admin/users"),
+            (Method::GET, "/v1/user/favoritesx"),
+        ] {
+            assert_eq!(required_scope(&method, path), None, "{} {}", method, path);
+        }
+    }
+
+    #[test]
+    fn generated_keys_hash_to_what_is_stored() {
+        let (key, prefix, hash) = generate_api_key();
+        assert!(key.starts_with(API_KEY_PREFIX));
+        assert_eq!(key.len(), API_KEY_PREFIX.len() + 64);
+        assert!(key.starts_with(&prefix));
+        assert_eq!(hash_api_key(&key), hash);
+        assert_ne!(generate_api_key().0, key);
+    }
+}
@@ -1,7 +1,19 @@
-use chrono::{Duration, Utc};
-use jsonwebtoken::{decode, encode, DecodingKey, EncodingKey, Header, Validation};
+use crate::middleware::signing_keys::{algorithm_name, generate_key, load_key, open_private_key, seal_private_key};
+use crate::structs::database_structs::{DatabaseConnection, SigningKeyRow};
+use actix_web::dev::Payload;
+use actix_web::error::Error as ActixError;
+use actix_web::{FromRequest, HttpMessage, HttpRequest};
+use chrono::{Duration, NaiveDateTime, Utc};
+use jsonwebtoken::errors::ErrorKind;
+use jsonwebtoken::{decode, decode_header, encode, Algorithm, DecodingKey, EncodingKey, Header, Validation};
 use serde::{Deserialize, Serialize};
+use base64::engine::general_purpose::STANDARD;
+use base64::Engine;
+use serde_json::{json, Value};
 use std::env;
+use std::future::{ready, Ready};
+use std::sync::{Arc, RwLock};
+use uuid::Uuid;
 
 #[derive(Debug, Serialize, Deserialize)]
 pub struct Claims {
@@ -9,18 +21,170 @@ pub struct Claims {
     pub sub: String,  // User ID
     pub role: String, // "user" or "admin"
     pub exp: usize,   // Expiration time
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub impersonator: Option<String>, // Admin ID when an admin is viewing as this user
+}
+
+// A key from jwt_signing_keys, with the window in which it signs new tokens
+struct SigningKey {
+    kid: String,
+    algorithm: Algorithm,
+    encoding: EncodingKey,
+    decoding: DecodingKey,
+    sign_from: NaiveDateTime,
+    sign_until: Option<NaiveDateTime>, // start of the next key
+    public_jwk: Value,
 }
 
 #[derive(Clone)]
 pub struct AuthConfig {
-    secret: String,
+    secret: Option<String>, // HS256 secret; with asymmetric keys it only verifies older tokens
+    algorithm: Algorithm,
+    rotation: Duration,
+    pub refresh_secs: u64,
+    key_encryption: Option<[u8; 32]>, // AES-256-GCM key for private keys at rest
+    keys: Arc<RwLock<Vec<SigningKey>>>, // shared by all workers
 }
 
+// New keys are published this long before they sign, so every instance and JWKS
+// consumer has them by the time tokens appear
+const PUBLISH_AHEAD_MINS: i64 = 10;
+// Longest token lifetime plus slack: how long a replaced key keeps verifying
+const VERIFY_GRACE_HOURS: i64 = 25;
+
 impl AuthConfig {
     pub fn new() -> Self {
         dotenv::dotenv().ok();
-        let secret = env::var("JWT_SECRET").expect("JWT_SECRET must be set in .env");
-        Self { secret }
+        let secret = env::var("JWT_SECRET").ok().filter(|s| !s.is_empty());
+        let algorithm = match env::var("JWT_ALGORITHM").unwrap_or_default().as_str() {
+            "" | "HS256" => Algorithm::HS256,
+            "RS256" => Algorithm::RS256,
+            "EdDSA" => Algorithm::EdDSA,
+            other => panic!("Unsupported JWT_ALGORITHM {}; use HS256, RS256 or EdDSA", other),
+        };
+        if algorithm == Algorithm::HS256 && secret.is_none() {
+            panic!("JWT_SECRET must be set in .env");
+        }
+        let rotation_days = env::var("JWT_KEY_ROTATION_DAYS")
+            .ok()
+            .and_then(|v| v.parse::<i64>().ok())
+            .unwrap_or(30)
+            .max(2);
+        let refresh_secs = env::var("JWT_KEY_REFRESH_SECS")
+            .ok()
+            .and_then(|v| v.parse::<u64>().ok())
+            .unwrap_or(60)
+            .clamp(10, 300);
+        let key_encryption = env::var("JWT_KEY_ENCRYPTION_KEY").ok().filter(|s| !s.is_empty()).map(|key| {
+            STANDARD
+                .decode(key.trim())
+                .ok()
+                .and_then(|bytes| <[u8; 32]>::try_from(bytes).ok())
+                .expect("JWT_KEY_ENCRYPTION_KEY must be 32 bytes, base64 encoded")
+        });
+        if algorithm != Algorithm::HS256 && key_encryption.is_none() {
+            log::warn!("JWT_KEY_ENCRYPTION_KEY is not set; JWT signing keys are stored unencrypted");
+        }
+
+        Self {
+            secret,
+            algorithm,
+            rotation: Duration::days(rotation_days),
+            refresh_secs,
+            key_encryption,
+            keys: Arc::new(RwLock::new(Vec::new())),
+        }
+    }
+
+    pub fn uses_signing_keys(&self) -> bool {
+        self.algorithm != Algorithm::HS256
+    }
+
+    // Creates a key when none exists, the newest is due for rotation or the configured
+    // algorithm changed; drops keys past their grace period; reloads the ring.
+    pub async fn refresh_keys(&self, db: &DatabaseConnection) -> Result<(), String> {
+        let now = Utc::now().naive_utc();
+        let rows = db.get_signing_keys().await.map_err(|e| e.to_string())?;
+        let newest = rows.last();
+        if let Some(sign_from) = self.next_sign_from(newest, now) {
+            let algorithm = self.algorithm;
+            let key = tokio::task::spawn_blocking(move || generate_key(algorithm))
+                .await
+                .map_err(|e| e.to_string())??;
+            let private_key = seal_private_key(&key.private_key, &key.kid, self.key_encryption.as_ref())?;
+            let inserted = db
+                .insert_signing_key(
+                    &key.kid,
+                    algorithm_name(algorithm),
+                    &private_key,
+                    &key.public_jwk,
+                    sign_from,
+                    newest.map(|k| k.sign_from),
+                )
+                .await
+                .map_err(|e| e.to_string())?;
+            if inserted {
+                log::info!("Added JWT signing key {} ({:?}), signing from {}", key.kid, algorithm, sign_from);
+            }
+        }
+
+        let retired = db
+            .delete_retired_signing_keys(now - Duration::hours(VERIFY_GRACE_HOURS))
+            .await
+            .map_err(|e| e.to_string())?;
+        if retired > 0 {
+            log::info!("Removed {} retired JWT signing keys", retired);
+        }
+
+        let rows = db.get_signing_keys().await.map_err(|e| e.to_string())?;
+        self.load_keys(&rows);
+        Ok(())
+    }
+
+    // When the next key should start signing, or None while the newest key is still current
+    fn next_sign_from(&self, newest: Option<&SigningKeyRow>, now: NaiveDateTime) -> Option<NaiveDateTime> {
+        match newest {
+            None => Some(now),
+            Some(key) if key.algorithm != algorithm_name(self.algorithm) => {
+                Some(now + Duration::minutes(PUBLISH_AHEAD_MINS))
+            }
+            Some(key) if key.sign_from + self.rotation - Duration::minutes(PUBLISH_AHEAD_MINS) <= now => {
+                Some((key.sign_from + self.rotation).max(now + Duration::minutes(PUBLISH_AHEAD_MINS)))
+            }
+            Some(_) => None,
+        }
+    }
+
+    // Replaces the in-memory ring with the stored keys, oldest first
+    fn load_keys(&self, rows: &[SigningKeyRow]) {
+        let mut keys = Vec::with_capacity(rows.len());
+        for (i, row) in rows.iter().enumerate() {
+            let loaded = open_private_key(&row.private_key, &row.kid, self.key_encryption.as_ref())
+                .and_then(|private_key| load_key(&row.algorithm, &private_key, &row.public_jwk));
+            match loaded {
+                Ok((algorithm, encoding, decoding)) => keys.push(SigningKey {
+                    kid: row.kid.clone(),
+                    algorithm,
+                    encoding,
+                    decoding,
+                    sign_from: row.sign_from,
+                    sign_until: rows.get(i + 1).map(|next| next.sign_from),
+                    public_jwk: row.public_jwk.clone(),
+                }),
+                Err(e) => log::error!("Skipping unusable JWT signing key {}: {}", row.kid, e),
+            }
+        }
+        *self.keys.write().expect("signing key lock poisoned") = keys;
+    }
+
+    // Public keys for /.well-known/jwks.json, including ones not signing yet or any more
+    pub fn public_jwks(&self) -> Vec<Value> {
+        self.keys
+            .read()
+            .expect("signing key lock poisoned")
+            .iter()
+            .map(|key| key.public_jwk.clone())
+            .collect()
     }
 }
 
@@ -29,34 +193,82 @@ pub fn create_token(
     role: &str,
     config: &AuthConfig,
 ) -> Result<String, jsonwebtoken::errors::Error> {
-    let expiration = Utc::now()
-        .checked_add_signed(Duration::hours(24))
-        .expect("valid timestamp")
-        .timestamp() as usize;
-
     let claims = Claims {
         sub: user_id.to_string(),
         role: role.to_string(), // or "admin" based on your logic
-        exp: expiration,
+        exp: expires_in(Duration::hours(24)),
+        impersonator: None,
+    };
+    encode_claims(&claims, config)
+}
+
+// Short-lived, read-only token that lets an admin see what the user sees
+pub fn create_impersonation_token(
+    user_id: &str,
+    admin_id: &str,
+    ttl: Duration,
+    config: &AuthConfig,
+) -> Result<String, jsonwebtoken::errors::Error> {
+    let claims = Claims {
+        sub: user_id.to_string(),
+        role: "user".to_string(),
+        exp: expires_in(ttl),
+        impersonator: Some(admin_id.to_string()),
     };
+    encode_claims(&claims, config)
+}
 
-    let token = encode(
-        &Header::default(),
-        &claims,
-        &EncodingKey::from_secret(config.secret.as_bytes()),
-    )?;
-    Ok(token)
+fn expires_in(ttl: Duration) -> usize {
+    Utc::now()
+        .checked_add_signed(ttl)
+        .expect("valid timestamp")
+        .timestamp() as usize
 }
 
+fn encode_claims(claims: &Claims, config: &AuthConfig) -> Result<String, jsonwebtoken::errors::Error> {
+    if !config.uses_signing_keys() {
+        let secret = config.secret.as_deref().unwrap_or_default();
+        return encode(&Header::default(), claims, &EncodingKey::from_secret(secret.as_bytes()));
+    }
+    let now = Utc::now().naive_utc();
+    let keys = config.keys.read().expect("signing key lock poisoned");
+    let key = keys
+        .iter()
+        .rev()
+        .find(|key| key.sign_from <= now)
+        .ok_or(ErrorKind::InvalidKeyFormat)?;
+    let mut header = Header::new(key.algorithm);
+    header.kid = Some(key.kid.clone());
+    encode(&header, claims, &key.encoding)
+}
+
+// Tokens with a kid are checked against that key only, with its own algorithm. Tokens
+// without one are legacy HS256 tokens, accepted while JWT_SECRET is set.
 pub fn validate_token(
     token: &str,
     config: &AuthConfig,
 ) -> Result<Claims, jsonwebtoken::errors::Error> {
-    let decoded = decode::<Claims>(
-        token,
-        &DecodingKey::from_secret(config.secret.as_bytes()),
-        &Validation::default(),
-    )?;
+    let header = decode_header(token)?;
+    let decoded = match header.kid {
+        Some(kid) => {
+            let now = Utc::now().naive_utc();
+            let keys = config.keys.read().expect("signing key lock poisoned");
+            let key = keys
+                .iter()
+                .find(|key| key.kid == kid)
+                .filter(|key| key.sign_until.is_none_or(|until| until + Duration::hours(VERIFY_GRACE_HOURS) > now))
+                .ok_or(ErrorKind::InvalidToken)?;
+            decode::<Claims>(token, &key.decoding, &Validation::new(key.algorithm))?
+        }
+        None => {
+            let secret = config.secret.as_deref().ok_or(ErrorKind::InvalidToken)?;
+            decode::<Claims>(
+                token,
+                &DecodingKey::from_secret(secret.as_bytes()),
+                &Validation::default(),
+            )?
+        }
+    };
     Ok(decoded.claims)
 }
 
@@ -65,10 +277,213 @@ pub fn validate_token(
 pub struct AuthData {
     pub id: String,
     pub role: String,
+    pub impersonator: Option<String>, // Admin ID for read-only impersonation sessions
+    pub api_key_id: Option<String>,   // Set when the request authenticated with an API key
 }
 
 impl AuthData {
     pub fn new(id: String, role: String) -> Self {
-        AuthData { id, role }
+        AuthData { id, role, impersonator: None, api_key_id: None }
+    }
+
+    pub fn impersonated_by(mut self, admin_id: String) -> Self {
+        self.impersonator = Some(admin_id);
+        self
+    }
+
+    pub fn via_api_key(mut self, key_id: String) -> Self {
+        self.api_key_id = Some(key_id);
+        self
+    }
+}
+
+// The signed-in user, as established by jwt_middleware. Handlers take this instead of
+// trusting any user identifier in the request body.
+#[derive(Clone)]
+pub struct AuthUser {
+    pub id: Uuid,
+    pub auth: AuthData,
+}
+
+impl FromRequest for AuthUser {
+    type Error = ActixError;
+    type Future = Ready<Result<Self, ActixError>>;
+
+    fn from_request(req: &HttpRequest, _payload: &mut Payload) -> Self::Future {
+        let Some(auth) = req.extensions().get::<AuthData>().cloned() else {
+            log::error!("Auth data not found in request extensions for {}", req.path());
+            return ready(Err(actix_web::error::ErrorUnauthorized(json!({ "error": "Not authenticated" }))));
+        };
+        ready(match Uuid::parse_str(&auth.id) {
+            Ok(id) => Ok(AuthUser { id, auth }),
+            Err(_) => {
+                log::error!("Invalid user ID in token");
+                Err(actix_web::error::ErrorBadRequest(json!({ "error": "Invalid user ID" })))
+            }
+        })
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::middleware::signing_keys::GeneratedKey;
+
+    fn config(algorithm: Algorithm, secret: Option<&str>) -> AuthConfig {
+        AuthConfig {
+            secret: secret.map(String::from),
+            algorithm,
+            rotation: Duration::days(30),
+            refresh_secs: 60,
+            key_encryption: None,
+            keys: Arc::default(),
+        }
+    }
+
+    fn row(key: &GeneratedKey, sign_from: NaiveDateTime) -> SigningKeyRow {
+        SigningKeyRow {
+            kid: key.kid.clone(),
+            algorithm: "EdDSA".to_string(),
+            private_key: key.private_key.clone(),
+            public_jwk: key.public_jwk.clone(),
+            sign_from,
+        }
+    }
+
+    fn kid_of(token: &str) -> Option<String> {
+        decode_header(token).unwrap().kid
+    }
+
+    // A token signed with a specific key from the ring, whichever key is current
+    fn token_signed_by(config: &AuthConfig, kid: &str) -> String {
+        let keys = config.keys.read().unwrap();
+        let key = keys.iter().find(|key| key.kid == kid).unwrap();
+        let mut header = Header::new(key.algorithm);
+        header.kid = Some(key.kid.clone());
+        let claims = Claims { sub: "u1".to_string(), role: "user".to_string(), exp: expires_in(Duration::hours(1)), impersonator: None };
+        encode(&header, &claims, &key.encoding).unwrap()
+    }
+
+    #[test]
+    fn keys_rotate_on_schedule_and_publish_ahead() {
+        let config = config(Algorithm::EdDSA, None);
+        let key = generate_key(Algorithm::EdDSA).unwrap();
+        let now = Utc::now().naive_utc();
+        let ahead = now + Duration::minutes(PUBLISH_AHEAD_MINS);
+
+        assert_eq!(config.next_sign_from(None, now), Some(now));
+        assert_eq!(config.next_sign_from(Some(&row(&key, now - Duration::days(1))), now), None);
+        // Not yet within the publish-ahead window of the rotation
+        let current = row(&key, now - Duration::days(30) + Duration::minutes(11));
+        assert_eq!(config.next_sign_from(Some(&current), now), None);
+        // Due: the next key starts exactly at the rotation, which is still ahead
+        let due = row(&key, now - Duration::days(30) + Duration::minutes(10));
+        assert_eq!(config.next_sign_from(Some(&due), now), Some(due.sign_from + Duration::days(30)));
+        // Overdue, e.g. after downtime: never sign with a key nobody has seen yet
+        let overdue = row(&key, now - Duration::days(45));
+        assert_eq!(config.next_sign_from(Some(&overdue), now), Some(ahead));
+
+        let mut rsa = row(&key, now - Duration::days(1));
+        rsa.algorithm = "RS256".to_string();
+        assert_eq!(config.next_sign_from(Some(&rsa), now), Some(ahead));
+    }
+
+    #[test]
+    fn upcoming_keys_are_published_before_they_sign() {
+        let config = config(Algorithm::EdDSA, None);
+        let (current, upcoming) = (generate_key(Algorithm::EdDSA).unwrap(), generate_key(Algorithm::EdDSA).unwrap());
+        let now = Utc::now().naive_utc();
+        config.load_keys(&[
+            row(&current, now - Duration::days(1)),
+            row(&upcoming, now + Duration::minutes(PUBLISH_AHEAD_MINS)),
+        ]);
+
+        let kids: Vec<Value> = config.public_jwks().iter().map(|jwk| jwk["kid"].clone()).collect();
+        assert_eq!(kids, vec![json!(current.kid), json!(upcoming.kid)]);
+
+        let token = create_token("u1", "user", &config).unwrap();
+        assert_eq!(kid_of(&token), Some(current.kid.clone()));
+        assert_eq!(validate_token(&token, &config).unwrap().sub, "u1");
+        // Tokens from the upcoming key verify as soon as it signs
+        assert!(validate_token(&token_signed_by(&config, &upcoming.kid), &config).is_ok());
+    }
+
+    #[test]
+    fn replaced_keys_verify_only_during_the_grace_window() {
+        let (old, new) = (generate_key(Algorithm::EdDSA).unwrap(), generate_key(Algorithm::EdDSA).unwrap());
+        let now = Utc::now().naive_utc();
+
+        let recent = config(Algorithm::EdDSA, None);
+        recent.load_keys(&[row(&old, now - Duration::days(30)), row(&new, now - Duration::hours(24))]);
+        assert_eq!(kid_of(&create_token("u1", "user", &recent).unwrap()), Some(new.kid.clone()));
+        let old_token = token_signed_by(&recent, &old.kid);
+        assert!(validate_token(&old_token, &recent).is_ok());
+
+        let past = config(Algorithm::EdDSA, None);
+        past.load_keys(&[
+            row(&old, now - Duration::days(30)),
+            row(&new, now - Duration::hours(VERIFY_GRACE_HOURS) - Duration::minutes(1)),
+        ]);
+        let err = validate_token(&old_token, &past).unwrap_err();
+        assert_eq!(*err.kind(), ErrorKind::InvalidToken);
+    }
+
+    #[test]
+    fn unknown_and_retired_kids_are_rejected() {
+        let (old, new) = (generate_key(Algorithm::EdDSA).unwrap(), generate_key(Algorithm::EdDSA).unwrap());
+        let now = Utc::now().naive_utc();
+        let config = config(Algorithm::EdDSA, Some("legacy-secret"));
+        config.load_keys(&[row(&old, now - Duration::days(30)), row(&new, now - Duration::hours(1))]);
+        let old_token = token_signed_by(&config, &old.kid);
+
+        // Retired: the key has been deleted and the ring reloaded
+        config.load_keys(&[row(&new, now - Duration::hours(1))]);
+        assert_eq!(*validate_token(&old_token, &config).unwrap_err().kind(), ErrorKind::InvalidToken);
+
+        // Unknown: a kid this server never issued, even when the signature checks out elsewhere
+        let stranger = jsonwebtoken::encode(
+            &Header { kid: Some("not-a-key".to_string()), ..Header::default() },
+            &Claims { sub: "u1".to_string(), role: "user".to_string(), exp: expires_in(Duration::hours(1)), impersonator: None },
+            &EncodingKey::from_secret(b"legacy-secret"),
+        )
+        .unwrap();
+        assert_eq!(*validate_token(&stranger, &config).unwrap_err().kind(), ErrorKind::InvalidToken);
+    }
+
+    #[test]
+    fn legacy_hs256_tokens_need_the_secret() {
+        let legacy = config(Algorithm::HS256, Some("legacy-secret"));
+        let token = create_token("u1", "user", &legacy).unwrap();
+        assert_eq!(kid_of(&token), None);
+        assert_eq!(validate_token(&token, &legacy).unwrap().sub, "u1");
+
+        // After switching algorithms the secret keeps verifying tokens issued before
+        let key = generate_key(Algorithm::EdDSA).unwrap();
+        let switched = config(Algorithm::EdDSA, Some("legacy-secret"));
+        switched.load_keys(&[row(&key, Utc::now().naive_utc())]);
+        assert!(validate_token(&token, &switched).is_ok());
+        assert!(validate_token(&token, &config(Algorithm::EdDSA, Some("other-secret"))).is_err());
+        assert_eq!(
+            *validate_token(&token, &config(Algorithm::EdDSA, None)).unwrap_err().kind(),
+            ErrorKind::InvalidToken
+        );
+    }
+
+    #[test]
+    fn encrypted_keys_need_the_encryption_key() {
+        let key = generate_key(Algorithm::EdDSA).unwrap();
+        let mut sealed = row(&key, Utc::now().naive_utc());
+        sealed.private_key = seal_private_key(&key.private_key, &key.kid, Some(&[7u8; 32])).unwrap();
+
+        let mut with_key = config(Algorithm::EdDSA, None);
+        with_key.key_encryption = Some([7u8; 32]);
+        with_key.load_keys(std::slice::from_ref(&sealed));
+        let token = create_token("u1", "user", &with_key).unwrap();
+        assert!(validate_token(&token, &with_key).is_ok());
+
+        let without_key = config(Algorithm::EdDSA, None);
+        without_key.load_keys(&[sealed]);
+        assert!(without_key.public_jwks().is_empty());
+        assert!(create_token("u1", "user", &without_key).is_err());
     }
 }
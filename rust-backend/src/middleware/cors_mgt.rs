@@ -1,18 +1,27 @@
+use crate::configs::cors::CorsConfig;
 use actix_cors::Cors;
 use actix_web::http::header;
-use std::env;
+use std::sync::Arc;
 
-pub fn handle_cors() -> Cors {
-    if let Ok(_allowed_origin) = env::var("FRONTEND_URL") {
-        Cors::default()
-            //            .allowed_origin(&allowed_origin) // ✅ only this is valid
-            .allow_any_origin()
-            .allowed_methods(vec!["GET", "POST", "PUT", "DELETE"])
-            .allowed_headers(vec![header::AUTHORIZATION, header::CONTENT_TYPE])
-            .supports_credentials()
-    } else {
-        // fallback: allow all
-        eprintln!("⚠️  FRONTEND_URL not set — defaulting to permissive CORS");
-        Cors::permissive()
-    }
+// Origins are checked against the allowlist for the request path, so routes can be opened
+// wider (JWKS) or narrower (admin) than the rest of the API. Auth uses bearer tokens, never
+// cookies, so credentials are not allowed.
+pub fn handle_cors(config: Arc<CorsConfig>) -> Cors {
+    let max_age = config.max_age_secs;
+    Cors::default()
+        .allowed_origin_fn(move |origin, req_head| {
+            let allowed = origin
+                .to_str()
+                .map(|origin| config.is_allowed(origin, req_head.uri.path()))
+                .unwrap_or(false);
+            if !allowed {
+                log::warn!("CORS: rejected origin {:?} for {}", origin, req_head.uri.path());
+            }
+            allowed
+        })
+        .allowed_methods(vec!["GET", "POST", "PUT", "DELETE"])
+        .allowed_headers(vec![header::AUTHORIZATION, header::CONTENT_TYPE])
+        // Let the frontend read its remaining rate limit
+        .expose_headers(vec!["ratelimit-limit", "ratelimit-remaining", "ratelimit-reset", "ratelimit-policy", "retry-after"])
+        .max_age(max_age)
 }
@@ -1,3 +1,9 @@
+pub mod api_key;
 pub mod auth;
 pub mod cors_mgt;
+pub mod signing_keys;
+pub mod totp;
 pub mod validate;
+pub mod validation;
+pub mod rate_limit;
+pub mod security_headers;
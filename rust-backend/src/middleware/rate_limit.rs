@@ -0,0 +1,238 @@
+use crate::configs::rate_limit::{LimitKey, RateLimitConfig, RatePolicy};
+use crate::middleware::auth::AuthData;
+use crate::structs::database_structs::DatabaseConnection;
+use actix_web::body::{EitherBody, MessageBody};
+use actix_web::dev::{ServiceRequest, ServiceResponse};
+use actix_web::http::header::{HeaderMap, HeaderName, HeaderValue, RETRY_AFTER};
+use actix_web::middleware::Next;
+use actix_web::{web, Error as ActixError, HttpMessage, HttpResponse};
+use chrono::Utc;
+use serde_json::json;
+
+const SECS_PER_DAY: u64 = 86_400;
+
+struct Usage {
+    limit: u64,
+    used: u64,
+    reset_secs: u64,
+    daily: bool,
+}
+
+type LimitedResponse<B> = Result<ServiceResponse<EitherBody<B>>, ActixError>;
+
+// Wraps the whole app and limits unauthenticated routes by client IP. Per-account routes pass
+// through here and are limited by `by_account` once the token has been checked.
+pub async fn by_ip<B: MessageBody>(req: ServiceRequest, next: Next<B>) -> LimitedResponse<B> {
+    let Some((policy, trust_proxy)) = policy_for(&req, LimitKey::Ip) else {
+        return pass(req, next).await;
+    };
+    let ip = if trust_proxy {
+        req.connection_info().realip_remote_addr().map(str::to_string)
+    } else {
+        req.peer_addr().map(|addr| addr.ip().to_string())
+    }
+    .unwrap_or_else(|| "unknown".to_string());
+    enforce(req, next, &policy, format!("ip:{}", ip), None).await
+}
+
+// Wraps the authenticated scopes, inside jwt_middleware. API keys are held to the api_key
+// window on every route instead of the route's, but share the owner's daily quota. Keys live
+// under user:<id>: so erasure clears them.
+pub async fn by_account<B: MessageBody>(req: ServiceRequest, next: Next<B>) -> LimitedResponse<B> {
+    let Some((policy, _)) = policy_for(&req, LimitKey::Account) else {
+        return pass(req, next).await;
+    };
+    let Some(auth) = req.extensions().get::<AuthData>().cloned() else {
+        return pass(req, next).await;
+    };
+    let user = format!("user:{}", auth.id);
+    let quota = policy.daily_quota.map(|limit| (limit, format!("{}:quota:{}", user, policy.name)));
+    match (auth.role.as_str(), &auth.api_key_id) {
+        ("admin", _) => enforce(req, next, &policy, format!("admin:{}", auth.id), None).await,
+        (_, Some(key_id)) => {
+            let Some(window) = req
+                .app_data::<web::Data<RateLimitConfig>>()
+                .map(|config| config.api_key_policy().clone())
+            else {
+                return pass(req, next).await;
+            };
+            enforce(req, next, &window, format!("{}:api_key:{}", user, key_id), quota).await
+        }
+        _ => enforce(req, next, &policy, user, quota).await,
+    }
+}
+
+fn policy_for(req: &ServiceRequest, key: LimitKey) -> Option<(RatePolicy, bool)> {
+    let config = req.app_data::<web::Data<RateLimitConfig>>()?;
+    if !config.enabled {
+        return None;
+    }
+    config
+        .policy_for(req.method(), req.path())
+        .filter(|policy| policy.key == key)
+        .map(|policy| (policy.clone(), config.trust_proxy))
+}
+
+async fn pass<B: MessageBody>(req: ServiceRequest, next: Next<B>) -> LimitedResponse<B> {
+    next.call(req).await.map(ServiceResponse::map_into_left_body)
+}
+
+async fn enforce<B: MessageBody>(
+    req: ServiceRequest,
+    next: Next<B>,
+    policy: &RatePolicy,
+    identity: String,
+    quota: Option<(u64, String)>, // daily limit and key prefix of the quota to charge
+) -> LimitedResponse<B> {
+    let Some(db) = req.app_data::<web::Data<DatabaseConnection>>().cloned() else {
+        return pass(req, next).await;
+    };
+    let now = Utc::now().timestamp().max(0) as u64;
+    let (window, reset_secs) = window_at(now, policy.window_secs);
+    let window_key = format!("{}:rate:{}:{}", identity, policy.name, window);
+    // Fail open: an unavailable Redis shouldn't take the API down with it
+    let used = match db.count_rate_limit_hits(&[(window_key, policy.window_secs * 2)]).await {
+        Ok(counts) => counts[0],
+        Err(e) => {
+            log::error!("Rate limiting skipped for {}: {}", identity, e);
+            return pass(req, next).await;
+        }
+    };
+    let within_window = used <= policy.limit;
+    let mut usages = vec![Usage { limit: policy.limit, used, reset_secs, daily: false }];
+
+    // The short window is checked first and only admitted requests use up the daily quota:
+    // while the window is exceeded the quota is only read, and a hit that overshoots the
+    // quota is given back
+    if let Some((limit, prefix)) = &quota {
+        let (day, reset_secs) = window_at(now, SECS_PER_DAY);
+        let key = format!("{}:{}", prefix, day);
+        let used = if within_window {
+            db.count_rate_limit_hits(&[(key.clone(), SECS_PER_DAY * 2)]).await.map(|counts| counts[0])
+        } else {
+            // Read as if admitted, so a quota that is used up still counts as exceeded
+            db.peek_rate_limit_hits(&key).await.map(|hits| hits + 1)
+        };
+        match used {
+            Ok(used) => {
+                if within_window && used > *limit {
+                    if let Err(e) = db.release_rate_limit_hit(&key).await {
+                        log::error!("Failed to release quota hit for {}: {}", identity, e);
+                    }
+                }
+                usages.push(Usage { limit: *limit, used, reset_secs, daily: true });
+            }
+            Err(e) => log::error!("Daily quota skipped for {}: {}", identity, e),
+        }
+    }
+    let mut policy_header = format!("{};w={}", policy.limit, policy.window_secs);
+    if let Some((limit, _)) = &quota {
+        policy_header.push_str(&format!(", {};w={}", limit, SECS_PER_DAY));
+    }
+
+    if let Some(exceeded) = exceeded(&usages) {
+        log::warn!("{} exceeded the {} {} for {}", identity, policy.name, if exceeded.daily { "daily quota" } else { "rate limit" }, req.path());
+        let error = if exceeded.daily { "Daily quota exceeded" } else { "Rate limit exceeded" };
+        let response = HttpResponse::TooManyRequests()
+            .insert_header((RETRY_AFTER, exceeded.reset_secs))
+            .json(json!({ "error": error, "retry_after": exceeded.reset_secs }));
+        let mut res = req.into_response(response).map_into_right_body();
+        set_headers(res.headers_mut(), exceeded, &policy_header);
+        return Ok(res);
+    }
+
+    let mut res = pass(req, next).await?;
+    if let Some(closest) = closest(&usages) {
+        set_headers(res.headers_mut(), closest, &policy_header);
+    }
+    Ok(res)
+}
+
+// The fixed window `now` falls in, and the seconds until the next one starts
+fn window_at(now: u64, window_secs: u64) -> (u64, u64) {
+    (now / window_secs, window_secs - now % window_secs)
+}
+
+// When several limits are exceeded, the client has to wait for the last one to reset
+fn exceeded(usages: &[Usage]) -> Option<&Usage> {
+    usages.iter().filter(|u| u.used > u.limit).max_by_key(|u| u.reset_secs)
+}
+
+// The limit closest to running out, reported on admitted requests
+fn closest(usages: &[Usage]) -> Option<&Usage> {
+    usages.iter().min_by_key(|u| u.limit.saturating_sub(u.used))
+}
+
+fn set_headers(headers: &mut HeaderMap, usage: &Usage, policy: &str) {
+    let values = [
+        ("ratelimit-limit", usage.limit.to_string()),
+        ("ratelimit-remaining", usage.limit.saturating_sub(usage.used).to_string()),
+        ("ratelimit-reset", usage.reset_secs.to_string()),
+        ("ratelimit-policy", policy.to_string()),
+    ];
+    for (name, value) in values {
+        if let Ok(value) = HeaderValue::from_str(&value) {
+            headers.insert(HeaderName::from_static(name), value);
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn usage(limit: u64, used: u64, reset_secs: u64, daily: bool) -> Usage {
+        Usage { limit, used, reset_secs, daily }
+    }
+
+    #[test]
+    fn windows_are_fixed_and_reset_on_their_boundary() {
+        assert_eq!(window_at(0, 60), (0, 60));
+        assert_eq!(window_at(59, 60), (0, 1));
+        assert_eq!(window_at(60, 60), (1, 60));
+        assert_eq!(window_at(7_250, 3600), (2, 3550));
+        // 2024-01-01T00:00:00Z starts a UTC day
+        assert_eq!(window_at(1_704_067_200, SECS_PER_DAY), (19_723, SECS_PER_DAY));
+        assert_eq!(window_at(1_704_067_199, SECS_PER_DAY), (19_722, 1));
+    }
+
+    #[test]
+    fn the_limit_itself_is_still_allowed() {
+        let usages = [usage(30, 30, 10, false), usage(500, 500, 4000, true)];
+        assert!(exceeded(&usages).is_none());
+        assert_eq!(closest(&usages).map(|u| u.used), Some(30));
+    }
+
+    #[test]
+    fn retry_after_is_the_last_exceeded_limit_to_reset() {
+        let window_only = [usage(30, 31, 10, false), usage(500, 20, 4000, true)];
+        assert!(!exceeded(&window_only).expect("window exceeded").daily);
+
+        let both = [usage(30, 31, 10, false), usage(500, 501, 4000, true)];
+        assert_eq!(exceeded(&both).map(|u| u.reset_secs), Some(4000));
+    }
+
+    #[test]
+    fn headers_report_the_limit_closest_to_running_out() {
+        let usages = [usage(30, 2, 10, false), usage(500, 495, 4000, true)];
+        let closest = closest(&usages).expect("a limit");
+        assert!(closest.daily);
+        assert_eq!(closest.limit - closest.used, 5);
+    }
+
+    #[actix_web::test]
+    async fn requests_pass_when_redis_is_down() {
+        let app = actix_web::test::init_service(
+            actix_web::App::new()
+                .wrap(actix_web::middleware::from_fn(by_ip))
+                .app_data(web::Data::new(RateLimitConfig::new()))
+                .app_data(web::Data::new(DatabaseConnection::unreachable()))
+                .route("/v1/auth/login/user", web::post().to(HttpResponse::Ok)),
+        )
+        .await;
+        let req = actix_web::test::TestRequest::post().uri("/v1/auth/login/user").to_request();
+        let resp = actix_web::test::call_service(&app, req).await;
+        assert_eq!(resp.status(), actix_web::http::StatusCode::OK);
+        assert!(resp.headers().get("ratelimit-policy").is_none());
+    }
+}
@@ -0,0 +1,164 @@
+use crate::configs::security_headers::SecurityHeadersConfig;
+use actix_web::body::MessageBody;
+use actix_web::dev::{ServiceRequest, ServiceResponse};
+use actix_web::error::InternalError;
+use actix_web::http::header::{HeaderMap, HeaderValue, AUTHORIZATION, CACHE_CONTROL, PRAGMA, STRICT_TRANSPORT_SECURITY};
+use actix_web::middleware::{Condition, DefaultHeaders, Next};
+use actix_web::{web, Error as ActixError};
+
+// Strict-Transport-Security, only when the server terminates TLS itself
+pub fn hsts(header: Option<String>) -> Condition<DefaultHeaders> {
+    Condition::new(
+        header.is_some(),
+        DefaultHeaders::new().add((STRICT_TRANSPORT_SECURITY, header.unwrap_or_default())),
+    )
+}
+
+// Wraps the whole app. Errors (401s and 403s from the auth middleware, CORS and rate limit
+// rejections) are rendered here and passed on with the headers, so they get them too.
+pub async fn set_headers<B: MessageBody>(
+    req: ServiceRequest,
+    next: Next<B>,
+) -> Result<ServiceResponse<B>, ActixError> {
+    let Some(config) = req.app_data::<web::Data<SecurityHeadersConfig>>().cloned() else {
+        return next.call(req).await;
+    };
+    let no_store = config.is_no_store(req.path()) || req.headers().contains_key(AUTHORIZATION);
+    // No clone of the request is kept for the error case: routing needs the only reference to it
+    match next.call(req).await {
+        Ok(mut res) => {
+            add_headers(res.headers_mut(), &config, no_store);
+            Ok(res)
+        }
+        Err(e) => {
+            let mut res = e.error_response();
+            add_headers(res.headers_mut(), &config, no_store);
+            Err(InternalError::from_response(e, res).into())
+        }
+    }
+}
+
+fn add_headers(headers: &mut HeaderMap, config: &SecurityHeadersConfig, no_store: bool) {
+    // Handlers that need something else (e.g. a looser CSP for an HTML page) set their own
+    for (name, value) in &config.headers {
+        if !headers.contains_key(name) {
+            headers.insert(name.clone(), value.clone());
+        }
+    }
+    // Responses for one user must not end up in a shared or browser cache
+    if no_store {
+        headers.insert(CACHE_CONTROL, HeaderValue::from_static("no-store"));
+        headers.insert(PRAGMA, HeaderValue::from_static("no-cache"));
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::routes::openapi::{init_docs, DOCS_CSP, DOCS_PATH};
+    use actix_web::http::header::{HeaderName, CONTENT_SECURITY_POLICY, REFERRER_POLICY, X_CONTENT_TYPE_OPTIONS, X_FRAME_OPTIONS};
+    use actix_web::http::StatusCode;
+    use actix_web::middleware::from_fn;
+    use actix_web::test::{call_service, init_service, try_call_service, TestRequest};
+    use actix_web::{error, App, HttpResponse};
+
+    // No .env in the crate, so SecurityHeadersConfig::new() gives the defaults
+    async fn headers_for(req: TestRequest) -> (StatusCode, HeaderMap) {
+        let app = init_service(
+            App::new()
+                .wrap(from_fn(set_headers))
+                .app_data(web::Data::new(SecurityHeadersConfig::new()))
+                .route("/public", web::get().to(HttpResponse::Ok))
+                .route("/v1/user/me", web::get().to(HttpResponse::Ok))
+                .route("/fails", web::get().to(|| async { Err::<HttpResponse, _>(error::ErrorForbidden("no")) })),
+        )
+        .await;
+        let res = call_service(&app, req.to_request()).await;
+        (res.status(), res.headers().clone())
+    }
+
+    fn value(headers: &HeaderMap, name: HeaderName) -> &str {
+        headers.get(name).and_then(|v| v.to_str().ok()).unwrap_or_default()
+    }
+
+    fn assert_defaults(headers: &HeaderMap) {
+        assert_eq!(value(headers, X_CONTENT_TYPE_OPTIONS), "nosniff");
+        assert_eq!(value(headers, CONTENT_SECURITY_POLICY), "default-src 'none'; frame-ancestors 'none'");
+        assert_eq!(value(headers, X_FRAME_OPTIONS), "DENY");
+        assert_eq!(value(headers, REFERRER_POLICY), "no-referrer");
+        assert!(headers.contains_key("permissions-policy"));
+    }
+
+    #[actix_web::test]
+    async fn every_response_gets_the_default_headers() {
+        let (status, headers) = headers_for(TestRequest::get().uri("/public")).await;
+        assert_eq!(status, StatusCode::OK);
+        assert_defaults(&headers);
+        assert!(!headers.contains_key(CACHE_CONTROL));
+        assert!(!headers.contains_key(PRAGMA));
+
+        let (status, headers) = headers_for(TestRequest::get().uri("/fails")).await;
+        assert_eq!(status, StatusCode::FORBIDDEN);
+        assert_defaults(&headers);
+    }
+
+    #[actix_web::test]
+    async fn middleware_rejections_get_the_headers() {
+        let app = init_service(
+            App::new()
+                .wrap(from_fn(set_headers))
+                .app_data(web::Data::new(SecurityHeadersConfig::new()))
+                .service(
+                    web::scope("/v1/admin")
+                        .wrap(from_fn(|_req: ServiceRequest, _next: Next<_>| async {
+                            Err::<ServiceResponse, _>(error::ErrorUnauthorized("no"))
+                        }))
+                        .route("/users", web::get().to(HttpResponse::Ok)),
+                ),
+        )
+        .await;
+        let err = try_call_service(&app, TestRequest::get().uri("/v1/admin/users").to_request())
+            .await
+            .unwrap_err();
+        let res = err.error_response();
+        assert_eq!(res.status(), StatusCode::UNAUTHORIZED);
+        assert_defaults(res.headers());
+        assert_eq!(value(res.headers(), CACHE_CONTROL), "no-store");
+    }
+
+    #[actix_web::test]
+    async fn authenticated_and_listed_responses_are_not_stored() {
+        let (_, headers) = headers_for(TestRequest::get().uri("/public").insert_header((AUTHORIZATION, "Bearer x"))).await;
+        assert_eq!(value(&headers, CACHE_CONTROL), "no-store");
+        assert_eq!(value(&headers, PRAGMA), "no-cache");
+
+        let (_, headers) = headers_for(TestRequest::get().uri("/v1/user/me")).await;
+        assert_eq!(value(&headers, CACHE_CONTROL), "no-store");
+        assert_eq!(value(&headers, PRAGMA), "no-cache");
+    }
+
+    #[actix_web::test]
+    async fn hsts_is_only_sent_when_configured() {
+        for header in [Some("max-age=63072000".to_string()), None] {
+            let app = init_service(App::new().wrap(hsts(header.clone())).route("/public", web::get().to(HttpResponse::Ok))).await;
+            let res = call_service(&app, TestRequest::get().uri("/public").to_request()).await;
+            assert_eq!(res.headers().get(STRICT_TRANSPORT_SECURITY).map(|v| v.to_str().unwrap().to_string()), header);
+        }
+    }
+
+    #[actix_web::test]
+    async fn the_docs_keep_their_own_csp() {
+        let app = init_service(
+            App::new()
+                .wrap(from_fn(set_headers))
+                .app_data(web::Data::new(SecurityHeadersConfig::new()))
+                .configure(init_docs),
+        )
+        .await;
+        let res = call_service(&app, TestRequest::get().uri(&format!("{}/", DOCS_PATH)).to_request()).await;
+        assert_eq!(res.status(), StatusCode::OK);
+        assert_eq!(value(res.headers(), CONTENT_SECURITY_POLICY), DOCS_CSP);
+        // The rest of the defaults still apply
+        assert_eq!(value(res.headers(), X_CONTENT_TYPE_OPTIONS), "nosniff");
+    }
+}
@@ -0,0 +1,155 @@
+use base64::engine::general_purpose::{STANDARD, URL_SAFE_NO_PAD};
+use base64::Engine;
+use jsonwebtoken::jwk::Jwk;
+use jsonwebtoken::{Algorithm, DecodingKey, EncodingKey};
+use rand::rngs::OsRng;
+use rand::RngCore;
+use ring::aead::{Aad, LessSafeKey, Nonce, UnboundKey, AES_256_GCM, NONCE_LEN};
+use ring::rand::SystemRandom;
+use ring::signature::{Ed25519KeyPair, KeyPair};
+use rsa::pkcs1::EncodeRsaPrivateKey;
+use rsa::traits::PublicKeyParts;
+use rsa::RsaPrivateKey;
+use serde_json::{json, Value};
+
+const RSA_BITS: usize = 2048;
+// Marks a private key sealed with JWT_KEY_ENCRYPTION_KEY; rows without it are plain base64 DER
+const SEALED_PREFIX: &str = "aes256gcm:";
+
+// A freshly generated key pair, in the form stored in jwt_signing_keys
+pub struct GeneratedKey {
+    pub kid: String,
+    pub private_key: String, // base64 DER: PKCS#1 for RSA, PKCS#8 for Ed25519
+    pub public_jwk: Value,
+}
+
+pub fn algorithm_name(algorithm: Algorithm) -> &'static str {
+    match algorithm {
+        Algorithm::RS256 => "RS256",
+        Algorithm::EdDSA => "EdDSA",
+        _ => "HS256",
+    }
+}
+
+// RSA generation takes a moment; call from spawn_blocking
+pub fn generate_key(algorithm: Algorithm) -> Result<GeneratedKey, String> {
+    let mut kid_bytes = [0u8; 8];
+    OsRng.fill_bytes(&mut kid_bytes);
+    let kid = hex::encode(kid_bytes);
+
+    match algorithm {
+        Algorithm::RS256 => {
+            let key = RsaPrivateKey::new(&mut OsRng, RSA_BITS).map_err(|e| e.to_string())?;
+            let der = key.to_pkcs1_der().map_err(|e| e.to_string())?;
+            let public_jwk = json!({
+                "kty": "RSA",
+                "use": "sig",
+                "alg": "RS256",
+                "kid": kid,
+                "n": URL_SAFE_NO_PAD.encode(key.n().to_bytes_be()),
+                "e": URL_SAFE_NO_PAD.encode(key.e().to_bytes_be()),
+            });
+            Ok(GeneratedKey { kid, private_key: STANDARD.encode(der.as_bytes()), public_jwk })
+        }
+        Algorithm::EdDSA => {
+            let pkcs8 = Ed25519KeyPair::generate_pkcs8(&SystemRandom::new()).map_err(|e| e.to_string())?;
+            let pair = Ed25519KeyPair::from_pkcs8(pkcs8.as_ref()).map_err(|e| e.to_string())?;
+            let public_jwk = json!({
+                "kty": "OKP",
+                "crv": "Ed25519",
+                "use": "sig",
+                "alg": "EdDSA",
+                "kid": kid,
+                "x": URL_SAFE_NO_PAD.encode(pair.public_key().as_ref()),
+            });
+            Ok(GeneratedKey { kid, private_key: STANDARD.encode(pkcs8.as_ref()), public_jwk })
+        }
+        other => Err(format!("{:?} keys are not generated", other)),
+    }
+}
+
+// Rebuilds the jsonwebtoken keys from a stored row
+pub fn load_key(algorithm: &str, private_key: &str, public_jwk: &Value) -> Result<(Algorithm, EncodingKey, DecodingKey), String> {
+    let der = STANDARD.decode(private_key).map_err(|e| e.to_string())?;
+    let (algorithm, encoding) = match algorithm {
+        "RS256" => (Algorithm::RS256, EncodingKey::from_rsa_der(&der)),
+        "EdDSA" => (Algorithm::EdDSA, EncodingKey::from_ed_der(&der)),
+        other => return Err(format!("Unsupported signing key algorithm {}", other)),
+    };
+    let jwk: Jwk = serde_json::from_value(public_jwk.clone()).map_err(|e| e.to_string())?;
+    let decoding = DecodingKey::from_jwk(&jwk).map_err(|e| e.to_string())?;
+    Ok((algorithm, encoding, decoding))
+}
+
+// Encrypts a private key for storage, with the kid as associated data so a sealed key
+// can't be moved to another row. Without an encryption key it is stored as is.
+pub fn seal_private_key(private_key: &str, kid: &str, encryption_key: Option<&[u8; 32]>) -> Result<String, String> {
+    let Some(encryption_key) = encryption_key else {
+        return Ok(private_key.to_string());
+    };
+    let key = LessSafeKey::new(UnboundKey::new(&AES_256_GCM, encryption_key).map_err(|e| e.to_string())?);
+    let mut nonce = [0u8; NONCE_LEN];
+    OsRng.fill_bytes(&mut nonce);
+    let mut sealed = private_key.as_bytes().to_vec();
+    key.seal_in_place_append_tag(Nonce::assume_unique_for_key(nonce), Aad::from(kid.as_bytes()), &mut sealed)
+        .map_err(|e| e.to_string())?;
+    let mut stored = nonce.to_vec();
+    stored.extend_from_slice(&sealed);
+    Ok(format!("{}{}", SEALED_PREFIX, STANDARD.encode(stored)))
+}
+
+// Reverses seal_private_key. Keys stored before encryption was configured pass through.
+pub fn open_private_key(stored: &str, kid: &str, encryption_key: Option<&[u8; 32]>) -> Result<String, String> {
+    let Some(sealed) = stored.strip_prefix(SEALED_PREFIX) else {
+        return Ok(stored.to_string());
+    };
+    let encryption_key = encryption_key.ok_or("key is encrypted but JWT_KEY_ENCRYPTION_KEY is not set")?;
+    let key = LessSafeKey::new(UnboundKey::new(&AES_256_GCM, encryption_key).map_err(|e| e.to_string())?);
+    let mut sealed = STANDARD.decode(sealed).map_err(|e| e.to_string())?;
+    if sealed.len() < NONCE_LEN {
+        return Err("sealed key is truncated".to_string());
+    }
+    let mut ciphertext = sealed.split_off(NONCE_LEN);
+    let nonce = Nonce::try_assume_unique_for_key(&sealed).map_err(|e| e.to_string())?;
+    let plaintext = key
+        .open_in_place(nonce, Aad::from(kid.as_bytes()), &mut ciphertext)
+        .map_err(|_| "wrong JWT_KEY_ENCRYPTION_KEY or tampered key".to_string())?;
+    String::from_utf8(plaintext.to_vec()).map_err(|e| e.to_string())
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn sealed_keys_only_open_with_the_same_key_and_kid() {
+        let encryption_key = [7u8; 32];
+        let sealed = seal_private_key("ZGVy", "kid1", Some(&encryption_key)).unwrap();
+        assert!(sealed.starts_with(SEALED_PREFIX));
+        assert!(!sealed.contains("ZGVy"));
+        assert_ne!(seal_private_key("ZGVy", "kid1", Some(&encryption_key)).unwrap(), sealed);
+
+        assert_eq!(open_private_key(&sealed, "kid1", Some(&encryption_key)).unwrap(), "ZGVy");
+        assert!(open_private_key(&sealed, "kid2", Some(&encryption_key)).is_err());
+        assert!(open_private_key(&sealed, "kid1", Some(&[8u8; 32])).is_err());
+        assert!(open_private_key(&sealed, "kid1", None).is_err());
+        assert!(open_private_key("aes256gcm:AAAA", "kid1", Some(&encryption_key)).is_err());
+    }
+
+    #[test]
+    fn unencrypted_keys_pass_through() {
+        assert_eq!(seal_private_key("ZGVy", "kid1", None).unwrap(), "ZGVy");
+        assert_eq!(open_private_key("ZGVy", "kid1", None).unwrap(), "ZGVy");
+        assert_eq!(open_private_key("ZGVy", "kid1", Some(&[7u8; 32])).unwrap(), "ZGVy");
+    }
+
+    #[test]
+    fn generated_keys_load_back() {
+        let key = generate_key(Algorithm::EdDSA).unwrap();
+        assert_eq!(key.public_jwk["kid"], key.kid);
+        let (algorithm, _, _) = load_key("EdDSA", &key.private_key, &key.public_jwk).unwrap();
+        assert_eq!(algorithm, Algorithm::EdDSA);
+        assert!(load_key("HS256", &key.private_key, &key.public_jwk).is_err());
+        assert!(generate_key(Algorithm::HS256).is_err());
+    }
+}
@@ -0,0 +1,148 @@
+use base32::Alphabet;
+use hmac::{Hmac, Mac};
+use rand::rngs::OsRng;
+use rand::RngCore;
+use reqwest::Url;
+use sha1::Sha1;
+use sha2::{Digest, Sha256};
+
+// RFC 6238 defaults, which is what authenticator apps assume
+const STEP_SECS: i64 = 30;
+const DIGITS: u32 = 6;
+const ALLOWED_DRIFT: i64 = 1; // steps either side of now, for clock skew
+const RECOVERY_CODE_COUNT: usize = 10;
+
+const SECRET_ALPHABET: Alphabet = Alphabet::Rfc4648 { padding: false };
+
+// 160-bit secret, base32 as authenticator apps expect
+pub fn generate_secret() -> String {
+    let mut bytes = [0u8; 20];
+    OsRng.fill_bytes(&mut bytes);
+    base32::encode(SECRET_ALPHABET, &bytes)
+}
+
+// Scanned as a QR code or pasted into an authenticator app
+pub fn otpauth_uri(issuer: &str, account: &str, secret: &str) -> String {
+    let mut url = Url::parse("otpauth://totp/").expect("static URL");
+    url.path_segments_mut()
+        .expect("otpauth URL has a path")
+        .pop_if_empty()
+        .push(&format!("{}:{}", issuer, account));
+    url.query_pairs_mut()
+        .append_pair("secret", secret)
+        .append_pair("issuer", issuer)
+        .append_pair("algorithm", "SHA1")
+        .append_pair("digits", &DIGITS.to_string())
+        .append_pair("period", &STEP_SECS.to_string());
+    url.to_string()
+}
+
+fn code_at(key: &[u8], step: i64) -> u32 {
+    let mut mac = Hmac::<Sha1>::new_from_slice(key).expect("HMAC accepts any key length");
+    mac.update(&step.to_be_bytes());
+    let digest = mac.finalize().into_bytes();
+    let offset = (digest[digest.len() - 1] & 0x0f) as usize;
+    let binary = u32::from_be_bytes([
+        digest[offset] & 0x7f,
+        digest[offset + 1],
+        digest[offset + 2],
+        digest[offset + 3],
+    ]);
+    binary % 10u32.pow(DIGITS)
+}
+
+// Returns the time step the code belongs to, so callers can refuse to accept it twice
+pub fn verify_code(secret: &str, code: &str, now_unix: i64) -> Option<i64> {
+    let code = code.trim();
+    if code.len() != DIGITS as usize || !code.bytes().all(|b| b.is_ascii_digit()) {
+        return None;
+    }
+    let expected: u32 = code.parse().ok()?;
+    let key = base32::decode(SECRET_ALPHABET, secret)?;
+    let now_step = now_unix.div_euclid(STEP_SECS);
+    (now_step - ALLOWED_DRIFT..=now_step + ALLOWED_DRIFT).find(|step| code_at(&key, *step) == expected)
+}
+
+pub fn looks_like_totp(code: &str) -> bool {
+    let code = code.trim();
+    code.len() == DIGITS as usize && code.bytes().all(|b| b.is_ascii_digit())
+}
+
+// Returns (plaintext codes to show once, hashes to store)
+pub fn generate_recovery_codes() -> (Vec<String>, Vec<String>) {
+    let codes: Vec<String> = (0..RECOVERY_CODE_COUNT)
+        .map(|_| {
+            let mut bytes = [0u8; 8];
+            OsRng.fill_bytes(&mut bytes);
+            let hex = hex::encode(bytes);
+            format!("{}-{}", &hex[..8], &hex[8..])
+        })
+        .collect();
+    let hashes = codes.iter().map(|c| hash_recovery_code(c)).collect();
+    (codes, hashes)
+}
+
+// Codes are 64 random bits and single use, so an unsalted SHA-256 is enough.
+// Case, spaces and dashes are ignored so codes can be typed loosely.
+pub fn hash_recovery_code(code: &str) -> String {
+    let normalized: String = code
+        .chars()
+        .filter(|c| c.is_ascii_alphanumeric())
+        .map(|c| c.to_ascii_lowercase())
+        .collect();
+    hex::encode(Sha256::digest(normalized.as_bytes()))
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    // The SHA-1 secret from RFC 6238 appendix B, "12345678901234567890"
+    const RFC_SECRET: &str = "GEZDGNBVGY3TQOJQGEZDGNBVGY3TQOJQ";
+
+    #[test]
+    fn accepts_the_rfc_6238_vectors() {
+        // The RFC lists 8 digits; apps show the last 6
+        for (time, code) in [(59, "287082"), (1111111109, "081804"), (1234567890, "005924"), (2000000000, "279037")] {
+            assert_eq!(verify_code(RFC_SECRET, code, time), Some(time / STEP_SECS), "code at {}", time);
+        }
+    }
+
+    #[test]
+    fn allows_one_step_of_clock_drift() {
+        let step = 59 / STEP_SECS;
+        assert_eq!(verify_code(RFC_SECRET, "287082", 59 + STEP_SECS), Some(step));
+        assert_eq!(verify_code(RFC_SECRET, "287082", 59 - STEP_SECS), Some(step));
+        assert_eq!(verify_code(RFC_SECRET, "287082", 59 + 2 * STEP_SECS), None);
+        assert_eq!(verify_code(RFC_SECRET, "287083", 59), None);
+    }
+
+    #[test]
+    fn rejects_malformed_codes_and_secrets() {
+        assert_eq!(verify_code(RFC_SECRET, " 287082\n", 59), Some(1));
+        for code in ["", "28708", "2870820", "28708a", "+28708", "287 082"] {
+            assert_eq!(verify_code(RFC_SECRET, code, 59), None, "{:?}", code);
+        }
+        assert_eq!(verify_code("not base32!", "287082", 59), None);
+        assert!(looks_like_totp(" 123456 "));
+        assert!(!looks_like_totp("1234-5678"));
+    }
+
+    #[test]
+    fn generated_secrets_verify() {
+        let secret = generate_secret();
+        let key = base32::decode(SECRET_ALPHABET, &secret).expect("base32 secret");
+        assert_eq!(key.len(), 20);
+        let code = format!("{:06}", code_at(&key, 1000));
+        assert_eq!(verify_code(&secret, &code, 1000 * STEP_SECS), Some(1000));
+    }
+
+    #[test]
+    fn recovery_codes_hash_loosely() {
+        let (codes, hashes) = generate_recovery_codes();
+        assert_eq!(codes.len(), RECOVERY_CODE_COUNT);
+        assert_eq!(hash_recovery_code(&codes[0]), hashes[0]);
+        assert_eq!(hash_recovery_code(&codes[0].to_uppercase().replace('-', " ")), hashes[0]);
+        assert_ne!(hash_recovery_code(&codes[1]), hashes[0]);
+    }
+}
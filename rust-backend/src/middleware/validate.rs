@@ -1,13 +1,121 @@
+use crate::middleware::api_key::{hash_api_key, required_scope, API_KEY_PREFIX};
 use crate::middleware::auth::{validate_token, AuthConfig, AuthData};
-use crate::structs::database_structs::DatabaseConnection;
-use actix_web::{dev::ServiceRequest, error::Error as ActixError, web, HttpMessage};
+use crate::structs::database_structs::{ApiKeyAuth, DatabaseConnection, User, UserStatus};
+use actix_web::{dev::ServiceRequest, error::Error as ActixError, http::Method, web, HttpMessage};
 use actix_web_httpauth::extractors::bearer::BearerAuth;
 use std::pin::Pin;
 
+const ADMIN_SCOPE: &str = "/v1/admin";
+
+// Endpoints an impersonating admin may call. Recommendation handlers are POSTs but skip
+// persistence for impersonated requests.
+const IMPERSONATION_ALLOWED: [(&str, &str); 9] = [
+    ("POST", "/v1/sticker/find"),
+    ("POST", "/v1/sticker/dashboard-find"),
+    ("GET", "/v1/sticker/dashboard-trending"),
+    ("GET", "/v1/user/history"),
+    ("GET", "/v1/user/top-stickers"),
+    ("GET", "/v1/user/analytics"),
+    ("GET", "/v1/user/favorites"),
+    ("GET", "/v1/user/collections"),
+    ("GET", "/v1/user/preferences"),
+];
+
+fn is_read_only(method: &Method, path: &str) -> bool {
+    let path = path.trim_end_matches('/');
+    IMPERSONATION_ALLOWED
+        .iter()
+        .any(|(m, p)| method.as_str() == *m && path == *p)
+}
+
+// Suspended and soft-deleted accounts keep their rows but can't use the API
+fn check_status(user: &User) -> Result<(), ActixError> {
+    match UserStatus::parse(&user.status) {
+        Some(UserStatus::Active) => Ok(()),
+        Some(UserStatus::Suspended) => {
+            log::warn!("Suspended user {} attempted access", user.id);
+            let reason = user.status_reason.as_deref().unwrap_or("Account suspended");
+            Err(actix_web::error::ErrorForbidden(format!("Account suspended: {}", reason)))
+        }
+        Some(UserStatus::Deleted) | None => {
+            log::warn!("User {} with status {} attempted access", user.id, user.status);
+            Err(actix_web::error::ErrorUnauthorized("Account deleted"))
+        }
+    }
+}
+
+// Whether a live key may call this route. Any key may revoke itself, e.g. when the extension logs out.
+fn check_api_key_scope(api_key: &ApiKeyAuth, method: &Method, path: &str) -> Result<(), ActixError> {
+    let self_revoke = *method == Method::DELETE
+        && path.trim_end_matches('/') == format!("/v1/user/api-keys/{}", api_key.id);
+    if self_revoke {
+        return Ok(());
+    }
+    match required_scope(method, path) {
+        Some(scope) if api_key.scopes.iter().any(|s| s == scope) => Ok(()),
+        Some(scope) => {
+            log::warn!("API key {} lacks scope {} for {}", api_key.id, scope, path);
+            Err(actix_web::error::ErrorForbidden(format!("API key is missing the {} scope", scope)))
+        }
+        None => {
+            log::warn!("API key {} used on {}, which requires a login", api_key.id, path);
+            Err(actix_web::error::ErrorForbidden("API keys cannot access this endpoint"))
+        }
+    }
+}
+
+// API keys skip JWT and session checks: the key is looked up by hash and limited to its
+// scopes. Rate limiting per key happens in rate_limit::by_account.
+async fn authenticate_api_key(
+    req: ServiceRequest,
+    key: &str,
+    db: web::Data<DatabaseConnection>,
+) -> Result<ServiceRequest, (ActixError, ServiceRequest)> {
+    let api_key = match db.get_api_key_by_hash(&hash_api_key(key)).await {
+        Ok(Some(api_key)) => api_key,
+        Ok(None) => {
+            log::warn!("Unknown, revoked or expired API key used on {}", req.path());
+            return Err((actix_web::error::ErrorUnauthorized("Invalid API key"), req));
+        }
+        Err(e) => {
+            log::error!("Database error during API key lookup: {}", e);
+            return Err((actix_web::error::ErrorInternalServerError("Database error"), req));
+        }
+    };
+
+    if let Err(e) = check_api_key_scope(&api_key, req.method(), req.path()) {
+        return Err((e, req));
+    }
+
+    let user = match db.get_user_by_id(api_key.user_id).await {
+        Ok(Some(user)) => user,
+        Ok(None) => return Err((actix_web::error::ErrorUnauthorized("User not found"), req)),
+        Err(e) => {
+            log::error!("Database error while fetching user {}: {}", api_key.user_id, e);
+            return Err((actix_web::error::ErrorInternalServerError("Database error"), req));
+        }
+    };
+    if let Err(e) = check_status(&user) {
+        return Err((e, req));
+    }
+
+    if let Err(e) = db.touch_api_key(api_key.id).await {
+        log::error!("Failed to update last use of API key {}: {}", api_key.id, e);
+    }
+
+    log::info!("Attaching AuthData: id={}, role=user, api_key={}", user.id, api_key.id);
+    req.extensions_mut().insert(
+        AuthData::new(user.id.to_string(), "user".to_string()).via_api_key(api_key.id.to_string()),
+    );
+    Ok(req)
+}
+
+type MiddlewareResult = Pin<Box<dyn futures::Future<Output = Result<ServiceRequest, (ActixError, ServiceRequest)>>>>;
+
 pub fn jwt_middleware(
     req: ServiceRequest,
     credentials: BearerAuth,
-) -> Pin<Box<dyn futures::Future<Output = Result<ServiceRequest, (ActixError, ServiceRequest)>>>> {
+) -> MiddlewareResult {
     Box::pin(async move {
         println!("🔐 JWT MIDDLEWARE CALLED for path: {}", req.path());
         log::info!("Processing JWT middleware for path: {}", req.path());
@@ -34,6 +142,11 @@ pub fn jwt_middleware(
             }
         };
 
+        // API keys arrive as bearer tokens too, but are checked by hash instead of as JWTs
+        if credentials.token().starts_with(API_KEY_PREFIX) {
+            return authenticate_api_key(req, credentials.token(), db).await;
+        }
+
         // Validate JWT token first
         log::info!("Validating token: {}", credentials.token());
         let claims = match validate_token(credentials.token(), auth_config.get_ref()) {
@@ -74,20 +187,49 @@ pub fn jwt_middleware(
             }
         };
 
-        // Get user ID from session or claims
-        let user_id = if let Some(session_user_id) = session.user_id {
-            session_user_id
-        } else {
-            // Parse user ID from claims if not in session
-            match uuid::Uuid::parse_str(&claims.sub) {
-                Ok(id) => id,
-                Err(e) => {
-                    log::error!("Invalid user ID format in claims: {}", e);
+        // Admin sessions carry only admin_id and may only reach the admin scope
+        let user_id = match (session.user_id, session.admin_id) {
+            (Some(user_id), _) => user_id,
+            (None, Some(admin_id)) => {
+                if claims.role != "admin" || claims.sub != admin_id.to_string() {
+                    log::warn!("Token sub {} does not match admin session {}", claims.sub, admin_id);
                     return Err((
-                        actix_web::error::ErrorBadRequest("Invalid user ID format"),
+                        actix_web::error::ErrorUnauthorized("Token does not match session"),
                         req,
                     ));
                 }
+                match db.get_admin_by_id(admin_id).await {
+                    Ok(Some(_)) => {}
+                    Ok(None) => {
+                        log::warn!("Admin {} not found in admins table", admin_id);
+                        return Err((actix_web::error::ErrorUnauthorized("Admin not found"), req));
+                    }
+                    Err(e) => {
+                        log::error!("Database error while fetching admin {}: {}", admin_id, e);
+                        return Err((
+                            actix_web::error::ErrorInternalServerError("Database error"),
+                            req,
+                        ));
+                    }
+                }
+                if !req.path().starts_with(ADMIN_SCOPE) {
+                    log::warn!("Admin {} attempted to use an admin token on {}", admin_id, req.path());
+                    return Err((
+                        actix_web::error::ErrorForbidden("Admin tokens are only valid for admin endpoints"),
+                        req,
+                    ));
+                }
+                log::info!("Attaching AuthData: id={}, role=admin", admin_id);
+                req.extensions_mut()
+                    .insert(AuthData::new(admin_id.to_string(), "admin".to_string()));
+                return Ok(req);
+            }
+            (None, None) => {
+                log::error!("Session for {} has neither user_id nor admin_id", claims.sub);
+                return Err((
+                    actix_web::error::ErrorUnauthorized("Invalid or expired session"),
+                    req,
+                ));
             }
         };
 
@@ -124,24 +266,13 @@ pub fn jwt_middleware(
             ));
         }
 
-        // Verify session's user_id matches user's ID (if session has user_id)
-        if let Some(session_user_id) = session.user_id {
-            if session_user_id != user.id {
-                log::warn!(
-                    "Session user_id {} does not match user ID {}",
-                    session_user_id,
-                    user.id
-                );
-                return Err((
-                    actix_web::error::ErrorUnauthorized("Session does not match user"),
-                    req,
-                ));
-            }
+        if let Err(e) = check_status(&user) {
+            return Err((e, req));
         }
 
-        // Enforce role-based access (only allow users for /v1/sticker/find)
+        // Only admins may use the admin scope
         let role = "user";
-        if req.path().starts_with("/admin") {
+        if req.path().starts_with(ADMIN_SCOPE) {
             log::warn!("Non-admin {} attempted admin access", claims.sub);
             return Err((
                 actix_web::error::ErrorForbidden("Admin access required"),
@@ -149,13 +280,106 @@ pub fn jwt_middleware(
             ));
         }
 
+        // Impersonation sessions name the admin on both the session row and the token,
+        // and are limited to read-only endpoints
+        let mut auth_data = AuthData::new(user.id.to_string(), role.to_string());
+        if let Some(admin_id) = session.admin_id {
+            if claims.impersonator.as_deref() != Some(admin_id.to_string().as_str()) {
+                log::warn!("Impersonation token for {} does not match session admin {}", user.id, admin_id);
+                return Err((
+                    actix_web::error::ErrorUnauthorized("Token does not match session"),
+                    req,
+                ));
+            }
+            if !is_read_only(req.method(), req.path()) {
+                log::warn!("Admin {} attempted {} {} while impersonating {}", admin_id, req.method(), req.path(), user.id);
+                return Err((
+                    actix_web::error::ErrorForbidden("Impersonation sessions are read-only"),
+                    req,
+                ));
+            }
+            log::info!("Admin {} impersonating user {}: {} {}", admin_id, user.id, req.method(), req.path());
+            auth_data = auth_data.impersonated_by(admin_id.to_string());
+        }
+
         // Attach AuthData to request extensions
         log::info!("Attaching AuthData: id={}, role={}", user.id, role);
-        req.extensions_mut()
-            .insert(AuthData::new(user.id.to_string(), role.to_string()));
+        req.extensions_mut().insert(auth_data);
 
         // Return the modified request
         log::info!("Middleware completed successfully for path: {}", req.path());
         Ok(req)
     })
 }
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use actix_web::http::StatusCode;
+    use uuid::Uuid;
+
+    fn user(status: &str, reason: Option<&str>) -> User {
+        User {
+            id: Uuid::new_v4(),
+            username: "alice".to_string(),
+            password_hash: String::new(),
+            status: status.to_string(),
+            status_reason: reason.map(String::from),
+            totp_enabled: false,
+            email: None,
+        }
+    }
+
+    #[test]
+    fn only_active_users_pass_the_status_check() {
+        assert!(check_status(&user("active", None)).is_ok());
+
+        let err = check_status(&user("suspended", Some("spam"))).unwrap_err();
+        assert_eq!(err.as_response_error().status_code(), StatusCode::FORBIDDEN);
+        assert_eq!(err.to_string(), "Account suspended: spam");
+
+        for status in ["deleted", "banned", ""] {
+            let err = check_status(&user(status, None)).unwrap_err();
+            assert_eq!(err.as_response_error().status_code(), StatusCode::UNAUTHORIZED, "{}", status);
+        }
+    }
+
+    #[test]
+    fn api_keys_are_held_to_their_scopes() {
+        let api_key = ApiKeyAuth {
+            id: Uuid::new_v4(),
+            user_id: Uuid::new_v4(),
+            scopes: vec!["sticker:find".to_string()],
+        };
+        assert!(check_api_key_scope(&api_key, &Method::POST, "/v1/sticker/find").is_ok());
+
+        let err = check_api_key_scope(&api_key, &Method::GET, "/v1/user/history").unwrap_err();
+        assert_eq!(err.as_response_error().status_code(), StatusCode::FORBIDDEN);
+        assert_eq!(err.to_string(), "API key is missing the history:read scope");
+
+        for (method, path) in [(Method::POST, "/v1/user/logout"), (Method::GET, "/v1/user/api-keys")] {
+            let err = check_api_key_scope(&api_key, &method, path).unwrap_err();
+            assert_eq!(err.to_string(), "API keys cannot access this endpoint", "{} {}", method, path);
+        }
+
+        let own = format!("/v1/user/api-keys/{}", api_key.id);
+        assert!(check_api_key_scope(&api_key, &Method::DELETE, &own).is_ok());
+        let other = format!("/v1/user/api-keys/{}", Uuid::new_v4());
+        assert!(check_api_key_scope(&api_key, &Method::DELETE, &other).is_err());
+    }
+
+    #[test]
+    fn impersonation_allows_only_listed_reads() {
+        for (method, path) in IMPERSONATION_ALLOWED {
+            let method = Method::from_bytes(method.as_bytes()).unwrap();
+            assert!(is_read_only(&method, path), "{} {}", method, path);
+        }
+        assert!(is_read_only(&Method::GET, "/v1/user/history/"));
+
+        assert!(!is_read_only(&Method::POST, "/v1/user/history"));
+        assert!(!is_read_only(&Method::DELETE, "/v1/user/delete"));
+        assert!(!is_read_only(&Method::PUT, "/v1/user/preferences"));
+        assert!(!is_read_only(&Method::GET, "/v1/user/export"));
+        assert!(!is_read_only(&Method::GET, "/v1/user/history/extra"));
+    }
+}
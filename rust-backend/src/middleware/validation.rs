@@ -0,0 +1,155 @@
+use actix_web::dev::Payload;
+use actix_web::error::{Error as ActixError, InternalError, JsonPayloadError};
+use actix_web::{web, FromRequest, HttpRequest, HttpResponse};
+use futures::future::LocalBoxFuture;
+use serde::de::DeserializeOwned;
+use serde_json::{json, Map, Value};
+use std::ops::Deref;
+use validator::{Validate, ValidationErrors};
+
+// JSON body that is rejected with per-field errors unless it passes `Validate`
+#[derive(Debug)]
+pub struct ValidatedJson<T>(pub T);
+
+impl<T> ValidatedJson<T> {
+    pub fn into_inner(self) -> T {
+        self.0
+    }
+}
+
+impl<T> Deref for ValidatedJson<T> {
+    type Target = T;
+
+    fn deref(&self) -> &T {
+        &self.0
+    }
+}
+
+impl<T: DeserializeOwned + Validate + 'static> FromRequest for ValidatedJson<T> {
+    type Error = ActixError;
+    type Future = LocalBoxFuture<'static, Result<Self, ActixError>>;
+
+    fn from_request(req: &HttpRequest, payload: &mut Payload) -> Self::Future {
+        let json = web::Json::<T>::from_request(req, payload);
+        Box::pin(async move {
+            let value = json.await?.into_inner();
+            value.validate().map_err(validation_error)?;
+            Ok(ValidatedJson(value))
+        })
+    }
+}
+
+// 400 of the form {"error": ..., "fields": {"username": ["..."]}}
+fn validation_error(errors: ValidationErrors) -> ActixError {
+    let fields: Map<String, Value> = errors
+        .field_errors()
+        .into_iter()
+        .map(|(field, errs)| {
+            let messages = errs
+                .iter()
+                .map(|e| e.message.as_ref().map(|m| m.to_string()).unwrap_or_else(|| e.code.to_string()))
+                .collect::<Vec<_>>();
+            (field.to_string(), json!(messages))
+        })
+        .collect();
+    let response = HttpResponse::BadRequest().json(json!({ "error": "Invalid request", "fields": fields }));
+    InternalError::from_response(errors, response).into()
+}
+
+// Bodies that can't be parsed get JSON errors too, instead of actix's plain text
+pub fn json_config(limit: usize) -> web::JsonConfig {
+    web::JsonConfig::default().limit(limit).error_handler(|err, _req| {
+        let response = match &err {
+            JsonPayloadError::Overflow { .. } | JsonPayloadError::OverflowKnownLength { .. } => {
+                HttpResponse::PayloadTooLarge().json(json!({ "error": "Request body is too large" }))
+            }
+            JsonPayloadError::ContentType => HttpResponse::UnsupportedMediaType()
+                .json(json!({ "error": "Content-Type must be application/json" })),
+            JsonPayloadError::Deserialize(e) => {
+                HttpResponse::BadRequest().json(json!({ "error": "Invalid request body", "details": e.to_string() }))
+            }
+            _ => HttpResponse::BadRequest().json(json!({ "error": "Invalid request body" })),
+        };
+        InternalError::from_response(err, response).into()
+    })
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use actix_web::http::header::{self, ContentType};
+    use actix_web::http::StatusCode;
+    use actix_web::test::{call_service, init_service, read_body_json, TestRequest};
+    use actix_web::{post, App};
+    use sticker_types::{RecommendRequest, RegisterRequest};
+
+    #[post("/register")]
+    async fn register(req: ValidatedJson<RegisterRequest>) -> HttpResponse {
+        HttpResponse::Ok().json(json!({ "username": req.username }))
+    }
+
+    async fn post(body: impl Into<String>) -> (StatusCode, Option<String>, Value) {
+        let app = init_service(App::new().app_data(json_config(256)).service(register)).await;
+        let req = TestRequest::post()
+            .uri("/register")
+            .insert_header(ContentType::json())
+            .set_payload(body.into())
+            .to_request();
+        let res = call_service(&app, req).await;
+        let status = res.status();
+        let content_type = res
+            .headers()
+            .get(header::CONTENT_TYPE)
+            .and_then(|v| v.to_str().ok())
+            .map(str::to_string);
+        (status, content_type, read_body_json(res).await)
+    }
+
+    #[actix_web::test]
+    async fn rule_violations_are_json_field_errors() {
+        let (status, content_type, body) = post(r#"{"username": "-ab", "password": ""}"#).await;
+        assert_eq!(status, StatusCode::BAD_REQUEST);
+        assert_eq!(content_type.as_deref(), Some("application/json"));
+        assert_eq!(body["error"], "Invalid request");
+        assert_eq!(
+            body["fields"]["username"],
+            json!(["May only contain letters, digits, '_', '-' and '.', and must start with a letter or digit"])
+        );
+        assert!(body["fields"].get("password").is_none());
+
+        let (_, _, body) = post(r#"{"username": "ab", "password": ""}"#).await;
+        assert_eq!(body["fields"]["username"], json!(["Must be 3-32 characters"]));
+    }
+
+    #[actix_web::test]
+    async fn valid_bodies_pass_normalized() {
+        let (status, _, body) = post(r#"{"username": "  Alice.B ", "password": "secret"}"#).await;
+        assert_eq!(status, StatusCode::OK);
+        assert_eq!(body["username"], "alice.b");
+    }
+
+    #[actix_web::test]
+    async fn unreadable_bodies_are_json_errors_too() {
+        let (status, content_type, body) = post("{").await;
+        assert_eq!(status, StatusCode::BAD_REQUEST);
+        assert_eq!(content_type.as_deref(), Some("application/json"));
+        assert_eq!(body["error"], "Invalid request body");
+
+        let (status, _, body) = post(r#"{"username": "alice"}"#).await;
+        assert_eq!(status, StatusCode::BAD_REQUEST);
+        assert!(body["details"].as_str().unwrap_or_default().contains("password"));
+
+        let too_long = format!(r#"{{"username": "alice", "password": "{}"}}"#, "x".repeat(300));
+        let (status, _, _) = post(too_long).await;
+        assert_eq!(status, StatusCode::PAYLOAD_TOO_LARGE);
+    }
+
+    #[test]
+    fn input_text_is_counted_in_characters_after_trimming() {
+        let request = |text: String| RecommendRequest { input_text: text, personalize: None };
+        assert!(request("é".repeat(1000)).validate().is_ok());
+        assert!(request("é".repeat(1001)).validate().is_err());
+        let blank: RecommendRequest = serde_json::from_str(r#"{"input_text": "   "}"#).unwrap();
+        assert!(blank.validate().is_err());
+    }
+}
@@ -0,0 +1,187 @@
+use crate::structs::database_structs::{
+    ActivityDay, DatabaseConnection, EmotionCount, GlobalSticker, UpstreamStat, WeeklyActiveUsers,
+};
+use chrono::{NaiveDate, Utc};
+use uuid::Uuid;
+
+// Rollup writers and readers for the admin dashboard. Writers are single upserts on
+// the current day's row so the dashboard never has to scan interactions.
+impl DatabaseConnection {
+    pub async fn record_recommendation_stats(
+        &self,
+        user_id: Uuid,
+        emotion: &str,
+        cache_hit: bool,
+    ) -> Result<(), sqlx::Error> {
+        let today = Utc::now().date_naive();
+        let mut tx = self.pool.begin().await?;
+        sqlx::query(
+            r#"
+            INSERT INTO daily_stats (day, recommendations, cache_hits, cache_misses)
+            VALUES ($1, 1, $2, $3)
+            ON CONFLICT (day) DO UPDATE SET
+                recommendations = daily_stats.recommendations + 1,
+                cache_hits = daily_stats.cache_hits + $2,
+                cache_misses = daily_stats.cache_misses + $3
+            "#,
+        )
+        .bind(today)
+        .bind(cache_hit as i64)
+        .bind(!cache_hit as i64)
+        .execute(&mut *tx)
+        .await?;
+        sqlx::query(
+            "INSERT INTO daily_active_users (day, user_id) VALUES ($1, $2) ON CONFLICT DO NOTHING",
+        )
+        .bind(today)
+        .bind(user_id)
+        .execute(&mut *tx)
+        .await?;
+        sqlx::query(
+            r#"
+            INSERT INTO daily_emotion_counts (day, emotion, count) VALUES ($1, $2, 1)
+            ON CONFLICT (day, emotion) DO UPDATE SET count = daily_emotion_counts.count + 1
+            "#,
+        )
+        .bind(today)
+        .bind(emotion)
+        .execute(&mut *tx)
+        .await?;
+        tx.commit().await
+    }
+
+    pub async fn record_signup(&self) -> Result<(), sqlx::Error> {
+        sqlx::query(
+            r#"
+            INSERT INTO daily_stats (day, signups) VALUES ($1, 1)
+            ON CONFLICT (day) DO UPDATE SET signups = daily_stats.signups + 1
+            "#,
+        )
+        .bind(Utc::now().date_naive())
+        .execute(&self.pool)
+        .await?;
+        Ok(())
+    }
+
+    pub async fn record_sticker_pick(&self, sticker_url: &str) -> Result<(), sqlx::Error> {
+        sqlx::query(
+            r#"
+            INSERT INTO daily_sticker_counts (day, sticker_url, count) VALUES ($1, $2, 1)
+            ON CONFLICT (day, sticker_url) DO UPDATE SET count = daily_sticker_counts.count + 1
+            "#,
+        )
+        .bind(Utc::now().date_naive())
+        .bind(sticker_url)
+        .execute(&self.pool)
+        .await?;
+        Ok(())
+    }
+
+    pub async fn record_upstream_call(&self, service: &str, success: bool) -> Result<(), sqlx::Error> {
+        sqlx::query(
+            r#"
+            INSERT INTO daily_upstream_stats (day, service, requests, errors) VALUES ($1, $2, 1, $3)
+            ON CONFLICT (day, service) DO UPDATE SET
+                requests = daily_upstream_stats.requests + 1,
+                errors = daily_upstream_stats.errors + $3
+            "#,
+        )
+        .bind(Utc::now().date_naive())
+        .bind(service)
+        .bind(!success as i64)
+        .execute(&self.pool)
+        .await?;
+        Ok(())
+    }
+
+    // Per-day activity from the given day through today (UTC), oldest first
+    pub async fn get_activity_series(&self, since: NaiveDate) -> Result<Vec<ActivityDay>, sqlx::Error> {
+        sqlx::query_as::<_, ActivityDay>(
+            r#"
+            SELECT d.day::date as day,
+                COALESCE(s.recommendations, 0) as recommendations,
+                COALESCE(s.cache_hits, 0) as cache_hits,
+                COALESCE(s.cache_misses, 0) as cache_misses,
+                COALESCE(s.signups, 0) as signups,
+                (SELECT COUNT(*) FROM daily_active_users a WHERE a.day = d.day::date) as active_users
+            FROM generate_series($1::date, $2::date, interval '1 day') AS d(day)
+            LEFT JOIN daily_stats s ON s.day = d.day::date
+            ORDER BY d.day
+            "#,
+        )
+        .bind(since)
+        .bind(Utc::now().date_naive())
+        .fetch_all(&self.pool)
+        .await
+    }
+
+    // Distinct active users per ISO week (weeks starting on Monday). The first week is
+    // counted from its Monday, even when that is before `since`, so no bucket is partial.
+    // Truncating a plain timestamp keeps the session time zone out of the week boundaries.
+    pub async fn get_weekly_active_users(&self, since: NaiveDate) -> Result<Vec<WeeklyActiveUsers>, sqlx::Error> {
+        sqlx::query_as::<_, WeeklyActiveUsers>(
+            r#"
+            SELECT date_trunc('week', day::timestamp)::date as week_start, COUNT(DISTINCT user_id) as active_users
+            FROM daily_active_users
+            WHERE day >= date_trunc('week', $1::date::timestamp)::date
+            GROUP BY week_start
+            ORDER BY week_start
+            "#,
+        )
+        .bind(since)
+        .fetch_all(&self.pool)
+        .await
+    }
+
+    pub async fn get_emotion_distribution(&self, since: NaiveDate) -> Result<Vec<EmotionCount>, sqlx::Error> {
+        sqlx::query_as::<_, EmotionCount>(
+            r#"
+            SELECT emotion, SUM(count)::bigint as count
+            FROM daily_emotion_counts
+            WHERE day >= $1
+            GROUP BY emotion
+            ORDER BY count DESC, emotion
+            "#,
+        )
+        .bind(since)
+        .fetch_all(&self.pool)
+        .await
+    }
+
+    pub async fn get_global_top_stickers(
+        &self,
+        since: NaiveDate,
+        limit: i64,
+    ) -> Result<Vec<GlobalSticker>, sqlx::Error> {
+        sqlx::query_as::<_, GlobalSticker>(
+            r#"
+            SELECT sticker_url, SUM(count)::bigint as picks
+            FROM daily_sticker_counts
+            WHERE day >= $1
+            GROUP BY sticker_url
+            ORDER BY picks DESC, sticker_url
+            LIMIT $2
+            "#,
+        )
+        .bind(since)
+        .bind(limit)
+        .fetch_all(&self.pool)
+        .await
+    }
+
+    pub async fn get_upstream_stats(&self, since: NaiveDate) -> Result<Vec<UpstreamStat>, sqlx::Error> {
+        sqlx::query_as::<_, UpstreamStat>(
+            r#"
+            SELECT service, SUM(requests)::bigint as requests, SUM(errors)::bigint as errors,
+                COALESCE(SUM(errors)::float8 / NULLIF(SUM(requests), 0), 0) as error_rate
+            FROM daily_upstream_stats
+            WHERE day >= $1
+            GROUP BY service
+            ORDER BY service
+            "#,
+        )
+        .bind(since)
+        .fetch_all(&self.pool)
+        .await
+    }
+}
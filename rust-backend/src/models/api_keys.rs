@@ -0,0 +1,140 @@
+use crate::structs::database_structs::{ApiKey, ApiKeyAuth, DatabaseConnection};
+use chrono::{Duration, NaiveDateTime, Utc};
+use uuid::Uuid;
+
+impl DatabaseConnection {
+    pub async fn create_api_key(
+        &self,
+        user_id: Uuid,
+        name: &str,
+        prefix: &str,
+        key_hash: &str,
+        scopes: &[String],
+        expires_at: Option<NaiveDateTime>,
+    ) -> Result<ApiKey, sqlx::Error> {
+        sqlx::query_as::<_, ApiKey>(
+            r#"
+            INSERT INTO api_keys (id, user_id, name, prefix, key_hash, scopes, created_at, expires_at)
+            VALUES ($1, $2, $3, $4, $5, $6, $7, $8)
+            RETURNING id, name, prefix, scopes, created_at, last_used_at, expires_at, revoked_at
+            "#,
+        )
+        .bind(Uuid::new_v4())
+        .bind(user_id)
+        .bind(name)
+        .bind(prefix)
+        .bind(key_hash)
+        .bind(scopes)
+        .bind(Utc::now().naive_utc())
+        .bind(expires_at)
+        .fetch_one(&self.pool)
+        .await
+    }
+
+    // Revoked keys are kept so they still show up in the listing
+    pub async fn get_api_keys(&self, user_id: Uuid) -> Result<Vec<ApiKey>, sqlx::Error> {
+        sqlx::query_as::<_, ApiKey>(
+            r#"
+            SELECT id, name, prefix, scopes, created_at, last_used_at, expires_at, revoked_at
+            FROM api_keys WHERE user_id = $1
+            ORDER BY created_at DESC
+            "#,
+        )
+        .bind(user_id)
+        .fetch_all(&self.pool)
+        .await
+    }
+
+    pub async fn count_active_api_keys(&self, user_id: Uuid) -> Result<i64, sqlx::Error> {
+        sqlx::query_scalar::<_, i64>(
+            "SELECT COUNT(*) FROM api_keys WHERE user_id = $1 AND revoked_at IS NULL AND (expires_at IS NULL OR expires_at > $2)",
+        )
+        .bind(user_id)
+        .bind(Utc::now().naive_utc())
+        .fetch_one(&self.pool)
+        .await
+    }
+
+    pub async fn revoke_api_key(&self, user_id: Uuid, key_id: Uuid) -> Result<bool, sqlx::Error> {
+        let result = sqlx::query(
+            "UPDATE api_keys SET revoked_at = $3 WHERE id = $1 AND user_id = $2 AND revoked_at IS NULL",
+        )
+        .bind(key_id)
+        .bind(user_id)
+        .bind(Utc::now().naive_utc())
+        .execute(&self.pool)
+        .await?;
+        Ok(result.rows_affected() > 0)
+    }
+
+    // Looks up an unrevoked, unexpired key by hash
+    pub async fn get_api_key_by_hash(&self, key_hash: &str) -> Result<Option<ApiKeyAuth>, sqlx::Error> {
+        sqlx::query_as::<_, ApiKeyAuth>(
+            r#"
+            SELECT id, user_id, scopes FROM api_keys
+            WHERE key_hash = $1 AND revoked_at IS NULL AND (expires_at IS NULL OR expires_at > $2)
+            "#,
+        )
+        .bind(key_hash)
+        .bind(Utc::now().naive_utc())
+        .fetch_optional(&self.pool)
+        .await
+    }
+
+    // Written at most once a minute per key to keep hot keys from hammering the row
+    pub async fn touch_api_key(&self, key_id: Uuid) -> Result<(), sqlx::Error> {
+        let now = Utc::now().naive_utc();
+        sqlx::query(
+            "UPDATE api_keys SET last_used_at = $2 WHERE id = $1 AND (last_used_at IS NULL OR last_used_at < $3)",
+        )
+        .bind(key_id)
+        .bind(now)
+        .bind(now - Duration::minutes(1))
+        .execute(&self.pool)
+        .await?;
+        Ok(())
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::middleware::api_key::generate_api_key;
+    use crate::structs::database_structs::RegisterRequest;
+
+    // Needs a scratch Postgres: DATABASE_URL=... REDIS_URL=redis://unused cargo test -- --ignored
+    #[actix_web::test]
+    #[ignore = "needs DATABASE_URL"]
+    async fn only_live_keys_authenticate() {
+        let db = DatabaseConnection::new().await.unwrap();
+        db.init_schema().await.unwrap();
+        let user = db
+            .register_user(RegisterRequest {
+                username: format!("key_test_{}", &Uuid::new_v4().simple().to_string()[..8]),
+                password: "correct horse battery".to_string(),
+                email: None,
+            })
+            .await
+            .unwrap();
+        let scopes = vec!["sticker:find".to_string()];
+
+        let (_, prefix, live) = generate_api_key();
+        let created = db.create_api_key(user.id, "live", &prefix, &live, &scopes, None).await.unwrap();
+        let found = db.get_api_key_by_hash(&live).await.unwrap().unwrap();
+        assert_eq!((found.id, found.user_id, found.scopes), (created.id, user.id, scopes.clone()));
+
+        let (_, prefix, revoked) = generate_api_key();
+        let key = db.create_api_key(user.id, "revoked", &prefix, &revoked, &scopes, None).await.unwrap();
+        assert!(db.revoke_api_key(user.id, key.id).await.unwrap());
+        assert!(db.get_api_key_by_hash(&revoked).await.unwrap().is_none());
+        assert!(!db.revoke_api_key(user.id, key.id).await.unwrap());
+
+        let (_, prefix, expired) = generate_api_key();
+        let past = (Utc::now() - Duration::minutes(1)).naive_utc();
+        db.create_api_key(user.id, "expired", &prefix, &expired, &scopes, Some(past)).await.unwrap();
+        assert!(db.get_api_key_by_hash(&expired).await.unwrap().is_none());
+
+        assert_eq!(db.count_active_api_keys(user.id).await.unwrap(), 1);
+        db.delete_user(user.id).await.unwrap();
+    }
+}
@@ -1,17 +1,26 @@
 use std::sync::Arc;
 
 use crate::{
-    configs::env_load::{load_database_url, load_redis_url},
+    configs::{
+        env_load::{load_database_url, load_redis_url},
+        retention::RetentionMode,
+    },
     structs::database_structs::{
-        DatabaseConnection, DbHistoryItem, HistoryItem, ManagementRequest, RegisterRequest, Session, TopSticker, TopStickerItem, User
+        Admin, AuditEvent, Collection, CollectionItem, CollectionItemAdded, CollectionResponse, DatabaseConnection, DailyActivity, DbHistoryItem, EmotionCount, ExportFeedback, ExportProfile, ExportSession, Favorite, FeedbackAction, HistoryCursor, HistoryItem, HistoryQuery, ImpersonationRecord, Interaction, ManagementRequest, Recommendation, MAX_COLLECTION_STICKERS, RegisterRequest, Session, StickerMetric, TopSticker, TopStickerItem, TrendingCacheEntry, TrendingRefreshLock, User, UserDetail, UserDto, UserExport, UserPreferences, UserStatus
     },
 };
-use chrono::{NaiveDateTime, DateTime, Utc};
+use chrono::{NaiveDate, NaiveDateTime, DateTime, Utc};
 use redis::{Client, Commands};
-use sqlx::{Pool, Postgres};
+use sha2::{Digest, Sha256};
+use sqlx::{Pool, Postgres, QueryBuilder};
 use uuid::Uuid;
 use std::collections::HashMap;
 
+// Stored instead of the raw text for users who opted out of keeping it
+fn hash_input_text(input_text: &str) -> String {
+    format!("sha256:{}", hex::encode(Sha256::digest(input_text.as_bytes())))
+}
+
 impl DatabaseConnection {
     pub async fn new() -> Result<Self, sqlx::Error> {
         let database_url = load_database_url();
@@ -21,6 +30,17 @@ impl DatabaseConnection {
         Ok(Self { pool, redis })
     }
 
+    // For handler tests that must not get as far as a query: every query fails
+    #[cfg(test)]
+    pub fn unreachable() -> Self {
+        let pool = sqlx::postgres::PgPoolOptions::new()
+            .acquire_timeout(std::time::Duration::from_millis(100))
+            .connect_lazy("postgres://unused@127.0.0.1:1/unused")
+            .expect("valid database URL");
+        let redis = Arc::new(Client::open("redis://127.0.0.1:1").expect("valid Redis URL"));
+        Self { pool, redis }
+    }
+
     pub async fn init_schema(&self) -> Result<(), sqlx::Error> {
         // Split into separate queries to avoid multiple commands in a prepared statement
         sqlx::query(
@@ -34,6 +54,44 @@ impl DatabaseConnection {
         .execute(&self.pool)
         .await?;
 
+        // Supports case-insensitive username prefix search in the admin user list
+        sqlx::query(
+            "CREATE INDEX IF NOT EXISTS users_username_lower_idx ON users (lower(username) text_pattern_ops)",
+        )
+        .execute(&self.pool)
+        .await?;
+
+        // New usernames are stored lowercased; this also stops "alice" being registered next to an
+        // older "Alice". Databases that already hold such pairs keep working without the index.
+        if let Err(e) = sqlx::query(
+            "CREATE UNIQUE INDEX IF NOT EXISTS users_username_ci_idx ON users (lower(username))",
+        )
+        .execute(&self.pool)
+        .await
+        {
+            log::warn!("Case-insensitive username index not created, usernames differing only in case exist: {}", e);
+        }
+
+        // Per-user privacy preference: when false only a hash of input_text is stored
+        sqlx::query(
+            "ALTER TABLE users ADD COLUMN IF NOT EXISTS store_input_text BOOLEAN NOT NULL DEFAULT TRUE",
+        )
+        .execute(&self.pool)
+        .await?;
+
+        // Account status: active, suspended or deleted (soft delete, erased after the restore window)
+        sqlx::query(
+            r#"
+            ALTER TABLE users
+                ADD COLUMN IF NOT EXISTS status VARCHAR NOT NULL DEFAULT 'active',
+                ADD COLUMN IF NOT EXISTS status_reason TEXT,
+                ADD COLUMN IF NOT EXISTS status_changed_at TIMESTAMP,
+                ADD COLUMN IF NOT EXISTS deleted_at TIMESTAMP
+            "#,
+        )
+        .execute(&self.pool)
+        .await?;
+
         sqlx::query(
             r#"
             CREATE TABLE IF NOT EXISTS admins (
@@ -89,30 +147,493 @@ impl DatabaseConnection {
         .execute(&self.pool)
         .await?;
 
+        sqlx::query(
+            r#"
+            CREATE TABLE IF NOT EXISTS recommendations (
+                id UUID PRIMARY KEY,
+                user_id UUID REFERENCES users(id),
+                detected_emotion VARCHAR NOT NULL,
+                sticker_urls TEXT[] NOT NULL,
+                created_at TIMESTAMP NOT NULL
+            )"#,
+        )
+        .execute(&self.pool)
+        .await?;
+
+        sqlx::query(
+            "ALTER TABLE interactions ADD COLUMN IF NOT EXISTS recommendation_id UUID REFERENCES recommendations(id)",
+        )
+        .execute(&self.pool)
+        .await?;
+
+        sqlx::query(
+            r#"
+            CREATE TABLE IF NOT EXISTS sticker_feedback (
+                id UUID PRIMARY KEY,
+                recommendation_id UUID NOT NULL REFERENCES recommendations(id),
+                user_id UUID REFERENCES users(id),
+                sticker_url TEXT NOT NULL,
+                action VARCHAR NOT NULL,
+                created_at TIMESTAMP NOT NULL
+            )"#,
+        )
+        .execute(&self.pool)
+        .await?;
+
+        // Marks the one event per (recommendation, sticker) that counted as a use. The partial
+        // unique index makes "first pick" atomic, so concurrent picks can't both count.
+        sqlx::query("ALTER TABLE sticker_feedback ADD COLUMN IF NOT EXISTS first_pick BOOLEAN NOT NULL DEFAULT FALSE")
+            .execute(&self.pool)
+            .await?;
+        sqlx::query(
+            r#"
+            UPDATE sticker_feedback SET first_pick = TRUE
+            WHERE id IN (
+                SELECT DISTINCT ON (recommendation_id, sticker_url) id FROM sticker_feedback f
+                WHERE action IN ('clicked', 'copied', 'sent')
+                AND NOT EXISTS (
+                    SELECT 1 FROM sticker_feedback p
+                    WHERE p.recommendation_id = f.recommendation_id AND p.sticker_url = f.sticker_url AND p.first_pick
+                )
+                ORDER BY recommendation_id, sticker_url, created_at
+            )"#,
+        )
+        .execute(&self.pool)
+        .await?;
+        sqlx::query(
+            "CREATE UNIQUE INDEX IF NOT EXISTS sticker_feedback_first_pick_idx ON sticker_feedback (recommendation_id, sticker_url) WHERE first_pick",
+        )
+        .execute(&self.pool)
+        .await?;
+
+        sqlx::query(
+            r#"
+            CREATE TABLE IF NOT EXISTS favorites (
+                id UUID PRIMARY KEY,
+                user_id UUID NOT NULL REFERENCES users(id),
+                sticker_url TEXT NOT NULL,
+                created_at TIMESTAMP NOT NULL,
+                UNIQUE (user_id, sticker_url)
+            )"#,
+        )
+        .execute(&self.pool)
+        .await?;
+
+        sqlx::query(
+            r#"
+            CREATE TABLE IF NOT EXISTS collections (
+                id UUID PRIMARY KEY,
+                user_id UUID NOT NULL REFERENCES users(id),
+                name VARCHAR NOT NULL,
+                created_at TIMESTAMP NOT NULL,
+                UNIQUE (user_id, name)
+            )"#,
+        )
+        .execute(&self.pool)
+        .await?;
+
+        sqlx::query(
+            r#"
+            CREATE TABLE IF NOT EXISTS collection_items (
+                id UUID PRIMARY KEY,
+                collection_id UUID NOT NULL REFERENCES collections(id) ON DELETE CASCADE,
+                sticker_url TEXT NOT NULL,
+                position INTEGER NOT NULL,
+                added_at TIMESTAMP NOT NULL,
+                UNIQUE (collection_id, sticker_url)
+            )"#,
+        )
+        .execute(&self.pool)
+        .await?;
+
+        // Impression tracking, used to demote stickers the user keeps skipping
+        sqlx::query(
+            r#"
+            ALTER TABLE sticker_metrics
+                ADD COLUMN IF NOT EXISTS shown_count INTEGER NOT NULL DEFAULT 0,
+                ADD COLUMN IF NOT EXISTS last_shown TIMESTAMP
+            "#,
+        )
+        .execute(&self.pool)
+        .await?;
+
+        // Explicit like (+1) or dislike (-1) from feedback; the latest one wins
+        sqlx::query("ALTER TABLE sticker_metrics ADD COLUMN IF NOT EXISTS rating SMALLINT NOT NULL DEFAULT 0")
+            .execute(&self.pool)
+            .await?;
+
+        // Rollup tables for the admin dashboard, updated as events happen (see analytics.rs)
+        sqlx::query(
+            r#"
+            CREATE TABLE IF NOT EXISTS daily_stats (
+                day DATE PRIMARY KEY,
+                recommendations BIGINT NOT NULL DEFAULT 0,
+                cache_hits BIGINT NOT NULL DEFAULT 0,
+                cache_misses BIGINT NOT NULL DEFAULT 0,
+                signups BIGINT NOT NULL DEFAULT 0
+            )"#,
+        )
+        .execute(&self.pool)
+        .await?;
+
+        sqlx::query(
+            r#"
+            CREATE TABLE IF NOT EXISTS daily_active_users (
+                day DATE NOT NULL,
+                user_id UUID NOT NULL,
+                PRIMARY KEY (day, user_id)
+            )"#,
+        )
+        .execute(&self.pool)
+        .await?;
+
+        sqlx::query(
+            r#"
+            CREATE TABLE IF NOT EXISTS daily_emotion_counts (
+                day DATE NOT NULL,
+                emotion VARCHAR NOT NULL,
+                count BIGINT NOT NULL DEFAULT 0,
+                PRIMARY KEY (day, emotion)
+            )"#,
+        )
+        .execute(&self.pool)
+        .await?;
+
+        sqlx::query(
+            r#"
+            CREATE TABLE IF NOT EXISTS daily_sticker_counts (
+                day DATE NOT NULL,
+                sticker_url TEXT NOT NULL,
+                count BIGINT NOT NULL DEFAULT 0,
+                PRIMARY KEY (day, sticker_url)
+            )"#,
+        )
+        .execute(&self.pool)
+        .await?;
+
+        sqlx::query(
+            r#"
+            CREATE TABLE IF NOT EXISTS daily_upstream_stats (
+                day DATE NOT NULL,
+                service VARCHAR NOT NULL,
+                requests BIGINT NOT NULL DEFAULT 0,
+                errors BIGINT NOT NULL DEFAULT 0,
+                PRIMARY KEY (day, service)
+            )"#,
+        )
+        .execute(&self.pool)
+        .await?;
+
+        // One row per impersonation session. user_id has no foreign key so the record
+        // outlives the user's erasure.
+        sqlx::query(
+            r#"
+            CREATE TABLE IF NOT EXISTS impersonation_log (
+                id UUID PRIMARY KEY,
+                admin_id UUID NOT NULL REFERENCES admins(id),
+                user_id UUID NOT NULL,
+                reason TEXT NOT NULL,
+                started_at TIMESTAMP NOT NULL,
+                expires_at TIMESTAMP NOT NULL
+            )"#,
+        )
+        .execute(&self.pool)
+        .await?;
+
+        // User-managed API keys. Only a SHA-256 of the key is kept.
+        sqlx::query(
+            r#"
+            CREATE TABLE IF NOT EXISTS api_keys (
+                id UUID PRIMARY KEY,
+                user_id UUID NOT NULL REFERENCES users(id),
+                name VARCHAR NOT NULL,
+                prefix VARCHAR NOT NULL,
+                key_hash VARCHAR NOT NULL UNIQUE,
+                scopes TEXT[] NOT NULL,
+                created_at TIMESTAMP NOT NULL,
+                last_used_at TIMESTAMP,
+                expires_at TIMESTAMP,
+                revoked_at TIMESTAMP
+            )"#,
+        )
+        .execute(&self.pool)
+        .await?;
+
+        // TOTP second factor. totp_secret is set at enrollment and only used for login
+        // once totp_enabled; totp_last_step stops a code being replayed.
+        for table in ["users", "admins"] {
+            sqlx::query(&format!(
+                r#"
+                ALTER TABLE {}
+                    ADD COLUMN IF NOT EXISTS totp_secret VARCHAR,
+                    ADD COLUMN IF NOT EXISTS totp_enabled BOOLEAN NOT NULL DEFAULT FALSE,
+                    ADD COLUMN IF NOT EXISTS totp_enabled_at TIMESTAMP,
+                    ADD COLUMN IF NOT EXISTS totp_last_step BIGINT
+                "#,
+                table
+            ))
+            .execute(&self.pool)
+            .await?;
+        }
+
+        // Single-use 2FA recovery codes, stored as SHA-256 hashes
+        sqlx::query(
+            r#"
+            CREATE TABLE IF NOT EXISTS totp_recovery_codes (
+                id UUID PRIMARY KEY,
+                user_id UUID REFERENCES users(id),
+                admin_id UUID REFERENCES admins(id),
+                code_hash VARCHAR NOT NULL,
+                created_at TIMESTAMP NOT NULL,
+                used_at TIMESTAMP,
+                CHECK (user_id IS NOT NULL OR admin_id IS NOT NULL)
+            )"#,
+        )
+        .execute(&self.pool)
+        .await?;
+
+        // Logins from external OpenID Connect providers, linked to a local user
+        sqlx::query(
+            r#"
+            CREATE TABLE IF NOT EXISTS user_identities (
+                id UUID PRIMARY KEY,
+                user_id UUID NOT NULL REFERENCES users(id),
+                provider VARCHAR NOT NULL,
+                subject VARCHAR NOT NULL,
+                email VARCHAR,
+                created_at TIMESTAMP NOT NULL,
+                last_login_at TIMESTAMP,
+                UNIQUE(provider, subject),
+                UNIQUE(user_id, provider)
+            )"#,
+        )
+        .execute(&self.pool)
+        .await?;
+
+        // Optional address for password reset mail, stored lowercased
+        sqlx::query("ALTER TABLE users ADD COLUMN IF NOT EXISTS email VARCHAR")
+            .execute(&self.pool)
+            .await?;
+        sqlx::query("CREATE UNIQUE INDEX IF NOT EXISTS users_email_idx ON users (email) WHERE email IS NOT NULL")
+            .execute(&self.pool)
+            .await?;
+
+        // Single-use password reset tokens. Only a SHA-256 of the token is kept.
+        sqlx::query(
+            r#"
+            CREATE TABLE IF NOT EXISTS password_reset_tokens (
+                id UUID PRIMARY KEY,
+                user_id UUID NOT NULL REFERENCES users(id),
+                token_hash VARCHAR NOT NULL UNIQUE,
+                created_at TIMESTAMP NOT NULL,
+                expires_at TIMESTAMP NOT NULL,
+                used_at TIMESTAMP
+            )"#,
+        )
+        .execute(&self.pool)
+        .await?;
+
+        // Asymmetric JWT signing keys (JWT_ALGORITHM=RS256 or EdDSA). A key signs from
+        // sign_from until the next key's sign_from, and verifies for a grace period after.
+        sqlx::query(
+            r#"
+            CREATE TABLE IF NOT EXISTS jwt_signing_keys (
+                kid VARCHAR PRIMARY KEY,
+                algorithm VARCHAR NOT NULL,
+                private_key TEXT NOT NULL,
+                public_jwk JSONB NOT NULL,
+                created_at TIMESTAMP NOT NULL,
+                sign_from TIMESTAMP NOT NULL
+            )"#,
+        )
+        .execute(&self.pool)
+        .await?;
+
+        // Security audit trail. Actor and target ids have no foreign keys so events
+        // survive account erasure.
+        sqlx::query(
+            r#"
+            CREATE TABLE IF NOT EXISTS audit_events (
+                id UUID PRIMARY KEY,
+                occurred_at TIMESTAMP NOT NULL,
+                actor_type VARCHAR NOT NULL,
+                actor_id UUID,
+                target_type VARCHAR,
+                target_id UUID,
+                action VARCHAR NOT NULL,
+                ip TEXT,
+                user_agent TEXT,
+                diff JSONB
+            )"#,
+        )
+        .execute(&self.pool)
+        .await?;
+
+        for index in [
+            "CREATE INDEX IF NOT EXISTS audit_events_occurred_at_idx ON audit_events (occurred_at DESC, id DESC)",
+            "CREATE INDEX IF NOT EXISTS audit_events_actor_idx ON audit_events (actor_id, occurred_at DESC)",
+            "CREATE INDEX IF NOT EXISTS audit_events_target_idx ON audit_events (target_id, occurred_at DESC)",
+        ] {
+            sqlx::query(index).execute(&self.pool).await?;
+        }
+
+        // Append-only: reject any UPDATE, DELETE or TRUNCATE on audit_events
+        sqlx::query(
+            r#"
+            CREATE OR REPLACE FUNCTION audit_events_append_only() RETURNS trigger AS $$
+            BEGIN
+                RAISE EXCEPTION 'audit_events is append-only';
+            END;
+            $$ LANGUAGE plpgsql"#,
+        )
+        .execute(&self.pool)
+        .await?;
+        for query in [
+            "DROP TRIGGER IF EXISTS audit_events_append_only ON audit_events",
+            "DROP TRIGGER IF EXISTS audit_events_no_truncate ON audit_events",
+            "CREATE TRIGGER audit_events_append_only BEFORE UPDATE OR DELETE ON audit_events FOR EACH ROW EXECUTE FUNCTION audit_events_append_only()",
+            "CREATE TRIGGER audit_events_no_truncate BEFORE TRUNCATE ON audit_events FOR EACH STATEMENT EXECUTE FUNCTION audit_events_append_only()",
+        ] {
+            sqlx::query(query).execute(&self.pool).await?;
+        }
+
         Ok(())
     }
 
+    pub async fn save_recommendation(
+        &self,
+        recommendation_id: Uuid,
+        user_id: Uuid,
+        detected_emotion: &str,
+        sticker_urls: &[String],
+    ) -> Result<(), sqlx::Error> {
+        sqlx::query(
+            "INSERT INTO recommendations (id, user_id, detected_emotion, sticker_urls, created_at) VALUES ($1, $2, $3, $4, $5)",
+        )
+        .bind(recommendation_id)
+        .bind(user_id)
+        .bind(detected_emotion)
+        .bind(sticker_urls)
+        .bind(chrono::Utc::now().naive_utc())
+        .execute(&self.pool)
+        .await?;
+        Ok(())
+    }
+
+    pub async fn get_recommendation(
+        &self,
+        recommendation_id: Uuid,
+        user_id: Uuid,
+    ) -> Result<Option<Recommendation>, sqlx::Error> {
+        let recommendation = sqlx::query_as::<_, Recommendation>(
+            "SELECT id, user_id, detected_emotion, sticker_urls, created_at FROM recommendations WHERE id = $1 AND user_id = $2",
+        )
+        .bind(recommendation_id)
+        .bind(user_id)
+        .fetch_optional(&self.pool)
+        .await?;
+        Ok(recommendation)
+    }
+
     pub async fn save_interaction(
         &self,
         user_id: Uuid,
+        recommendation_id: Option<Uuid>,
         input_text: &str,
         detected_emotion: &str,
         sticker_url: &str,
     ) -> Result<(), sqlx::Error> {
+        // Honour the user's store_input_text preference in the same statement
         sqlx::query(
-            "INSERT INTO interactions (id, user_id, input_text, detected_emotion, sticker_url, created_at) VALUES ($1, $2, $3, $4, $5, $6)",
+            r#"
+            INSERT INTO interactions (id, user_id, recommendation_id, input_text, detected_emotion, sticker_url, created_at)
+            SELECT $1, u.id, $3, CASE WHEN u.store_input_text THEN $4 ELSE $8 END, $5, $6, $7
+            FROM users u WHERE u.id = $2
+            "#,
         )
         .bind(Uuid::new_v4())
         .bind(user_id)
+        .bind(recommendation_id)
         .bind(input_text)
         .bind(detected_emotion)
         .bind(sticker_url)
         .bind(chrono::Utc::now().naive_utc())
+        .bind(hash_input_text(input_text))
         .execute(&self.pool)
         .await?;
         Ok(())
     }
 
+    pub async fn get_preferences(&self, user_id: Uuid) -> Result<Option<UserPreferences>, sqlx::Error> {
+        let preferences = sqlx::query_as::<_, UserPreferences>(
+            "SELECT store_input_text FROM users WHERE id = $1",
+        )
+        .bind(user_id)
+        .fetch_optional(&self.pool)
+        .await?;
+        Ok(preferences)
+    }
+
+    pub async fn update_preferences(
+        &self,
+        user_id: Uuid,
+        store_input_text: bool,
+    ) -> Result<UserPreferences, sqlx::Error> {
+        let preferences = sqlx::query_as::<_, UserPreferences>(
+            "UPDATE users SET store_input_text = $1 WHERE id = $2 RETURNING store_input_text",
+        )
+        .bind(store_input_text)
+        .bind(user_id)
+        .fetch_one(&self.pool)
+        .await?;
+        Ok(preferences)
+    }
+
+    // Records a feedback event. Returns true when this is the first pick of the sticker
+    // for the recommendation, so callers only count a use once per recommendation.
+    pub async fn save_feedback(
+        &self,
+        recommendation_id: Uuid,
+        user_id: Uuid,
+        sticker_url: &str,
+        action: FeedbackAction,
+    ) -> Result<bool, sqlx::Error> {
+        let mut tx = self.pool.begin().await?;
+        let insert = |first_pick: bool| {
+            sqlx::query(
+                r#"
+                INSERT INTO sticker_feedback (id, recommendation_id, user_id, sticker_url, action, created_at, first_pick)
+                VALUES ($1, $2, $3, $4, $5, $6, $7)
+                ON CONFLICT (recommendation_id, sticker_url) WHERE first_pick DO NOTHING
+                "#,
+            )
+            .bind(Uuid::new_v4())
+            .bind(recommendation_id)
+            .bind(user_id)
+            .bind(sticker_url)
+            .bind(action.as_str())
+            .bind(chrono::Utc::now().naive_utc())
+            .bind(first_pick)
+        };
+
+        // A pick claims the first-pick slot; if another pick holds it, the event is kept unflagged
+        let first_pick = action.is_pick() && insert(true).execute(&mut *tx).await?.rows_affected() == 1;
+        if !first_pick {
+            insert(false).execute(&mut *tx).await?;
+        }
+
+        if first_pick {
+            // Point the logged interaction at the sticker the user actually picked
+            sqlx::query("UPDATE interactions SET sticker_url = $1 WHERE recommendation_id = $2")
+                .bind(sticker_url)
+                .bind(recommendation_id)
+                .execute(&mut *tx)
+                .await?;
+        }
+        tx.commit().await?;
+        Ok(first_pick)
+    }
+
     pub async fn update_sticker_metrics(
         &self,
         user_id: Uuid,
@@ -120,28 +641,321 @@ impl DatabaseConnection {
     ) -> Result<(), sqlx::Error> {
         sqlx::query(
             r#"
-            INSERT INTO sticker_metrics (id, user_id, sticker_url, usage_count, last_used)
-            VALUES ($1, $2, $3, 1, $4)
-            ON CONFLICT (user_id, sticker_url)
-            DO UPDATE SET usage_count = sticker_metrics.usage_count + 1, last_used = $4
+            INSERT INTO sticker_metrics (id, user_id, sticker_url, usage_count, last_used)
+            VALUES ($1, $2, $3, 1, $4)
+            ON CONFLICT (user_id, sticker_url)
+            DO UPDATE SET usage_count = sticker_metrics.usage_count + 1, last_used = $4
+            "#,
+        )
+        .bind(Uuid::new_v4())
+        .bind(user_id)
+        .bind(sticker_url)
+        .bind(chrono::Utc::now().naive_utc())
+        .execute(&self.pool)
+        .await?;
+        Ok(())
+    }
+
+    pub async fn rate_sticker(
+        &self,
+        user_id: Uuid,
+        sticker_url: &str,
+        rating: i16,
+    ) -> Result<(), sqlx::Error> {
+        sqlx::query(
+            r#"
+            INSERT INTO sticker_metrics (id, user_id, sticker_url, usage_count, last_used, rating)
+            VALUES ($1, $2, $3, 0, $4, $5)
+            ON CONFLICT (user_id, sticker_url)
+            DO UPDATE SET rating = $5
+            "#,
+        )
+        .bind(Uuid::new_v4())
+        .bind(user_id)
+        .bind(sticker_url)
+        .bind(chrono::Utc::now().naive_utc())
+        .bind(rating)
+        .execute(&self.pool)
+        .await?;
+        Ok(())
+    }
+
+    pub async fn record_sticker_impressions(
+        &self,
+        user_id: Uuid,
+        sticker_urls: &[String],
+    ) -> Result<(), sqlx::Error> {
+        let ids: Vec<Uuid> = sticker_urls.iter().map(|_| Uuid::new_v4()).collect();
+        sqlx::query(
+            r#"
+            INSERT INTO sticker_metrics (id, user_id, sticker_url, usage_count, last_used, shown_count, last_shown)
+            SELECT id, $2, sticker_url, 0, $4, 1, $4
+            FROM UNNEST($1::uuid[], $3::text[]) AS t(id, sticker_url)
+            ON CONFLICT (user_id, sticker_url)
+            DO UPDATE SET shown_count = sticker_metrics.shown_count + 1, last_shown = $4
+            "#,
+        )
+        .bind(&ids)
+        .bind(user_id)
+        .bind(sticker_urls)
+        .bind(chrono::Utc::now().naive_utc())
+        .execute(&self.pool)
+        .await?;
+        Ok(())
+    }
+
+    pub async fn get_sticker_metrics(
+        &self,
+        user_id: Uuid,
+        sticker_urls: &[String],
+    ) -> Result<Vec<StickerMetric>, sqlx::Error> {
+        let metrics = sqlx::query_as::<_, StickerMetric>(
+            "SELECT * FROM sticker_metrics WHERE user_id = $1 AND sticker_url = ANY($2)",
+        )
+        .bind(user_id)
+        .bind(sticker_urls)
+        .fetch_all(&self.pool)
+        .await?;
+        Ok(metrics)
+    }
+
+    ////////////////////////////////////////////  FAVORITES & COLLECTIONS ////////////////////////////////////////////
+    pub async fn add_favorite(&self, user_id: Uuid, sticker_url: &str) -> Result<(), sqlx::Error> {
+        sqlx::query(
+            r#"
+            INSERT INTO favorites (id, user_id, sticker_url, created_at)
+            VALUES ($1, $2, $3, $4)
+            ON CONFLICT (user_id, sticker_url) DO NOTHING
+            "#,
+        )
+        .bind(Uuid::new_v4())
+        .bind(user_id)
+        .bind(sticker_url)
+        .bind(chrono::Utc::now().naive_utc())
+        .execute(&self.pool)
+        .await?;
+        Ok(())
+    }
+
+    pub async fn remove_favorite(&self, user_id: Uuid, sticker_url: &str) -> Result<bool, sqlx::Error> {
+        let result = sqlx::query("DELETE FROM favorites WHERE user_id = $1 AND sticker_url = $2")
+            .bind(user_id)
+            .bind(sticker_url)
+            .execute(&self.pool)
+            .await?;
+        Ok(result.rows_affected() > 0)
+    }
+
+    pub async fn get_favorites(&self, user_id: Uuid) -> Result<Vec<Favorite>, sqlx::Error> {
+        let favorites = sqlx::query_as::<_, Favorite>(
+            "SELECT sticker_url, created_at FROM favorites WHERE user_id = $1 ORDER BY created_at DESC",
+        )
+        .bind(user_id)
+        .fetch_all(&self.pool)
+        .await?;
+        Ok(favorites)
+    }
+
+    // Favorited stickers among the given candidates, used for ranking
+    pub async fn get_favorited_urls(
+        &self,
+        user_id: Uuid,
+        sticker_urls: &[String],
+    ) -> Result<Vec<String>, sqlx::Error> {
+        let urls = sqlx::query_scalar::<_, String>(
+            "SELECT sticker_url FROM favorites WHERE user_id = $1 AND sticker_url = ANY($2)",
+        )
+        .bind(user_id)
+        .bind(sticker_urls)
+        .fetch_all(&self.pool)
+        .await?;
+        Ok(urls)
+    }
+
+    pub async fn create_collection(&self, user_id: Uuid, name: &str) -> Result<Collection, sqlx::Error> {
+        let collection = sqlx::query_as::<_, Collection>(
+            "INSERT INTO collections (id, user_id, name, created_at) VALUES ($1, $2, $3, $4) RETURNING id, name, created_at",
+        )
+        .bind(Uuid::new_v4())
+        .bind(user_id)
+        .bind(name)
+        .bind(chrono::Utc::now().naive_utc())
+        .fetch_one(&self.pool)
+        .await?;
+        Ok(collection)
+    }
+
+    pub async fn delete_collection(&self, user_id: Uuid, collection_id: Uuid) -> Result<bool, sqlx::Error> {
+        let result = sqlx::query("DELETE FROM collections WHERE id = $1 AND user_id = $2")
+            .bind(collection_id)
+            .bind(user_id)
+            .execute(&self.pool)
+            .await?;
+        Ok(result.rows_affected() > 0)
+    }
+
+    pub async fn get_collections(&self, user_id: Uuid) -> Result<Vec<CollectionResponse>, sqlx::Error> {
+        let collections = sqlx::query_as::<_, Collection>(
+            "SELECT id, name, created_at FROM collections WHERE user_id = $1 ORDER BY created_at",
+        )
+        .bind(user_id)
+        .fetch_all(&self.pool)
+        .await?;
+
+        let items = sqlx::query_as::<_, CollectionItem>(
+            r#"
+            SELECT ci.collection_id, ci.sticker_url
+            FROM collection_items ci
+            JOIN collections c ON c.id = ci.collection_id
+            WHERE c.user_id = $1
+            ORDER BY ci.collection_id, ci.position
+            "#,
+        )
+        .bind(user_id)
+        .fetch_all(&self.pool)
+        .await?;
+
+        let mut items_by_collection: HashMap<Uuid, Vec<String>> = HashMap::new();
+        for item in items {
+            items_by_collection
+                .entry(item.collection_id)
+                .or_default()
+                .push(item.sticker_url);
+        }
+
+        Ok(collections
+            .into_iter()
+            .map(|c| CollectionResponse {
+                sticker_urls: items_by_collection.remove(&c.id).unwrap_or_default(),
+                id: c.id,
+                name: c.name,
+                created_at: c.created_at,
+            })
+            .collect())
+    }
+
+    // Appends a sticker to the end of a collection, up to MAX_COLLECTION_STICKERS
+    pub async fn add_collection_item(
+        &self,
+        user_id: Uuid,
+        collection_id: Uuid,
+        sticker_url: &str,
+    ) -> Result<CollectionItemAdded, sqlx::Error> {
+        let mut tx = self.pool.begin().await?;
+        // Lock the collection row so concurrent adds and reorders take positions one at a time
+        let owned = sqlx::query_scalar::<_, Uuid>(
+            "SELECT id FROM collections WHERE id = $1 AND user_id = $2 FOR UPDATE",
+        )
+        .bind(collection_id)
+        .bind(user_id)
+        .fetch_optional(&mut *tx)
+        .await?;
+        if owned.is_none() {
+            return Ok(CollectionItemAdded::NotFound);
+        }
+
+        let (count, present) = sqlx::query_as::<_, (i64, bool)>(
+            "SELECT COUNT(*), COALESCE(BOOL_OR(sticker_url = $2), false) FROM collection_items WHERE collection_id = $1",
+        )
+        .bind(collection_id)
+        .bind(sticker_url)
+        .fetch_one(&mut *tx)
+        .await?;
+        if present {
+            return Ok(CollectionItemAdded::Added);
+        }
+        if count >= MAX_COLLECTION_STICKERS as i64 {
+            return Ok(CollectionItemAdded::Full);
+        }
+
+        sqlx::query(
+            r#"
+            INSERT INTO collection_items (id, collection_id, sticker_url, position, added_at)
+            SELECT $1, $2, $3, COALESCE(MAX(position) + 1, 0), $4
+            FROM collection_items WHERE collection_id = $2
+            "#,
+        )
+        .bind(Uuid::new_v4())
+        .bind(collection_id)
+        .bind(sticker_url)
+        .bind(chrono::Utc::now().naive_utc())
+        .execute(&mut *tx)
+        .await?;
+        tx.commit().await?;
+        Ok(CollectionItemAdded::Added)
+    }
+
+    pub async fn remove_collection_item(
+        &self,
+        user_id: Uuid,
+        collection_id: Uuid,
+        sticker_url: &str,
+    ) -> Result<bool, sqlx::Error> {
+        let result = sqlx::query(
+            r#"
+            DELETE FROM collection_items ci
+            USING collections c
+            WHERE ci.collection_id = c.id AND c.id = $1 AND c.user_id = $2 AND ci.sticker_url = $3
+            "#,
+        )
+        .bind(collection_id)
+        .bind(user_id)
+        .bind(sticker_url)
+        .execute(&self.pool)
+        .await?;
+        Ok(result.rows_affected() > 0)
+    }
+
+    // Sets item positions to the order given. The list must contain exactly the collection's
+    // current stickers; returns false otherwise (or if the collection isn't the user's).
+    pub async fn reorder_collection(
+        &self,
+        user_id: Uuid,
+        collection_id: Uuid,
+        sticker_urls: &[String],
+    ) -> Result<bool, sqlx::Error> {
+        let mut tx = self.pool.begin().await?;
+        // Lock the collection row for the duration of the reorder
+        let owned = sqlx::query_scalar::<_, Uuid>(
+            "SELECT id FROM collections WHERE id = $1 AND user_id = $2 FOR UPDATE",
+        )
+        .bind(collection_id)
+        .bind(user_id)
+        .fetch_optional(&mut *tx)
+        .await?;
+        if owned.is_none() {
+            return Ok(false);
+        }
+
+        let mut current = sqlx::query_scalar::<_, String>(
+            "SELECT sticker_url FROM collection_items WHERE collection_id = $1",
+        )
+        .bind(collection_id)
+        .fetch_all(&mut *tx)
+        .await?;
+        let mut requested = sticker_urls.to_vec();
+        current.sort();
+        requested.sort();
+        if current != requested {
+            return Ok(false);
+        }
+
+        sqlx::query(
+            r#"
+            UPDATE collection_items ci
+            SET position = t.position - 1
+            FROM UNNEST($2::text[]) WITH ORDINALITY AS t(sticker_url, position)
+            WHERE ci.collection_id = $1 AND ci.sticker_url = t.sticker_url
             "#,
         )
-        .bind(Uuid::new_v4())
-        .bind(user_id)
-        .bind(sticker_url)
-        .bind(chrono::Utc::now().naive_utc())
-        .execute(&self.pool)
+        .bind(collection_id)
+        .bind(sticker_urls)
+        .execute(&mut *tx)
         .await?;
-        Ok(())
+        tx.commit().await?;
+        Ok(true)
     }
-
-    // pub async fn get_sticker_metrics(&self, user_id: Uuid) -> Result<Vec<String>, sqlx::Error> {
-    //     let stickers = sqlx::query_scalar::<_, String>("SELECT sticker_url FROM sticker_metrics WHERE user_id = $1")
-    //         .bind(user_id)
-    //         .fetch_all(&self.pool)
-    //         .await?;
-    //     Ok(stickers)
-    // }
+    ////////////////////////////////////////////////  ////////////////////////////////////////////
 
     pub async fn cache_sticker(
         &self,
@@ -178,6 +992,65 @@ impl DatabaseConnection {
         }
     }
 
+    pub async fn cache_trending(
+        &self,
+        entry: &TrendingCacheEntry,
+        ttl_secs: u64,
+    ) -> Result<(), redis::RedisError> {
+        let mut conn = self.redis.get_connection()?;
+        let value = serde_json::to_string(entry).map_err(|e| {
+            redis::RedisError::from((
+                redis::ErrorKind::TypeError,
+                "Failed to serialize trending entry",
+                e.to_string(),
+            ))
+        })?;
+        conn.set_ex::<_, _, ()>(format!("trending:{}", entry.rating), value, ttl_secs)?;
+        Ok(())
+    }
+
+    pub async fn get_cached_trending(
+        &self,
+        rating: &str,
+    ) -> Result<Option<TrendingCacheEntry>, redis::RedisError> {
+        let mut conn = self.redis.get_connection()?;
+        let key = format!("trending:{}", rating);
+        match conn.get::<_, Option<String>>(&key)? {
+            Some(value) => match serde_json::from_str::<TrendingCacheEntry>(&value) {
+                Ok(entry) => Ok(Some(entry)),
+                Err(e) => {
+                    log::warn!("Discarding malformed trending cache entry {}: {}", key, e);
+                    Ok(None)
+                }
+            },
+            None => Ok(None),
+        }
+    }
+
+    // Short-lived lock so only one worker refreshes a given rating at a time. The lock
+    // is released when the returned guard drops; the TTL only covers a worker that dies.
+    pub async fn try_lock_trending_refresh(
+        &self,
+        rating: &str,
+        ttl_secs: u64,
+    ) -> Result<Option<TrendingRefreshLock>, redis::RedisError> {
+        let mut conn = self.redis.get_connection()?;
+        let key = format!("trending:{}:refreshing", rating);
+        let token = Uuid::new_v4().to_string();
+        let acquired: Option<String> = redis::cmd("SET")
+            .arg(&key)
+            .arg(&token)
+            .arg("NX")
+            .arg("EX")
+            .arg(ttl_secs)
+            .query(&mut conn)?;
+        Ok(acquired.map(|_| TrendingRefreshLock {
+            redis: self.redis.clone(),
+            key,
+            token,
+        }))
+    }
+
     pub async fn save_session(
         &self,
         user_id: Option<Uuid>,
@@ -208,13 +1081,15 @@ impl DatabaseConnection {
         Ok(session)
     }
 
-    // pub async fn delete_session(&self, token: &str) -> Result<(), sqlx::Error> {
-    //     sqlx::query("DELETE FROM sessions WHERE token = $1")
-    //         .bind(token)
-    //         .execute(&self.pool)
-    //         .await?;
-    //     Ok(())
-    // }
+    // Scoped to the user so a token can only ever end its own session
+    pub async fn delete_session(&self, user_id: Uuid, token: &str) -> Result<bool, sqlx::Error> {
+        let result = sqlx::query("DELETE FROM sessions WHERE token = $1 AND user_id = $2")
+            .bind(token)
+            .bind(user_id)
+            .execute(&self.pool)
+            .await?;
+        Ok(result.rows_affected() > 0)
+    }
 
 
     ////////////////////////////////////////////  USER MANAGEMENT FUNCTIONS ////////////////////////////////////////////
@@ -223,11 +1098,12 @@ impl DatabaseConnection {
             bcrypt::hash(&req.password, bcrypt::DEFAULT_COST).expect("Failed to hash password");
         let user_id = Uuid::new_v4();
         let user = sqlx::query_as::<_, User>(
-            "INSERT INTO users (id, username, password_hash) VALUES ($1, $2, $3) RETURNING *",
+            "INSERT INTO users (id, username, password_hash, email) VALUES ($1, $2, $3, $4) RETURNING *",
         )
         .bind(user_id)
         .bind(&req.username)
         .bind(&password_hash)
+        .bind(req.email.as_deref().map(str::to_lowercase))
         .fetch_one(&self.pool)
         .await?;
         Ok(user)
@@ -292,26 +1168,169 @@ impl DatabaseConnection {
         Ok(user)
     }
 
-    pub async fn delete_user(&self, user_id: Uuid) -> Result<(), sqlx::Error> {
-        sqlx::query("DELETE FROM users WHERE id = $1")
+    // Erases a user and everything that references them in one transaction, then clears
+    // any per-user Redis keys. Returns false if the user didn't exist.
+    pub async fn delete_user(&self, user_id: Uuid) -> Result<bool, sqlx::Error> {
+        let mut tx = self.pool.begin().await?;
+        // Children first; the tables have no ON DELETE CASCADE
+        for query in [
+            "DELETE FROM sticker_feedback WHERE user_id = $1 OR recommendation_id IN (SELECT id FROM recommendations WHERE user_id = $1)",
+            "DELETE FROM interactions WHERE user_id = $1 OR recommendation_id IN (SELECT id FROM recommendations WHERE user_id = $1)",
+            "DELETE FROM recommendations WHERE user_id = $1",
+            "DELETE FROM sticker_metrics WHERE user_id = $1",
+            "DELETE FROM favorites WHERE user_id = $1",
+            "DELETE FROM collections WHERE user_id = $1",
+            "DELETE FROM sessions WHERE user_id = $1",
+            "DELETE FROM api_keys WHERE user_id = $1",
+            "DELETE FROM user_identities WHERE user_id = $1",
+            "DELETE FROM totp_recovery_codes WHERE user_id = $1",
+            "DELETE FROM password_reset_tokens WHERE user_id = $1",
+            "DELETE FROM daily_active_users WHERE user_id = $1",
+        ] {
+            sqlx::query(query).bind(user_id).execute(&mut *tx).await?;
+        }
+        let result = sqlx::query("DELETE FROM users WHERE id = $1")
             .bind(user_id)
-            .execute(&self.pool)
+            .execute(&mut *tx)
             .await?;
+        tx.commit().await?;
+
+        if let Err(e) = self.purge_user_cache(user_id).await {
+            log::error!("Failed to purge Redis state for user {}: {}", user_id, e);
+        }
+        Ok(result.rows_affected() > 0)
+    }
+
+    // Removes all Redis keys namespaced under user:<id>:
+    pub async fn purge_user_cache(&self, user_id: Uuid) -> Result<(), redis::RedisError> {
+        let mut conn = self.redis.get_connection()?;
+        let keys: Vec<String> = conn.scan_match::<_, String>(format!("user:{}:*", user_id))?.collect();
+        if !keys.is_empty() {
+            conn.del::<_, ()>(keys)?;
+        }
         Ok(())
     }
 
-    pub async fn get_all_users(&self) -> Result<Vec<User>, sqlx::Error> {
-        let users = sqlx::query_as::<_, User>("SELECT * FROM users")
-            .fetch_all(&self.pool)
-            .await?;
-        Ok(users)
+    pub async fn export_user_data(&self, user_id: Uuid) -> Result<Option<UserExport>, sqlx::Error> {
+        let profile = match sqlx::query_as::<_, ExportProfile>(
+            "SELECT id, username, store_input_text, totp_enabled FROM users WHERE id = $1",
+        )
+        .bind(user_id)
+        .fetch_optional(&self.pool)
+        .await?
+        {
+            Some(profile) => profile,
+            None => return Ok(None),
+        };
+
+        let history = sqlx::query_as::<_, Interaction>(
+            "SELECT id, user_id, recommendation_id, input_text, detected_emotion, sticker_url, created_at FROM interactions WHERE user_id = $1 ORDER BY created_at DESC",
+        )
+        .bind(user_id)
+        .fetch_all(&self.pool)
+        .await?;
+        let sticker_metrics = sqlx::query_as::<_, StickerMetric>(
+            "SELECT * FROM sticker_metrics WHERE user_id = $1 ORDER BY last_used DESC",
+        )
+        .bind(user_id)
+        .fetch_all(&self.pool)
+        .await?;
+        let sessions = sqlx::query_as::<_, ExportSession>(
+            "SELECT id, expires_at FROM sessions WHERE user_id = $1 ORDER BY expires_at DESC",
+        )
+        .bind(user_id)
+        .fetch_all(&self.pool)
+        .await?;
+        let feedback = sqlx::query_as::<_, ExportFeedback>(
+            "SELECT recommendation_id, sticker_url, action, created_at FROM sticker_feedback WHERE user_id = $1 ORDER BY created_at DESC",
+        )
+        .bind(user_id)
+        .fetch_all(&self.pool)
+        .await?;
+        let audit_events = sqlx::query_as::<_, AuditEvent>(
+            "SELECT * FROM audit_events WHERE actor_id = $1 OR target_id = $1 ORDER BY occurred_at DESC, id DESC",
+        )
+        .bind(user_id)
+        .fetch_all(&self.pool)
+        .await?;
+
+        Ok(Some(UserExport {
+            exported_at: Utc::now(),
+            profile,
+            history,
+            sticker_metrics,
+            sessions,
+            favorites: self.get_favorites(user_id).await?,
+            collections: self.get_collections(user_id).await?,
+            feedback,
+            api_keys: self.get_api_keys(user_id).await?,
+            identities: self.get_identities(user_id).await?,
+            audit_events,
+        }))
+    }
+
+    // One page of users, optionally filtered by username prefix. Returns the page and the total match count.
+    pub async fn list_users(
+        &self,
+        prefix: Option<&str>,
+        status: Option<UserStatus>,
+        descending: bool,
+        limit: i64,
+        offset: i64,
+    ) -> Result<(Vec<UserDto>, i64), sqlx::Error> {
+        let pattern = prefix.map(|p| {
+            format!(
+                "{}%",
+                p.to_lowercase().replace('\\', "\\\\").replace('%', "\\%").replace('_', "\\_")
+            )
+        });
+        let order = if descending { "DESC" } else { "ASC" };
+        let users = sqlx::query_as::<_, UserDto>(&format!(
+            "SELECT id, username, status FROM users WHERE ($1::text IS NULL OR lower(username) LIKE $1) AND ($4::text IS NULL OR status = $4) ORDER BY username {}, id LIMIT $2 OFFSET $3",
+            order
+        ))
+        .bind(&pattern)
+        .bind(limit)
+        .bind(offset)
+        .bind(status.map(|s| s.as_str()))
+        .fetch_all(&self.pool)
+        .await?;
+        let total = sqlx::query_scalar::<_, i64>(
+            "SELECT COUNT(*) FROM users WHERE ($1::text IS NULL OR lower(username) LIKE $1) AND ($2::text IS NULL OR status = $2)",
+        )
+        .bind(&pattern)
+        .bind(status.map(|s| s.as_str()))
+        .fetch_one(&self.pool)
+        .await?;
+        Ok((users, total))
+    }
+
+    pub async fn get_user_detail(&self, user_id: Uuid) -> Result<Option<UserDetail>, sqlx::Error> {
+        let detail = sqlx::query_as::<_, UserDetail>(
+            r#"
+            SELECT u.id, u.username, u.status, u.status_reason, u.status_changed_at, u.deleted_at, u.store_input_text, u.totp_enabled,
+                (SELECT COUNT(*) FROM sessions s WHERE s.user_id = u.id) as session_count,
+                (SELECT COUNT(*) FROM sessions s WHERE s.user_id = u.id AND s.expires_at > NOW()) as active_session_count,
+                (SELECT COUNT(*) FROM interactions i WHERE i.user_id = u.id) as interaction_count,
+                (SELECT MAX(created_at) FROM interactions i WHERE i.user_id = u.id) as last_interaction_at
+            FROM users u
+            WHERE u.id = $1
+            "#,
+        )
+        .bind(user_id)
+        .fetch_optional(&self.pool)
+        .await?;
+        Ok(detail)
     }
 
     pub async fn get_user_by_username(&self, username: &str) -> Result<Option<User>, sqlx::Error> {
-        let user = sqlx::query_as::<_, User>("SELECT * FROM users WHERE username = $1")
-            .bind(username)
-            .fetch_optional(&self.pool)
-            .await?;
+        // Case-insensitive, preferring an exact match for accounts that predate normalization
+        let user = sqlx::query_as::<_, User>(
+            "SELECT * FROM users WHERE lower(username) = lower($1) ORDER BY username = $1 DESC LIMIT 1",
+        )
+        .bind(username)
+        .fetch_optional(&self.pool)
+        .await?;
         Ok(user)
     }
 
@@ -322,63 +1341,503 @@ impl DatabaseConnection {
             .await?;
         Ok(user)
     }
-    ////////////////////////////////////////////////  ////////////////////////////////////////////
 
-    pub async fn get_user_history(&self, user_id: Uuid) -> Result<Vec<HistoryItem>, sqlx::Error> {
-        let rows = sqlx::query_as::<_, DbHistoryItem>(
-            "SELECT input_text, detected_emotion, sticker_url, created_at 
-            FROM interactions 
-            WHERE user_id = $1 
-            ORDER BY created_at DESC",
+    // Suspends an active user, or updates the reason of an already suspended one. Deleted users
+    // are left alone. Returns the previous status, or None if nothing changed.
+    pub async fn suspend_user(&self, user_id: Uuid, reason: Option<&str>) -> Result<Option<String>, sqlx::Error> {
+        sqlx::query_scalar::<_, String>(
+            r#"
+            UPDATE users u SET status = 'suspended', status_reason = $2, status_changed_at = $3
+            FROM (SELECT id, status FROM users WHERE id = $1 FOR UPDATE) old
+            WHERE u.id = old.id AND old.status <> 'deleted'
+            RETURNING old.status
+            "#,
+        )
+        .bind(user_id)
+        .bind(reason)
+        .bind(Utc::now().naive_utc())
+        .fetch_optional(&self.pool)
+        .await
+    }
+
+    pub async fn reactivate_user(&self, user_id: Uuid) -> Result<bool, sqlx::Error> {
+        let result = sqlx::query(
+            "UPDATE users SET status = 'active', status_reason = NULL, status_changed_at = $2 WHERE id = $1 AND status = 'suspended'",
+        )
+        .bind(user_id)
+        .bind(Utc::now().naive_utc())
+        .execute(&self.pool)
+        .await?;
+        Ok(result.rows_affected() > 0)
+    }
+
+    // Marks the user deleted and signs them out everywhere. Their data stays in place
+    // until purge_deleted_users erases it after the restore window. Returns the previous
+    // status, or None if the user doesn't exist or is already deleted.
+    pub async fn soft_delete_user(&self, user_id: Uuid, reason: Option<&str>) -> Result<Option<String>, sqlx::Error> {
+        let mut tx = self.pool.begin().await?;
+        let previous = sqlx::query_scalar::<_, String>(
+            r#"
+            UPDATE users u SET status = 'deleted', status_reason = $2, status_changed_at = $3, deleted_at = $3
+            FROM (SELECT id, status FROM users WHERE id = $1 FOR UPDATE) old
+            WHERE u.id = old.id AND old.status <> 'deleted'
+            RETURNING old.status
+            "#,
+        )
+        .bind(user_id)
+        .bind(reason)
+        .bind(Utc::now().naive_utc())
+        .fetch_optional(&mut *tx)
+        .await?;
+        if previous.is_none() {
+            return Ok(None);
+        }
+        sqlx::query("DELETE FROM sessions WHERE user_id = $1")
+            .bind(user_id)
+            .execute(&mut *tx)
+            .await?;
+        tx.commit().await?;
+        Ok(previous)
+    }
+
+    // Undoes a soft delete made after deleted_after. Returns false if the user isn't
+    // deleted or the restore window has passed.
+    pub async fn restore_user(&self, user_id: Uuid, deleted_after: NaiveDateTime) -> Result<bool, sqlx::Error> {
+        let result = sqlx::query(
+            r#"
+            UPDATE users SET status = 'active', status_reason = NULL, status_changed_at = $3, deleted_at = NULL
+            WHERE id = $1 AND status = 'deleted' AND deleted_at > $2
+            "#,
         )
         .bind(user_id)
+        .bind(deleted_after)
+        .bind(Utc::now().naive_utc())
+        .execute(&self.pool)
+        .await?;
+        Ok(result.rows_affected() > 0)
+    }
+
+    // Erases users soft-deleted at or before the cutoff. Returns how many were erased.
+    pub async fn purge_deleted_users(&self, deleted_before: NaiveDateTime) -> Result<u64, sqlx::Error> {
+        let user_ids = sqlx::query_scalar::<_, Uuid>(
+            "SELECT id FROM users WHERE status = 'deleted' AND deleted_at <= $1",
+        )
+        .bind(deleted_before)
         .fetch_all(&self.pool)
         .await?;
+        let mut purged = 0;
+        for user_id in user_ids {
+            if self.delete_user(user_id).await? {
+                purged += 1;
+            }
+        }
+        Ok(purged)
+    }
+
+    ////////////////////////////////////////////  ADMIN FUNCTIONS ////////////////////////////////////////////
+    pub async fn get_admin_by_username(&self, username: &str) -> Result<Option<Admin>, sqlx::Error> {
+        sqlx::query_as::<_, Admin>("SELECT id, username, password_hash, totp_enabled FROM admins WHERE username = $1")
+            .bind(username)
+            .fetch_optional(&self.pool)
+            .await
+    }
+
+    pub async fn get_admin_by_id(&self, admin_id: Uuid) -> Result<Option<Admin>, sqlx::Error> {
+        sqlx::query_as::<_, Admin>("SELECT id, username, password_hash, totp_enabled FROM admins WHERE id = $1")
+            .bind(admin_id)
+            .fetch_optional(&self.pool)
+            .await
+    }
+
+    pub async fn record_admin_login(&self, admin_id: Uuid, success: bool) -> Result<(), sqlx::Error> {
+        if success {
+            sqlx::query("UPDATE admins SET last_login = $2, failed_attempts = 0 WHERE id = $1")
+                .bind(admin_id)
+                .bind(Utc::now().naive_utc())
+                .execute(&self.pool)
+                .await?;
+        } else {
+            sqlx::query("UPDATE admins SET failed_attempts = COALESCE(failed_attempts, 0) + 1 WHERE id = $1")
+                .bind(admin_id)
+                .execute(&self.pool)
+                .await?;
+        }
+        Ok(())
+    }
+
+    // Creates the bootstrap admin if the username is free. Returns true if it was created.
+    pub async fn ensure_admin(&self, username: &str, password: &str) -> Result<bool, sqlx::Error> {
+        let password_hash =
+            bcrypt::hash(password, bcrypt::DEFAULT_COST).map_err(|e| sqlx::Error::Encode(Box::new(e)))?;
+        let result = sqlx::query(
+            "INSERT INTO admins (id, username, password_hash) VALUES ($1, $2, $3) ON CONFLICT (username) DO NOTHING",
+        )
+        .bind(Uuid::new_v4())
+        .bind(username)
+        .bind(&password_hash)
+        .execute(&self.pool)
+        .await?;
+        Ok(result.rows_affected() > 0)
+    }
+
+    // Saves an impersonation session (a session carrying both user_id and admin_id) and its log entry together
+    pub async fn start_impersonation(
+        &self,
+        admin_id: Uuid,
+        user_id: Uuid,
+        reason: &str,
+        token: &str,
+        expires_at: NaiveDateTime,
+    ) -> Result<ImpersonationRecord, sqlx::Error> {
+        let mut tx = self.pool.begin().await?;
+        sqlx::query(
+            "INSERT INTO sessions (id, user_id, admin_id, token, expires_at) VALUES ($1, $2, $3, $4, $5)",
+        )
+        .bind(Uuid::new_v4())
+        .bind(user_id)
+        .bind(admin_id)
+        .bind(token)
+        .bind(expires_at)
+        .execute(&mut *tx)
+        .await?;
+        let record = sqlx::query_as::<_, ImpersonationRecord>(
+            r#"
+            INSERT INTO impersonation_log (id, admin_id, user_id, reason, started_at, expires_at)
+            VALUES ($1, $2, $3, $4, $5, $6)
+            RETURNING *
+            "#,
+        )
+        .bind(Uuid::new_v4())
+        .bind(admin_id)
+        .bind(user_id)
+        .bind(reason)
+        .bind(Utc::now().naive_utc())
+        .bind(expires_at)
+        .fetch_one(&mut *tx)
+        .await?;
+        tx.commit().await?;
+        Ok(record)
+    }
+
+    pub async fn get_impersonations(&self, user_id: Uuid) -> Result<Vec<ImpersonationRecord>, sqlx::Error> {
+        sqlx::query_as::<_, ImpersonationRecord>(
+            "SELECT * FROM impersonation_log WHERE user_id = $1 ORDER BY started_at DESC",
+        )
+        .bind(user_id)
+        .fetch_all(&self.pool)
+        .await
+    }
+    ////////////////////////////////////////////////  ////////////////////////////////////////////
 
-        // Group by input_text and detected_emotion
-        let mut grouped_history: HashMap<(String, String), (Vec<String>, DateTime<Utc>)> = HashMap::new();
-        for row in rows {
-            let key = (row.input_text.clone(), row.detected_emotion.clone());
-            let created_at = DateTime::from_naive_utc_and_offset(row.created_at, Utc);
-            grouped_history
-                .entry(key)
-                .and_modify(|(urls, _)| urls.push(row.sticker_url.clone()))
-                .or_insert((vec![row.sticker_url], created_at));
+    // One page of a user's interactions, newest first. Fetches one extra row to know
+    // whether another page follows.
+    pub async fn get_user_history(
+        &self,
+        user_id: Uuid,
+        query: &HistoryQuery,
+    ) -> Result<(Vec<HistoryItem>, Option<HistoryCursor>), sqlx::Error> {
+        let mut builder = QueryBuilder::<Postgres>::new(
+            "SELECT id, input_text, detected_emotion, sticker_url, created_at FROM interactions WHERE user_id = ",
+        );
+        builder.push_bind(user_id);
+        if let Some(cursor) = &query.cursor {
+            builder
+                .push(" AND (created_at, id) < (")
+                .push_bind(cursor.created_at)
+                .push(", ")
+                .push_bind(cursor.id)
+                .push(")");
+        }
+        if let Some(emotion) = &query.emotion {
+            builder.push(" AND detected_emotion = ").push_bind(emotion.clone());
         }
+        if let Some(from) = query.from {
+            builder.push(" AND created_at >= ").push_bind(from);
+        }
+        if let Some(to) = query.to {
+            builder.push(" AND created_at < ").push_bind(to);
+        }
+        if let Some(q) = &query.q {
+            let pattern = format!(
+                "%{}%",
+                q.replace('\\', "\\\\").replace('%', "\\%").replace('_', "\\_")
+            );
+            builder.push(" AND input_text ILIKE ").push_bind(pattern);
+        }
+        builder
+            .push(" ORDER BY created_at DESC, id DESC LIMIT ")
+            .push_bind(query.limit + 1);
 
-        // Convert to HistoryItem format, using the most recent created_at
-        let history = grouped_history.into_iter().map(|((input_text, detected_emotion), (sticker_url, created_at))| HistoryItem {
-            input_text,
-            detected_emotion,
-            sticker_url,
-            created_at,
-        }).collect();
+        let mut rows = builder
+            .build_query_as::<DbHistoryItem>()
+            .fetch_all(&self.pool)
+            .await?;
+
+        let next_cursor = if rows.len() as i64 > query.limit {
+            rows.truncate(query.limit as usize);
+            rows.last().map(|row| HistoryCursor {
+                created_at: row.created_at,
+                id: row.id,
+            })
+        } else {
+            None
+        };
+
+        let history = rows
+            .into_iter()
+            .map(|row| HistoryItem {
+                id: row.id,
+                input_text: row.input_text,
+                detected_emotion: row.detected_emotion,
+                sticker_url: vec![row.sticker_url],
+                created_at: DateTime::from_naive_utc_and_offset(row.created_at, Utc),
+            })
+            .collect();
+
+        Ok((history, next_cursor))
+    }
+
+    // Deletes the user's interactions matching the given ids and/or time range; with no
+    // filters, deletes all of them (the route only allows that for an explicit `all`).
+    // Returns the number of entries removed.
+    pub async fn clear_history(
+        &self,
+        user_id: Uuid,
+        ids: Option<&[Uuid]>,
+        from: Option<NaiveDateTime>,
+        to: Option<NaiveDateTime>,
+    ) -> Result<u64, sqlx::Error> {
+        let mut builder = QueryBuilder::<Postgres>::new("DELETE FROM interactions WHERE user_id = ");
+        builder.push_bind(user_id);
+        if let Some(ids) = ids {
+            builder.push(" AND id = ANY(").push_bind(ids.to_vec()).push(")");
+        }
+        if let Some(from) = from {
+            builder.push(" AND created_at >= ").push_bind(from);
+        }
+        if let Some(to) = to {
+            builder.push(" AND created_at < ").push_bind(to);
+        }
+        let result = builder.build().execute(&self.pool).await?;
+        Ok(result.rows_affected())
+    }
+
+    // Applies the retention policy to interactions older than the cutoff. Both modes also
+    // handle the recommendations and feedback behind them, which would otherwise lead back
+    // to the user with one join: purging deletes them, anonymizing detaches them.
+    pub async fn apply_history_retention(
+        &self,
+        cutoff: NaiveDateTime,
+        mode: RetentionMode,
+    ) -> Result<u64, sqlx::Error> {
+        let mut tx = self.pool.begin().await?;
+        if mode == RetentionMode::Purge {
+            sqlx::query(
+                r#"
+                DELETE FROM sticker_feedback
+                WHERE created_at < $1
+                    OR recommendation_id IN (SELECT id FROM recommendations WHERE created_at < $1)
+                    OR recommendation_id IN (SELECT recommendation_id FROM interactions WHERE created_at < $1)
+                "#,
+            )
+            .bind(cutoff)
+            .execute(&mut *tx)
+            .await?;
+            let purged = sqlx::query_scalar::<_, Option<Uuid>>(
+                "DELETE FROM interactions WHERE created_at < $1 RETURNING recommendation_id",
+            )
+            .bind(cutoff)
+            .fetch_all(&mut *tx)
+            .await?;
+            let recommendation_ids: Vec<Uuid> = purged.iter().flatten().copied().collect();
+            sqlx::query(
+                r#"
+                DELETE FROM recommendations r
+                WHERE (r.created_at < $1 OR r.id = ANY($2))
+                    AND NOT EXISTS (SELECT 1 FROM interactions i WHERE i.recommendation_id = r.id)
+                    AND NOT EXISTS (SELECT 1 FROM sticker_feedback f WHERE f.recommendation_id = r.id)
+                "#,
+            )
+            .bind(cutoff)
+            .bind(&recommendation_ids)
+            .execute(&mut *tx)
+            .await?;
+            tx.commit().await?;
+            return Ok(purged.len() as u64);
+        }
 
-        Ok(history)
+        sqlx::query(
+            r#"
+            UPDATE recommendations SET user_id = NULL
+            WHERE user_id IS NOT NULL AND (
+                created_at < $1
+                OR id IN (SELECT recommendation_id FROM interactions WHERE created_at < $1 AND user_id IS NOT NULL)
+            )
+            "#,
+        )
+        .bind(cutoff)
+        .execute(&mut *tx)
+        .await?;
+        sqlx::query(
+            r#"
+            UPDATE sticker_feedback SET user_id = NULL
+            WHERE user_id IS NOT NULL AND (
+                created_at < $1
+                OR recommendation_id IN (SELECT id FROM recommendations WHERE user_id IS NULL)
+            )
+            "#,
+        )
+        .bind(cutoff)
+        .execute(&mut *tx)
+        .await?;
+        let result = sqlx::query(
+            r#"
+            UPDATE interactions SET user_id = NULL, input_text = '', recommendation_id = NULL
+            WHERE created_at < $1 AND user_id IS NOT NULL
+            "#,
+        )
+        .bind(cutoff)
+        .execute(&mut *tx)
+        .await?;
+        tx.commit().await?;
+        Ok(result.rows_affected())
     }
 
+    pub async fn delete_history_entry(&self, user_id: Uuid, interaction_id: Uuid) -> Result<bool, sqlx::Error> {
+        let result = sqlx::query("DELETE FROM interactions WHERE id = $1 AND user_id = $2")
+            .bind(interaction_id)
+            .bind(user_id)
+            .execute(&self.pool)
+            .await?;
+        Ok(result.rows_affected() > 0)
+    }
 
-    pub async fn get_top_stickers(&self, user_id: Uuid) -> Result<Vec<TopSticker>, sqlx::Error> {
+    // Most-used stickers grouped by sticker, optionally limited to interactions since a point in time
+    pub async fn get_top_stickers(
+        &self,
+        user_id: Uuid,
+        since: Option<NaiveDateTime>,
+        limit: i64,
+    ) -> Result<Vec<TopSticker>, sqlx::Error> {
         let rows = sqlx::query_as::<_, TopStickerItem>(
             r#"
-            SELECT input_text, sticker_url, COUNT(*) as usage_count
+            SELECT sticker_url, COUNT(*) as usage_count, MAX(created_at) as last_used
             FROM interactions
-            WHERE user_id = $1
-            GROUP BY input_text, sticker_url
-            ORDER BY usage_count DESC
-            LIMIT 4
+            WHERE user_id = $1 AND ($2::timestamp IS NULL OR created_at >= $2)
+            GROUP BY sticker_url
+            ORDER BY usage_count DESC, last_used DESC
+            LIMIT $3
             "#,
         )
         .bind(user_id)
+        .bind(since)
+        .bind(limit)
         .fetch_all(&self.pool)
         .await?;
 
         let top_stickers = rows.into_iter().map(|row| TopSticker {
-            input_text: row.input_text,
             sticker_url: row.sticker_url,
             usage_count: row.usage_count as u32,
+            last_used: DateTime::from_naive_utc_and_offset(row.last_used, Utc),
         }).collect();
 
         Ok(top_stickers)
     }
+
+    pub async fn get_top_emotions(
+        &self,
+        user_id: Uuid,
+        since: Option<NaiveDateTime>,
+        limit: i64,
+    ) -> Result<Vec<EmotionCount>, sqlx::Error> {
+        sqlx::query_as::<_, EmotionCount>(
+            r#"
+            SELECT detected_emotion as emotion, COUNT(*) as count
+            FROM interactions
+            WHERE user_id = $1 AND ($2::timestamp IS NULL OR created_at >= $2)
+            GROUP BY detected_emotion
+            ORDER BY count DESC, emotion
+            LIMIT $3
+            "#,
+        )
+        .bind(user_id)
+        .bind(since)
+        .bind(limit)
+        .fetch_all(&self.pool)
+        .await
+    }
+
+    // Interactions per day, oldest first, with empty days filled in with zero
+    pub async fn get_daily_activity(
+        &self,
+        user_id: Uuid,
+        since: Option<NaiveDateTime>,
+    ) -> Result<Vec<DailyActivity>, sqlx::Error> {
+        let rows = sqlx::query_as::<_, DailyActivity>(
+            r#"
+            SELECT created_at::date as day, COUNT(*) as count
+            FROM interactions
+            WHERE user_id = $1 AND ($2::timestamp IS NULL OR created_at >= $2)
+            GROUP BY day
+            ORDER BY day
+            "#,
+        )
+        .bind(user_id)
+        .bind(since)
+        .fetch_all(&self.pool)
+        .await?;
+
+        let first_day = match since.map(|s| s.date()).or_else(|| rows.first().map(|r| r.day)) {
+            Some(day) => day,
+            None => return Ok(rows),
+        };
+        let counts: HashMap<NaiveDate, i64> = rows.into_iter().map(|r| (r.day, r.count)).collect();
+        let today = Utc::now().date_naive();
+        Ok(first_day
+            .iter_days()
+            .take_while(|day| *day <= today)
+            .map(|day| DailyActivity {
+                day,
+                count: counts.get(&day).copied().unwrap_or(0),
+            })
+            .collect())
+    }
+}
+
+impl Drop for TrendingRefreshLock {
+    fn drop(&mut self) {
+        // Only delete the lock if it is still ours; after the TTL another worker may hold it
+        let script = redis::Script::new(
+            r#"if redis.call("GET", KEYS[1]) == ARGV[1] then return redis.call("DEL", KEYS[1]) else return 0 end"#,
+        );
+        let released = self
+            .redis
+            .get_connection()
+            .and_then(|mut conn| script.key(&self.key).arg(&self.token).invoke::<i32>(&mut conn));
+        if let Err(e) = released {
+            log::warn!("Failed to release {}: {}", self.key, e);
+        }
+    }
 }
+
+#[cfg(test)]
+mod tests {
+    use super::hash_input_text;
+
+    #[test]
+    fn hash_input_text_stores_a_prefixed_sha256() {
+        assert_eq!(
+            hash_input_text("hello"),
+            "sha256:2cf24dba5fb0a30e26e83b2ac5b9e29e1b161e5c1fa7425e73043362938b9824"
+        );
+    }
+
+    #[test]
+    fn hash_input_text_never_contains_the_text() {
+        let hash = hash_input_text("happy birthday");
+        assert!(!hash.contains("happy"));
+        assert_eq!(hash, hash_input_text("happy birthday"));
+        assert_ne!(hash, hash_input_text("happy birthday!"));
+    }
+}
+
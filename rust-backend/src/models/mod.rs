@@ -1 +1,10 @@
+pub mod analytics;
+pub mod api_keys;
+pub mod audit;
 pub mod database;
+pub mod oidc;
+pub mod ranking;
+pub mod totp;
+pub mod signing_keys;
+pub mod passwords;
+pub mod rate_limit;
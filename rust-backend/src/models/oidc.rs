@@ -0,0 +1,156 @@
+use crate::structs::database_structs::{DatabaseConnection, OidcIdentity, OidcState, PendingLink, User};
+use chrono::Utc;
+use redis::Commands;
+use uuid::Uuid;
+
+// password_hash for users created through OIDC. It is not a valid bcrypt hash, so
+// password login always fails for them.
+pub const NO_PASSWORD: &str = "!";
+
+impl DatabaseConnection {
+    pub async fn save_oidc_state(&self, state: &str, data: &OidcState, ttl_secs: u64) -> Result<(), redis::RedisError> {
+        let mut conn = self.redis.get_connection()?;
+        let value = serde_json::to_string(data).expect("OidcState serializes");
+        conn.set_ex::<_, _, ()>(format!("oidc:state:{}", state), value, ttl_secs)
+    }
+
+    // States are single use
+    pub async fn take_oidc_state(&self, state: &str) -> Result<Option<OidcState>, redis::RedisError> {
+        let mut conn = self.redis.get_connection()?;
+        let value: Option<String> = redis::cmd("GETDEL")
+            .arg(format!("oidc:state:{}", state))
+            .query(&mut conn)?;
+        Ok(value.and_then(|v| serde_json::from_str(&v).ok()))
+    }
+
+    pub async fn save_pending_link(&self, code: &str, link: &PendingLink, ttl_secs: u64) -> Result<(), redis::RedisError> {
+        let mut conn = self.redis.get_connection()?;
+        let value = serde_json::to_string(link).expect("PendingLink serializes");
+        conn.set_ex::<_, _, ()>(format!("oidc:link:{}", code), value, ttl_secs)
+    }
+
+    // Link codes are single use too, so a mismatched confirm burns the code
+    pub async fn take_pending_link(&self, code: &str) -> Result<Option<PendingLink>, redis::RedisError> {
+        let mut conn = self.redis.get_connection()?;
+        let value: Option<String> = redis::cmd("GETDEL")
+            .arg(format!("oidc:link:{}", code))
+            .query(&mut conn)?;
+        Ok(value.and_then(|v| serde_json::from_str(&v).ok()))
+    }
+
+    // Discovery documents and JWKS are shared between workers for an hour
+    pub async fn cache_oidc_document(&self, key: &str, value: &str, ttl_secs: u64) -> Result<(), redis::RedisError> {
+        let mut conn = self.redis.get_connection()?;
+        conn.set_ex::<_, _, ()>(format!("oidc:{}", key), value, ttl_secs)
+    }
+
+    pub async fn get_cached_oidc_document(&self, key: &str) -> Result<Option<String>, redis::RedisError> {
+        let mut conn = self.redis.get_connection()?;
+        conn.get(format!("oidc:{}", key))
+    }
+
+    pub async fn get_user_by_identity(&self, provider: &str, subject: &str) -> Result<Option<User>, sqlx::Error> {
+        sqlx::query_as::<_, User>(
+            r#"
+            SELECT u.* FROM users u
+            JOIN user_identities i ON i.user_id = u.id
+            WHERE i.provider = $1 AND i.subject = $2
+            "#,
+        )
+        .bind(provider)
+        .bind(subject)
+        .fetch_optional(&self.pool)
+        .await
+    }
+
+    pub async fn link_identity(
+        &self,
+        user_id: Uuid,
+        provider: &str,
+        subject: &str,
+        email: Option<&str>,
+    ) -> Result<(), sqlx::Error> {
+        sqlx::query(
+            r#"
+            INSERT INTO user_identities (id, user_id, provider, subject, email, created_at, last_login_at)
+            VALUES ($1, $2, $3, $4, $5, $6, $6)
+            "#,
+        )
+        .bind(Uuid::new_v4())
+        .bind(user_id)
+        .bind(provider)
+        .bind(subject)
+        .bind(email)
+        .bind(Utc::now().naive_utc())
+        .execute(&self.pool)
+        .await?;
+        Ok(())
+    }
+
+    pub async fn touch_identity(&self, provider: &str, subject: &str, email: Option<&str>) -> Result<(), sqlx::Error> {
+        sqlx::query(
+            "UPDATE user_identities SET last_login_at = $3, email = COALESCE($4, email) WHERE provider = $1 AND subject = $2",
+        )
+        .bind(provider)
+        .bind(subject)
+        .bind(Utc::now().naive_utc())
+        .bind(email)
+        .execute(&self.pool)
+        .await?;
+        Ok(())
+    }
+
+    pub async fn get_identities(&self, user_id: Uuid) -> Result<Vec<OidcIdentity>, sqlx::Error> {
+        sqlx::query_as::<_, OidcIdentity>(
+            "SELECT provider, subject, email, created_at, last_login_at FROM user_identities WHERE user_id = $1 ORDER BY created_at",
+        )
+        .bind(user_id)
+        .fetch_all(&self.pool)
+        .await
+    }
+
+    pub async fn unlink_identity(&self, user_id: Uuid, provider: &str) -> Result<bool, sqlx::Error> {
+        let result = sqlx::query("DELETE FROM user_identities WHERE user_id = $1 AND provider = $2")
+            .bind(user_id)
+            .bind(provider)
+            .execute(&self.pool)
+            .await?;
+        Ok(result.rows_affected() > 0)
+    }
+
+    // Creates a password-less user and its identity together
+    pub async fn provision_oidc_user(
+        &self,
+        username: &str,
+        provider: &str,
+        subject: &str,
+        email: Option<&str>,
+    ) -> Result<User, sqlx::Error> {
+        let mut tx = self.pool.begin().await?;
+        let user = sqlx::query_as::<_, User>(
+            "INSERT INTO users (id, username, password_hash) VALUES ($1, $2, $3) RETURNING *",
+        )
+        .bind(Uuid::new_v4())
+        .bind(username)
+        .bind(NO_PASSWORD)
+        .fetch_one(&mut *tx)
+        .await?;
+        let now = Utc::now().naive_utc();
+        sqlx::query(
+            r#"
+            INSERT INTO user_identities (id, user_id, provider, subject, email, created_at, last_login_at)
+            VALUES ($1, $2, $3, $4, $5, $6, $6)
+            "#,
+        )
+        .bind(Uuid::new_v4())
+        .bind(user.id)
+        .bind(provider)
+        .bind(subject)
+        .bind(email)
+        .bind(now)
+        .execute(&mut *tx)
+        .await?;
+        tx.commit().await?;
+        Ok(user)
+    }
+}
@@ -0,0 +1,145 @@
+use crate::structs::database_structs::{DatabaseConnection, User};
+use chrono::{Duration, NaiveDateTime, Utc};
+use sqlx::{Postgres, Transaction};
+use uuid::Uuid;
+
+// A new password should cut off everything the old one let someone set up, including
+// keys minted with a stolen session. Revoked rather than deleted so they stay listed.
+async fn revoke_all_api_keys(tx: &mut Transaction<'_, Postgres>, user_id: Uuid) -> Result<(), sqlx::Error> {
+    sqlx::query("UPDATE api_keys SET revoked_at = $2 WHERE user_id = $1 AND revoked_at IS NULL")
+        .bind(user_id)
+        .bind(Utc::now().naive_utc())
+        .execute(&mut **tx)
+        .await?;
+    Ok(())
+}
+
+impl DatabaseConnection {
+    // Sets a new bcrypt hash, ends every other session of the user and revokes their API keys
+    pub async fn set_user_password(
+        &self,
+        user_id: Uuid,
+        password_hash: &str,
+        keep_token: Option<&str>,
+    ) -> Result<bool, sqlx::Error> {
+        let mut tx = self.pool.begin().await?;
+        let result = sqlx::query("UPDATE users SET password_hash = $2 WHERE id = $1")
+            .bind(user_id)
+            .bind(password_hash)
+            .execute(&mut *tx)
+            .await?;
+        sqlx::query("DELETE FROM sessions WHERE user_id = $1 AND token IS DISTINCT FROM $2")
+            .bind(user_id)
+            .bind(keep_token)
+            .execute(&mut *tx)
+            .await?;
+        revoke_all_api_keys(&mut tx, user_id).await?;
+        tx.commit().await?;
+        Ok(result.rows_affected() > 0)
+    }
+
+    pub async fn set_user_email(&self, user_id: Uuid, email: Option<&str>) -> Result<bool, sqlx::Error> {
+        let result = sqlx::query("UPDATE users SET email = $2 WHERE id = $1")
+            .bind(user_id)
+            .bind(email)
+            .execute(&self.pool)
+            .await?;
+        Ok(result.rows_affected() > 0)
+    }
+
+    pub async fn get_user_by_email(&self, email: &str) -> Result<Option<User>, sqlx::Error> {
+        sqlx::query_as::<_, User>("SELECT * FROM users WHERE email = $1")
+            .bind(email)
+            .fetch_optional(&self.pool)
+            .await
+    }
+
+    // Replaces any outstanding token for the user. Returns false without creating one
+    // if a token was issued in the last minute, so the endpoint can't be used to flood
+    // someone's inbox.
+    pub async fn create_password_reset_token(
+        &self,
+        user_id: Uuid,
+        token_hash: &str,
+        expires_at: NaiveDateTime,
+    ) -> Result<bool, sqlx::Error> {
+        let now = Utc::now().naive_utc();
+        let mut tx = self.pool.begin().await?;
+        let recent: bool = sqlx::query_scalar(
+            "SELECT EXISTS (SELECT 1 FROM password_reset_tokens WHERE user_id = $1 AND created_at > $2)",
+        )
+        .bind(user_id)
+        .bind(now - Duration::minutes(1))
+        .fetch_one(&mut *tx)
+        .await?;
+        if recent {
+            return Ok(false);
+        }
+        sqlx::query("DELETE FROM password_reset_tokens WHERE user_id = $1")
+            .bind(user_id)
+            .execute(&mut *tx)
+            .await?;
+        sqlx::query(
+            "INSERT INTO password_reset_tokens (id, user_id, token_hash, created_at, expires_at) VALUES ($1, $2, $3, $4, $5)",
+        )
+        .bind(Uuid::new_v4())
+        .bind(user_id)
+        .bind(token_hash)
+        .bind(now)
+        .bind(expires_at)
+        .execute(&mut *tx)
+        .await?;
+        tx.commit().await?;
+        Ok(true)
+    }
+
+    // The user a still-valid reset token belongs to, without using it up
+    pub async fn get_password_reset_user(&self, token_hash: &str) -> Result<Option<User>, sqlx::Error> {
+        sqlx::query_as::<_, User>(
+            r#"
+            SELECT u.* FROM users u
+            JOIN password_reset_tokens t ON t.user_id = u.id
+            WHERE t.token_hash = $1 AND t.used_at IS NULL AND t.expires_at > $2
+            "#,
+        )
+        .bind(token_hash)
+        .bind(Utc::now().naive_utc())
+        .fetch_optional(&self.pool)
+        .await
+    }
+
+    // Uses the token, sets the password, ends all sessions and revokes all API keys, in one transaction
+    pub async fn reset_password_with_token(
+        &self,
+        token_hash: &str,
+        password_hash: &str,
+    ) -> Result<Option<Uuid>, sqlx::Error> {
+        let mut tx = self.pool.begin().await?;
+        let user_id: Option<Uuid> = sqlx::query_scalar(
+            r#"
+            UPDATE password_reset_tokens SET used_at = $2
+            WHERE token_hash = $1 AND used_at IS NULL AND expires_at > $2
+            RETURNING user_id
+            "#,
+        )
+        .bind(token_hash)
+        .bind(Utc::now().naive_utc())
+        .fetch_optional(&mut *tx)
+        .await?;
+        let Some(user_id) = user_id else {
+            return Ok(None);
+        };
+        sqlx::query("UPDATE users SET password_hash = $2 WHERE id = $1")
+            .bind(user_id)
+            .bind(password_hash)
+            .execute(&mut *tx)
+            .await?;
+        sqlx::query("DELETE FROM sessions WHERE user_id = $1")
+            .bind(user_id)
+            .execute(&mut *tx)
+            .await?;
+        revoke_all_api_keys(&mut tx, user_id).await?;
+        tx.commit().await?;
+        Ok(Some(user_id))
+    }
+}
@@ -0,0 +1,136 @@
+use crate::structs::database_structs::StickerMetric;
+use chrono::NaiveDateTime;
+use std::collections::{HashMap, HashSet};
+
+const USAGE_WEIGHT: f64 = 1.0;
+const USAGE_HALF_LIFE_DAYS: f64 = 14.0; // a use two weeks ago counts half as much as one today
+const SKIP_THRESHOLD: i32 = 3; // skips tolerated before a sticker starts sinking
+const SKIP_WEIGHT: f64 = 0.5;
+const FAVORITE_BOOST: f64 = 5.0; // enough to lift a favorite above most usage history
+const RATING_WEIGHT: f64 = 3.0; // a like or dislike outweighs a few uses or skips
+
+// Exponential decay by age, halving every USAGE_HALF_LIFE_DAYS
+fn decay(last_used: NaiveDateTime, now: NaiveDateTime) -> f64 {
+    let age_days = (now - last_used).num_seconds().max(0) as f64 / 86400.0;
+    0.5_f64.powf(age_days / USAGE_HALF_LIFE_DAYS)
+}
+
+// Positive for stickers the user keeps using or liked, negative for ones they keep
+// ignoring or disliked
+pub fn personal_score(metric: &StickerMetric, now: NaiveDateTime) -> f64 {
+    let boost = USAGE_WEIGHT * metric.usage_count as f64 * decay(metric.last_used, now);
+    let skips = metric.shown_count - metric.usage_count;
+    let penalty = if skips >= SKIP_THRESHOLD {
+        SKIP_WEIGHT * (skips - SKIP_THRESHOLD + 1) as f64
+    } else {
+        0.0
+    };
+    boost - penalty + RATING_WEIGHT * metric.rating as f64
+}
+
+// Re-orders candidates by personal score plus a boost for favorites. The sort is
+// stable, so stickers without history keep the order the sticker service returned them in.
+pub fn rerank(
+    sticker_urls: Vec<String>,
+    metrics: &[StickerMetric],
+    favorites: &HashSet<String>,
+    now: NaiveDateTime,
+) -> Vec<String> {
+    let scores: HashMap<&str, f64> = metrics
+        .iter()
+        .map(|m| (m.sticker_url.as_str(), personal_score(m, now)))
+        .collect();
+
+    let mut scored: Vec<(f64, String)> = sticker_urls
+        .into_iter()
+        .map(|url| {
+            let mut score = scores.get(url.as_str()).copied().unwrap_or(0.0);
+            if favorites.contains(&url) {
+                score += FAVORITE_BOOST;
+            }
+            (score, url)
+        })
+        .collect();
+    scored.sort_by(|a, b| b.0.total_cmp(&a.0));
+    scored.into_iter().map(|(_, url)| url).collect()
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use chrono::{Duration, NaiveDate};
+    use uuid::Uuid;
+
+    fn now() -> NaiveDateTime {
+        NaiveDate::from_ymd_opt(2025, 6, 1).unwrap().and_hms_opt(12, 0, 0).unwrap()
+    }
+
+    fn metric(url: &str, usage_count: i32, shown_count: i32, days_ago: i64) -> StickerMetric {
+        StickerMetric {
+            id: Uuid::new_v4(),
+            user_id: Uuid::nil(),
+            sticker_url: url.to_string(),
+            usage_count,
+            last_used: now() - Duration::days(days_ago),
+            shown_count,
+            last_shown: Some(now()),
+            rating: 0,
+        }
+    }
+
+    fn close(a: f64, b: f64) -> bool {
+        (a - b).abs() < 1e-9
+    }
+
+    #[test]
+    fn usage_halves_every_half_life() {
+        assert!(close(decay(now(), now()), 1.0));
+        assert!(close(decay(now() - Duration::days(14), now()), 0.5));
+        assert!(close(decay(now() - Duration::days(28), now()), 0.25));
+        // A last_used in the future (clock skew between instances) counts as now
+        assert!(close(decay(now() + Duration::days(3), now()), 1.0));
+    }
+
+    #[test]
+    fn skips_only_count_past_the_threshold() {
+        assert!(close(personal_score(&metric("a", 2, 4, 0), now()), 2.0)); // 2 skips
+        assert!(close(personal_score(&metric("a", 2, 5, 0), now()), 2.0 - 0.5)); // 3 skips
+        assert!(close(personal_score(&metric("a", 2, 7, 0), now()), 2.0 - 1.5)); // 5 skips
+        assert!(close(personal_score(&metric("a", 0, 6, 0), now()), -2.0));
+        assert!(close(personal_score(&metric("a", 4, 4, 14), now()), 2.0));
+    }
+
+    #[test]
+    fn likes_lift_and_dislikes_sink() {
+        let rated = |rating| StickerMetric { rating, ..metric("a", 1, 1, 0) };
+        assert!(close(personal_score(&rated(1), now()), 1.0 + 3.0));
+        assert!(close(personal_score(&rated(-1), now()), 1.0 - 3.0));
+
+        let urls = ["a", "b", "c"].map(String::from).to_vec();
+        let metrics = [
+            StickerMetric { rating: -1, ..metric("a", 2, 2, 0) },
+            StickerMetric { rating: 1, ..metric("c", 0, 1, 0) },
+        ];
+        assert_eq!(rerank(urls, &metrics, &HashSet::new(), now()), ["c", "b", "a"]);
+    }
+
+    #[test]
+    fn rerank_orders_by_score_and_keeps_ties_in_service_order() {
+        let urls = ["a", "b", "c", "d", "e"].map(String::from).to_vec();
+        let metrics = [
+            metric("b", 0, 10, 0), // ignored often: sinks below stickers without history
+            metric("d", 3, 3, 0),  // used recently: rises to the top
+            metric("e", 3, 3, 56), // used long ago: barely above no history
+        ];
+        let favorites = HashSet::from(["c".to_string()]);
+
+        let ranked = rerank(urls, &metrics, &favorites, now());
+        assert_eq!(ranked, ["c", "d", "e", "a", "b"]);
+    }
+
+    #[test]
+    fn rerank_without_history_is_unchanged() {
+        let urls = ["x", "y", "z"].map(String::from).to_vec();
+        assert_eq!(rerank(urls.clone(), &[], &HashSet::new(), now()), urls);
+    }
+}
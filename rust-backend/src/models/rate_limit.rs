@@ -0,0 +1,29 @@
+use crate::structs::database_structs::DatabaseConnection;
+use redis::AsyncCommands;
+
+// These run on every request, so they use async connections rather than blocking a worker
+impl DatabaseConnection {
+    // Fixed-window counters: increments every key and returns the new counts in order.
+    // Each key carries its own expiry so finished windows clean themselves up.
+    pub async fn count_rate_limit_hits(&self, keys: &[(String, u64)]) -> Result<Vec<u64>, redis::RedisError> {
+        let mut conn = self.redis.get_multiplexed_async_connection().await?;
+        let mut pipe = redis::pipe();
+        pipe.atomic();
+        for (key, ttl_secs) in keys {
+            pipe.incr(key, 1).expire(key, *ttl_secs as i64).ignore();
+        }
+        pipe.query_async(&mut conn).await
+    }
+
+    // Reads a counter without counting the request, 0 when the window hasn't started
+    pub async fn peek_rate_limit_hits(&self, key: &str) -> Result<u64, redis::RedisError> {
+        let mut conn = self.redis.get_multiplexed_async_connection().await?;
+        Ok(conn.get::<_, Option<u64>>(key).await?.unwrap_or(0))
+    }
+
+    // Gives back a hit counted for a request that was then rejected
+    pub async fn release_rate_limit_hit(&self, key: &str) -> Result<(), redis::RedisError> {
+        let mut conn = self.redis.get_multiplexed_async_connection().await?;
+        conn.decr::<_, _, ()>(key, 1).await
+    }
+}
@@ -0,0 +1,69 @@
+use crate::structs::database_structs::{DatabaseConnection, SigningKeyRow};
+use chrono::{NaiveDateTime, Utc};
+
+impl DatabaseConnection {
+    // Oldest first, so each key's signing period ends where the next one's starts
+    pub async fn get_signing_keys(&self) -> Result<Vec<SigningKeyRow>, sqlx::Error> {
+        sqlx::query_as::<_, SigningKeyRow>(
+            "SELECT kid, algorithm, private_key, public_jwk, sign_from FROM jwt_signing_keys ORDER BY sign_from",
+        )
+        .fetch_all(&self.pool)
+        .await
+    }
+
+    // Inserts a key unless another instance already added one after `newest`.
+    // The advisory lock serializes rotation across instances.
+    pub async fn insert_signing_key(
+        &self,
+        kid: &str,
+        algorithm: &str,
+        private_key: &str,
+        public_jwk: &serde_json::Value,
+        sign_from: NaiveDateTime,
+        newest: Option<NaiveDateTime>,
+    ) -> Result<bool, sqlx::Error> {
+        let mut tx = self.pool.begin().await?;
+        sqlx::query("SELECT pg_advisory_xact_lock(hashtext('jwt_signing_keys'))")
+            .execute(&mut *tx)
+            .await?;
+        let current: Option<NaiveDateTime> = sqlx::query_scalar("SELECT MAX(sign_from) FROM jwt_signing_keys")
+            .fetch_one(&mut *tx)
+            .await?;
+        if current != newest {
+            return Ok(false);
+        }
+        sqlx::query(
+            r#"
+            INSERT INTO jwt_signing_keys (kid, algorithm, private_key, public_jwk, created_at, sign_from)
+            VALUES ($1, $2, $3, $4, $5, $6)
+            "#,
+        )
+        .bind(kid)
+        .bind(algorithm)
+        .bind(private_key)
+        .bind(public_jwk)
+        .bind(Utc::now().naive_utc())
+        .bind(sign_from)
+        .execute(&mut *tx)
+        .await?;
+        tx.commit().await?;
+        Ok(true)
+    }
+
+    // Drops keys that were replaced before `replaced_before`; tokens they signed have expired
+    pub async fn delete_retired_signing_keys(&self, replaced_before: NaiveDateTime) -> Result<u64, sqlx::Error> {
+        let result = sqlx::query(
+            r#"
+            DELETE FROM jwt_signing_keys k
+            WHERE EXISTS (
+                SELECT 1 FROM jwt_signing_keys n
+                WHERE n.sign_from > k.sign_from AND n.sign_from <= $1
+            )
+            "#,
+        )
+        .bind(replaced_before)
+        .execute(&self.pool)
+        .await?;
+        Ok(result.rows_affected())
+    }
+}
@@ -0,0 +1,178 @@
+use crate::structs::database_structs::{AccountKind, DatabaseConnection, MfaChallenge, TotpState};
+use chrono::Utc;
+use redis::Commands;
+use uuid::Uuid;
+
+impl DatabaseConnection {
+    pub async fn get_totp_state(&self, kind: AccountKind, id: Uuid) -> Result<Option<TotpState>, sqlx::Error> {
+        sqlx::query_as::<_, TotpState>(&format!(
+            "SELECT totp_secret, totp_enabled FROM {} WHERE id = $1",
+            kind.table()
+        ))
+        .bind(id)
+        .fetch_optional(&self.pool)
+        .await
+    }
+
+    // Stores a new secret awaiting confirmation; refused while 2FA is already on
+    pub async fn set_pending_totp_secret(&self, kind: AccountKind, id: Uuid, secret: &str) -> Result<bool, sqlx::Error> {
+        let result = sqlx::query(&format!(
+            "UPDATE {} SET totp_secret = $2, totp_last_step = NULL WHERE id = $1 AND NOT totp_enabled",
+            kind.table()
+        ))
+        .bind(id)
+        .bind(secret)
+        .execute(&self.pool)
+        .await?;
+        Ok(result.rows_affected() > 0)
+    }
+
+    // Turns on the pending secret and replaces any recovery codes
+    pub async fn enable_totp(
+        &self,
+        kind: AccountKind,
+        id: Uuid,
+        step: i64,
+        code_hashes: &[String],
+    ) -> Result<bool, sqlx::Error> {
+        let mut tx = self.pool.begin().await?;
+        let result = sqlx::query(&format!(
+            r#"
+            UPDATE {} SET totp_enabled = TRUE, totp_enabled_at = $2, totp_last_step = $3
+            WHERE id = $1 AND NOT totp_enabled AND totp_secret IS NOT NULL
+            "#,
+            kind.table()
+        ))
+        .bind(id)
+        .bind(Utc::now().naive_utc())
+        .bind(step)
+        .execute(&mut *tx)
+        .await?;
+        if result.rows_affected() == 0 {
+            return Ok(false);
+        }
+        Self::insert_recovery_codes(&mut tx, kind, id, code_hashes).await?;
+        tx.commit().await?;
+        Ok(true)
+    }
+
+    // Records the step of an accepted code. Fails if that step (or a later one) was
+    // already used, so each code works once.
+    pub async fn consume_totp_step(&self, kind: AccountKind, id: Uuid, step: i64) -> Result<bool, sqlx::Error> {
+        let result = sqlx::query(&format!(
+            "UPDATE {} SET totp_last_step = $2 WHERE id = $1 AND (totp_last_step IS NULL OR totp_last_step < $2)",
+            kind.table()
+        ))
+        .bind(id)
+        .bind(step)
+        .execute(&self.pool)
+        .await?;
+        Ok(result.rows_affected() > 0)
+    }
+
+    pub async fn consume_recovery_code(&self, kind: AccountKind, id: Uuid, code_hash: &str) -> Result<bool, sqlx::Error> {
+        let result = sqlx::query(&format!(
+            "UPDATE totp_recovery_codes SET used_at = $3 WHERE {} = $1 AND code_hash = $2 AND used_at IS NULL",
+            kind.owner_column()
+        ))
+        .bind(id)
+        .bind(code_hash)
+        .bind(Utc::now().naive_utc())
+        .execute(&self.pool)
+        .await?;
+        Ok(result.rows_affected() > 0)
+    }
+
+    pub async fn replace_recovery_codes(&self, kind: AccountKind, id: Uuid, code_hashes: &[String]) -> Result<(), sqlx::Error> {
+        let mut tx = self.pool.begin().await?;
+        Self::insert_recovery_codes(&mut tx, kind, id, code_hashes).await?;
+        tx.commit().await
+    }
+
+    async fn insert_recovery_codes(
+        tx: &mut sqlx::Transaction<'_, sqlx::Postgres>,
+        kind: AccountKind,
+        id: Uuid,
+        code_hashes: &[String],
+    ) -> Result<(), sqlx::Error> {
+        sqlx::query(&format!("DELETE FROM totp_recovery_codes WHERE {} = $1", kind.owner_column()))
+            .bind(id)
+            .execute(&mut **tx)
+            .await?;
+        let now = Utc::now().naive_utc();
+        for hash in code_hashes {
+            sqlx::query(&format!(
+                "INSERT INTO totp_recovery_codes (id, {}, code_hash, created_at) VALUES ($1, $2, $3, $4)",
+                kind.owner_column()
+            ))
+            .bind(Uuid::new_v4())
+            .bind(id)
+            .bind(hash)
+            .bind(now)
+            .execute(&mut **tx)
+            .await?;
+        }
+        Ok(())
+    }
+
+    pub async fn count_recovery_codes(&self, kind: AccountKind, id: Uuid) -> Result<i64, sqlx::Error> {
+        sqlx::query_scalar::<_, i64>(&format!(
+            "SELECT COUNT(*) FROM totp_recovery_codes WHERE {} = $1 AND used_at IS NULL",
+            kind.owner_column()
+        ))
+        .bind(id)
+        .fetch_one(&self.pool)
+        .await
+    }
+
+    // Clears the secret and recovery codes. Returns false if there was nothing to reset.
+    pub async fn reset_totp(&self, kind: AccountKind, id: Uuid) -> Result<bool, sqlx::Error> {
+        let mut tx = self.pool.begin().await?;
+        let result = sqlx::query(&format!(
+            r#"
+            UPDATE {} SET totp_secret = NULL, totp_enabled = FALSE, totp_enabled_at = NULL, totp_last_step = NULL
+            WHERE id = $1 AND totp_secret IS NOT NULL
+            "#,
+            kind.table()
+        ))
+        .bind(id)
+        .execute(&mut *tx)
+        .await?;
+        sqlx::query(&format!("DELETE FROM totp_recovery_codes WHERE {} = $1", kind.owner_column()))
+            .bind(id)
+            .execute(&mut *tx)
+            .await?;
+        tx.commit().await?;
+        Ok(result.rows_affected() > 0)
+    }
+
+    pub async fn save_mfa_challenge(&self, token: &str, challenge: &MfaChallenge, ttl_secs: u64) -> Result<(), redis::RedisError> {
+        let mut conn = self.redis.get_connection()?;
+        let value = serde_json::to_string(challenge).expect("MfaChallenge serializes");
+        conn.set_ex::<_, _, ()>(format!("mfa:challenge:{}", token), value, ttl_secs)
+    }
+
+    pub async fn get_mfa_challenge(&self, token: &str) -> Result<Option<MfaChallenge>, redis::RedisError> {
+        let mut conn = self.redis.get_connection()?;
+        let value: Option<String> = conn.get(format!("mfa:challenge:{}", token))?;
+        Ok(value.and_then(|v| serde_json::from_str(&v).ok()))
+    }
+
+    // Drops the challenge and its attempt counter once it succeeds or runs out of attempts
+    pub async fn delete_mfa_challenge(&self, token: &str) -> Result<(), redis::RedisError> {
+        let mut conn = self.redis.get_connection()?;
+        conn.del::<_, ()>(&[format!("mfa:challenge:{}", token), format!("mfa:attempts:{}", token)])
+    }
+
+    pub async fn count_mfa_attempt(&self, token: &str, ttl_secs: u64) -> Result<i64, redis::RedisError> {
+        let mut conn = self.redis.get_connection()?;
+        let key = format!("mfa:attempts:{}", token);
+        let (count,): (i64,) = redis::pipe()
+            .atomic()
+            .incr(&key, 1)
+            .expire(&key, ttl_secs as i64)
+            .ignore()
+            .query(&mut conn)?;
+        Ok(count)
+    }
+}
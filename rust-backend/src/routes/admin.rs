@@ -1,79 +1,745 @@
-use crate::structs::database_structs::{DatabaseConnection, ManagementRequest, RegisterRequest};
+use crate::configs::accounts::AccountConfig;
+use crate::configs::password::PasswordPolicy;
+use crate::middleware::validation::ValidatedJson;
+use crate::middleware::auth::{create_impersonation_token, AuthConfig, AuthData};
+use crate::routes::audit::{self, audit_event};
+use crate::routes::password::{check_password, normalize_email};
+use crate::structs::database_structs::{
+    AccountKind, ActivityResponse, AdminAnalyticsQuery, DatabaseConnection, ImpersonateRequest,
+    ManagementRequest, NewAuditEvent, RegisterRequest, StatusChangeRequest, UserDetail,
+    UserListQuery, UserListResponse
+};
 use actix_web::error::Error as ActixError;
-use actix_web::{delete, get, post, put, web, HttpResponse};
+use actix_web::{delete, get, post, put, web, HttpRequest, HttpResponse};
+use chrono::{Duration, NaiveDate, Utc};
 use uuid::Uuid;
+use utoipa::OpenApi;
 
+fn parse_user_id(id: &str) -> Result<Uuid, ActixError> {
+    Uuid::parse_str(id).map_err(|_| {
+        log::error!("Invalid user ID");
+        actix_web::error::ErrorBadRequest("Invalid user ID")
+    })
+}
+
+// Audit event attributed to the calling admin, targeting a user
+fn admin_event(http_req: &HttpRequest, auth_data: &AuthData, action: &str, user_id: Uuid) -> NewAuditEvent {
+    let mut event = audit_event(http_req, action).target("user", user_id);
+    event.actor_type = "admin".to_string();
+    event.actor_id = Uuid::parse_str(&auth_data.id).ok();
+    event
+}
+
+// Empty reasons are stored as NULL
+fn reason_from(req: Option<web::Json<StatusChangeRequest>>) -> Option<String> {
+    req.and_then(|r| r.into_inner().reason)
+        .map(|reason| reason.trim().to_string())
+        .filter(|reason| !reason.is_empty())
+}
+
+// Row offset of a 1-based page. A page so large the offset overflows is a 400, not a panic.
+pub fn page_offset(page: i64, per_page: i64) -> Result<i64, ActixError> {
+    (page.max(1) - 1)
+        .checked_mul(per_page)
+        .ok_or_else(|| actix_web::error::ErrorBadRequest("page is too large"))
+}
+
+#[utoipa::path(
+    get,
+    path = "/v1/admin/users",
+    tag = "admin",
+    params(UserListQuery),
+    responses(
+        (status = 200, description = "A page of users", body = UserListResponse),
+        (status = 400, description = "sort must be username or -username, or page is too large"),
+    )
+)]
 #[get("/users")]
-async fn list_users(db: web::Data<DatabaseConnection>) -> Result<HttpResponse, ActixError> {
-    db.get_all_users()
+async fn list_users(
+    db: web::Data<DatabaseConnection>,
+    query: web::Query<UserListQuery>,
+) -> Result<HttpResponse, ActixError> {
+    let page = query.page.unwrap_or(1).max(1);
+    let per_page = query.per_page.unwrap_or(25).clamp(1, 100);
+    let descending = match query.sort.as_deref() {
+        None | Some("username") => false,
+        Some("-username") => true,
+        Some(_) => return Err(actix_web::error::ErrorBadRequest("sort must be username or -username")),
+    };
+    let prefix = query.q.as_deref().map(str::trim).filter(|q| !q.is_empty());
+    let offset = page_offset(page, per_page)?;
+
+    db.list_users(prefix, query.status, descending, per_page, offset)
         .await
-        .map(|users| HttpResponse::Ok().json(users))
+        .map(|(users, total)| {
+            HttpResponse::Ok().json(UserListResponse {
+                users,
+                total,
+                page,
+                per_page,
+            })
+        })
         .map_err(|e| {
             log::error!("Failed to fetch users: {}", e);
             actix_web::error::ErrorInternalServerError("Failed to fetch users")
         })
 }
 
+#[utoipa::path(
+    get,
+    path = "/v1/admin/users/{id}",
+    tag = "admin",
+    params(("id" = Uuid, Path, description = "User ID")),
+    responses(
+        (status = 200, description = "Account status and activity counts", body = UserDetail),
+        (status = 404, description = "User not found"),
+    )
+)]
+#[get("/users/{id}")]
+async fn get_user(
+    db: web::Data<DatabaseConnection>,
+    path: web::Path<String>,
+) -> Result<HttpResponse, ActixError> {
+    let user_id = parse_user_id(&path.into_inner())?;
+    match db.get_user_detail(user_id).await {
+        Ok(Some(detail)) => Ok(HttpResponse::Ok().json(detail)),
+        Ok(None) => Err(actix_web::error::ErrorNotFound("User not found")),
+        Err(e) => {
+            log::error!("Failed to fetch user {}: {}", user_id, e);
+            Err(actix_web::error::ErrorInternalServerError("Failed to fetch user"))
+        }
+    }
+}
+
+#[utoipa::path(
+    post,
+    path = "/v1/admin/users",
+    tag = "admin",
+    request_body = RegisterRequest,
+    responses(
+        (status = 200, description = "The new user's `username`", body = Object),
+        (status = 400, description = "Invalid fields, password policy violations or username taken"),
+    )
+)]
 #[post("/users")]
 async fn add_user(
     db: web::Data<DatabaseConnection>,
-    req: web::Json<RegisterRequest>,
+    policy: web::Data<PasswordPolicy>,
+    auth_data: web::ReqData<AuthData>,
+    http_req: HttpRequest,
+    req: ValidatedJson<RegisterRequest>,
 ) -> Result<HttpResponse, ActixError> {
-    db.register_user(req.into_inner())
-        .await
-        .map(|user| HttpResponse::Ok().json(serde_json::json!({ "username": user.username })))
-        .map_err(|e| {
-            log::error!("Failed to add user: {}", e);
-            actix_web::error::ErrorBadRequest("Username already exists")
-        })
+    let mut req = req.into_inner();
+    check_password(&policy, &req.password, &req.username)?;
+    req.email = req.email.as_deref().map(normalize_email).transpose()?;
+    let user = db.register_user(req).await.map_err(|e| {
+        log::error!("Failed to add user: {}", e);
+        actix_web::error::ErrorBadRequest("Username already exists")
+    })?;
+    audit::record(
+        &db,
+        admin_event(&http_req, &auth_data, "admin.create_user", user.id)
+            .diff(serde_json::json!({ "username": audit::change(serde_json::Value::Null, user.username.clone()) })),
+    )
+    .await;
+    Ok(HttpResponse::Ok().json(serde_json::json!({ "username": user.username })))
 }
 
+#[utoipa::path(
+    put,
+    path = "/v1/admin/users/{id}",
+    tag = "admin",
+    params(("id" = Uuid, Path, description = "User ID")),
+    request_body = RegisterRequest,
+    responses(
+        (status = 200, description = "The updated `username`", body = Object),
+        (status = 400, description = "Invalid fields, password policy violations or username taken"),
+        (status = 404, description = "User not found"),
+    )
+)]
 #[put("/users/{id}")]
 async fn update_user(
     db: web::Data<DatabaseConnection>,
+    policy: web::Data<PasswordPolicy>,
+    auth_data: web::ReqData<AuthData>,
+    http_req: HttpRequest,
     path: web::Path<String>,
-    req: web::Json<RegisterRequest>,
+    req: ValidatedJson<RegisterRequest>,
 ) -> Result<HttpResponse, ActixError> {
-    let user_id = Uuid::parse_str(&path.into_inner()).map_err(|_| {
-        log::error!("Invalid user ID");
-        actix_web::error::ErrorBadRequest("Invalid user ID")
-    })?;
-    db.update_user(
-        user_id,
-        ManagementRequest {
-            username: Some(req.username.clone()),
-            password: Some(req.password.clone()),
-        },
-    )
-    .await
-    .map(|user| HttpResponse::Ok().json(serde_json::json!({ "username": user.username })))
-    .map_err(|e| {
-        log::error!("Failed to update user {}: {}", user_id, e);
-        actix_web::error::ErrorBadRequest("Username already exists or invalid")
-    })
+    let user_id = parse_user_id(&path.into_inner())?;
+    check_password(&policy, &req.password, &req.username)?;
+    let old_username = match db.get_user_by_id(user_id).await {
+        Ok(Some(user)) => user.username,
+        Ok(None) => return Err(actix_web::error::ErrorNotFound("User not found")),
+        Err(e) => {
+            log::error!("Failed to fetch user {}: {}", user_id, e);
+            return Err(actix_web::error::ErrorInternalServerError("Failed to update user"));
+        }
+    };
+    let user = db
+        .update_user(
+            user_id,
+            ManagementRequest {
+                username: Some(req.username.clone()),
+                password: Some(req.password.clone()),
+            },
+        )
+        .await
+        .map_err(|e| {
+            log::error!("Failed to update user {}: {}", user_id, e);
+            actix_web::error::ErrorBadRequest("Username already exists or invalid")
+        })?;
+    // Passwords are never written to the audit log, only the fact that one was set
+    let mut diff = serde_json::json!({ "password": "changed" });
+    if old_username != user.username {
+        diff["username"] = audit::change(old_username, user.username.clone());
+    }
+    audit::record(&db, admin_event(&http_req, &auth_data, "admin.update_user", user_id).diff(diff)).await;
+    Ok(HttpResponse::Ok().json(serde_json::json!({ "username": user.username })))
 }
 
+// Soft delete: the user is signed out and blocked, and erased once the restore window passes
+#[utoipa::path(
+    delete,
+    path = "/v1/admin/users/{id}",
+    tag = "admin",
+    params(("id" = Uuid, Path, description = "User ID")),
+    request_body(content = Option<StatusChangeRequest>, description = "Optional reason, kept in the audit log"),
+    responses(
+        (status = 200, description = "Soft-deleted; `restorable_until` says when the account is erased", body = Object),
+        (status = 404, description = "User not found or already deleted"),
+    )
+)]
 #[delete("/users/{id}")]
-async fn delete_user(
+async fn soft_delete_user(
     db: web::Data<DatabaseConnection>,
+    account_config: web::Data<AccountConfig>,
+    auth_data: web::ReqData<AuthData>,
+    http_req: HttpRequest,
     path: web::Path<String>,
+    req: Option<web::Json<StatusChangeRequest>>,
 ) -> Result<HttpResponse, ActixError> {
-    let user_id = Uuid::parse_str(&path.into_inner()).map_err(|_| {
-        log::error!("Invalid user ID");
-        actix_web::error::ErrorBadRequest("Invalid user ID")
-    })?;
-    db.delete_user(user_id)
+    let user_id = parse_user_id(&path.into_inner())?;
+    let reason = reason_from(req);
+    match db.soft_delete_user(user_id, reason.as_deref()).await {
+        Ok(Some(previous_status)) => {
+            audit::record(
+                &db,
+                admin_event(&http_req, &auth_data, "admin.delete_user", user_id).diff(serde_json::json!({
+                    "status": audit::change(previous_status, "deleted"),
+                    "reason": reason,
+                })),
+            )
+            .await;
+            let purge_after = Utc::now() + Duration::days(account_config.restore_window_days);
+            Ok(HttpResponse::Ok().json(serde_json::json!({
+                "id": user_id,
+                "status": "deleted",
+                "restorable_until": purge_after,
+            })))
+        }
+        Ok(None) => Err(actix_web::error::ErrorNotFound("User not found or already deleted")),
+        Err(e) => {
+            log::error!("Failed to delete user {}: {}", user_id, e);
+            Err(actix_web::error::ErrorInternalServerError("Failed to delete user"))
+        }
+    }
+}
+
+#[utoipa::path(
+    post,
+    path = "/v1/admin/users/{id}/restore",
+    tag = "admin",
+    params(("id" = Uuid, Path, description = "User ID")),
+    responses(
+        (status = 200, description = "User active again", body = Object),
+        (status = 404, description = "No deleted user within the restore window"),
+    )
+)]
+#[post("/users/{id}/restore")]
+async fn restore_user(
+    db: web::Data<DatabaseConnection>,
+    account_config: web::Data<AccountConfig>,
+    auth_data: web::ReqData<AuthData>,
+    http_req: HttpRequest,
+    path: web::Path<String>,
+) -> Result<HttpResponse, ActixError> {
+    let user_id = parse_user_id(&path.into_inner())?;
+    let deleted_after = (Utc::now() - Duration::days(account_config.restore_window_days)).naive_utc();
+    match db.restore_user(user_id, deleted_after).await {
+        Ok(true) => {
+            audit::record(
+                &db,
+                admin_event(&http_req, &auth_data, "admin.restore_user", user_id)
+                    .diff(serde_json::json!({ "status": audit::change("deleted", "active") })),
+            )
+            .await;
+            Ok(HttpResponse::Ok().json(serde_json::json!({ "id": user_id, "status": "active" })))
+        }
+        Ok(false) => Err(actix_web::error::ErrorNotFound("No deleted user within the restore window")),
+        Err(e) => {
+            log::error!("Failed to restore user {}: {}", user_id, e);
+            Err(actix_web::error::ErrorInternalServerError("Failed to restore user"))
+        }
+    }
+}
+
+#[utoipa::path(
+    post,
+    path = "/v1/admin/users/{id}/suspend",
+    tag = "admin",
+    params(("id" = Uuid, Path, description = "User ID")),
+    request_body(content = Option<StatusChangeRequest>, description = "Optional reason, shown to the user"),
+    responses(
+        (status = 200, description = "User suspended and signed out", body = Object),
+        (status = 404, description = "User not found"),
+    )
+)]
+#[post("/users/{id}/suspend")]
+async fn suspend_user(
+    db: web::Data<DatabaseConnection>,
+    auth_data: web::ReqData<AuthData>,
+    http_req: HttpRequest,
+    path: web::Path<String>,
+    req: Option<web::Json<StatusChangeRequest>>,
+) -> Result<HttpResponse, ActixError> {
+    let user_id = parse_user_id(&path.into_inner())?;
+    let reason = reason_from(req);
+    match db.suspend_user(user_id, reason.as_deref()).await {
+        Ok(Some(previous_status)) => {
+            audit::record(
+                &db,
+                admin_event(&http_req, &auth_data, "admin.suspend_user", user_id).diff(serde_json::json!({
+                    "status": audit::change(previous_status, "suspended"),
+                    "reason": reason,
+                })),
+            )
+            .await;
+            Ok(HttpResponse::Ok().json(serde_json::json!({
+                "id": user_id,
+                "status": "suspended",
+                "reason": reason,
+            })))
+        }
+        Ok(None) => Err(actix_web::error::ErrorNotFound("User not found")),
+        Err(e) => {
+            log::error!("Failed to suspend user {}: {}", user_id, e);
+            Err(actix_web::error::ErrorInternalServerError("Failed to suspend user"))
+        }
+    }
+}
+
+#[utoipa::path(
+    post,
+    path = "/v1/admin/users/{id}/reactivate",
+    tag = "admin",
+    params(("id" = Uuid, Path, description = "User ID")),
+    responses(
+        (status = 200, description = "User active again", body = Object),
+        (status = 404, description = "No suspended user with this ID"),
+    )
+)]
+#[post("/users/{id}/reactivate")]
+async fn reactivate_user(
+    db: web::Data<DatabaseConnection>,
+    auth_data: web::ReqData<AuthData>,
+    http_req: HttpRequest,
+    path: web::Path<String>,
+) -> Result<HttpResponse, ActixError> {
+    let user_id = parse_user_id(&path.into_inner())?;
+    match db.reactivate_user(user_id).await {
+        Ok(true) => {
+            audit::record(
+                &db,
+                admin_event(&http_req, &auth_data, "admin.reactivate_user", user_id)
+                    .diff(serde_json::json!({ "status": audit::change("suspended", "active") })),
+            )
+            .await;
+            Ok(HttpResponse::Ok().json(serde_json::json!({ "id": user_id, "status": "active" })))
+        }
+        Ok(false) => Err(actix_web::error::ErrorNotFound("No suspended user with this ID")),
+        Err(e) => {
+            log::error!("Failed to reactivate user {}: {}", user_id, e);
+            Err(actix_web::error::ErrorInternalServerError("Failed to reactivate user"))
+        }
+    }
+}
+
+// Issues a short-lived read-only token for viewing the API as this user
+#[utoipa::path(
+    post,
+    path = "/v1/admin/users/{id}/impersonate",
+    tag = "admin",
+    params(("id" = Uuid, Path, description = "User ID")),
+    request_body = ImpersonateRequest,
+    responses(
+        (status = 200, description = "A read-only `token` for the user and the `impersonation` record", body = Object),
+        (status = 400, description = "A reason is required"),
+        (status = 404, description = "User not found"),
+        (status = 409, description = "Only active users can be impersonated"),
+    )
+)]
+#[post("/users/{id}/impersonate")]
+async fn impersonate_user(
+    db: web::Data<DatabaseConnection>,
+    auth_config: web::Data<AuthConfig>,
+    account_config: web::Data<AccountConfig>,
+    auth_data: web::ReqData<AuthData>,
+    http_req: HttpRequest,
+    path: web::Path<String>,
+    req: web::Json<ImpersonateRequest>,
+) -> Result<HttpResponse, ActixError> {
+    let user_id = parse_user_id(&path.into_inner())?;
+    let admin_id = parse_user_id(&auth_data.id)?;
+    let reason = req.reason.trim();
+    if reason.is_empty() {
+        return Err(actix_web::error::ErrorBadRequest("A reason is required to impersonate a user"));
+    }
+    match db.get_user_by_id(user_id).await {
+        Ok(Some(user)) if user.status == "active" => {}
+        Ok(Some(_)) => return Err(actix_web::error::ErrorConflict("Only active users can be impersonated")),
+        Ok(None) => return Err(actix_web::error::ErrorNotFound("User not found")),
+        Err(e) => {
+            log::error!("Failed to fetch user {}: {}", user_id, e);
+            return Err(actix_web::error::ErrorInternalServerError("Failed to fetch user"));
+        }
+    }
+
+    let ttl = Duration::minutes(account_config.impersonation_ttl_mins);
+    let token = create_impersonation_token(&user_id.to_string(), &admin_id.to_string(), ttl, auth_config.get_ref())
+        .map_err(|e| {
+            log::error!("Failed to generate impersonation token: {}", e);
+            actix_web::error::ErrorInternalServerError("Failed to generate token")
+        })?;
+    let expires_at = (Utc::now() + ttl).naive_utc();
+    let record = db
+        .start_impersonation(admin_id, user_id, reason, &token, expires_at)
         .await
-        .map(|_| HttpResponse::Ok().body("User deleted"))
         .map_err(|e| {
-            log::error!("Failed to delete user {}: {}", user_id, e);
-            actix_web::error::ErrorInternalServerError("Failed to delete user")
+            log::error!("Failed to start impersonation of {} by {}: {}", user_id, admin_id, e);
+            actix_web::error::ErrorInternalServerError("Failed to start impersonation")
+        })?;
+
+    log::warn!("Admin {} started impersonating user {}: {}", admin_id, user_id, reason);
+    audit::record(
+        &db,
+        admin_event(&http_req, &auth_data, "admin.impersonate", user_id).diff(serde_json::json!({
+            "reason": reason,
+            "impersonation_id": record.id,
+            "expires_at": record.expires_at,
+        })),
+    )
+    .await;
+    Ok(HttpResponse::Ok().json(serde_json::json!({
+        "token": token,
+        "impersonation": record,
+    })))
+}
+
+// For users who lost their authenticator and recovery codes; they can log in with
+// their password alone and enroll again.
+#[utoipa::path(
+    post,
+    path = "/v1/admin/users/{id}/2fa/reset",
+    tag = "admin",
+    params(("id" = Uuid, Path, description = "User ID")),
+    responses(
+        (status = 200, description = "2FA turned off for the user", body = Object),
+        (status = 404, description = "No user with 2FA set up for this ID"),
+    )
+)]
+#[post("/users/{id}/2fa/reset")]
+async fn reset_user_2fa(
+    db: web::Data<DatabaseConnection>,
+    auth_data: web::ReqData<AuthData>,
+    http_req: HttpRequest,
+    path: web::Path<String>,
+) -> Result<HttpResponse, ActixError> {
+    let user_id = parse_user_id(&path.into_inner())?;
+    match db.reset_totp(AccountKind::User, user_id).await {
+        Ok(true) => {
+            log::warn!("Admin {} reset 2FA for user {}", auth_data.id, user_id);
+            audit::record(&db, admin_event(&http_req, &auth_data, "admin.reset_2fa", user_id)).await;
+            Ok(HttpResponse::Ok().json(serde_json::json!({ "id": user_id, "totp_enabled": false })))
+        }
+        Ok(false) => Err(actix_web::error::ErrorNotFound("No user with 2FA set up for this ID")),
+        Err(e) => {
+            log::error!("Failed to reset 2FA for user {}: {}", user_id, e);
+            Err(actix_web::error::ErrorInternalServerError("Failed to reset 2FA"))
+        }
+    }
+}
+
+// Recovery for an admin who lost their authenticator: another admin clears it, and the
+// next login enrolls again. With no second admin, see "Admin 2FA recovery" in the README.
+#[utoipa::path(
+    post,
+    path = "/v1/admin/admins/{id}/2fa/reset",
+    tag = "admin",
+    params(("id" = Uuid, Path, description = "Admin ID")),
+    responses(
+        (status = 200, description = "2FA turned off for the admin", body = Object),
+        (status = 400, description = "Invalid ID, or the caller's own ID"),
+        (status = 404, description = "No admin with 2FA set up for this ID"),
+    )
+)]
+#[post("/admins/{id}/2fa/reset")]
+async fn reset_admin_2fa(
+    db: web::Data<DatabaseConnection>,
+    auth_data: web::ReqData<AuthData>,
+    http_req: HttpRequest,
+    path: web::Path<String>,
+) -> Result<HttpResponse, ActixError> {
+    let admin_id = Uuid::parse_str(&path.into_inner())
+        .map_err(|_| actix_web::error::ErrorBadRequest("Invalid admin ID"))?;
+    // An admin who can still sign in has a working second factor; resetting it would only weaken the account
+    if auth_data.id == admin_id.to_string() {
+        return Err(actix_web::error::ErrorBadRequest("Admins cannot reset their own 2FA"));
+    }
+    match db.reset_totp(AccountKind::Admin, admin_id).await {
+        Ok(true) => {
+            log::warn!("Admin {} reset 2FA for admin {}", auth_data.id, admin_id);
+            let mut event = audit_event(&http_req, "admin.reset_admin_2fa").target("admin", admin_id);
+            event.actor_type = "admin".to_string();
+            event.actor_id = Uuid::parse_str(&auth_data.id).ok();
+            audit::record(&db, event).await;
+            Ok(HttpResponse::Ok().json(serde_json::json!({ "id": admin_id, "totp_enabled": false })))
+        }
+        Ok(false) => Err(actix_web::error::ErrorNotFound("No admin with 2FA set up for this ID")),
+        Err(e) => {
+            log::error!("Failed to reset 2FA for admin {}: {}", admin_id, e);
+            Err(actix_web::error::ErrorInternalServerError("Failed to reset 2FA"))
+        }
+    }
+}
+
+#[utoipa::path(
+    get,
+    path = "/v1/admin/users/{id}/impersonations",
+    tag = "admin",
+    params(("id" = Uuid, Path, description = "User ID")),
+    responses((status = 200, description = "Impersonations of the user as `{\"impersonations\": [...]}`", body = Object))
+)]
+#[get("/users/{id}/impersonations")]
+async fn list_impersonations(
+    db: web::Data<DatabaseConnection>,
+    path: web::Path<String>,
+) -> Result<HttpResponse, ActixError> {
+    let user_id = parse_user_id(&path.into_inner())?;
+    db.get_impersonations(user_id)
+        .await
+        .map(|records| HttpResponse::Ok().json(serde_json::json!({ "impersonations": records })))
+        .map_err(|e| {
+            log::error!("Failed to fetch impersonations for {}: {}", user_id, e);
+            actix_web::error::ErrorInternalServerError("Failed to fetch impersonations")
         })
 }
 
+fn analytics_since(query: &AdminAnalyticsQuery) -> (i64, NaiveDate) {
+    analytics_window(query.days, Utc::now().date_naive())
+}
+
+// Whole UTC days ending today, today included
+fn analytics_window(days: Option<i64>, today: NaiveDate) -> (i64, NaiveDate) {
+    let days = days.unwrap_or(30).clamp(1, 365);
+    (days, today - Duration::days(days - 1))
+}
+
+fn analytics_error(e: sqlx::Error) -> ActixError {
+    log::error!("Failed to fetch analytics: {}", e);
+    actix_web::error::ErrorInternalServerError("Failed to fetch analytics")
+}
+
+#[utoipa::path(
+    get,
+    path = "/v1/admin/analytics/activity",
+    tag = "admin",
+    params(AdminAnalyticsQuery),
+    responses((status = 200, description = "Daily and weekly activity", body = ActivityResponse))
+)]
+#[get("/analytics/activity")]
+async fn activity_analytics(
+    db: web::Data<DatabaseConnection>,
+    query: web::Query<AdminAnalyticsQuery>,
+) -> Result<HttpResponse, ActixError> {
+    let (days, since) = analytics_since(&query);
+    let daily = db.get_activity_series(since).await.map_err(analytics_error)?;
+    let weekly_active_users = db.get_weekly_active_users(since).await.map_err(analytics_error)?;
+
+    let total_recommendations = daily.iter().map(|d| d.recommendations).sum();
+    let total_signups = daily.iter().map(|d| d.signups).sum();
+    let hits: i64 = daily.iter().map(|d| d.cache_hits).sum();
+    let lookups = hits + daily.iter().map(|d| d.cache_misses).sum::<i64>();
+    let cache_hit_ratio = if lookups > 0 { hits as f64 / lookups as f64 } else { 0.0 };
+
+    Ok(HttpResponse::Ok().json(ActivityResponse {
+        days,
+        daily,
+        weekly_active_users,
+        total_recommendations,
+        total_signups,
+        cache_hit_ratio,
+    }))
+}
+
+#[utoipa::path(
+    get,
+    path = "/v1/admin/analytics/emotions",
+    tag = "admin",
+    params(AdminAnalyticsQuery),
+    responses((status = 200, description = "`days` and `emotions`, a list of EmotionCount", body = Object))
+)]
+#[get("/analytics/emotions")]
+async fn emotion_analytics(
+    db: web::Data<DatabaseConnection>,
+    query: web::Query<AdminAnalyticsQuery>,
+) -> Result<HttpResponse, ActixError> {
+    let (days, since) = analytics_since(&query);
+    let emotions = db.get_emotion_distribution(since).await.map_err(analytics_error)?;
+    Ok(HttpResponse::Ok().json(serde_json::json!({ "days": days, "emotions": emotions })))
+}
+
+#[utoipa::path(
+    get,
+    path = "/v1/admin/analytics/top-stickers",
+    tag = "admin",
+    params(AdminAnalyticsQuery),
+    responses((status = 200, description = "`days` and `top_stickers`, a list of GlobalSticker", body = Object))
+)]
+#[get("/analytics/top-stickers")]
+async fn top_sticker_analytics(
+    db: web::Data<DatabaseConnection>,
+    query: web::Query<AdminAnalyticsQuery>,
+) -> Result<HttpResponse, ActixError> {
+    let (days, since) = analytics_since(&query);
+    let limit = query.limit.unwrap_or(10).clamp(1, 100);
+    let stickers = db.get_global_top_stickers(since, limit).await.map_err(analytics_error)?;
+    Ok(HttpResponse::Ok().json(serde_json::json!({ "days": days, "top_stickers": stickers })))
+}
+
+#[utoipa::path(
+    get,
+    path = "/v1/admin/analytics/upstream",
+    tag = "admin",
+    params(AdminAnalyticsQuery),
+    responses((status = 200, description = "`days` and `services`, a list of UpstreamStat", body = Object))
+)]
+#[get("/analytics/upstream")]
+async fn upstream_analytics(
+    db: web::Data<DatabaseConnection>,
+    query: web::Query<AdminAnalyticsQuery>,
+) -> Result<HttpResponse, ActixError> {
+    let (days, since) = analytics_since(&query);
+    let services = db.get_upstream_stats(since).await.map_err(analytics_error)?;
+    Ok(HttpResponse::Ok().json(serde_json::json!({ "days": days, "services": services })))
+}
+
+#[derive(OpenApi)]
+#[openapi(paths(
+    list_users,
+    get_user,
+    add_user,
+    update_user,
+    soft_delete_user,
+    restore_user,
+    suspend_user,
+    reactivate_user,
+    impersonate_user,
+    reset_user_2fa,
+    reset_admin_2fa,
+    list_impersonations,
+    activity_analytics,
+    emotion_analytics,
+    top_sticker_analytics,
+    upstream_analytics,
+))]
+pub struct ApiDoc;
+
 pub fn init_routes(cfg: &mut web::ServiceConfig) {
     cfg.service(list_users);
+    cfg.service(get_user);
     cfg.service(add_user);
     cfg.service(update_user);
-    cfg.service(delete_user);
+    cfg.service(soft_delete_user);
+    cfg.service(restore_user);
+    cfg.service(suspend_user);
+    cfg.service(reactivate_user);
+    cfg.service(impersonate_user);
+    cfg.service(list_impersonations);
+    cfg.service(reset_user_2fa);
+    cfg.service(reset_admin_2fa);
+    cfg.service(activity_analytics);
+    cfg.service(emotion_analytics);
+    cfg.service(top_sticker_analytics);
+    cfg.service(upstream_analytics);
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use actix_web::http::StatusCode;
+    use actix_web::App;
+
+    #[test]
+    fn analytics_windows_are_clamped_whole_days() {
+        let today = NaiveDate::from_ymd_opt(2025, 3, 10).unwrap();
+        assert_eq!(analytics_window(None, today), (30, NaiveDate::from_ymd_opt(2025, 2, 9).unwrap()));
+        assert_eq!(analytics_window(Some(1), today), (1, today));
+        assert_eq!(analytics_window(Some(0), today), (1, today));
+        assert_eq!(analytics_window(Some(-5), today), (1, today));
+        assert_eq!(analytics_window(Some(7), today), (7, NaiveDate::from_ymd_opt(2025, 3, 4).unwrap()));
+        assert_eq!(analytics_window(Some(10_000), today).0, 365);
+    }
+
+    #[test]
+    fn page_offsets_are_checked() {
+        assert_eq!(page_offset(1, 25).unwrap(), 0);
+        assert_eq!(page_offset(3, 25).unwrap(), 50);
+        assert_eq!(page_offset(-4, 25).unwrap(), 0);
+        assert!(page_offset(i64::MAX, 100).is_err());
+        assert!(page_offset(i64::MAX / 100 + 2, 100).is_err());
+    }
+
+    #[actix_web::test]
+    async fn huge_user_list_pages_are_rejected() {
+        let app = actix_web::test::init_service(
+            App::new()
+                .app_data(web::Data::new(DatabaseConnection::unreachable()))
+                .service(list_users),
+        )
+        .await;
+        for (uri, status) in [
+            (format!("/users?page={}", i64::MAX), StatusCode::BAD_REQUEST),
+            ("/users?page=99999999999999999999".to_string(), StatusCode::BAD_REQUEST),
+            // Within range, so the request reaches the (unreachable) database
+            ("/users?page=2&per_page=100".to_string(), StatusCode::INTERNAL_SERVER_ERROR),
+        ] {
+            let req = actix_web::test::TestRequest::get().uri(&uri).to_request();
+            assert_eq!(actix_web::test::call_service(&app, req).await.status(), status, "{}", uri);
+        }
+    }
+
+    #[actix_web::test]
+    async fn admins_cannot_reset_their_own_2fa() {
+        use actix_web::HttpMessage;
+        let app = actix_web::test::init_service(
+            App::new()
+                .app_data(web::Data::new(DatabaseConnection::unreachable()))
+                .service(reset_admin_2fa),
+        )
+        .await;
+        let admin_id = Uuid::new_v4();
+        for (target, status) in [
+            (admin_id.to_string(), StatusCode::BAD_REQUEST),
+            ("not-a-uuid".to_string(), StatusCode::BAD_REQUEST),
+            // Another admin, so the request reaches the (unreachable) database
+            (Uuid::new_v4().to_string(), StatusCode::INTERNAL_SERVER_ERROR),
+        ] {
+            let req = actix_web::test::TestRequest::post()
+                .uri(&format!("/admins/{}/2fa/reset", target))
+                .to_request();
+            req.extensions_mut().insert(AuthData::new(admin_id.to_string(), "admin".to_string()));
+            assert_eq!(actix_web::test::call_service(&app, req).await.status(), status, "{}", target);
+        }
+    }
 }
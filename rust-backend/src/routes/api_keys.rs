@@ -0,0 +1,156 @@
+use crate::configs::api_keys::ApiKeyConfig;
+use crate::middleware::api_key::{generate_api_key, SCOPES};
+use crate::middleware::auth::AuthUser;
+use crate::routes::audit::{self, audit_event};
+use crate::structs::database_structs::{CreateApiKeyRequest, DatabaseConnection};
+use actix_web::error::Error as ActixError;
+use actix_web::{delete, get, post, web, HttpRequest, HttpResponse};
+use chrono::{Duration, Utc};
+use serde_json::json;
+use uuid::Uuid;
+use crate::routes::openapi::ErrorResponse;
+use utoipa::OpenApi;
+
+#[utoipa::path(
+    get,
+    path = "/v1/user/api-keys",
+    tag = "user",
+    responses((status = 200, description = "`api_keys` (without secrets) and `available_scopes`", body = Object))
+)]
+#[get("/api-keys")]
+async fn list_api_keys(
+    db: web::Data<DatabaseConnection>,
+    user: AuthUser,
+) -> Result<HttpResponse, ActixError> {
+    let user_id = user.id;
+    db.get_api_keys(user_id)
+        .await
+        .map(|keys| HttpResponse::Ok().json(json!({ "api_keys": keys, "available_scopes": SCOPES })))
+        .map_err(|e| {
+            log::error!("Failed to fetch API keys for user {}: {}", user_id, e);
+            actix_web::error::ErrorInternalServerError(json!({ "error": "Failed to fetch API keys" }))
+        })
+}
+
+// The plaintext key is only ever returned here
+#[utoipa::path(
+    post,
+    path = "/v1/user/api-keys",
+    tag = "user",
+    request_body = CreateApiKeyRequest,
+    responses(
+        (status = 200, description = "The `key`, shown only once, and its `api_key` record", body = Object),
+        (status = 400, description = "Invalid name, scopes or expiry, or too many keys", body = ErrorResponse),
+    )
+)]
+#[post("/api-keys")]
+async fn create_api_key(
+    db: web::Data<DatabaseConnection>,
+    config: web::Data<ApiKeyConfig>,
+    user: AuthUser,
+    http_req: HttpRequest,
+    req: web::Json<CreateApiKeyRequest>,
+) -> Result<HttpResponse, ActixError> {
+    let user_id = user.id;
+    let name = req.name.trim();
+    if name.is_empty() {
+        return Err(actix_web::error::ErrorBadRequest(json!({ "error": "API key name is required" })));
+    }
+    if req.scopes.is_empty() {
+        return Err(actix_web::error::ErrorBadRequest(json!({ "error": "At least one scope is required" })));
+    }
+    if let Some(unknown) = req.scopes.iter().find(|scope| !SCOPES.contains(&scope.as_str())) {
+        return Err(actix_web::error::ErrorBadRequest(json!({
+            "error": format!("Unknown scope {}", unknown),
+            "available_scopes": SCOPES,
+        })));
+    }
+    let expires_at = match req.expires_in_days {
+        Some(days) if !(1..=365).contains(&days) => {
+            return Err(actix_web::error::ErrorBadRequest(json!({ "error": "expires_in_days must be between 1 and 365" })));
+        }
+        Some(days) => Some((Utc::now() + Duration::days(days)).naive_utc()),
+        None => None,
+    };
+
+    let active = db.count_active_api_keys(user_id).await.map_err(|e| {
+        log::error!("Failed to count API keys for user {}: {}", user_id, e);
+        actix_web::error::ErrorInternalServerError(json!({ "error": "Failed to create API key" }))
+    })?;
+    if active >= config.max_keys_per_user {
+        return Err(actix_web::error::ErrorBadRequest(json!({
+            "error": format!("At most {} active API keys are allowed", config.max_keys_per_user)
+        })));
+    }
+
+    let mut scopes = req.scopes.clone();
+    scopes.sort();
+    scopes.dedup();
+    let (key, prefix, key_hash) = generate_api_key();
+    let api_key = db
+        .create_api_key(user_id, name, &prefix, &key_hash, &scopes, expires_at)
+        .await
+        .map_err(|e| {
+            log::error!("Failed to create API key for user {}: {}", user_id, e);
+            actix_web::error::ErrorInternalServerError(json!({ "error": "Failed to create API key" }))
+        })?;
+
+    audit::record(
+        &db,
+        audit_event(&http_req, "user.create_api_key")
+            .actor("user", user_id)
+            .target("api_key", api_key.id)
+            .diff(json!({ "name": api_key.name, "scopes": api_key.scopes, "expires_at": api_key.expires_at })),
+    )
+    .await;
+
+    Ok(HttpResponse::Ok().json(json!({ "key": key, "api_key": api_key })))
+}
+
+#[utoipa::path(
+    delete,
+    path = "/v1/user/api-keys/{id}",
+    tag = "user",
+    params(("id" = Uuid, Path, description = "API key ID")),
+    responses(
+        (status = 200, description = "Key revoked", body = Object),
+        (status = 404, description = "API key not found", body = ErrorResponse),
+    )
+)]
+#[delete("/api-keys/{id}")]
+async fn revoke_api_key(
+    db: web::Data<DatabaseConnection>,
+    user: AuthUser,
+    http_req: HttpRequest,
+    path: web::Path<Uuid>,
+) -> Result<HttpResponse, ActixError> {
+    let user_id = user.id;
+    let key_id = path.into_inner();
+    match db.revoke_api_key(user_id, key_id).await {
+        Ok(true) => {
+            audit::record(
+                &db,
+                audit_event(&http_req, "user.revoke_api_key")
+                    .actor("user", user_id)
+                    .target("api_key", key_id),
+            )
+            .await;
+            Ok(HttpResponse::Ok().json(json!({ "id": key_id, "revoked": true })))
+        }
+        Ok(false) => Err(actix_web::error::ErrorNotFound(json!({ "error": "API key not found" }))),
+        Err(e) => {
+            log::error!("Failed to revoke API key {}: {}", key_id, e);
+            Err(actix_web::error::ErrorInternalServerError(json!({ "error": "Failed to revoke API key" })))
+        }
+    }
+}
+
+#[derive(OpenApi)]
+#[openapi(paths(list_api_keys, create_api_key, revoke_api_key))]
+pub struct ApiDoc;
+
+pub fn init_routes(cfg: &mut web::ServiceConfig) {
+    cfg.service(list_api_keys);
+    cfg.service(create_api_key);
+    cfg.service(revoke_api_key);
+}
@@ -0,0 +1,228 @@
+use crate::routes::admin::page_offset;
+use crate::structs::database_structs::{AuditEvent, AuditQuery, DatabaseConnection, NewAuditEvent};
+use actix_web::error::Error as ActixError;
+use actix_web::http::header;
+use actix_web::web::Bytes;
+use actix_web::{get, web, HttpRequest, HttpResponse};
+use chrono::NaiveDateTime;
+use futures::stream::{self, StreamExt};
+use serde_json::{json, Value};
+use uuid::Uuid;
+use utoipa::OpenApi;
+
+// Starts an anonymous event carrying the caller's IP and user agent; handlers add the
+// actor, target and diff. The IP is the socket peer, not X-Forwarded-For, so clients can't spoof it.
+pub fn audit_event(req: &HttpRequest, action: &str) -> NewAuditEvent {
+    NewAuditEvent {
+        action: action.to_string(),
+        actor_type: "anonymous".to_string(),
+        actor_id: None,
+        target_type: None,
+        target_id: None,
+        ip: req.peer_addr().map(|addr| addr.ip().to_string()),
+        user_agent: req
+            .headers()
+            .get(header::USER_AGENT)
+            .and_then(|ua| ua.to_str().ok())
+            .map(str::to_string),
+        diff: None,
+    }
+}
+
+// A single changed field for NewAuditEvent::diff
+pub fn change(old: impl Into<Value>, new: impl Into<Value>) -> Value {
+    json!({ "old": old.into(), "new": new.into() })
+}
+
+// Audit failures are logged rather than failing the action being audited
+pub async fn record(db: &DatabaseConnection, event: NewAuditEvent) {
+    if let Err(e) = db.record_audit_event(&event).await {
+        log::error!("Failed to record audit event {}: {}", event.action, e);
+    }
+}
+
+#[utoipa::path(
+    get,
+    path = "/v1/admin/audit",
+    tag = "admin",
+    params(AuditQuery),
+    responses(
+        (status = 200, description = "A page of `events` with `total`, `page` and `per_page`, newest first", body = Object),
+        (status = 400, description = "page is too large"),
+    )
+)]
+#[get("/audit")]
+async fn list_audit_events(
+    db: web::Data<DatabaseConnection>,
+    query: web::Query<AuditQuery>,
+) -> Result<HttpResponse, ActixError> {
+    let page = query.page.unwrap_or(1).max(1);
+    let per_page = query.per_page.unwrap_or(50).clamp(1, 500);
+    let events = db
+        .get_audit_events(&query, per_page, page_offset(page, per_page)?)
+        .await
+        .map_err(audit_error)?;
+    let total = db.count_audit_events(&query).await.map_err(audit_error)?;
+    Ok(HttpResponse::Ok().json(json!({
+        "events": events,
+        "total": total,
+        "page": page,
+        "per_page": per_page,
+    })))
+}
+
+// Events fetched per query while streaming an export
+const EXPORT_BATCH_SIZE: i64 = 500;
+
+// Every matching event as JSON Lines, newest first. Streamed in batches so the export
+// never holds the whole log in memory.
+#[utoipa::path(
+    get,
+    path = "/v1/admin/audit/export",
+    tag = "admin",
+    params(AuditQuery),
+    responses((status = 200, description = "Every matching AuditEvent, one JSON object per line", body = String, content_type = "application/x-ndjson"))
+)]
+#[get("/audit/export")]
+async fn export_audit_events(
+    db: web::Data<DatabaseConnection>,
+    query: web::Query<AuditQuery>,
+) -> Result<HttpResponse, ActixError> {
+    let filter = query.into_inner();
+    // The first batch is fetched up front so a database error is still a 500
+    let first = db
+        .get_audit_events_before(&filter, None, EXPORT_BATCH_SIZE)
+        .await
+        .map_err(audit_error)?;
+    let first_chunk = ndjson_chunk(&first)?;
+    let cursor = export_cursor(&first);
+
+    let rest = stream::try_unfold((db, filter, cursor), |(db, filter, cursor)| async move {
+        let Some(before) = cursor else {
+            return Ok(None);
+        };
+        let events = db
+            .get_audit_events_before(&filter, Some(before), EXPORT_BATCH_SIZE)
+            .await
+            .map_err(audit_error)?;
+        if events.is_empty() {
+            return Ok(None);
+        }
+        let chunk = ndjson_chunk(&events)?;
+        let cursor = export_cursor(&events);
+        Ok(Some((chunk, (db, filter, cursor))))
+    });
+
+    Ok(HttpResponse::Ok()
+        .content_type("application/x-ndjson")
+        .insert_header((
+            header::CONTENT_DISPOSITION,
+            "attachment; filename=\"audit-events.jsonl\"",
+        ))
+        .streaming(stream::once(async move { Ok::<_, ActixError>(first_chunk) }).chain(rest)))
+}
+
+// Where the next export batch starts, or None once a short batch shows there is nothing left
+fn export_cursor(events: &[AuditEvent]) -> Option<(NaiveDateTime, Uuid)> {
+    if (events.len() as i64) < EXPORT_BATCH_SIZE {
+        return None;
+    }
+    events.last().map(|event| (event.occurred_at, event.id))
+}
+
+fn ndjson_chunk(events: &[AuditEvent]) -> Result<Bytes, ActixError> {
+    let mut body = Vec::new();
+    for event in events {
+        serde_json::to_writer(&mut body, event).map_err(|e| {
+            log::error!("Failed to serialize audit event {}: {}", event.id, e);
+            actix_web::error::ErrorInternalServerError("Failed to export audit events")
+        })?;
+        body.push(b'\n');
+    }
+    Ok(Bytes::from(body))
+}
+
+fn audit_error(e: sqlx::Error) -> ActixError {
+    log::error!("Failed to fetch audit events: {}", e);
+    actix_web::error::ErrorInternalServerError("Failed to fetch audit events")
+}
+
+#[derive(OpenApi)]
+#[openapi(paths(list_audit_events, export_audit_events))]
+pub struct ApiDoc;
+
+pub fn init_routes(cfg: &mut web::ServiceConfig) {
+    cfg.service(list_audit_events);
+    cfg.service(export_audit_events);
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use actix_web::http::StatusCode;
+    use actix_web::App;
+
+    fn event() -> AuditEvent {
+        AuditEvent {
+            id: Uuid::new_v4(),
+            occurred_at: chrono::Utc::now().naive_utc(),
+            actor_type: "user".to_string(),
+            actor_id: Some(Uuid::new_v4()),
+            target_type: None,
+            target_id: None,
+            action: "auth.login".to_string(),
+            ip: None,
+            user_agent: None,
+            diff: Some(json!({ "line": "a\nb" })),
+        }
+    }
+
+    #[test]
+    fn export_chunks_are_one_event_per_line() {
+        let events = vec![event(), event()];
+        let chunk = ndjson_chunk(&events).unwrap();
+        let lines: Vec<&str> = std::str::from_utf8(&chunk).unwrap().lines().collect();
+        assert_eq!(lines.len(), 2);
+        let first: Value = serde_json::from_str(lines[0]).unwrap();
+        assert_eq!(first["id"], json!(events[0].id));
+        assert_eq!(first["diff"]["line"], "a\nb");
+    }
+
+    #[test]
+    fn export_stops_after_a_short_batch() {
+        assert_eq!(export_cursor(&[]), None);
+        assert_eq!(export_cursor(&[event()]), None);
+
+        let full: Vec<AuditEvent> = (0..EXPORT_BATCH_SIZE).map(|_| event()).collect();
+        let last = full.last().unwrap();
+        assert_eq!(export_cursor(&full), Some((last.occurred_at, last.id)));
+    }
+
+    #[actix_web::test]
+    async fn huge_audit_pages_are_rejected() {
+        let app = actix_web::test::init_service(
+            App::new()
+                .app_data(web::Data::new(DatabaseConnection::unreachable()))
+                .configure(init_routes),
+        )
+        .await;
+        let req = actix_web::test::TestRequest::get()
+            .uri(&format!("/audit?page={}&per_page=500", i64::MAX))
+            .to_request();
+        let resp = actix_web::test::call_service(&app, req).await;
+        assert_eq!(resp.status(), StatusCode::BAD_REQUEST);
+    }
+
+    #[actix_web::test]
+    async fn export_fails_before_streaming_when_the_database_is_down() {
+        let app = actix_web::test::init_service(
+            App::new()
+                .app_data(web::Data::new(DatabaseConnection::unreachable()))
+                .configure(init_routes),
+        )
+        .await;
+        let req = actix_web::test::TestRequest::get().uri("/audit/export").to_request();
+        let resp = actix_web::test::call_service(&app, req).await;
+        assert_eq!(resp.status(), StatusCode::INTERNAL_SERVER_ERROR);
+    }
+}
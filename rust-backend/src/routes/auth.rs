@@ -1,84 +1,284 @@
+use crate::configs::password::PasswordPolicy;
+use crate::configs::totp::TotpConfig;
+use crate::middleware::validation::ValidatedJson;
 use crate::middleware::auth::{create_token, AuthConfig};
-use crate::structs::database_structs::{DatabaseConnection, LoginRequest, RegisterRequest};
+use crate::routes::audit::{self, audit_event};
+use crate::routes::password::{check_password, normalize_email};
+use crate::routes::totp::{begin_enrollment, start_mfa_challenge};
+use crate::structs::database_structs::{
+    AccountKind, DatabaseConnection, LoginRequest, MfaChallenge, RegisterRequest, UserStatus,
+};
 use actix_web::error::Error as ActixError;
-use actix_web::{post, web, HttpResponse};
+use actix_web::{post, web, HttpRequest, HttpResponse};
 use bcrypt::verify;
 use chrono::{Duration, Utc};
 use serde_json::json;
+use uuid::Uuid;
+use crate::routes::openapi::{ErrorResponse, SessionResponse, ValidationErrorResponse};
+use utoipa::OpenApi;
 
+// Issues a 24h user token and records its session
+pub async fn start_user_session(
+    db: &DatabaseConnection,
+    auth_config: &AuthConfig,
+    user_id: Uuid,
+) -> Result<String, ActixError> {
+    let token = create_token(&user_id.to_string(), "user", auth_config).map_err(|e| {
+        log::error!("Failed to generate token: {}", e);
+        actix_web::error::ErrorInternalServerError(json!({ "error": "Failed to generate token"}))
+    })?;
+    let expires_at = Utc::now()
+        .checked_add_signed(Duration::hours(24))
+        .unwrap()
+        .naive_utc();
+    db.save_session(Some(user_id), None, &token, expires_at)
+        .await
+        .map_err(|e| {
+            log::error!("Failed to save session for user {}: {}", user_id, e);
+            actix_web::error::ErrorInternalServerError(json!({ "error": "Failed to save session"}))
+        })?;
+    Ok(token)
+}
+
+// Admin tokens are only accepted under /v1/admin
+pub async fn start_admin_session(
+    db: &DatabaseConnection,
+    auth_config: &AuthConfig,
+    admin_id: Uuid,
+) -> Result<String, ActixError> {
+    let token = create_token(&admin_id.to_string(), "admin", auth_config).map_err(|e| {
+        log::error!("Failed to generate token: {}", e);
+        actix_web::error::ErrorInternalServerError(json!({ "error": "Failed to generate token"}))
+    })?;
+    let expires_at = Utc::now()
+        .checked_add_signed(Duration::hours(24))
+        .unwrap()
+        .naive_utc();
+    db.save_session(None, Some(admin_id), &token, expires_at)
+        .await
+        .map_err(|e| {
+            log::error!("Failed to save session for admin {}: {}", admin_id, e);
+            actix_web::error::ErrorInternalServerError(json!({ "error": "Failed to save session"}))
+        })?;
+    Ok(token)
+}
+
+#[utoipa::path(
+    post,
+    path = "/v1/auth/register/user",
+    tag = "auth",
+    security(()),
+    request_body = RegisterRequest,
+    responses(
+        (status = 200, description = "Registered and signed in", body = SessionResponse),
+        (status = 400, description = "Invalid fields, password policy violations or username taken", body = ValidationErrorResponse),
+    )
+)]
 #[post("/auth/register/user")]
 async fn register_user(
     db: web::Data<DatabaseConnection>,
     auth_config: web::Data<AuthConfig>,
-    req: web::Json<RegisterRequest>,
+    policy: web::Data<PasswordPolicy>,
+    http_req: HttpRequest,
+    req: ValidatedJson<RegisterRequest>,
 ) -> Result<HttpResponse, ActixError> {
-    println!("▶️ Register payload: {:?}", req);
+    let mut req = req.into_inner();
+    check_password(&policy, &req.password, &req.username)?;
+    req.email = req.email.as_deref().map(normalize_email).transpose()?;
 
-    let user = db.register_user(req.into_inner()).await.map_err(|e| {
+    let user = db.register_user(req).await.map_err(|e| {
         log::warn!("User registration failed: {}", e);
         actix_web::error::InternalError::new(
             json!({ "error": "Username already exists" }).to_string(),
             actix_web::http::StatusCode::BAD_REQUEST,
         )
     })?;
+    if let Err(e) = db.record_signup().await {
+        log::error!("Failed to record signup: {}", e);
+    }
+    audit::record(
+        &db,
+        audit_event(&http_req, "auth.register")
+            .actor("user", user.id)
+            .target("user", user.id),
+    )
+    .await;
 
-    let token = create_token(&user.id.to_string(), "user", auth_config.get_ref()).map_err(|e| {
-        log::error!("Failed to generate token: {}", e);
-        actix_web::error::ErrorInternalServerError(json!({ "error": "Failed to generate token" }))
-    })?;
-    let expires_at = Utc::now()
-        .checked_add_signed(Duration::hours(24))
-        .unwrap()
-        .naive_utc();
-    db.save_session(Some(user.id), None, &token, expires_at)
-        .await
-        .map_err(|e| {
-            log::error!("Failed to save session for user {}: {}", user.id, e);
-            actix_web::error::ErrorInternalServerError(json!({ "error": "Failed to save session"}))
-        })?;
+    let token = start_user_session(&db, &auth_config, user.id).await?;
     Ok(HttpResponse::Ok().json(json!({ "token": token, "username": user.username })))
 }
 
+#[utoipa::path(
+    post,
+    path = "/v1/auth/login/user",
+    tag = "auth",
+    security(()),
+    request_body = LoginRequest,
+    responses(
+        (status = 200, description = "Session token, or an MfaChallengeResponse when 2FA is on", body = SessionResponse),
+        (status = 401, description = "Invalid credentials", body = ErrorResponse),
+        (status = 403, description = "Account suspended or deleted", body = ErrorResponse),
+    )
+)]
 #[post("/auth/login/user")]
 async fn login_user(
     db: web::Data<DatabaseConnection>,
     auth_config: web::Data<AuthConfig>,
-    req: web::Json<LoginRequest>,
+    totp_config: web::Data<TotpConfig>,
+    http_req: HttpRequest,
+    req: ValidatedJson<LoginRequest>,
 ) -> Result<HttpResponse, ActixError> {
-    let user = db
-        .get_user_by_username(&req.username)
-        .await
-        .map_err(|_| {
-            actix_web::error::ErrorInternalServerError(json!({ "error": "Database error"}))
-        })?
-        .ok_or_else(|| {
+    let user = match db.get_user_by_username(&req.username).await.map_err(|_| {
+        actix_web::error::ErrorInternalServerError(json!({ "error": "Database error"}))
+    })? {
+        Some(user) => user,
+        None => {
             log::warn!("Login failed: Invalid username");
-            actix_web::error::ErrorUnauthorized(json!({ "error": "Invalid credentials"}))
-        })?;
+            audit::record(
+                &db,
+                audit_event(&http_req, "auth.login_failed")
+                    .diff(json!({ "username": req.username, "reason": "unknown_username" })),
+            )
+            .await;
+            return Err(actix_web::error::ErrorUnauthorized(json!({ "error": "Invalid credentials"})));
+        }
+    };
     if !verify(&req.password, &user.password_hash).unwrap_or(false) {
         log::warn!("Login failed: Invalid password for {}", req.username);
+        audit::record(
+            &db,
+            audit_event(&http_req, "auth.login_failed")
+                .target("user", user.id)
+                .diff(json!({ "reason": "invalid_password" })),
+        )
+        .await;
         return Err(actix_web::error::ErrorUnauthorized(
             json!({ "error": "Invalid credentials"}),
         ));
     }
-    let token = create_token(&user.id.to_string(), "user", auth_config.get_ref()).map_err(|e| {
-        log::error!("Failed to generate token: {}", e);
-        actix_web::error::ErrorInternalServerError(json!({ "error": "Failed to generate token"}))
-    })?;
-    let expires_at = Utc::now()
-        .checked_add_signed(Duration::hours(24))
-        .unwrap()
-        .naive_utc();
-    db.save_session(Some(user.id), None, &token, expires_at)
+    match UserStatus::parse(&user.status) {
+        Some(UserStatus::Active) => {}
+        Some(UserStatus::Suspended) => {
+            log::warn!("Login refused: {} is suspended", req.username);
+            audit::record(
+                &db,
+                audit_event(&http_req, "auth.login_failed")
+                    .target("user", user.id)
+                    .diff(json!({ "reason": "suspended" })),
+            )
+            .await;
+            return Err(actix_web::error::ErrorForbidden(json!({
+                "error": "Account suspended",
+                "reason": user.status_reason,
+            })));
+        }
+        _ => {
+            log::warn!("Login refused: {} has status {}", req.username, user.status);
+            audit::record(
+                &db,
+                audit_event(&http_req, "auth.login_failed")
+                    .target("user", user.id)
+                    .diff(json!({ "reason": "deleted" })),
+            )
+            .await;
+            return Err(actix_web::error::ErrorForbidden(json!({ "error": "Account deleted" })));
+        }
+    }
+    if user.totp_enabled {
+        let challenge = MfaChallenge { kind: AccountKind::User, id: user.id, enrolling: false, provider: None };
+        let mfa_token = start_mfa_challenge(&db, &totp_config, challenge).await?;
+        return Ok(HttpResponse::Ok().json(json!({ "mfa_required": true, "mfa_token": mfa_token })));
+    }
+    let token = start_user_session(&db, &auth_config, user.id).await?;
+    audit::record(
+        &db,
+        audit_event(&http_req, "auth.login")
+            .actor("user", user.id)
+            .target("user", user.id),
+    )
+    .await;
+    Ok(HttpResponse::Ok().json(serde_json::json!({ "token": token, "username": user.username })))
+}
+
+#[utoipa::path(
+    post,
+    path = "/v1/auth/login/admin",
+    tag = "auth",
+    security(()),
+    request_body = LoginRequest,
+    responses(
+        (status = 200, description = "An MfaChallengeResponse, or for admins without 2FA `mfa_enrollment_required` with `mfa_token`, `secret` and `otpauth_uri`", body = Object),
+        (status = 401, description = "Invalid credentials", body = ErrorResponse),
+    )
+)]
+#[post("/auth/login/admin")]
+async fn login_admin(
+    db: web::Data<DatabaseConnection>,
+    totp_config: web::Data<TotpConfig>,
+    http_req: HttpRequest,
+    req: ValidatedJson<LoginRequest>,
+) -> Result<HttpResponse, ActixError> {
+    let admin = match db.get_admin_by_username(&req.username).await.map_err(|_| {
+        actix_web::error::ErrorInternalServerError(json!({ "error": "Database error"}))
+    })? {
+        Some(admin) => admin,
+        None => {
+            log::warn!("Admin login failed: Invalid username");
+            audit::record(
+                &db,
+                audit_event(&http_req, "auth.admin_login_failed")
+                    .diff(json!({ "username": req.username, "reason": "unknown_username" })),
+            )
+            .await;
+            return Err(actix_web::error::ErrorUnauthorized(json!({ "error": "Invalid credentials"})));
+        }
+    };
+    let valid = verify(&req.password, &admin.password_hash).unwrap_or(false);
+    if let Err(e) = db.record_admin_login(admin.id, valid).await {
+        log::error!("Failed to record admin login for {}: {}", admin.id, e);
+    }
+    if !valid {
+        log::warn!("Admin login failed: Invalid password for {}", req.username);
+        audit::record(
+            &db,
+            audit_event(&http_req, "auth.admin_login_failed")
+                .target("admin", admin.id)
+                .diff(json!({ "reason": "invalid_password" })),
+        )
+        .await;
+        return Err(actix_web::error::ErrorUnauthorized(
+            json!({ "error": "Invalid credentials"}),
+        ));
+    }
+    // 2FA is mandatory for admins: the password alone never yields a token
+    if admin.totp_enabled {
+        let challenge = MfaChallenge { kind: AccountKind::Admin, id: admin.id, enrolling: false, provider: None };
+        let mfa_token = start_mfa_challenge(&db, &totp_config, challenge).await?;
+        return Ok(HttpResponse::Ok().json(json!({ "mfa_required": true, "mfa_token": mfa_token })));
+    }
+    let (secret, otpauth_uri) = begin_enrollment(&db, &totp_config, AccountKind::Admin, admin.id, &admin.username)
         .await
         .map_err(|e| {
-            log::error!("Failed to save session for user {}: {}", user.id, e);
-            actix_web::error::ErrorInternalServerError(json!({ "error": "Failed to save session"}))
-        })?;
-    Ok(HttpResponse::Ok().json(serde_json::json!({ "token": token, "username": user.username })))
+            log::error!("Failed to start 2FA enrollment for admin {}: {}", admin.id, e);
+            actix_web::error::ErrorInternalServerError(json!({ "error": "Failed to start 2FA enrollment"}))
+        })?
+        .ok_or_else(|| actix_web::error::ErrorConflict(json!({ "error": "2FA is already enabled"})))?;
+    let challenge = MfaChallenge { kind: AccountKind::Admin, id: admin.id, enrolling: true, provider: None };
+    let mfa_token = start_mfa_challenge(&db, &totp_config, challenge).await?;
+    Ok(HttpResponse::Ok().json(json!({
+        "mfa_enrollment_required": true,
+        "mfa_token": mfa_token,
+        "secret": secret,
+        "otpauth_uri": otpauth_uri,
+    })))
 }
 
+#[derive(OpenApi)]
+#[openapi(paths(register_user, login_user, login_admin))]
+pub struct ApiDoc;
+
 pub fn init_routes(cfg: &mut web::ServiceConfig) {
     cfg.service(register_user);
     cfg.service(login_user);
+    cfg.service(login_admin);
 }
@@ -0,0 +1,354 @@
+use crate::middleware::auth::AuthUser;
+use crate::middleware::validation::ValidatedJson;
+use crate::structs::database_structs::{
+    CollectionItemAdded, CreateCollectionRequest, DatabaseConnection, FavoriteRequest, ReorderCollectionRequest,
+};
+use actix_web::error::Error as ActixError;
+use actix_web::{delete, get, post, put, web, HttpResponse};
+use serde_json::json;
+use uuid::Uuid;
+use crate::routes::openapi::{ErrorResponse, ValidationErrorResponse};
+use utoipa::OpenApi;
+
+
+fn collection_not_found() -> ActixError {
+    actix_web::error::ErrorNotFound(json!({ "error": "Collection not found" }))
+}
+
+#[utoipa::path(
+    get,
+    path = "/v1/user/favorites",
+    tag = "user",
+    responses((status = 200, description = "Favorites as `{\"favorites\": [...]}`, newest first", body = Object))
+)]
+#[get("/favorites")]
+async fn list_favorites(
+    db: web::Data<DatabaseConnection>,
+    user: AuthUser,
+) -> Result<HttpResponse, ActixError> {
+    let user_id = user.id;
+    db.get_favorites(user_id)
+        .await
+        .map(|favorites| HttpResponse::Ok().json(json!({ "favorites": favorites })))
+        .map_err(|e| {
+            log::error!("Failed to fetch favorites for user {}: {}", user_id, e);
+            actix_web::error::ErrorInternalServerError(json!({ "error": "Failed to fetch favorites" }))
+        })
+}
+
+#[utoipa::path(
+    post,
+    path = "/v1/user/favorites",
+    tag = "user",
+    request_body = FavoriteRequest,
+    responses(
+        (status = 200, description = "Sticker favorited", body = Object),
+        (status = 400, description = "Invalid sticker URL", body = ValidationErrorResponse),
+    )
+)]
+#[post("/favorites")]
+async fn add_favorite(
+    db: web::Data<DatabaseConnection>,
+    user: AuthUser,
+    req: ValidatedJson<FavoriteRequest>,
+) -> Result<HttpResponse, ActixError> {
+    let user_id = user.id;
+    db.add_favorite(user_id, &req.sticker_url)
+        .await
+        .map(|_| HttpResponse::Ok().json(json!({ "sticker_url": req.sticker_url, "favorite": true })))
+        .map_err(|e| {
+            log::error!("Failed to favorite sticker for user {}: {}", user_id, e);
+            actix_web::error::ErrorInternalServerError(json!({ "error": "Failed to add favorite" }))
+        })
+}
+
+#[utoipa::path(
+    delete,
+    path = "/v1/user/favorites",
+    tag = "user",
+    params(FavoriteRequest),
+    responses(
+        (status = 200, description = "Sticker unfavorited", body = Object),
+        (status = 404, description = "Favorite not found", body = ErrorResponse),
+    )
+)]
+#[delete("/favorites")]
+async fn remove_favorite(
+    db: web::Data<DatabaseConnection>,
+    user: AuthUser,
+    query: web::Query<FavoriteRequest>,
+) -> Result<HttpResponse, ActixError> {
+    let user_id = user.id;
+    match db.remove_favorite(user_id, &query.sticker_url).await {
+        Ok(true) => Ok(HttpResponse::Ok().json(json!({ "sticker_url": query.sticker_url, "favorite": false }))),
+        Ok(false) => Err(actix_web::error::ErrorNotFound(json!({ "error": "Favorite not found" }))),
+        Err(e) => {
+            log::error!("Failed to unfavorite sticker for user {}: {}", user_id, e);
+            Err(actix_web::error::ErrorInternalServerError(json!({ "error": "Failed to remove favorite" })))
+        }
+    }
+}
+
+#[utoipa::path(
+    get,
+    path = "/v1/user/collections",
+    tag = "user",
+    responses((status = 200, description = "Collections as `{\"collections\": [...]}`", body = Object))
+)]
+#[get("/collections")]
+async fn list_collections(
+    db: web::Data<DatabaseConnection>,
+    user: AuthUser,
+) -> Result<HttpResponse, ActixError> {
+    let user_id = user.id;
+    db.get_collections(user_id)
+        .await
+        .map(|collections| HttpResponse::Ok().json(json!({ "collections": collections })))
+        .map_err(|e| {
+            log::error!("Failed to fetch collections for user {}: {}", user_id, e);
+            actix_web::error::ErrorInternalServerError(json!({ "error": "Failed to fetch collections" }))
+        })
+}
+
+#[utoipa::path(
+    post,
+    path = "/v1/user/collections",
+    tag = "user",
+    request_body = CreateCollectionRequest,
+    responses(
+        (status = 200, description = "The new collection's `id`, `name` and `created_at`", body = Object),
+        (status = 400, description = "Invalid name, or the name is already used", body = ValidationErrorResponse),
+    )
+)]
+#[post("/collections")]
+async fn create_collection(
+    db: web::Data<DatabaseConnection>,
+    user: AuthUser,
+    req: ValidatedJson<CreateCollectionRequest>,
+) -> Result<HttpResponse, ActixError> {
+    let user_id = user.id;
+    match db.create_collection(user_id, &req.name).await {
+        Ok(collection) => Ok(HttpResponse::Ok().json(
+            json!({ "id": collection.id, "name": collection.name, "created_at": collection.created_at }),
+        )),
+        Err(sqlx::Error::Database(e)) if e.is_unique_violation() => {
+            Err(actix_web::error::ErrorBadRequest(json!({ "error": "Collection already exists" })))
+        }
+        Err(e) => {
+            log::error!("Failed to create collection for user {}: {}", user_id, e);
+            Err(actix_web::error::ErrorInternalServerError(json!({ "error": "Failed to create collection" })))
+        }
+    }
+}
+
+#[utoipa::path(
+    delete,
+    path = "/v1/user/collections/{id}",
+    tag = "user",
+    params(("id" = Uuid, Path, description = "Collection ID")),
+    responses(
+        (status = 200, description = "Collection deleted", body = String, content_type = "text/plain"),
+        (status = 404, description = "Collection not found", body = ErrorResponse),
+    )
+)]
+#[delete("/collections/{id}")]
+async fn delete_collection(
+    db: web::Data<DatabaseConnection>,
+    user: AuthUser,
+    path: web::Path<Uuid>,
+) -> Result<HttpResponse, ActixError> {
+    let user_id = user.id;
+    let collection_id = path.into_inner();
+    match db.delete_collection(user_id, collection_id).await {
+        Ok(true) => Ok(HttpResponse::Ok().body("Collection deleted")),
+        Ok(false) => Err(collection_not_found()),
+        Err(e) => {
+            log::error!("Failed to delete collection {}: {}", collection_id, e);
+            Err(actix_web::error::ErrorInternalServerError(json!({ "error": "Failed to delete collection" })))
+        }
+    }
+}
+
+#[utoipa::path(
+    post,
+    path = "/v1/user/collections/{id}/items",
+    tag = "user",
+    params(("id" = Uuid, Path, description = "Collection ID")),
+    request_body = FavoriteRequest,
+    responses(
+        (status = 200, description = "Sticker added to the end of the collection", body = Object),
+        (status = 400, description = "Invalid sticker URL", body = ValidationErrorResponse),
+        (status = 404, description = "Collection not found", body = ErrorResponse),
+        (status = 409, description = "Collection already holds 1000 stickers", body = ErrorResponse),
+    )
+)]
+#[post("/collections/{id}/items")]
+async fn add_collection_item(
+    db: web::Data<DatabaseConnection>,
+    user: AuthUser,
+    path: web::Path<Uuid>,
+    req: ValidatedJson<FavoriteRequest>,
+) -> Result<HttpResponse, ActixError> {
+    let user_id = user.id;
+    let collection_id = path.into_inner();
+    match db.add_collection_item(user_id, collection_id, &req.sticker_url).await {
+        Ok(CollectionItemAdded::Added) => Ok(HttpResponse::Ok().json(json!({ "collection_id": collection_id, "sticker_url": req.sticker_url }))),
+        Ok(CollectionItemAdded::Full) => Err(actix_web::error::ErrorConflict(json!({
+            "error": "Collection is full"
+        }))),
+        Ok(CollectionItemAdded::NotFound) => Err(collection_not_found()),
+        Err(e) => {
+            log::error!("Failed to add sticker to collection {}: {}", collection_id, e);
+            Err(actix_web::error::ErrorInternalServerError(json!({ "error": "Failed to add sticker" })))
+        }
+    }
+}
+
+#[utoipa::path(
+    delete,
+    path = "/v1/user/collections/{id}/items",
+    tag = "user",
+    params(("id" = Uuid, Path, description = "Collection ID"), FavoriteRequest),
+    responses(
+        (status = 200, description = "Sticker removed", body = String, content_type = "text/plain"),
+        (status = 404, description = "Sticker not in collection", body = ErrorResponse),
+    )
+)]
+#[delete("/collections/{id}/items")]
+async fn remove_collection_item(
+    db: web::Data<DatabaseConnection>,
+    user: AuthUser,
+    path: web::Path<Uuid>,
+    query: web::Query<FavoriteRequest>,
+) -> Result<HttpResponse, ActixError> {
+    let user_id = user.id;
+    let collection_id = path.into_inner();
+    match db.remove_collection_item(user_id, collection_id, &query.sticker_url).await {
+        Ok(true) => Ok(HttpResponse::Ok().body("Sticker removed")),
+        Ok(false) => Err(actix_web::error::ErrorNotFound(json!({ "error": "Sticker not in collection" }))),
+        Err(e) => {
+            log::error!("Failed to remove sticker from collection {}: {}", collection_id, e);
+            Err(actix_web::error::ErrorInternalServerError(json!({ "error": "Failed to remove sticker" })))
+        }
+    }
+}
+
+#[utoipa::path(
+    put,
+    path = "/v1/user/collections/{id}/order",
+    tag = "user",
+    params(("id" = Uuid, Path, description = "Collection ID")),
+    request_body = ReorderCollectionRequest,
+    responses(
+        (status = 200, description = "Collection reordered", body = Object),
+        (status = 400, description = "Invalid list, collection not found or order does not match its stickers", body = ValidationErrorResponse),
+    )
+)]
+#[put("/collections/{id}/order")]
+async fn reorder_collection(
+    db: web::Data<DatabaseConnection>,
+    user: AuthUser,
+    path: web::Path<Uuid>,
+    req: ValidatedJson<ReorderCollectionRequest>,
+) -> Result<HttpResponse, ActixError> {
+    let user_id = user.id;
+    let collection_id = path.into_inner();
+    match db.reorder_collection(user_id, collection_id, &req.sticker_urls).await {
+        Ok(true) => Ok(HttpResponse::Ok().json(json!({ "collection_id": collection_id, "sticker_urls": req.sticker_urls }))),
+        Ok(false) => Err(actix_web::error::ErrorBadRequest(json!({
+            "error": "Collection not found or order does not match its stickers"
+        }))),
+        Err(e) => {
+            log::error!("Failed to reorder collection {}: {}", collection_id, e);
+            Err(actix_web::error::ErrorInternalServerError(json!({ "error": "Failed to reorder collection" })))
+        }
+    }
+}
+
+#[derive(OpenApi)]
+#[openapi(paths(
+    list_favorites,
+    add_favorite,
+    remove_favorite,
+    list_collections,
+    create_collection,
+    delete_collection,
+    add_collection_item,
+    remove_collection_item,
+    reorder_collection,
+))]
+pub struct ApiDoc;
+
+pub fn init_routes(cfg: &mut web::ServiceConfig) {
+    cfg.service(list_favorites);
+    cfg.service(add_favorite);
+    cfg.service(remove_favorite);
+    cfg.service(list_collections);
+    cfg.service(create_collection);
+    cfg.service(delete_collection);
+    cfg.service(add_collection_item);
+    cfg.service(remove_collection_item);
+    cfg.service(reorder_collection);
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::middleware::auth::AuthData;
+    use crate::middleware::validation::json_config;
+    use actix_web::http::StatusCode;
+    use actix_web::{test, App, HttpMessage};
+
+    // The database is unreachable, so a request that passes validation answers 500
+    async fn call(req: test::TestRequest) -> StatusCode {
+        let app = test::init_service(
+            App::new()
+                .app_data(json_config(64 * 1024))
+                .app_data(web::Data::new(DatabaseConnection::unreachable()))
+                .configure(init_routes),
+        )
+        .await;
+        let req = req.to_request();
+        req.extensions_mut().insert(AuthData::new(Uuid::new_v4().to_string(), "user".to_string()));
+        test::call_service(&app, req).await.status()
+    }
+
+    fn items_uri() -> String {
+        format!("/collections/{}/items", Uuid::new_v4())
+    }
+
+    #[actix_web::test]
+    async fn collection_names_are_trimmed_and_bounded() {
+        for name in ["", "   ", &"n".repeat(101)] {
+            let req = test::TestRequest::post().uri("/collections").set_json(json!({ "name": name }));
+            assert_eq!(call(req).await, StatusCode::BAD_REQUEST, "name {:?}", name);
+        }
+        let req = test::TestRequest::post().uri("/collections").set_json(json!({ "name": "  Cats  " }));
+        assert_eq!(call(req).await, StatusCode::INTERNAL_SERVER_ERROR);
+    }
+
+    #[actix_web::test]
+    async fn added_stickers_need_a_valid_url() {
+        for url in ["", &"u".repeat(2049)] {
+            let req = test::TestRequest::post().uri(&items_uri()).set_json(json!({ "sticker_url": url }));
+            assert_eq!(call(req).await, StatusCode::BAD_REQUEST);
+        }
+        let req = test::TestRequest::post().uri(&items_uri()).set_json(json!({ "sticker_url": "https://example.com/a.gif" }));
+        assert_eq!(call(req).await, StatusCode::INTERNAL_SERVER_ERROR);
+    }
+
+    #[actix_web::test]
+    async fn reorders_are_capped_at_the_collection_size() {
+        let uri = format!("/collections/{}/order", Uuid::new_v4());
+        let too_many: Vec<String> = (0..1001).map(|i| format!("https://example.com/{}.gif", i)).collect();
+        let req = test::TestRequest::put().uri(&uri).set_json(json!({ "sticker_urls": too_many }));
+        assert_eq!(call(req).await, StatusCode::BAD_REQUEST);
+
+        let req = test::TestRequest::put().uri(&uri).set_json(json!({ "sticker_urls": ["", "https://example.com/a.gif"] }));
+        assert_eq!(call(req).await, StatusCode::BAD_REQUEST);
+
+        let max: Vec<String> = (0..1000).map(|i| format!("https://example.com/{}.gif", i)).collect();
+        let req = test::TestRequest::put().uri(&uri).set_json(json!({ "sticker_urls": max }));
+        assert_eq!(call(req).await, StatusCode::INTERNAL_SERVER_ERROR);
+    }
+}
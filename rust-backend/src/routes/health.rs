@@ -1,10 +1,22 @@
 use actix_web::{get, web, HttpResponse, Responder};
+use utoipa::OpenApi;
 
+#[utoipa::path(
+    get,
+    path = "/v1/health",
+    tag = "health",
+    security(()),
+    responses((status = 200, description = "Server is up", body = String, content_type = "text/plain"))
+)]
 #[get("/health")]
 async fn server_check() -> impl Responder {
     HttpResponse::Ok().body("Server is healthy")
 }
 
+#[derive(OpenApi)]
+#[openapi(paths(server_check))]
+pub struct ApiDoc;
+
 pub fn init_routes(cfg: &mut web::ServiceConfig) {
     cfg.service(server_check);
 }
@@ -1,63 +1,202 @@
-use actix_web::{post, web, HttpMessage, HttpRequest, HttpResponse, Responder};
-use crate::structs::database_structs::{DatabaseConnection, HistoryResponse, HistoryRequest};
-use crate::middleware::auth::AuthData;
+use actix_web::error::Error as ActixError;
+use actix_web::{delete, get, web, HttpResponse, Responder};
+use crate::structs::database_structs::{ClearHistoryRequest, DatabaseConnection, HistoryCursor, HistoryQuery, HistoryResponse, HistoryRequest};
+use crate::middleware::auth::AuthUser;
 use uuid::Uuid;
 use log;
+use crate::routes::openapi::ErrorResponse;
+use utoipa::OpenApi;
 
-#[post("/history")]
+#[utoipa::path(
+    get,
+    path = "/v1/user/history",
+    tag = "user",
+    params(HistoryRequest),
+    responses(
+        (status = 200, description = "A page of history, newest first", body = HistoryResponse),
+        (status = 400, description = "Invalid cursor", body = ErrorResponse),
+    )
+)]
+#[get("/history")]
 async fn get_history(
     db: web::Data<DatabaseConnection>,
-    req: web::Json<HistoryRequest>,
-    http_req: HttpRequest,
+    user: AuthUser,
+    req: web::Query<HistoryRequest>,
 ) -> impl Responder {
-
-
-    // Extract the request payload
     let req = req.into_inner();
-    println!("▶️ find payload: {:?}", req);
-    let username = &req.username;
-    println!("▶️ username payload: {:?}", username);
-    // Extract AuthData from extensions
-    let extensions = http_req.extensions();
-    println!("▶️ extensions payload: {:?}", extensions);
-    let auth_data = match extensions.get::<AuthData>() {
-        Some(data) => data.clone(),
-        None => {
-            log::error!("Auth data not found in request extensions");
-            return HttpResponse::Unauthorized().json(serde_json::json!({
-                "error": "Auth data not found"
-            }));
-        }
-    };
-    let user_id = match Uuid::parse_str(&auth_data.id) {
-        Ok(id) => id,
-        Err(e) => {
-            log::error!("Invalid user ID format: {}", e);
+    let user_id = user.id;
+
+    let cursor = match req.cursor.as_deref().map(HistoryCursor::decode) {
+        Some(Some(cursor)) => Some(cursor),
+        Some(None) => {
             return HttpResponse::BadRequest().json(serde_json::json!({
-                "error": "Invalid user ID format"
+                "error": "Invalid cursor"
             }));
         }
+        None => None,
+    };
+    let query = HistoryQuery {
+        cursor,
+        limit: req.limit.unwrap_or(20).clamp(1, 100),
+        emotion: req.emotion.as_deref().map(str::trim).filter(|e| !e.is_empty()).map(str::to_lowercase),
+        from: req.from.map(|from| from.naive_utc()),
+        to: req.to.map(|to| to.naive_utc()),
+        q: req.q.as_deref().map(str::trim).filter(|q| !q.is_empty()).map(str::to_string),
     };
 
     // Log the request
-    log::info!("User {} (ID: {}) requested history", username, user_id);
-
-    // Fetch user history from the database
-    match db.get_user_history(user_id).await {
-        Ok(history) => {
-            if history.is_empty() {
-                log::info!("No history found for user {} (ID: {})", username, user_id);
-                return HttpResponse::Ok().json(HistoryResponse { history });
-            }
-            log::info!("Fetched history for user {} (ID: {}) with {} entries", username, user_id, history.len());
-            HttpResponse::Ok().json(HistoryResponse { history })
+    log::info!("User {} requested history: {:?}", user_id, query);
+
+    // Fetch one page of user history from the database
+    match db.get_user_history(user_id, &query).await {
+        Ok((history, next_cursor)) => {
+            log::info!("Fetched history for user {} with {} entries", user_id, history.len());
+            HttpResponse::Ok().json(HistoryResponse {
+                history,
+                next_cursor: next_cursor.map(|cursor| cursor.encode()),
+            })
         }
         Err(e) => {
-            log::error!("Failed to fetch history for user {} (ID: {}): {}", username, user_id, e);
+            log::error!("Failed to fetch history for user {}: {}", user_id, e);
             HttpResponse::InternalServerError().body("Failed to fetch history")
         }
     }
 }
+
+#[utoipa::path(
+    delete,
+    path = "/v1/user/history/{id}",
+    tag = "user",
+    params(("id" = Uuid, Path, description = "History entry ID")),
+    responses(
+        (status = 200, description = "Entry deleted", body = String, content_type = "text/plain"),
+        (status = 404, description = "History entry not found"),
+    )
+)]
+#[delete("/history/{id}")]
+async fn delete_history_entry(
+    db: web::Data<DatabaseConnection>,
+    user: AuthUser,
+    path: web::Path<Uuid>,
+) -> Result<HttpResponse, ActixError> {
+    let user_id = user.id;
+    let interaction_id = path.into_inner();
+    match db.delete_history_entry(user_id, interaction_id).await {
+        Ok(true) => Ok(HttpResponse::Ok().body("History entry deleted")),
+        Ok(false) => Err(actix_web::error::ErrorNotFound("History entry not found")),
+        Err(e) => {
+            log::error!("Failed to delete history entry {} for user {}: {}", interaction_id, user_id, e);
+            Err(actix_web::error::ErrorInternalServerError("Failed to delete history entry"))
+        }
+    }
+}
+
+#[utoipa::path(
+    delete,
+    path = "/v1/user/history",
+    tag = "user",
+    request_body(content = ClearHistoryRequest, description = "Filters, or `{\"all\": true}` to delete every entry"),
+    responses(
+        (status = 200, description = "Number of entries deleted as `{\"deleted\": n}`", body = Object),
+        (status = 400, description = "Missing or unreadable body, or neither filters nor `all`", body = ErrorResponse),
+        (status = 415, description = "Body is not JSON", body = ErrorResponse),
+    )
+)]
+#[delete("/history")]
+async fn clear_history(
+    db: web::Data<DatabaseConnection>,
+    user: AuthUser,
+    req: web::Json<ClearHistoryRequest>,
+) -> Result<HttpResponse, ActixError> {
+    let user_id = user.id;
+    let req = req.into_inner();
+    // Deleting everything has to be asked for, so a body that lost its filters can't do it
+    if req.all == req.has_filters() {
+        let error = if req.all {
+            "\"all\" can't be combined with filters"
+        } else {
+            "Pass filters, or {\"all\": true} to delete the whole history"
+        };
+        return Err(actix_web::error::ErrorBadRequest(serde_json::json!({ "error": error })));
+    }
+    match db
+        .clear_history(
+            user_id,
+            req.ids.as_deref(),
+            req.from.map(|from| from.naive_utc()),
+            req.to.map(|to| to.naive_utc()),
+        )
+        .await
+    {
+        Ok(deleted) => {
+            log::info!("Cleared {} history entries for user {}", deleted, user_id);
+            Ok(HttpResponse::Ok().json(serde_json::json!({ "deleted": deleted })))
+        }
+        Err(e) => {
+            log::error!("Failed to clear history for user {}: {}", user_id, e);
+            Err(actix_web::error::ErrorInternalServerError("Failed to clear history"))
+        }
+    }
+}
+
+#[derive(OpenApi)]
+#[openapi(paths(get_history, delete_history_entry, clear_history))]
+pub struct ApiDoc;
+
 pub fn init_routes(cfg: &mut web::ServiceConfig) {
     cfg.service(get_history);
-}
\ No newline at end of file
+    cfg.service(delete_history_entry);
+    cfg.service(clear_history);
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::middleware::auth::AuthData;
+    use crate::middleware::validation::json_config;
+    use actix_web::http::header::ContentType;
+    use actix_web::http::StatusCode;
+    use actix_web::{test, App, HttpMessage};
+
+    // The database is unreachable, so a request that got as far as deleting answers 500
+    async fn clear(content_type: ContentType, body: &'static str) -> StatusCode {
+        let app = test::init_service(
+            App::new()
+                .app_data(json_config(1024))
+                .app_data(web::Data::new(DatabaseConnection::unreachable()))
+                .service(clear_history),
+        )
+        .await;
+        let req = test::TestRequest::delete()
+            .uri("/history")
+            .insert_header(content_type)
+            .set_payload(body)
+            .to_request();
+        req.extensions_mut().insert(AuthData::new(Uuid::new_v4().to_string(), "user".to_string()));
+        test::call_service(&app, req).await.status()
+    }
+
+    #[actix_web::test]
+    async fn unreadable_bodies_delete_nothing() {
+        for body in ["", "{", "{\"ids\": [", "{\"ids\": [\"not-a-uuid\"]}", "{\"from\": \"yesterday\"}", "{\"all\": \"yes\"}"] {
+            assert_eq!(clear(ContentType::json(), body).await, StatusCode::BAD_REQUEST, "body {:?}", body);
+        }
+        assert_eq!(clear(ContentType::plaintext(), "{\"all\": true}").await, StatusCode::UNSUPPORTED_MEDIA_TYPE);
+    }
+
+    #[actix_web::test]
+    async fn clearing_everything_must_be_explicit() {
+        assert_eq!(clear(ContentType::json(), "{}").await, StatusCode::BAD_REQUEST);
+        assert_eq!(clear(ContentType::json(), "{\"all\": false}").await, StatusCode::BAD_REQUEST);
+        assert_eq!(
+            clear(ContentType::json(), "{\"all\": true, \"from\": \"2025-01-01T00:00:00Z\"}").await,
+            StatusCode::BAD_REQUEST
+        );
+        // These pass the checks and reach the (unreachable) database
+        assert_eq!(clear(ContentType::json(), "{\"all\": true}").await, StatusCode::INTERNAL_SERVER_ERROR);
+        assert_eq!(
+            clear(ContentType::json(), "{\"from\": \"2025-01-01T00:00:00Z\"}").await,
+            StatusCode::INTERNAL_SERVER_ERROR
+        );
+    }
+}
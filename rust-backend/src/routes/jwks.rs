@@ -0,0 +1,28 @@
+use crate::middleware::auth::AuthConfig;
+use actix_web::http::header;
+use actix_web::{get, web, HttpResponse};
+use serde_json::json;
+use utoipa::OpenApi;
+
+// Public keys for verifying our tokens elsewhere. Empty when tokens are HS256.
+#[utoipa::path(
+    get,
+    path = "/.well-known/jwks.json",
+    tag = "auth",
+    security(()),
+    responses((status = 200, description = "Public keys for RS256/EdDSA tokens, as `{\"keys\": [...]}`", body = Object))
+)]
+#[get("/.well-known/jwks.json")]
+async fn jwks(auth_config: web::Data<AuthConfig>) -> HttpResponse {
+    HttpResponse::Ok()
+        .insert_header((header::CACHE_CONTROL, "public, max-age=300"))
+        .json(json!({ "keys": auth_config.public_jwks() }))
+}
+
+#[derive(OpenApi)]
+#[openapi(paths(jwks))]
+pub struct ApiDoc;
+
+pub fn init_routes(cfg: &mut web::ServiceConfig) {
+    cfg.service(jwks);
+}
@@ -1,7 +1,154 @@
 pub mod admin;
+pub mod api_keys;
+pub mod audit;
 pub mod auth;
+pub mod favorites;
 pub mod health;
+pub mod oidc;
+pub mod password;
 pub mod recommend;
 pub mod user;
 pub mod history;
-pub mod top_stickers;
\ No newline at end of file
+pub mod jwks;
+pub mod top_stickers;
+pub mod totp;
+pub mod openapi;
+
+use crate::middleware;
+use actix_web::middleware::from_fn;
+use actix_web::web;
+use actix_web_httpauth::middleware::HttpAuthentication;
+
+// Everything the app serves, with the auth and per-account rate limiting of each scope.
+// init_app adds the app-wide middleware and state around it, as do the integration tests.
+pub fn init_api(cfg: &mut web::ServiceConfig) {
+    let auth = HttpAuthentication::bearer(middleware::validate::jwt_middleware);
+    cfg.configure(jwks::init_routes).configure(openapi::init_docs).service(
+        web::scope("/v1")
+            .configure(init_public_routes)
+            .service(
+                web::scope("/sticker")
+                    // Registered first so it runs after auth and can see the caller
+                    .wrap(from_fn(middleware::rate_limit::by_account))
+                    .wrap(auth.clone())
+                    .configure(init_sticker_routes),
+            )
+            .service(
+                web::scope("/user")
+                    .wrap(from_fn(middleware::rate_limit::by_account))
+                    .wrap(auth.clone())
+                    .configure(init_user_routes),
+            )
+            .service(
+                web::scope("/admin")
+                    .wrap(from_fn(middleware::rate_limit::by_account))
+                    .wrap(auth)
+                    .configure(init_admin_routes),
+            ),
+    );
+}
+
+// What each /v1 scope serves. init_app wraps the scopes in auth and rate limiting; the
+// OpenAPI drift test mounts the same routes bare and checks them against ROUTES.
+pub fn init_public_routes(cfg: &mut web::ServiceConfig) {
+    cfg.configure(health::init_routes)
+        .configure(openapi::init_routes)
+        .configure(auth::init_routes)
+        .configure(oidc::init_routes)
+        .configure(totp::init_routes)
+        .configure(password::init_routes);
+}
+
+pub fn init_sticker_routes(cfg: &mut web::ServiceConfig) {
+    cfg.configure(recommend::init_routes); // Route becomes /v1/sticker/find
+}
+
+pub fn init_user_routes(cfg: &mut web::ServiceConfig) {
+    cfg.configure(user::init_routes)
+        .configure(history::init_routes)
+        .configure(top_stickers::init_routes)
+        .configure(favorites::init_routes)
+        .configure(api_keys::init_routes)
+        .configure(oidc::init_user_routes)
+        .configure(totp::init_user_routes)
+        .configure(password::init_user_routes);
+}
+
+pub fn init_admin_routes(cfg: &mut web::ServiceConfig) {
+    cfg.configure(admin::init_routes).configure(audit::init_routes);
+}
+
+// Every named route (method, path template, handler) the app serves, outside the docs UI.
+// New routes go here too: the drift test checks each entry is registered under that name and
+// path and is in the OpenAPI document, and that the document lists nothing else.
+#[cfg(test)]
+pub const ROUTES: &[(&str, &str, &str)] = &[
+    ("GET", "/.well-known/jwks.json", "jwks"),
+    ("POST", "/v1/auth/login/admin", "login_admin"),
+    ("POST", "/v1/auth/login/mfa", "login_mfa"),
+    ("POST", "/v1/auth/login/user", "login_user"),
+    ("GET", "/v1/auth/oidc/providers", "list_providers"),
+    ("GET", "/v1/auth/oidc/{provider}/callback", "oidc_callback"),
+    ("GET", "/v1/auth/oidc/{provider}/login", "oidc_login"),
+    ("POST", "/v1/auth/password/forgot", "forgot_password"),
+    ("POST", "/v1/auth/password/reset", "reset_password"),
+    ("POST", "/v1/auth/register/user", "register_user"),
+    ("GET", "/v1/health", "server_check"),
+    ("GET", "/v1/openapi.json", "openapi_json"),
+    ("POST", "/v1/sticker/dashboard-find", "find_sticker_dashboard"),
+    ("GET", "/v1/sticker/dashboard-trending", "trending_dashboard"),
+    ("POST", "/v1/sticker/feedback", "sticker_feedback"),
+    ("POST", "/v1/sticker/find", "find_sticker"),
+    ("GET", "/v1/user/2fa", "get_2fa_status"),
+    ("POST", "/v1/user/2fa/disable", "disable_2fa"),
+    ("POST", "/v1/user/2fa/enroll", "enroll_2fa"),
+    ("POST", "/v1/user/2fa/recovery-codes", "regenerate_recovery_codes"),
+    ("POST", "/v1/user/2fa/verify", "verify_2fa"),
+    ("GET", "/v1/user/analytics", "get_analytics"),
+    ("GET", "/v1/user/api-keys", "list_api_keys"),
+    ("POST", "/v1/user/api-keys", "create_api_key"),
+    ("DELETE", "/v1/user/api-keys/{id}", "revoke_api_key"),
+    ("GET", "/v1/user/collections", "list_collections"),
+    ("POST", "/v1/user/collections", "create_collection"),
+    ("DELETE", "/v1/user/collections/{id}", "delete_collection"),
+    ("POST", "/v1/user/collections/{id}/items", "add_collection_item"),
+    ("DELETE", "/v1/user/collections/{id}/items", "remove_collection_item"),
+    ("PUT", "/v1/user/collections/{id}/order", "reorder_collection"),
+    ("DELETE", "/v1/user/delete", "delete_user"),
+    ("PUT", "/v1/user/email", "update_email"),
+    ("GET", "/v1/user/export", "export_user_data"),
+    ("GET", "/v1/user/favorites", "list_favorites"),
+    ("POST", "/v1/user/favorites", "add_favorite"),
+    ("DELETE", "/v1/user/favorites", "remove_favorite"),
+    ("GET", "/v1/user/history", "get_history"),
+    ("DELETE", "/v1/user/history", "clear_history"),
+    ("DELETE", "/v1/user/history/{id}", "delete_history_entry"),
+    ("GET", "/v1/user/identities", "list_identities"),
+    ("POST", "/v1/user/identities/{provider}", "link_identity"),
+    ("DELETE", "/v1/user/identities/{provider}", "unlink_identity"),
+    ("POST", "/v1/user/identities/{provider}/confirm", "confirm_identity"),
+    ("POST", "/v1/user/logout", "logout"),
+    ("PUT", "/v1/user/password", "change_password"),
+    ("GET", "/v1/user/preferences", "get_preferences"),
+    ("PUT", "/v1/user/preferences", "update_preferences"),
+    ("GET", "/v1/user/top-stickers", "get_top_stickers"),
+    ("PUT", "/v1/user/update-username", "update_username"),
+    ("POST", "/v1/admin/admins/{id}/2fa/reset", "reset_admin_2fa"),
+    ("GET", "/v1/admin/analytics/activity", "activity_analytics"),
+    ("GET", "/v1/admin/analytics/emotions", "emotion_analytics"),
+    ("GET", "/v1/admin/analytics/top-stickers", "top_sticker_analytics"),
+    ("GET", "/v1/admin/analytics/upstream", "upstream_analytics"),
+    ("GET", "/v1/admin/audit", "list_audit_events"),
+    ("GET", "/v1/admin/audit/export", "export_audit_events"),
+    ("GET", "/v1/admin/users", "list_users"),
+    ("POST", "/v1/admin/users", "add_user"),
+    ("GET", "/v1/admin/users/{id}", "get_user"),
+    ("PUT", "/v1/admin/users/{id}", "update_user"),
+    ("DELETE", "/v1/admin/users/{id}", "soft_delete_user"),
+    ("POST", "/v1/admin/users/{id}/2fa/reset", "reset_user_2fa"),
+    ("POST", "/v1/admin/users/{id}/impersonate", "impersonate_user"),
+    ("GET", "/v1/admin/users/{id}/impersonations", "list_impersonations"),
+    ("POST", "/v1/admin/users/{id}/reactivate", "reactivate_user"),
+    ("POST", "/v1/admin/users/{id}/restore", "restore_user"),
+    ("POST", "/v1/admin/users/{id}/suspend", "suspend_user"),
+];
@@ -0,0 +1,886 @@
+use crate::configs::oidc::{OidcConfig, OidcProvider};
+use crate::configs::totp::TotpConfig;
+use crate::middleware::auth::{AuthConfig, AuthUser};
+use crate::models::oidc::NO_PASSWORD;
+use crate::routes::audit::{self, audit_event};
+use crate::routes::auth::start_user_session;
+use crate::routes::totp::start_mfa_challenge;
+use crate::structs::database_structs::{
+    AccountKind, ConfirmLinkRequest, DatabaseConnection, IdTokenClaims, MfaChallenge, OidcCallbackQuery, OidcState,
+    PendingLink, User, UserStatus,
+};
+use actix_web::error::Error as ActixError;
+use actix_web::http::header;
+use actix_web::{delete, get, post, web, HttpRequest, HttpResponse};
+use base64::engine::general_purpose::URL_SAFE_NO_PAD;
+use base64::Engine;
+use jsonwebtoken::jwk::JwkSet;
+use jsonwebtoken::{decode, decode_header, Algorithm, DecodingKey, Validation};
+use rand::rngs::OsRng;
+use rand::RngCore;
+use reqwest::{Client, Url};
+use serde::Deserialize;
+use serde_json::json;
+use sha2::{Digest, Sha256};
+use uuid::Uuid;
+use crate::routes::openapi::{ErrorResponse, SessionResponse};
+use utoipa::OpenApi;
+
+const STATE_TTL_SECS: u64 = 600; // time allowed between the redirect and the callback
+const DOCUMENT_TTL_SECS: u64 = 3600;
+
+// The parts of the provider's discovery document this flow uses
+#[derive(Deserialize)]
+struct ProviderMetadata {
+    issuer: String,
+    authorization_endpoint: String,
+    token_endpoint: String,
+    jwks_uri: String,
+}
+
+#[derive(Deserialize)]
+struct TokenResponse {
+    id_token: String,
+}
+
+fn random_token() -> String {
+    let mut bytes = [0u8; 32];
+    OsRng.fill_bytes(&mut bytes);
+    URL_SAFE_NO_PAD.encode(bytes)
+}
+
+fn pkce_challenge(verifier: &str) -> String {
+    URL_SAFE_NO_PAD.encode(Sha256::digest(verifier.as_bytes()))
+}
+
+fn provider_from<'a>(config: &'a OidcConfig, name: &str) -> Result<&'a OidcProvider, ActixError> {
+    config
+        .provider(name)
+        .ok_or_else(|| actix_web::error::ErrorNotFound(json!({ "error": "Unknown login provider" })))
+}
+
+// Fetches a JSON document, caching it in Redis under oidc:<cache_key>
+async fn fetch_document(
+    db: &DatabaseConnection,
+    cache_key: &str,
+    url: &str,
+    refresh: bool,
+) -> Result<String, String> {
+    if !refresh {
+        if let Ok(Some(cached)) = db.get_cached_oidc_document(cache_key).await {
+            return Ok(cached);
+        }
+    }
+    let result = Client::new().get(url).send().await;
+    if let Err(e) = db.record_upstream_call("oidc", matches!(&result, Ok(res) if res.status().is_success())).await {
+        log::error!("Failed to record upstream call: {}", e);
+    }
+    let res = result.map_err(|e| format!("Request to {} failed: {}", url, e))?;
+    if !res.status().is_success() {
+        return Err(format!("{} returned {}", url, res.status()));
+    }
+    let body = res.text().await.map_err(|e| format!("Failed to read {}: {}", url, e))?;
+    if let Err(e) = db.cache_oidc_document(cache_key, &body, DOCUMENT_TTL_SECS).await {
+        log::warn!("Failed to cache {}: {}", cache_key, e);
+    }
+    Ok(body)
+}
+
+async fn discover(db: &DatabaseConnection, provider: &OidcProvider) -> Result<ProviderMetadata, String> {
+    let url = format!("{}/.well-known/openid-configuration", provider.issuer);
+    let body = fetch_document(db, &format!("discovery:{}", provider.name), &url, false).await?;
+    let metadata: ProviderMetadata =
+        serde_json::from_str(&body).map_err(|e| format!("Invalid discovery document: {}", e))?;
+    if metadata.issuer.trim_end_matches('/') != provider.issuer {
+        return Err(format!("Discovery issuer {} does not match {}", metadata.issuer, provider.issuer));
+    }
+    Ok(metadata)
+}
+
+// Saves a fresh state/nonce/PKCE verifier and returns the provider URL to send the browser to.
+// `link` is the user and session linking a provider, None for a login.
+async fn authorization_url(
+    db: &DatabaseConnection,
+    provider: &OidcProvider,
+    link: Option<(Uuid, Uuid)>,
+) -> Result<String, ActixError> {
+    let metadata = discover(db, provider).await.map_err(|e| {
+        log::error!("OIDC discovery for {} failed: {}", provider.name, e);
+        actix_web::error::ErrorBadGateway(json!({ "error": "Login provider unavailable" }))
+    })?;
+
+    let state = random_token();
+    let data = OidcState {
+        provider: provider.name.clone(),
+        code_verifier: random_token(),
+        nonce: random_token(),
+        link_user_id: link.map(|(user_id, _)| user_id),
+        link_session_id: link.map(|(_, session_id)| session_id),
+    };
+    db.save_oidc_state(&state, &data, STATE_TTL_SECS).await.map_err(|e| {
+        log::error!("Failed to save OIDC state: {}", e);
+        actix_web::error::ErrorInternalServerError(json!({ "error": "Failed to start login" }))
+    })?;
+
+    let mut url = Url::parse(&metadata.authorization_endpoint).map_err(|e| {
+        log::error!("Invalid authorization endpoint for {}: {}", provider.name, e);
+        actix_web::error::ErrorBadGateway(json!({ "error": "Login provider unavailable" }))
+    })?;
+    url.query_pairs_mut()
+        .append_pair("response_type", "code")
+        .append_pair("client_id", &provider.client_id)
+        .append_pair("redirect_uri", &provider.redirect_uri)
+        .append_pair("scope", &provider.scopes)
+        .append_pair("state", &state)
+        .append_pair("nonce", &data.nonce)
+        .append_pair("code_challenge", &pkce_challenge(&data.code_verifier))
+        .append_pair("code_challenge_method", "S256");
+    Ok(url.to_string())
+}
+
+async fn exchange_code(
+    provider: &OidcProvider,
+    metadata: &ProviderMetadata,
+    code: &str,
+    code_verifier: &str,
+) -> Result<String, String> {
+    let mut form = vec![
+        ("grant_type", "authorization_code"),
+        ("code", code),
+        ("redirect_uri", provider.redirect_uri.as_str()),
+        ("client_id", provider.client_id.as_str()),
+        ("code_verifier", code_verifier),
+    ];
+    if let Some(secret) = &provider.client_secret {
+        form.push(("client_secret", secret.as_str()));
+    }
+    let res = Client::new()
+        .post(&metadata.token_endpoint)
+        .form(&form)
+        .send()
+        .await
+        .map_err(|e| format!("Token request failed: {}", e))?;
+    if !res.status().is_success() {
+        let status = res.status();
+        let body = res.text().await.unwrap_or_default();
+        return Err(format!("Token endpoint returned {}: {}", status, body));
+    }
+    let tokens: TokenResponse = res
+        .json()
+        .await
+        .map_err(|e| format!("Invalid token response: {}", e))?;
+    Ok(tokens.id_token)
+}
+
+// Checks signature, issuer, audience, expiry and nonce. The JWKS is refetched once when
+// the token's key id is unknown, to pick up provider key rotation.
+async fn verify_id_token(
+    db: &DatabaseConnection,
+    provider: &OidcProvider,
+    metadata: &ProviderMetadata,
+    id_token: &str,
+    nonce: &str,
+) -> Result<IdTokenClaims, String> {
+    let header = decode_header(id_token).map_err(|e| format!("Invalid id_token header: {}", e))?;
+    if matches!(header.alg, Algorithm::HS256 | Algorithm::HS384 | Algorithm::HS512) {
+        return Err("Symmetric id_token algorithms are not accepted".to_string());
+    }
+    let kid = header.kid.ok_or_else(|| "id_token has no kid".to_string())?;
+
+    let cache_key = format!("jwks:{}", provider.name);
+    let mut jwk = None;
+    for refresh in [false, true] {
+        let body = fetch_document(db, &cache_key, &metadata.jwks_uri, refresh).await?;
+        let jwks: JwkSet = serde_json::from_str(&body).map_err(|e| format!("Invalid JWKS: {}", e))?;
+        if let Some(found) = jwks.find(&kid) {
+            jwk = Some(found.clone());
+            break;
+        }
+    }
+    let jwk = jwk.ok_or_else(|| format!("No key {} in provider JWKS", kid))?;
+    let key = DecodingKey::from_jwk(&jwk).map_err(|e| format!("Unusable JWK {}: {}", kid, e))?;
+
+    let mut validation = Validation::new(header.alg);
+    validation.set_issuer(&[metadata.issuer.as_str()]);
+    validation.set_audience(&[provider.client_id.as_str()]);
+    let claims = decode::<IdTokenClaims>(id_token, &key, &validation)
+        .map_err(|e| format!("id_token rejected: {}", e))?
+        .claims;
+    if claims.nonce.as_deref() != Some(nonce) {
+        return Err("id_token nonce does not match".to_string());
+    }
+    Ok(claims)
+}
+
+// preferred_username or the email's local part, reduced to characters usernames allow
+fn username_candidate(claims: &IdTokenClaims) -> String {
+    let raw = claims
+        .preferred_username
+        .as_deref()
+        .or_else(|| claims.email.as_deref().and_then(|e| e.split('@').next()))
+        .unwrap_or("user");
+    // Same rules as registration: lowercase, starting with a letter or digit
+    let cleaned: String = raw
+        .to_ascii_lowercase()
+        .chars()
+        .filter(|c| c.is_ascii_alphanumeric() || matches!(c, '_' | '.' | '-'))
+        .skip_while(|c| !c.is_ascii_alphanumeric())
+        .take(24)
+        .collect();
+    if cleaned.len() < 3 {
+        "user".to_string()
+    } else {
+        cleaned
+    }
+}
+
+// Creates the user, suffixing the username if it is taken
+async fn provision_user(
+    db: &DatabaseConnection,
+    provider: &OidcProvider,
+    claims: &IdTokenClaims,
+) -> Result<User, sqlx::Error> {
+    let base = username_candidate(claims);
+    let mut username = base.clone();
+    for _ in 0..5 {
+        match db
+            .provision_oidc_user(&username, &provider.name, &claims.sub, claims.email.as_deref())
+            .await
+        {
+            Err(sqlx::Error::Database(e)) if e.is_unique_violation() => {
+                let mut suffix = [0u8; 3];
+                OsRng.fill_bytes(&mut suffix);
+                username = format!("{}-{}", base, hex::encode(suffix));
+            }
+            result => return result,
+        }
+    }
+    Err(sqlx::Error::Protocol("could not find a free username".to_string()))
+}
+
+// Sends the browser to the frontend with the outcome in the fragment, or answers with
+// JSON when no frontend redirect is configured.
+fn finish(config: &OidcConfig, status: actix_web::http::StatusCode, body: serde_json::Value) -> HttpResponse {
+    match &config.success_redirect {
+        Some(redirect) => {
+            let mut fragment = Url::parse("http://fragment.invalid/").expect("static URL");
+            {
+                let mut pairs = fragment.query_pairs_mut();
+                if let Some(map) = body.as_object() {
+                    for (key, value) in map {
+                        let value = value.as_str().map(str::to_string).unwrap_or_else(|| value.to_string());
+                        pairs.append_pair(key, &value);
+                    }
+                }
+            }
+            let location = format!("{}#{}", redirect, fragment.query().unwrap_or_default());
+            HttpResponse::Found()
+                .insert_header((header::LOCATION, location))
+                .finish()
+        }
+        None => HttpResponse::build(status).json(body),
+    }
+}
+
+fn fail(config: &OidcConfig, status: actix_web::http::StatusCode, error: &str) -> HttpResponse {
+    finish(config, status, json!({ "error": error }))
+}
+
+// The session behind this request's bearer token. Linking is tied to it, so an expired or
+// signed-out session can't complete a link started earlier.
+async fn current_session(db: &DatabaseConnection, http_req: &HttpRequest, user_id: Uuid) -> Result<Uuid, ActixError> {
+    let expired = || actix_web::error::ErrorUnauthorized(json!({ "error": "Session expired, please sign in again" }));
+    let token = http_req
+        .headers()
+        .get(header::AUTHORIZATION)
+        .and_then(|v| v.to_str().ok())
+        .and_then(|v| v.strip_prefix("Bearer "))
+        .ok_or_else(expired)?;
+    match db.validate_session(token).await {
+        Ok(Some(session)) if session.user_id == Some(user_id) => Ok(session.id),
+        Ok(_) => Err(expired()),
+        Err(e) => {
+            log::error!("Failed to check session for user {}: {}", user_id, e);
+            Err(actix_web::error::ErrorInternalServerError(json!({ "error": "Failed to link login" })))
+        }
+    }
+}
+
+// A link code only completes for the user and session that started the flow, so a code
+// from someone else's link can't attach their provider login to this account or vice versa
+fn link_belongs_to(link: &PendingLink, provider: &str, user_id: Uuid, session_id: Uuid) -> bool {
+    link.provider == provider && link.user_id == user_id && link.session_id == session_id
+}
+
+#[utoipa::path(
+    get,
+    path = "/v1/auth/oidc/providers",
+    tag = "auth",
+    security(()),
+    responses((status = 200, description = "Configured provider names as `{\"providers\": [...]}`", body = Object))
+)]
+#[get("/auth/oidc/providers")]
+async fn list_providers(config: web::Data<OidcConfig>) -> HttpResponse {
+    let providers: Vec<&str> = config.providers.iter().map(|p| p.name.as_str()).collect();
+    HttpResponse::Ok().json(json!({ "providers": providers }))
+}
+
+#[utoipa::path(
+    get,
+    path = "/v1/auth/oidc/{provider}/login",
+    tag = "auth",
+    security(()),
+    params(("provider" = String, Path, description = "Provider name from OIDC_PROVIDERS")),
+    responses(
+        (status = 302, description = "Redirect to the provider's login page"),
+        (status = 404, description = "Unknown login provider", body = ErrorResponse),
+    )
+)]
+#[get("/auth/oidc/{provider}/login")]
+async fn oidc_login(
+    db: web::Data<DatabaseConnection>,
+    config: web::Data<OidcConfig>,
+    path: web::Path<String>,
+) -> Result<HttpResponse, ActixError> {
+    let provider = provider_from(&config, &path.into_inner())?;
+    let url = authorization_url(&db, provider, None).await?;
+    Ok(HttpResponse::Found()
+        .insert_header((header::LOCATION, url))
+        .finish())
+}
+
+#[utoipa::path(
+    get,
+    path = "/v1/auth/oidc/{provider}/callback",
+    tag = "auth",
+    security(()),
+    params(("provider" = String, Path, description = "Provider name from OIDC_PROVIDERS"), OidcCallbackQuery),
+    responses(
+        (status = 200, description = "Session token or 2FA challenge, when no OIDC_SUCCESS_REDIRECT is set", body = SessionResponse),
+        (status = 302, description = "Redirect to OIDC_SUCCESS_REDIRECT with the outcome in the fragment"),
+        (status = 400, description = "Login cancelled, expired or not verifiable", body = ErrorResponse),
+    )
+)]
+#[get("/auth/oidc/{provider}/callback")]
+async fn oidc_callback(
+    db: web::Data<DatabaseConnection>,
+    auth_config: web::Data<AuthConfig>,
+    config: web::Data<OidcConfig>,
+    totp_config: web::Data<TotpConfig>,
+    http_req: HttpRequest,
+    path: web::Path<String>,
+    query: web::Query<OidcCallbackQuery>,
+) -> Result<HttpResponse, ActixError> {
+    use actix_web::http::StatusCode;
+
+    let provider = provider_from(&config, &path.into_inner())?;
+    if let Some(error) = &query.error {
+        log::warn!(
+            "OIDC provider {} returned {}: {}",
+            provider.name,
+            error,
+            query.error_description.as_deref().unwrap_or("")
+        );
+        return Ok(fail(&config, StatusCode::BAD_REQUEST, "Login was cancelled or refused"));
+    }
+    let (Some(code), Some(state)) = (&query.code, &query.state) else {
+        return Ok(fail(&config, StatusCode::BAD_REQUEST, "Missing code or state"));
+    };
+    let state = match db.take_oidc_state(state).await {
+        Ok(Some(state)) if state.provider == provider.name => state,
+        Ok(_) => return Ok(fail(&config, StatusCode::BAD_REQUEST, "Login expired, please try again")),
+        Err(e) => {
+            log::error!("Failed to read OIDC state: {}", e);
+            return Ok(fail(&config, StatusCode::INTERNAL_SERVER_ERROR, "Login failed"));
+        }
+    };
+
+    let claims = async {
+        let metadata = discover(&db, provider).await?;
+        let id_token = exchange_code(provider, &metadata, code, &state.code_verifier).await?;
+        verify_id_token(&db, provider, &metadata, &id_token, &state.nonce).await
+    }
+    .await;
+    let claims = match claims {
+        Ok(claims) => claims,
+        Err(e) => {
+            log::warn!("OIDC login with {} failed: {}", provider.name, e);
+            audit::record(
+                &db,
+                audit_event(&http_req, "auth.oidc_login_failed")
+                    .diff(json!({ "provider": provider.name, "reason": e })),
+            )
+            .await;
+            return Ok(fail(&config, StatusCode::UNAUTHORIZED, "Login could not be verified"));
+        }
+    };
+
+    let existing = match db.get_user_by_identity(&provider.name, &claims.sub).await {
+        Ok(existing) => existing,
+        Err(e) => {
+            log::error!("Failed to look up identity: {}", e);
+            return Ok(fail(&config, StatusCode::INTERNAL_SERVER_ERROR, "Login failed"));
+        }
+    };
+
+    // Linking a provider to the account that started the flow. The browser finishing here may
+    // not be the one that started it, so nothing is written yet: the identity waits under a
+    // one-time code that the linking user confirms with their own session.
+    if let Some(user_id) = state.link_user_id {
+        if let Some(user) = existing {
+            if user.id != user_id {
+                return Ok(fail(&config, StatusCode::CONFLICT, "This login is already linked to another account"));
+            }
+            return Ok(finish(&config, StatusCode::OK, json!({ "linked": provider.name })));
+        }
+        let Some(session_id) = state.link_session_id else {
+            return Ok(fail(&config, StatusCode::BAD_REQUEST, "Login expired, please try again"));
+        };
+        let link_code = random_token();
+        let pending = PendingLink {
+            user_id,
+            session_id,
+            provider: provider.name.clone(),
+            subject: claims.sub,
+            email: claims.email,
+        };
+        if let Err(e) = db.save_pending_link(&link_code, &pending, STATE_TTL_SECS).await {
+            log::error!("Failed to save pending link for user {}: {}", user_id, e);
+            return Ok(fail(&config, StatusCode::INTERNAL_SERVER_ERROR, "Login failed"));
+        }
+        return Ok(finish(&config, StatusCode::OK, json!({ "link_code": link_code, "provider": provider.name })));
+    }
+
+    let user = match existing {
+        Some(user) => {
+            if let Err(e) = db.touch_identity(&provider.name, &claims.sub, claims.email.as_deref()).await {
+                log::error!("Failed to update identity login time: {}", e);
+            }
+            user
+        }
+        None if provider.auto_provision => match provision_user(&db, provider, &claims).await {
+            Ok(user) => {
+                if let Err(e) = db.record_signup().await {
+                    log::error!("Failed to record signup: {}", e);
+                }
+                audit::record(
+                    &db,
+                    audit_event(&http_req, "auth.register")
+                        .actor("user", user.id)
+                        .target("user", user.id)
+                        .diff(json!({ "provider": provider.name })),
+                )
+                .await;
+                user
+            }
+            Err(e) => {
+                log::error!("Failed to provision user from {}: {}", provider.name, e);
+                return Ok(fail(&config, StatusCode::INTERNAL_SERVER_ERROR, "Failed to create account"));
+            }
+        },
+        None => {
+            return Ok(fail(&config, StatusCode::FORBIDDEN, "No account is linked to this login"));
+        }
+    };
+
+    if UserStatus::parse(&user.status) != Some(UserStatus::Active) {
+        audit::record(
+            &db,
+            audit_event(&http_req, "auth.login_failed")
+                .target("user", user.id)
+                .diff(json!({ "provider": provider.name, "reason": user.status })),
+        )
+        .await;
+        return Ok(fail(&config, StatusCode::FORBIDDEN, "Account is not active"));
+    }
+
+    if user.totp_enabled {
+        let challenge = MfaChallenge {
+            kind: AccountKind::User,
+            id: user.id,
+            enrolling: false,
+            provider: Some(provider.name.clone()),
+        };
+        let mfa_token = start_mfa_challenge(&db, &totp_config, challenge).await?;
+        return Ok(finish(&config, StatusCode::OK, json!({ "mfa_token": mfa_token })));
+    }
+    let token = start_user_session(&db, &auth_config, user.id).await?;
+    audit::record(
+        &db,
+        audit_event(&http_req, "auth.login")
+            .actor("user", user.id)
+            .target("user", user.id)
+            .diff(json!({ "provider": provider.name })),
+    )
+    .await;
+    Ok(finish(&config, StatusCode::OK, json!({ "token": token, "username": user.username })))
+}
+
+// Returns the provider URL; the frontend navigates there and confirms the link code the
+// callback hands back
+#[utoipa::path(
+    post,
+    path = "/v1/user/identities/{provider}",
+    tag = "user",
+    params(("provider" = String, Path, description = "Provider name from OIDC_PROVIDERS")),
+    responses(
+        (status = 200, description = "`{\"authorization_url\": ...}` for the browser to open", body = Object),
+        (status = 401, description = "Session expired", body = ErrorResponse),
+        (status = 404, description = "Unknown login provider", body = ErrorResponse),
+    )
+)]
+#[post("/identities/{provider}")]
+async fn link_identity(
+    db: web::Data<DatabaseConnection>,
+    config: web::Data<OidcConfig>,
+    user: AuthUser,
+    http_req: HttpRequest,
+    path: web::Path<String>,
+) -> Result<HttpResponse, ActixError> {
+    let user_id = user.id;
+    let provider = provider_from(&config, &path.into_inner())?;
+    let session_id = current_session(&db, &http_req, user_id).await?;
+    let url = authorization_url(&db, provider, Some((user_id, session_id))).await?;
+    Ok(HttpResponse::Ok().json(json!({ "authorization_url": url })))
+}
+
+// Writes the identity the callback verified, once the session that started the link is
+// confirmed to still be valid and to belong to the same user
+#[utoipa::path(
+    post,
+    path = "/v1/user/identities/{provider}/confirm",
+    tag = "user",
+    params(("provider" = String, Path, description = "Provider name from OIDC_PROVIDERS")),
+    request_body = ConfirmLinkRequest,
+    responses(
+        (status = 200, description = "Login linked", body = Object),
+        (status = 400, description = "Link code unknown or expired", body = ErrorResponse),
+        (status = 401, description = "Session expired", body = ErrorResponse),
+        (status = 403, description = "Link was started by another session", body = ErrorResponse),
+        (status = 409, description = "A login from this provider is already linked", body = ErrorResponse),
+    )
+)]
+#[post("/identities/{provider}/confirm")]
+async fn confirm_identity(
+    db: web::Data<DatabaseConnection>,
+    user: AuthUser,
+    http_req: HttpRequest,
+    path: web::Path<String>,
+    req: web::Json<ConfirmLinkRequest>,
+) -> Result<HttpResponse, ActixError> {
+    let user_id = user.id;
+    let provider = path.into_inner();
+    let session_id = current_session(&db, &http_req, user_id).await?;
+    let link = db
+        .take_pending_link(&req.link_code)
+        .await
+        .map_err(|e| {
+            log::error!("Failed to read pending link for user {}: {}", user_id, e);
+            actix_web::error::ErrorInternalServerError(json!({ "error": "Failed to link login" }))
+        })?
+        .ok_or_else(|| actix_web::error::ErrorBadRequest(json!({ "error": "Link expired, please try again" })))?;
+    if !link_belongs_to(&link, &provider, user_id, session_id) {
+        log::warn!(
+            "User {} tried to confirm a {} link started by user {}",
+            user_id,
+            link.provider,
+            link.user_id
+        );
+        return Err(actix_web::error::ErrorForbidden(json!({
+            "error": "This link was started from another session"
+        })));
+    }
+
+    match db.link_identity(user_id, &provider, &link.subject, link.email.as_deref()).await {
+        Ok(()) => {}
+        Err(sqlx::Error::Database(e)) if e.is_unique_violation() => {
+            return Err(actix_web::error::ErrorConflict(json!({
+                "error": "A login from this provider is already linked"
+            })));
+        }
+        Err(e) => {
+            log::error!("Failed to link {} to user {}: {}", provider, user_id, e);
+            return Err(actix_web::error::ErrorInternalServerError(json!({ "error": "Failed to link login" })));
+        }
+    }
+    audit::record(
+        &db,
+        audit_event(&http_req, "user.link_identity")
+            .actor("user", user_id)
+            .target("user", user_id)
+            .diff(json!({ "provider": provider, "subject": link.subject })),
+    )
+    .await;
+    Ok(HttpResponse::Ok().json(json!({ "provider": provider, "linked": true })))
+}
+
+#[utoipa::path(
+    get,
+    path = "/v1/user/identities",
+    tag = "user",
+    responses((status = 200, description = "Linked logins as `{\"identities\": [...]}`", body = Object))
+)]
+#[get("/identities")]
+async fn list_identities(
+    db: web::Data<DatabaseConnection>,
+    user: AuthUser,
+) -> Result<HttpResponse, ActixError> {
+    let user_id = user.id;
+    db.get_identities(user_id)
+        .await
+        .map(|identities| HttpResponse::Ok().json(json!({ "identities": identities })))
+        .map_err(|e| {
+            log::error!("Failed to fetch identities for user {}: {}", user_id, e);
+            actix_web::error::ErrorInternalServerError(json!({ "error": "Failed to fetch linked logins" }))
+        })
+}
+
+// Refuses to remove the last way into an account without a password
+#[utoipa::path(
+    delete,
+    path = "/v1/user/identities/{provider}",
+    tag = "user",
+    params(("provider" = String, Path, description = "Provider name from OIDC_PROVIDERS")),
+    responses(
+        (status = 200, description = "Login unlinked", body = Object),
+        (status = 400, description = "It is the only way into an account without a password", body = ErrorResponse),
+        (status = 404, description = "No login linked for this provider", body = ErrorResponse),
+    )
+)]
+#[delete("/identities/{provider}")]
+async fn unlink_identity(
+    db: web::Data<DatabaseConnection>,
+    user: AuthUser,
+    http_req: HttpRequest,
+    path: web::Path<String>,
+) -> Result<HttpResponse, ActixError> {
+    let user_id = user.id;
+    let provider = path.into_inner();
+    let internal = |e: sqlx::Error| {
+        log::error!("Failed to unlink {} for user {}: {}", provider, user_id, e);
+        actix_web::error::ErrorInternalServerError(json!({ "error": "Failed to unlink login" }))
+    };
+    let user = db
+        .get_user_by_id(user_id)
+        .await
+        .map_err(internal)?
+        .ok_or_else(|| actix_web::error::ErrorNotFound(json!({ "error": "User not found" })))?;
+    let identities = db.get_identities(user_id).await.map_err(internal)?;
+    if user.password_hash == NO_PASSWORD && identities.len() <= 1 {
+        return Err(actix_web::error::ErrorBadRequest(json!({
+            "error": "Set a password or link another login before removing this one"
+        })));
+    }
+    match db.unlink_identity(user_id, &provider).await.map_err(internal)? {
+        true => {
+            audit::record(
+                &db,
+                audit_event(&http_req, "user.unlink_identity")
+                    .actor("user", user_id)
+                    .target("user", user_id)
+                    .diff(json!({ "provider": provider })),
+            )
+            .await;
+            Ok(HttpResponse::Ok().json(json!({ "provider": provider, "linked": false })))
+        }
+        false => Err(actix_web::error::ErrorNotFound(json!({ "error": "No login linked for this provider" }))),
+    }
+}
+
+#[derive(OpenApi)]
+#[openapi(paths(
+    list_providers,
+    oidc_login,
+    oidc_callback,
+    link_identity,
+    confirm_identity,
+    list_identities,
+    unlink_identity,
+))]
+pub struct ApiDoc;
+
+pub fn init_routes(cfg: &mut web::ServiceConfig) {
+    cfg.service(list_providers);
+    cfg.service(oidc_login);
+    cfg.service(oidc_callback);
+}
+
+// Mounted under the authenticated /user scope
+pub fn init_user_routes(cfg: &mut web::ServiceConfig) {
+    cfg.service(list_identities);
+    cfg.service(link_identity);
+    cfg.service(confirm_identity);
+    cfg.service(unlink_identity);
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::middleware::auth::AuthData;
+    use crate::middleware::signing_keys::{generate_key, load_key};
+    use actix_web::http::StatusCode;
+    use actix_web::{App, HttpMessage};
+    use jsonwebtoken::{encode, get_current_timestamp, EncodingKey, Header};
+    use serde_json::Value;
+    use wiremock::matchers::{body_string_contains, method, path};
+    use wiremock::{Mock, MockServer, ResponseTemplate};
+
+    // An issuer serving discovery, JWKS and a token endpoint; the database is unreachable, so
+    // nothing is cached and every document comes from the mock
+    struct MockIssuer {
+        _server: MockServer,
+        provider: OidcProvider,
+        kid: String,
+        key: EncodingKey,
+    }
+
+    impl MockIssuer {
+        async fn start(advertised_issuer: Option<&str>) -> Self {
+            let server = MockServer::start().await;
+            let issuer = server.uri();
+            let generated = generate_key(Algorithm::EdDSA).expect("Ed25519 key");
+            let (_, key, _) = load_key("EdDSA", &generated.private_key, &generated.public_jwk).expect("usable key");
+            Mock::given(method("GET"))
+                .and(path("/.well-known/openid-configuration"))
+                .respond_with(ResponseTemplate::new(200).set_body_json(json!({
+                    "issuer": advertised_issuer.unwrap_or(&issuer),
+                    "authorization_endpoint": format!("{}/authorize", issuer),
+                    "token_endpoint": format!("{}/token", issuer),
+                    "jwks_uri": format!("{}/jwks", issuer),
+                })))
+                .mount(&server)
+                .await;
+            Mock::given(method("GET"))
+                .and(path("/jwks"))
+                .respond_with(ResponseTemplate::new(200).set_body_json(json!({ "keys": [generated.public_jwk] })))
+                .mount(&server)
+                .await;
+            let provider = OidcProvider {
+                name: "mock".to_string(),
+                issuer,
+                client_id: "sticker-app".to_string(),
+                client_secret: None,
+                redirect_uri: "http://localhost:8080/v1/auth/oidc/mock/callback".to_string(),
+                scopes: "openid".to_string(),
+                auto_provision: false,
+            };
+            MockIssuer { _server: server, provider, kid: generated.kid, key }
+        }
+
+        fn claims(&self, nonce: &str) -> Value {
+            json!({
+                "iss": self.provider.issuer,
+                "aud": self.provider.client_id,
+                "sub": "subject-1",
+                "nonce": nonce,
+                "exp": get_current_timestamp() + 300,
+            })
+        }
+
+        fn id_token(&self, claims: &Value) -> String {
+            let mut header = Header::new(Algorithm::EdDSA);
+            header.kid = Some(self.kid.clone());
+            encode(&header, claims, &self.key).expect("signed token")
+        }
+    }
+
+    #[actix_web::test]
+    async fn discovery_must_name_the_configured_issuer() {
+        let db = DatabaseConnection::unreachable();
+        let issuer = MockIssuer::start(None).await;
+        assert!(discover(&db, &issuer.provider).await.is_ok());
+
+        let impostor = MockIssuer::start(Some("https://accounts.example.com")).await;
+        let err = discover(&db, &impostor.provider).await.err().expect("issuer mismatch");
+        assert!(err.contains("does not match"), "{}", err);
+    }
+
+    #[actix_web::test]
+    async fn id_tokens_are_checked_against_the_issuer_keys() {
+        let db = DatabaseConnection::unreachable();
+        let issuer = MockIssuer::start(None).await;
+        let metadata = discover(&db, &issuer.provider).await.expect("discovery");
+        let verify = |token: String| {
+            let (db, provider, metadata) = (&db, &issuer.provider, &metadata);
+            async move { verify_id_token(db, provider, metadata, &token, "nonce-1").await }
+        };
+
+        let claims = verify(issuer.id_token(&issuer.claims("nonce-1"))).await.expect("valid token");
+        assert_eq!(claims.sub, "subject-1");
+
+        assert!(verify(issuer.id_token(&issuer.claims("nonce-2"))).await.is_err());
+        let mut other_audience = issuer.claims("nonce-1");
+        other_audience["aud"] = json!("another-app");
+        assert!(verify(issuer.id_token(&other_audience)).await.is_err());
+        let mut expired = issuer.claims("nonce-1");
+        expired["exp"] = json!(get_current_timestamp() - 3600);
+        assert!(verify(issuer.id_token(&expired)).await.is_err());
+
+        // Signed by a key the issuer never published
+        let stranger = MockIssuer::start(None).await;
+        let mut foreign = stranger.claims("nonce-1");
+        foreign["iss"] = json!(issuer.provider.issuer);
+        foreign["aud"] = json!(issuer.provider.client_id);
+        let err = verify(stranger.id_token(&foreign)).await.expect_err("unknown key");
+        assert!(err.starts_with("No key"), "{}", err);
+
+        let mut header = Header::new(Algorithm::HS256);
+        header.kid = Some(issuer.kid.clone());
+        let symmetric = encode(&header, &issuer.claims("nonce-1"), &EncodingKey::from_secret(b"secret")).unwrap();
+        let err = verify(symmetric).await.expect_err("symmetric token");
+        assert!(err.starts_with("Symmetric"), "{}", err);
+    }
+
+    #[actix_web::test]
+    async fn the_code_is_exchanged_with_the_pkce_verifier() {
+        let db = DatabaseConnection::unreachable();
+        let issuer = MockIssuer::start(None).await;
+        let metadata = discover(&db, &issuer.provider).await.expect("discovery");
+        Mock::given(method("POST"))
+            .and(path("/token"))
+            .and(body_string_contains("code=code-1"))
+            .and(body_string_contains("code_verifier=verifier-1"))
+            .respond_with(ResponseTemplate::new(200).set_body_json(json!({ "id_token": "token-1" })))
+            .mount(&issuer._server)
+            .await;
+
+        let id_token = exchange_code(&issuer.provider, &metadata, "code-1", "verifier-1").await;
+        assert_eq!(id_token.as_deref(), Ok("token-1"));
+        assert!(exchange_code(&issuer.provider, &metadata, "code-1", "verifier-2").await.is_err());
+    }
+
+    #[test]
+    fn link_codes_complete_only_for_the_starting_session() {
+        let (user_id, session_id) = (Uuid::new_v4(), Uuid::new_v4());
+        let link = PendingLink {
+            user_id,
+            session_id,
+            provider: "mock".to_string(),
+            subject: "subject-1".to_string(),
+            email: None,
+        };
+        assert!(link_belongs_to(&link, "mock", user_id, session_id));
+        assert!(!link_belongs_to(&link, "mock", Uuid::new_v4(), session_id));
+        assert!(!link_belongs_to(&link, "mock", user_id, Uuid::new_v4()));
+        assert!(!link_belongs_to(&link, "other", user_id, session_id));
+    }
+
+    // Without the bearer session that started the link nothing is read or written
+    #[actix_web::test]
+    async fn confirming_a_link_needs_a_session() {
+        let app = actix_web::test::init_service(
+            App::new()
+                .app_data(web::Data::new(DatabaseConnection::unreachable()))
+                .service(confirm_identity),
+        )
+        .await;
+        let req = actix_web::test::TestRequest::post()
+            .uri("/identities/mock/confirm")
+            .set_json(json!({ "link_code": "code-1" }))
+            .to_request();
+        req.extensions_mut()
+            .insert(AuthData::new(Uuid::new_v4().to_string(), "user".to_string()));
+        let res = actix_web::test::call_service(&app, req).await;
+        assert_eq!(res.status(), StatusCode::UNAUTHORIZED);
+    }
+}
@@ -0,0 +1,213 @@
+use crate::routes;
+use actix_web::http::header;
+use actix_web::middleware::DefaultHeaders;
+use actix_web::{get, web, HttpResponse};
+use std::sync::OnceLock;
+use utoipa::openapi::security::{HttpAuthScheme, HttpBuilder, SecurityScheme};
+use utoipa::{Modify, OpenApi};
+use utoipa_swagger_ui::{Config, SwaggerUi};
+
+pub const SPEC_PATH: &str = "/v1/openapi.json";
+pub const DOCS_PATH: &str = "/v1/docs";
+
+// Swagger UI loads its own scripts and styles and uses inline styles and data: images
+pub const DOCS_CSP: &str = "default-src 'self'; img-src 'self' data:; style-src 'self' 'unsafe-inline'; frame-ancestors 'none'";
+
+// Shapes of JSON bodies the handlers build inline, shared with clients through sticker-types
+pub use sticker_types::{ErrorResponse, MfaChallengeResponse, SessionResponse, ValidationErrorResponse};
+
+// Session tokens and API keys are both sent as bearer tokens
+struct BearerAuth;
+
+impl Modify for BearerAuth {
+    fn modify(&self, openapi: &mut utoipa::openapi::OpenApi) {
+        let components = openapi.components.get_or_insert_with(Default::default);
+        components.add_security_scheme(
+            "bearer",
+            SecurityScheme::Http(
+                HttpBuilder::new()
+                    .scheme(HttpAuthScheme::Bearer)
+                    .bearer_format("JWT or API key")
+                    .build(),
+            ),
+        );
+    }
+}
+
+#[derive(OpenApi)]
+#[openapi(
+    info(title = "Sticker Recommender API", description = "Emotion-based sticker recommendations"),
+    components(schemas(ErrorResponse, ValidationErrorResponse, SessionResponse, MfaChallengeResponse)),
+    modifiers(&BearerAuth),
+    security(("bearer" = [])),
+    tags(
+        (name = "health"),
+        (name = "auth", description = "Registration, login and password reset"),
+        (name = "sticker", description = "Recommendations and trending stickers"),
+        (name = "user", description = "The caller's account, history, favorites and keys"),
+        (name = "admin", description = "Admin tokens only"),
+    )
+)]
+pub struct ApiDoc;
+
+// Each route module lists its handlers in its own ApiDoc, next to init_routes
+pub fn document() -> utoipa::openapi::OpenApi {
+    let mut doc = ApiDoc::openapi();
+    for module in [
+        routes::health::ApiDoc::openapi(),
+        routes::jwks::ApiDoc::openapi(),
+        routes::auth::ApiDoc::openapi(),
+        routes::password::ApiDoc::openapi(),
+        routes::oidc::ApiDoc::openapi(),
+        routes::totp::ApiDoc::openapi(),
+        routes::recommend::ApiDoc::openapi(),
+        routes::user::ApiDoc::openapi(),
+        routes::history::ApiDoc::openapi(),
+        routes::top_stickers::ApiDoc::openapi(),
+        routes::favorites::ApiDoc::openapi(),
+        routes::api_keys::ApiDoc::openapi(),
+        routes::admin::ApiDoc::openapi(),
+        routes::audit::ApiDoc::openapi(),
+    ] {
+        doc.merge(module);
+    }
+    doc
+}
+
+// Built once; the handlers can't change while the server runs
+fn spec() -> &'static str {
+    static SPEC: OnceLock<String> = OnceLock::new();
+    SPEC.get_or_init(|| document().to_json().expect("OpenAPI document serializes"))
+}
+
+#[get("/openapi.json")]
+async fn openapi_json() -> HttpResponse {
+    HttpResponse::Ok()
+        .content_type("application/json")
+        .insert_header((header::CACHE_CONTROL, "public, max-age=300"))
+        .body(spec())
+}
+
+pub fn init_routes(cfg: &mut web::ServiceConfig) {
+    cfg.service(openapi_json);
+}
+
+// The UI links its assets relatively, so it has to be opened with the trailing slash
+async fn docs_redirect() -> HttpResponse {
+    HttpResponse::PermanentRedirect()
+        .insert_header((header::LOCATION, format!("{}/", DOCS_PATH)))
+        .finish()
+}
+
+// Swagger UI, bundled into the binary. Registered ahead of the /v1 scope, which would
+// otherwise answer 404 for everything under /v1.
+pub fn init_docs(cfg: &mut web::ServiceConfig) {
+    cfg.service(
+        web::scope(DOCS_PATH)
+            .wrap(DefaultHeaders::new().add((header::CONTENT_SECURITY_POLICY, DOCS_CSP)))
+            .route("", web::get().to(docs_redirect))
+            .service(SwaggerUi::new("/{_:.*}").config(Config::from(SPEC_PATH))),
+    );
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use actix_web::http::{Method, StatusCode};
+    use actix_web::test::{call_service, init_service, read_body_json, TestRequest};
+    use actix_web::{App, HttpRequest};
+    use std::collections::BTreeMap;
+
+    // Stands in for every path parameter; parses as both a String and a Uuid
+    const PARAM: &str = "00000000-0000-0000-0000-000000000000";
+    // Serves the document rather than appearing in it
+    const UNDOCUMENTED: [&str; 1] = ["openapi_json"];
+
+    // Replaces each {parameter} in a route template
+    fn concrete(template: &str) -> String {
+        template
+            .split('/')
+            .map(|segment| if segment.starts_with('{') { PARAM } else { segment })
+            .collect::<Vec<_>>()
+            .join("/")
+    }
+
+    // Where each entry of routes::ROUTES is registered, looked up by its resource name
+    // (actix names resources after the handler)
+    async fn route_table(req: HttpRequest) -> HttpResponse {
+        let registered: BTreeMap<String, Option<String>> = routes::ROUTES
+            .iter()
+            .map(|(_, _, name)| (name.to_string(), req.url_for(name, [PARAM; 4]).ok().map(|url| url.path().to_string())))
+            .collect();
+        HttpResponse::Ok().json(registered)
+    }
+
+    // operation_id (the handler name) -> (method, path template)
+    fn documented() -> BTreeMap<String, (Method, String)> {
+        let mut operations = BTreeMap::new();
+        for (path, item) in document().paths.paths {
+            for (method, operation) in [
+                (Method::GET, item.get),
+                (Method::POST, item.post),
+                (Method::PUT, item.put),
+                (Method::PATCH, item.patch),
+                (Method::DELETE, item.delete),
+            ] {
+                let Some(operation) = operation else { continue };
+                let id = operation.operation_id.expect("operations are named after their handler");
+                let previous = operations.insert(id.clone(), (method, path.clone()));
+                assert!(previous.is_none(), "two documented operations are named {}", id);
+            }
+        }
+        operations
+    }
+
+    #[actix_web::test]
+    async fn document_matches_the_registered_routes() {
+        // The same mounts as init_app, without the auth and rate limit middleware
+        let app = init_service(
+            App::new()
+                .configure(routes::jwks::init_routes)
+                .configure(init_docs)
+                .route("/__routes", web::get().to(route_table))
+                .service(
+                    web::scope("/v1")
+                        .configure(routes::init_public_routes)
+                        .service(web::scope("/sticker").configure(routes::init_sticker_routes))
+                        .service(web::scope("/user").configure(routes::init_user_routes))
+                        .service(web::scope("/admin").configure(routes::init_admin_routes)),
+                ),
+        )
+        .await;
+        let res = call_service(&app, TestRequest::get().uri("/__routes").to_request()).await;
+        let registered: BTreeMap<String, Option<String>> = read_body_json(res).await;
+        let mut documented = documented();
+
+        for (method, template, name) in routes::ROUTES {
+            let path = concrete(template);
+            assert_eq!(registered[*name].as_ref(), Some(&path), "{} is not registered at {}", name, template);
+            // Without app data the handler fails in its extractors, but never with a routing error
+            let req = TestRequest::default().method(Method::from_bytes(method.as_bytes()).unwrap()).uri(&path).to_request();
+            let status = call_service(&app, req).await.status();
+            assert!(
+                status != StatusCode::NOT_FOUND && status != StatusCode::METHOD_NOT_ALLOWED,
+                "{} {} answered {}",
+                method,
+                template,
+                status
+            );
+            if UNDOCUMENTED.contains(name) {
+                continue;
+            }
+            let (documented_method, documented_path) =
+                documented.remove(*name).unwrap_or_else(|| panic!("{} {} is missing from the OpenAPI document", method, template));
+            assert_eq!(
+                (documented_method.as_str(), documented_path.as_str()),
+                (*method, *template),
+                "{} is documented at a different route",
+                name
+            );
+        }
+        assert!(documented.is_empty(), "documented but not in routes::ROUTES: {:?}", documented.keys());
+    }
+}
@@ -0,0 +1,396 @@
+use crate::configs::email::EmailConfig;
+use crate::configs::password::PasswordPolicy;
+use crate::email::{EmailMessage, EmailSender};
+use crate::middleware::auth::AuthUser;
+use crate::models::oidc::NO_PASSWORD;
+use crate::routes::audit::{self, audit_event};
+use crate::structs::database_structs::{
+    ChangePasswordRequest, DatabaseConnection, ForgotPasswordRequest, NewAuditEvent, ResetPasswordRequest,
+    UpdateEmailRequest, UserStatus,
+};
+use actix_web::error::Error as ActixError;
+use actix_web::http::header;
+use actix_web::{post, put, web, HttpRequest, HttpResponse};
+use bcrypt::verify;
+use chrono::{Duration, Utc};
+use rand::rngs::OsRng;
+use rand::RngCore;
+use reqwest::Url;
+use serde_json::json;
+use sha2::{Digest, Sha256};
+use crate::routes::openapi::ErrorResponse;
+use utoipa::OpenApi;
+
+// 400 listing every rule the password breaks
+pub fn check_password(policy: &PasswordPolicy, password: &str, username: &str) -> Result<(), ActixError> {
+    let problems = policy.violations(password, username);
+    if problems.is_empty() {
+        return Ok(());
+    }
+    Err(actix_web::error::ErrorBadRequest(json!({
+        "error": "Password does not meet the password policy",
+        "details": problems,
+    })))
+}
+
+// Lowercased; only a basic shape check, since the reset mail is the real test
+pub fn normalize_email(email: &str) -> Result<String, ActixError> {
+    let email = email.trim().to_lowercase();
+    let valid = email.len() <= 254
+        && !email.chars().any(char::is_whitespace)
+        && matches!(email.split_once('@'), Some((local, domain)) if !local.is_empty() && domain.contains('.'));
+    if valid {
+        Ok(email)
+    } else {
+        Err(actix_web::error::ErrorBadRequest(json!({ "error": "Invalid email address" })))
+    }
+}
+
+fn hash_password(password: &str) -> Result<String, ActixError> {
+    bcrypt::hash(password, bcrypt::DEFAULT_COST).map_err(|e| {
+        log::error!("Failed to hash password: {}", e);
+        actix_web::error::ErrorInternalServerError(json!({ "error": "Failed to update password" }))
+    })
+}
+
+fn hash_reset_token(token: &str) -> String {
+    hex::encode(Sha256::digest(token.as_bytes()))
+}
+
+// Accounts created through OIDC have no password; they can set one without the current one
+fn check_current_password(current: Option<&str>, password_hash: &str) -> Result<(), ActixError> {
+    if password_hash == NO_PASSWORD {
+        return Ok(());
+    }
+    match current {
+        Some(current) if verify(current, password_hash).unwrap_or(false) => Ok(()),
+        _ => Err(actix_web::error::ErrorForbidden(json!({ "error": "Current password is incorrect" }))),
+    }
+}
+
+// Changes the password, signs out every other session and revokes every API key
+#[utoipa::path(
+    put,
+    path = "/v1/user/password",
+    tag = "user",
+    request_body = ChangePasswordRequest,
+    responses(
+        (status = 200, description = "Password changed; other sessions signed out and API keys revoked", body = Object),
+        (status = 400, description = "New password breaks the password policy", body = Object),
+        (status = 403, description = "Current password is incorrect", body = ErrorResponse),
+    )
+)]
+#[put("/password")]
+async fn change_password(
+    db: web::Data<DatabaseConnection>,
+    policy: web::Data<PasswordPolicy>,
+    user: AuthUser,
+    http_req: HttpRequest,
+    req: web::Json<ChangePasswordRequest>,
+) -> Result<HttpResponse, ActixError> {
+    let user_id = user.id;
+    let user = db
+        .get_user_by_id(user_id)
+        .await
+        .map_err(|e| {
+            log::error!("Failed to fetch user {}: {}", user_id, e);
+            actix_web::error::ErrorInternalServerError(json!({ "error": "Failed to update password" }))
+        })?
+        .ok_or_else(|| actix_web::error::ErrorNotFound(json!({ "error": "User not found" })))?;
+    if let Err(e) = check_current_password(req.current_password.as_deref(), &user.password_hash) {
+        audit::record(
+            &db,
+            audit_event(&http_req, "user.change_password_failed")
+                .actor("user", user_id)
+                .target("user", user_id),
+        )
+        .await;
+        return Err(e);
+    }
+    check_password(&policy, &req.new_password, &user.username)?;
+    if user.password_hash != NO_PASSWORD && verify(&req.new_password, &user.password_hash).unwrap_or(false) {
+        return Err(actix_web::error::ErrorBadRequest(json!({
+            "error": "New password must differ from the current one"
+        })));
+    }
+
+    let current_token = http_req
+        .headers()
+        .get(header::AUTHORIZATION)
+        .and_then(|v| v.to_str().ok())
+        .and_then(|v| v.strip_prefix("Bearer "));
+    let password_hash = hash_password(&req.new_password)?;
+    db.set_user_password(user_id, &password_hash, current_token)
+        .await
+        .map_err(|e| {
+            log::error!("Failed to update password for user {}: {}", user_id, e);
+            actix_web::error::ErrorInternalServerError(json!({ "error": "Failed to update password" }))
+        })?;
+    audit::record(
+        &db,
+        audit_event(&http_req, "user.change_password")
+            .actor("user", user_id)
+            .target("user", user_id)
+            .diff(json!({ "password": "changed", "had_password": user.password_hash != NO_PASSWORD })),
+    )
+    .await;
+    Ok(HttpResponse::Ok().json(json!({ "message": "Password updated; other sessions were signed out and API keys revoked" })))
+}
+
+// The address reset links are sent to. Changing it needs the password, or a stolen
+// session could redirect resets to an attacker.
+#[utoipa::path(
+    put,
+    path = "/v1/user/email",
+    tag = "user",
+    request_body = UpdateEmailRequest,
+    responses(
+        (status = 200, description = "The new address as `{\"email\": ...}`", body = Object),
+        (status = 400, description = "Invalid email address", body = ErrorResponse),
+        (status = 403, description = "Current password is incorrect", body = ErrorResponse),
+        (status = 409, description = "Email address is already in use", body = ErrorResponse),
+    )
+)]
+#[put("/email")]
+async fn update_email(
+    db: web::Data<DatabaseConnection>,
+    user: AuthUser,
+    http_req: HttpRequest,
+    req: web::Json<UpdateEmailRequest>,
+) -> Result<HttpResponse, ActixError> {
+    let user_id = user.id;
+    let email = req.email.as_deref().map(normalize_email).transpose()?;
+    let user = db
+        .get_user_by_id(user_id)
+        .await
+        .map_err(|e| {
+            log::error!("Failed to fetch user {}: {}", user_id, e);
+            actix_web::error::ErrorInternalServerError(json!({ "error": "Failed to update email" }))
+        })?
+        .ok_or_else(|| actix_web::error::ErrorNotFound(json!({ "error": "User not found" })))?;
+    check_current_password(req.current_password.as_deref(), &user.password_hash)?;
+    db.set_user_email(user_id, email.as_deref()).await.map_err(|e| match e {
+        sqlx::Error::Database(ref db_err) if db_err.is_unique_violation() => {
+            actix_web::error::ErrorConflict(json!({ "error": "Email address is already in use" }))
+        }
+        e => {
+            log::error!("Failed to update email for user {}: {}", user_id, e);
+            actix_web::error::ErrorInternalServerError(json!({ "error": "Failed to update email" }))
+        }
+    })?;
+    audit::record(
+        &db,
+        audit_event(&http_req, "user.update_email")
+            .actor("user", user_id)
+            .target("user", user_id)
+            .diff(json!({ "email": audit::change(user.email, email.clone()) })),
+    )
+    .await;
+    Ok(HttpResponse::Ok().json(json!({ "email": email })))
+}
+
+// Always answers the same way, and at once: the lookup and the email happen after the
+// response, so neither the body nor the timing tells which addresses exist
+#[utoipa::path(
+    post,
+    path = "/v1/auth/password/forgot",
+    tag = "auth",
+    security(()),
+    request_body = ForgotPasswordRequest,
+    responses((status = 202, description = "Same answer whether or not the address is known", body = Object))
+)]
+#[post("/auth/password/forgot")]
+async fn forgot_password(
+    db: web::Data<DatabaseConnection>,
+    email_config: web::Data<EmailConfig>,
+    sender: web::Data<dyn EmailSender>,
+    http_req: HttpRequest,
+    req: web::Json<ForgotPasswordRequest>,
+) -> Result<HttpResponse, ActixError> {
+    if let Ok(email) = normalize_email(&req.email) {
+        let event = audit_event(&http_req, "auth.password_reset_requested");
+        actix_web::rt::spawn(send_reset_link(db, email_config, sender, event, email));
+    }
+    Ok(HttpResponse::Accepted().json(json!({
+        "message": "If an account uses this address, a reset link has been sent"
+    })))
+}
+
+// The part of forgot_password that runs after the response; failures are only logged
+async fn send_reset_link(
+    db: web::Data<DatabaseConnection>,
+    email_config: web::Data<EmailConfig>,
+    sender: web::Data<dyn EmailSender>,
+    event: NewAuditEvent,
+    email: String,
+) {
+    let user = match db.get_user_by_email(&email).await {
+        Ok(Some(user)) if UserStatus::parse(&user.status) == Some(UserStatus::Active) => user,
+        Ok(_) => return,
+        Err(e) => {
+            log::error!("Failed to look up user for password reset: {}", e);
+            return;
+        }
+    };
+
+    let mut bytes = [0u8; 32];
+    OsRng.fill_bytes(&mut bytes);
+    let token = hex::encode(bytes);
+    let expires_at = (Utc::now() + Duration::minutes(email_config.password_reset_ttl_mins)).naive_utc();
+    match db.create_password_reset_token(user.id, &hash_reset_token(&token), expires_at).await {
+        Ok(true) => {}
+        Ok(false) => {
+            log::info!("Skipped password reset for user {}: requested again within a minute", user.id);
+            return;
+        }
+        Err(e) => {
+            log::error!("Failed to create password reset token for user {}: {}", user.id, e);
+            return;
+        }
+    }
+
+    let link = match Url::parse_with_params(&email_config.password_reset_url, &[("token", &token)]) {
+        Ok(url) => url.to_string(),
+        Err(e) => {
+            log::error!("Invalid PASSWORD_RESET_URL {}: {}", email_config.password_reset_url, e);
+            return;
+        }
+    };
+    let message = EmailMessage {
+        to: email,
+        subject: "Reset your password".to_string(),
+        body: format!(
+            "Hi {},\n\nUse this link to choose a new password. It expires in {} minutes and works once:\n\n{}\n\nIf you didn't ask for this, you can ignore this email.",
+            user.username, email_config.password_reset_ttl_mins, link
+        ),
+    };
+    if let Err(e) = sender.send(&message).await {
+        log::error!("Failed to send password reset email to user {}: {}", user.id, e);
+    }
+    audit::record(&db, event.target("user", user.id)).await;
+}
+
+// Sets the new password, signs the user out everywhere and revokes their API keys
+#[utoipa::path(
+    post,
+    path = "/v1/auth/password/reset",
+    tag = "auth",
+    security(()),
+    request_body = ResetPasswordRequest,
+    responses(
+        (status = 200, description = "Password updated; all sessions signed out and API keys revoked", body = Object),
+        (status = 400, description = "Link invalid or expired, or the password breaks the policy", body = ErrorResponse),
+    )
+)]
+#[post("/auth/password/reset")]
+async fn reset_password(
+    db: web::Data<DatabaseConnection>,
+    policy: web::Data<PasswordPolicy>,
+    http_req: HttpRequest,
+    req: web::Json<ResetPasswordRequest>,
+) -> Result<HttpResponse, ActixError> {
+    let invalid = || actix_web::error::ErrorBadRequest(json!({ "error": "Reset link is invalid or has expired" }));
+    let token_hash = hash_reset_token(req.token.trim());
+    let user = db
+        .get_password_reset_user(&token_hash)
+        .await
+        .map_err(|e| {
+            log::error!("Failed to look up password reset token: {}", e);
+            actix_web::error::ErrorInternalServerError(json!({ "error": "Failed to reset password" }))
+        })?
+        .ok_or_else(invalid)?;
+    check_password(&policy, &req.new_password, &user.username)?;
+
+    let password_hash = hash_password(&req.new_password)?;
+    let user_id = db
+        .reset_password_with_token(&token_hash, &password_hash)
+        .await
+        .map_err(|e| {
+            log::error!("Failed to reset password for user {}: {}", user.id, e);
+            actix_web::error::ErrorInternalServerError(json!({ "error": "Failed to reset password" }))
+        })?
+        .ok_or_else(invalid)?;
+    audit::record(
+        &db,
+        audit_event(&http_req, "auth.password_reset")
+            .actor("user", user_id)
+            .target("user", user_id),
+    )
+    .await;
+    Ok(HttpResponse::Ok().json(json!({ "message": "Password updated; please log in" })))
+}
+
+#[derive(OpenApi)]
+#[openapi(paths(
+    change_password,
+    update_email,
+    forgot_password,
+    reset_password,
+))]
+pub struct ApiDoc;
+
+pub fn init_routes(cfg: &mut web::ServiceConfig) {
+    cfg.service(forgot_password);
+    cfg.service(reset_password);
+}
+
+// Mounted under the authenticated /user scope
+pub fn init_user_routes(cfg: &mut web::ServiceConfig) {
+    cfg.service(change_password);
+    cfg.service(update_email);
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use actix_web::http::StatusCode;
+    use actix_web::App;
+    use futures::future::BoxFuture;
+    use std::sync::Arc;
+    use std::time::{Duration as StdDuration, Instant};
+
+    // Would hold the request open forever if the handler waited for delivery
+    struct StuckSender;
+
+    impl EmailSender for StuckSender {
+        fn send<'a>(&'a self, _message: &'a EmailMessage) -> BoxFuture<'a, Result<(), String>> {
+            Box::pin(futures::future::pending())
+        }
+    }
+
+    #[actix_web::test]
+    async fn forgot_password_answers_before_doing_any_work() {
+        let sender: Arc<dyn EmailSender> = Arc::new(StuckSender);
+        let app = actix_web::test::init_service(
+            App::new()
+                // Every query waits out the pool timeout before failing
+                .app_data(web::Data::new(DatabaseConnection::unreachable()))
+                .app_data(web::Data::new(EmailConfig::new()))
+                .app_data(web::Data::from(sender))
+                .service(forgot_password),
+        )
+        .await;
+
+        let mut bodies = Vec::new();
+        for email in ["alice@example.com", "not an email"] {
+            let started = Instant::now();
+            let req = actix_web::test::TestRequest::post()
+                .uri("/auth/password/forgot")
+                .set_json(json!({ "email": email }))
+                .to_request();
+            let resp = actix_web::test::call_service(&app, req).await;
+            assert!(started.elapsed() < StdDuration::from_millis(100), "{} took {:?}", email, started.elapsed());
+            assert_eq!(resp.status(), StatusCode::ACCEPTED);
+            bodies.push(actix_web::test::read_body(resp).await);
+        }
+        assert_eq!(bodies[0], bodies[1]);
+    }
+
+    #[test]
+    fn emails_are_normalized_and_shape_checked() {
+        assert_eq!(normalize_email("  Alice@Example.COM ").unwrap(), "alice@example.com");
+        for email in ["", "alice", "@example.com", "alice@localhost", "al ice@example.com"] {
+            assert!(normalize_email(email).is_err(), "{}", email);
+        }
+    }
+}
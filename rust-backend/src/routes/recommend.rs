@@ -1,50 +1,55 @@
-use actix_web::{post, web, HttpMessage, HttpRequest, HttpResponse, Responder};
+use actix_web::{get, post, web, HttpResponse, Responder};
 use uuid::Uuid;
-use crate::structs::database_structs::{RecommendRequest, RecommendResponse, TrendingRequest, TrendingResponse};
-use crate::middleware::auth::AuthData;
+use crate::structs::database_structs::{DatabaseConnection, FeedbackRequest, RecommendRequest, RecommendResponse, TrendingCacheEntry, TrendingRequest, TrendingResponse};
+use crate::configs::trending::{TrendingConfig, TRENDING_PROVIDER, TRENDING_RATINGS};
+use crate::middleware::validation::ValidatedJson;
+use crate::middleware::auth::{AuthData, AuthUser};
+use crate::models::ranking;
+use chrono::Utc;
+use std::collections::HashSet;
 use serde_json::Value;
 use reqwest::Client;
-
+use crate::routes::openapi::{ErrorResponse, ValidationErrorResponse};
+use utoipa::OpenApi;
+
+#[utoipa::path(
+    post,
+    path = "/v1/sticker/find",
+    tag = "sticker",
+    request_body = RecommendRequest,
+    responses(
+        (status = 200, description = "Stickers for the detected emotion", body = RecommendResponse),
+        (status = 400, description = "Invalid input text", body = ValidationErrorResponse),
+        (status = 429, description = "Rate limit or daily quota exceeded", body = ErrorResponse),
+        (status = 502, description = "The sticker service returned nothing"),
+    )
+)]
 #[post("/find")]
 async fn find_sticker(
     db: web::Data<crate::structs::database_structs::DatabaseConnection>,
-    req: web::Json<RecommendRequest>,
-    http_req: HttpRequest,
+    user: AuthUser,
+    req: ValidatedJson<RecommendRequest>,
 ) -> impl Responder {
     // Extract the request payload
     let req = req.into_inner();
-    println!("▶️ find payload: {:?}", req);
     let input_text = req.input_text.trim().to_lowercase();
-    let username = &req.username;
-
-    // Extract AuthData from extensions
-    let extensions = http_req.extensions();
-    let auth_data = match extensions.get::<AuthData>() {
-        Some(data) => data.clone(),
-        None => {
-            log::error!("Auth data not found in request extensions");
-            return HttpResponse::Unauthorized().body("Auth data not found");
-        }
-    };
-    let user_id = match Uuid::parse_str(&auth_data.id) {
-        Ok(id) => id,
-        Err(e) => {
-            log::error!("Invalid user ID format: {}", e);
-            return HttpResponse::BadRequest().body("Invalid user ID format");
-        }
-    };
+    let personalize = req.personalize.unwrap_or(true);
+
+    let user_id = user.id;
 
     // Log the request
-    log::info!("User {} (ID: {}) requested sticker for text: {}", username, user_id, input_text);
+    // Never the text itself: users who opted out of storing it must not find it in the logs
+    log::info!("User {} requested stickers ({} characters)", user_id, input_text.chars().count());
     let client = Client::new();
 
     // Call Emotion Detection Service
-    let emotion_response = match client
+    let emotion_result = client
         .post("http:///sticker-api:8000/detect_emotion")
         .json(&serde_json::json!({ "input_text": &input_text }))
         .send()
-        .await
-    {
+        .await;
+    record_upstream(&db, "emotion", &emotion_result).await;
+    let emotion_response = match emotion_result {
         Ok(res) => res,
         Err(e) => {
             log::error!("Emotion detection request failed: {}", e);
@@ -73,30 +78,33 @@ async fn find_sticker(
     if let Ok(Some((emotion, cached_value))) = db.get_cached_sticker(&cache_key).await {
         if let Ok(sticker_urls) = serde_json::from_str::<Vec<String>>(&cached_value) {
             // Cache hit with a list of stickers
-            if let Some(first_sticker) = sticker_urls.first() {
-                save_interaction(&db, user_id, &input_text, &emotion, first_sticker).await;
-            }
+            let sticker_urls = personalize_stickers(&db, user_id, sticker_urls, personalize).await;
+            let recommendation_id = record_recommendation(&db, &user.auth, user_id, &input_text, &emotion, &sticker_urls, true).await;
             return HttpResponse::Ok().json(RecommendResponse {
+                recommendation_id,
                 detected_emotion: emotion,
                 sticker_urls,
             });
         } else {
             // Cache hit with a single sticker (backward compatibility)
-            save_interaction(&db, user_id, &input_text, &emotion, &cached_value).await;
+            let sticker_urls = vec![cached_value];
+            let recommendation_id = record_recommendation(&db, &user.auth, user_id, &input_text, &emotion, &sticker_urls, true).await;
             return HttpResponse::Ok().json(RecommendResponse {
+                recommendation_id,
                 detected_emotion: emotion,
-                sticker_urls: vec![cached_value],
+                sticker_urls,
             });
         }
     }
 
     // Call Sticker Search Service
-    let sticker_response = match client
+    let sticker_result = client
         .post("http:///sticker-api:8000/search_stickers")
         .json(&serde_json::json!({ "q": &detected_emotion, "rating": "g" }))
         .send()
-        .await
-    {
+        .await;
+    record_upstream(&db, "sticker_search", &sticker_result).await;
+    let sticker_response = match sticker_result {
         Ok(res) => res,
         Err(e) => {
             log::error!("Sticker search request failed: {}", e);
@@ -136,13 +144,15 @@ async fn find_sticker(
             }
         });
 
-        // Save interaction for the first sticker
-        if let Some(first_sticker) = sticker_urls.first() {
-            save_interaction(&db, user_id, &input_text, &detected_emotion, first_sticker).await;
-        }
+        // Re-rank the shared results for this user before picking the first sticker
+        let sticker_urls = personalize_stickers(&db, user_id, sticker_urls, personalize).await;
+
+        // Save the recommendation; the interaction points at the first sticker until feedback says otherwise
+        let recommendation_id = record_recommendation(&db, &user.auth, user_id, &input_text, &detected_emotion, &sticker_urls, false).await;
 
         // Return all sticker URLs
         HttpResponse::Ok().json(RecommendResponse {
+            recommendation_id,
             detected_emotion,
             sticker_urls,
         })
@@ -167,46 +177,44 @@ async fn find_sticker(
 
 
 
+#[utoipa::path(
+    post,
+    path = "/v1/sticker/dashboard-find",
+    tag = "sticker",
+    request_body = RecommendRequest,
+    responses(
+        (status = 200, description = "Stickers for the detected emotion", body = RecommendResponse),
+        (status = 400, description = "Invalid input text", body = ValidationErrorResponse),
+        (status = 429, description = "Rate limit or daily quota exceeded", body = ErrorResponse),
+        (status = 502, description = "The sticker service returned nothing"),
+    )
+)]
 #[post("/dashboard-find")]
 async fn find_sticker_dashboard(
     db: web::Data<crate::structs::database_structs::DatabaseConnection>,
-    req: web::Json<RecommendRequest>,
-    http_req: HttpRequest,
+    user: AuthUser,
+    req: ValidatedJson<RecommendRequest>,
 ) -> impl Responder {
     // Extract the request payload
     let req = req.into_inner();
-    println!("▶️ find payload: {:?}", req);
     let input_text = req.input_text.trim().to_lowercase();
-    let username = &req.username;
-
-    // Extract AuthData from extensions
-    let extensions = http_req.extensions();
-    let auth_data = match extensions.get::<AuthData>() {
-        Some(data) => data.clone(),
-        None => {
-            log::error!("Auth data not found in request extensions");
-            return HttpResponse::Unauthorized().body("Auth data not found");
-        }
-    };
-    let user_id = match Uuid::parse_str(&auth_data.id) {
-        Ok(id) => id,
-        Err(e) => {
-            log::error!("Invalid user ID format: {}", e);
-            return HttpResponse::BadRequest().body("Invalid user ID format");
-        }
-    };
+    let personalize = req.personalize.unwrap_or(true);
+
+    let user_id = user.id;
 
     // Log the request
-    log::info!("User {} (ID: {}) requested sticker for text: {}", username, user_id, input_text);
+    // Never the text itself: users who opted out of storing it must not find it in the logs
+    log::info!("User {} requested stickers ({} characters)", user_id, input_text.chars().count());
     let client = Client::new();
 
     // Call Emotion Detection Service
-    let emotion_response = match client
+    let emotion_result = client
         .post("http:///sticker-api:8000/detect_emotion")
         .json(&serde_json::json!({ "input_text": &input_text }))
         .send()
-        .await
-    {
+        .await;
+    record_upstream(&db, "emotion", &emotion_result).await;
+    let emotion_response = match emotion_result {
         Ok(res) => res,
         Err(e) => {
             log::error!("Emotion detection request failed: {}", e);
@@ -235,30 +243,33 @@ async fn find_sticker_dashboard(
     if let Ok(Some((emotion, cached_value))) = db.get_cached_sticker(&cache_key).await {
         if let Ok(sticker_urls) = serde_json::from_str::<Vec<String>>(&cached_value) {
             // Cache hit with a list of stickers
-            if let Some(first_sticker) = sticker_urls.first() {
-                save_interaction(&db, user_id, &input_text, &emotion, first_sticker).await;
-            }
+            let sticker_urls = personalize_stickers(&db, user_id, sticker_urls, personalize).await;
+            let recommendation_id = record_recommendation(&db, &user.auth, user_id, &input_text, &emotion, &sticker_urls, true).await;
             return HttpResponse::Ok().json(RecommendResponse {
+                recommendation_id,
                 detected_emotion: emotion,
                 sticker_urls,
             });
         } else {
             // Cache hit with a single sticker (backward compatibility)
-            save_interaction(&db, user_id, &input_text, &emotion, &cached_value).await;
+            let sticker_urls = vec![cached_value];
+            let recommendation_id = record_recommendation(&db, &user.auth, user_id, &input_text, &emotion, &sticker_urls, true).await;
             return HttpResponse::Ok().json(RecommendResponse {
+                recommendation_id,
                 detected_emotion: emotion,
-                sticker_urls: vec![cached_value],
+                sticker_urls,
             });
         }
     }
 
     // Call Sticker Search Service
-    let sticker_response = match client
+    let sticker_result = client
         .post("http:///sticker-api:8000/search_stickers_dashboard")
         .json(&serde_json::json!({ "q": &detected_emotion, "rating": "g" }))
         .send()
-        .await
-    {
+        .await;
+    record_upstream(&db, "sticker_search", &sticker_result).await;
+    let sticker_response = match sticker_result {
         Ok(res) => res,
         Err(e) => {
             log::error!("Sticker search request failed: {}", e);
@@ -298,13 +309,15 @@ async fn find_sticker_dashboard(
             }
         });
 
-        // Save interaction for the first sticker
-        if let Some(first_sticker) = sticker_urls.first() {
-            save_interaction(&db, user_id, &input_text, &detected_emotion, first_sticker).await;
-        }
+        // Re-rank the shared results for this user before picking the first sticker
+        let sticker_urls = personalize_stickers(&db, user_id, sticker_urls, personalize).await;
+
+        // Save the recommendation; the interaction points at the first sticker until feedback says otherwise
+        let recommendation_id = record_recommendation(&db, &user.auth, user_id, &input_text, &detected_emotion, &sticker_urls, false).await;
 
         // Return all sticker URLs
         HttpResponse::Ok().json(RecommendResponse {
+            recommendation_id,
             detected_emotion,
             sticker_urls,
         })
@@ -319,97 +332,163 @@ async fn find_sticker_dashboard(
 
 
 
-#[post("/dashboard-trending")]
+#[utoipa::path(
+    get,
+    path = "/v1/sticker/dashboard-trending",
+    tag = "sticker",
+    params(TrendingRequest),
+    responses(
+        (status = 200, description = "A page of trending stickers", body = TrendingResponse),
+        (status = 400, description = "Invalid rating", body = ErrorResponse),
+        (status = 502, description = "Trending stickers unavailable", body = ErrorResponse),
+    )
+)]
+#[get("/dashboard-trending")]
 async fn trending_dashboard(
-    req: web::Json<TrendingRequest>,
-    http_req: HttpRequest,
+    db: web::Data<DatabaseConnection>,
+    trending_config: web::Data<TrendingConfig>,
+    user: AuthUser,
+    req: web::Query<TrendingRequest>,
 ) -> impl Responder {
-    // Extract the request payload
     let req = req.into_inner();
-    println!("▶️ find payload: {:?}", req);
-    let username = &req.username;
-    println!("▶️ username payload: {:?}", username);
-    // Extract AuthData from extensions
-    let extensions = http_req.extensions();
-    println!("▶️ extensions payload: {:?}", extensions);
-    let auth_data = match extensions.get::<AuthData>() {
-        Some(data) => data.clone(),
-        None => {
-            log::error!("Auth data not found in request extensions");
-            return HttpResponse::Unauthorized().json(serde_json::json!({
-                "error": "Auth data not found"
-            }));
+    let user_id = user.id;
+
+    let rating = req.rating.as_deref().unwrap_or("g").trim().to_lowercase();
+    if !TRENDING_RATINGS.contains(&rating.as_str()) {
+        return HttpResponse::BadRequest().json(serde_json::json!({
+            "error": format!("Invalid rating, expected one of {:?}", TRENDING_RATINGS)
+        }));
+    }
+    let limit = req.limit.unwrap_or(9).clamp(1, trending_config.cache_size);
+    let offset = req.offset.unwrap_or(0);
+
+    // Log the request
+    log::info!("User {} requested trending stickers (rating {})", user_id, rating);
+
+    // Serve from cache when possible, refreshing ahead of expiry in the background
+    let cached = match db.get_cached_trending(&rating).await {
+        Ok(entry) => entry,
+        Err(e) => {
+            log::error!("Failed to read trending cache: {}", e);
+            None
         }
     };
-    let user_id = match Uuid::parse_str(&auth_data.id) {
-        Ok(id) => id,
-        Err(e) => {
-            log::error!("Invalid user ID format: {}", e);
-            return HttpResponse::BadRequest().json(serde_json::json!({
-                "error": "Invalid user ID format"
-            }));
+
+    let (entry, stale) = match cached {
+        Some(entry) => {
+            let age = Utc::now().signed_duration_since(entry.fetched_at).num_seconds();
+            let needs_refresh = age >= trending_config.refresh_after_secs;
+            if needs_refresh {
+                let db_clone = db.clone();
+                let config_clone = trending_config.clone();
+                let rating_clone = rating.clone();
+                actix_web::rt::spawn(async move {
+                    if let Err(e) = refresh_trending(&db_clone, &config_clone, &rating_clone).await {
+                        log::warn!("Background trending refresh for {} failed: {}", rating_clone, e);
+                    }
+                });
+            }
+            (entry, needs_refresh)
         }
+        None => match refresh_trending(&db, &trending_config, &rating).await {
+            Ok(entry) => (entry, false),
+            Err(e) => {
+                log::error!("Failed to fetch trending stickers: {}", e);
+                return HttpResponse::BadGateway().json(serde_json::json!({
+                    "error": "Failed to fetch trending stickers"
+                }));
+            }
+        },
     };
 
-    // Log the request
-    log::info!("User {} (ID: {}) requested trending stickers", username, user_id);
+    let total = entry.sticker_urls.len();
+    let sticker_urls = entry
+        .sticker_urls
+        .into_iter()
+        .skip(offset)
+        .take(limit)
+        .collect();
 
-    // Get GIPHY API key from environment variables
-    let giphy_api_key = match std::env::var("GIPHY_API_KEY") {
-        Ok(key) => key,
-        Err(_) => {
-            log::error!("GIPHY_API_KEY is not set");
-            return HttpResponse::InternalServerError().json(serde_json::json!({
-                "error": "GIPHY_API_KEY is not set"
-            }));
+    HttpResponse::Ok().json(TrendingResponse {
+        sticker_urls,
+        provider: entry.provider,
+        fetched_at: entry.fetched_at,
+        stale,
+        rating,
+        limit,
+        offset,
+        total,
+    })
+}
+
+// Fetches trending stickers from Giphy and stores them in Redis. Used both on cache
+// misses and by the background pre-warm task started in init.rs.
+pub async fn refresh_trending(
+    db: &DatabaseConnection,
+    config: &TrendingConfig,
+    rating: &str,
+) -> Result<TrendingCacheEntry, String> {
+    let giphy_api_key = config
+        .giphy_api_key
+        .as_ref()
+        .ok_or_else(|| "GIPHY_API_KEY is not set".to_string())?;
+
+    // Skip if another worker is already refreshing this rating
+    let _lock = match db.try_lock_trending_refresh(rating, 30).await {
+        Ok(Some(lock)) => Some(lock),
+        Ok(None) => {
+            if let Ok(Some(entry)) = db.get_cached_trending(rating).await {
+                return Ok(entry);
+            }
+            None
+        }
+        Err(e) => {
+            log::warn!("Failed to take trending refresh lock: {}", e);
+            None
         }
     };
 
-    // Fetch trending stickers from Giphy API
-    let client = reqwest::Client::new();
+    let client = Client::new();
     let params = [
-        ("api_key", &giphy_api_key),
-        ("limit", &"9".to_string()),
-        ("rating", &"g".to_string()),
-        ("bundle", &"messaging_non_clips".to_string()),
+        ("api_key", giphy_api_key.to_string()),
+        ("limit", config.cache_size.to_string()),
+        ("rating", rating.to_string()),
+        ("bundle", "messaging_non_clips".to_string()),
     ];
     let url = "https://api.giphy.com/v1/stickers/trending";
 
-    match client.get(url).query(&params).send().await {
-        Ok(res) => {
-            if res.status().is_success() {
-                let data: Value = match res.json().await {
-                    Ok(data) => data,
-                    Err(e) => {
-                        log::error!("Failed to parse Giphy response: {}", e);
-                        return HttpResponse::InternalServerError().json(serde_json::json!({
-                            "error": "Failed to parse Giphy response"
-                        }));
-                    }
-                };
-                let sticker_urls: Vec<String> = data["data"]
-                    .as_array()
-                    .unwrap_or(&vec![])
-                    .iter()
-                    .filter_map(|item| item["images"]["original"]["url"].as_str().map(|s| s.to_string()))
-                    .collect();
-                HttpResponse::Ok().json(TrendingResponse {
-                    sticker_urls,
-                })
-            } else {
-                log::error!("Giphy API error: {}", res.status());
-                HttpResponse::InternalServerError().json(serde_json::json!({
-                    "error": "Failed to fetch stickers from Giphy"
-                }))
-            }
-        }
-        Err(e) => {
-            log::error!("Failed to fetch stickers: {}", e);
-            HttpResponse::InternalServerError().json(serde_json::json!({
-                "error": "Failed to fetch stickers"
-            }))
-        }
+    let result = client.get(url).query(&params).send().await;
+    if let Err(e) = db.record_upstream_call("giphy", matches!(&result, Ok(res) if res.status().is_success())).await {
+        log::error!("Failed to record upstream call: {}", e);
+    }
+    let res = result.map_err(|e| format!("Giphy request failed: {}", e))?;
+    if !res.status().is_success() {
+        return Err(format!("Giphy API error: {}", res.status()));
+    }
+    let data: Value = res
+        .json()
+        .await
+        .map_err(|e| format!("Failed to parse Giphy response: {}", e))?;
+    let sticker_urls: Vec<String> = data["data"]
+        .as_array()
+        .unwrap_or(&vec![])
+        .iter()
+        .filter_map(|item| item["images"]["original"]["url"].as_str().map(|s| s.to_string()))
+        .collect();
+    if sticker_urls.is_empty() {
+        return Err("Giphy returned no trending stickers".to_string());
+    }
+
+    let entry = TrendingCacheEntry {
+        provider: TRENDING_PROVIDER.to_string(),
+        rating: rating.to_string(),
+        fetched_at: Utc::now(),
+        sticker_urls,
+    };
+    if let Err(e) = db.cache_trending(&entry, config.stale_ttl_secs).await {
+        log::error!("Failed to cache trending stickers: {}", e);
     }
+    Ok(entry)
 }
 
 
@@ -425,19 +504,235 @@ async fn trending_dashboard(
 
 
 
-async fn save_interaction(db: &web::Data<crate::structs::database_structs::DatabaseConnection>, user_id: Uuid, input_text: &str, emotion: &str, sticker_url: &str) {
-    if let Err(e) = db.save_interaction(user_id, input_text, emotion, sticker_url).await {
-        log::error!("Failed to save interaction: {}", e);
+// Orders candidates using the user's sticker_metrics and favorites; a no-op when personalization is off
+// or the metrics can't be loaded.
+async fn personalize_stickers(db: &web::Data<DatabaseConnection>, user_id: Uuid, sticker_urls: Vec<String>, personalize: bool) -> Vec<String> {
+    if !personalize || sticker_urls.len() < 2 {
+        return sticker_urls;
     }
-    if let Err(e) = db.update_sticker_metrics(user_id, sticker_url).await {
-        log::error!("Failed to update sticker metrics: {}", e);
+    let metrics = match db.get_sticker_metrics(user_id, &sticker_urls).await {
+        Ok(metrics) => metrics,
+        Err(e) => {
+            log::error!("Failed to load sticker metrics for {}: {}", user_id, e);
+            return sticker_urls;
+        }
+    };
+    let favorites: HashSet<String> = match db.get_favorited_urls(user_id, &sticker_urls).await {
+        Ok(urls) => urls.into_iter().collect(),
+        Err(e) => {
+            log::error!("Failed to load favorites for {}: {}", user_id, e);
+            HashSet::new()
+        }
+    };
+    ranking::rerank(sticker_urls, &metrics, &favorites, Utc::now().naive_utc())
+}
+
+async fn record_impressions(db: &web::Data<DatabaseConnection>, user_id: Uuid, sticker_urls: &[String]) {
+    if let Err(e) = db.record_sticker_impressions(user_id, sticker_urls).await {
+        log::error!("Failed to record sticker impressions: {}", e);
+    }
+}
+
+async fn record_upstream(db: &web::Data<DatabaseConnection>, service: &str, result: &Result<reqwest::Response, reqwest::Error>) {
+    let success = matches!(result, Ok(res) if res.status().is_success());
+    if let Err(e) = db.record_upstream_call(service, success).await {
+        log::error!("Failed to record upstream call: {}", e);
+    }
+}
+
+async fn record_recommendation_stats(db: &web::Data<DatabaseConnection>, user_id: Uuid, emotion: &str, cache_hit: bool) {
+    if let Err(e) = db.record_recommendation_stats(user_id, emotion, cache_hit).await {
+        log::error!("Failed to record recommendation stats: {}", e);
+    }
+}
+
+// Persists the impressions, recommendation, interaction and rollups for a result. Impersonated
+// requests get an unsaved recommendation ID so support staff leave no trace in the user's data.
+async fn record_recommendation(
+    db: &web::Data<DatabaseConnection>,
+    auth_data: &AuthData,
+    user_id: Uuid,
+    input_text: &str,
+    emotion: &str,
+    sticker_urls: &[String],
+    cache_hit: bool,
+) -> Uuid {
+    if auth_data.impersonator.is_some() {
+        return Uuid::new_v4();
+    }
+    record_impressions(db, user_id, sticker_urls).await;
+    let recommendation_id = save_interaction(db, user_id, input_text, emotion, sticker_urls).await;
+    record_recommendation_stats(db, user_id, emotion, cache_hit).await;
+    recommendation_id
+}
+
+async fn save_interaction(db: &web::Data<DatabaseConnection>, user_id: Uuid, input_text: &str, emotion: &str, sticker_urls: &[String]) -> Uuid {
+    let recommendation_id = Uuid::new_v4();
+    if let Err(e) = db.save_recommendation(recommendation_id, user_id, emotion, sticker_urls).await {
+        log::error!("Failed to save recommendation: {}", e);
+        return recommendation_id;
+    }
+    if let Some(first_sticker) = sticker_urls.first() {
+        if let Err(e) = db.save_interaction(user_id, Some(recommendation_id), input_text, emotion, first_sticker).await {
+            log::error!("Failed to save interaction: {}", e);
+        }
     }
+    recommendation_id
 }
 
+#[utoipa::path(
+    post,
+    path = "/v1/sticker/feedback",
+    tag = "sticker",
+    request_body = FeedbackRequest,
+    responses(
+        (status = 200, description = "Feedback recorded", body = Object),
+        (status = 400, description = "Invalid fields, or the sticker was not part of this recommendation", body = ValidationErrorResponse),
+        (status = 404, description = "Recommendation not found", body = ErrorResponse),
+    )
+)]
+#[post("/feedback")]
+async fn sticker_feedback(
+    db: web::Data<DatabaseConnection>,
+    user: AuthUser,
+    req: ValidatedJson<FeedbackRequest>,
+) -> impl Responder {
+    let req = req.into_inner();
+    let user_id = user.id;
+
+    let recommendation = match db.get_recommendation(req.recommendation_id, user_id).await {
+        Ok(Some(recommendation)) => recommendation,
+        Ok(None) => {
+            return HttpResponse::NotFound().json(serde_json::json!({
+                "error": "Recommendation not found"
+            }));
+        }
+        Err(e) => {
+            log::error!("Failed to load recommendation {}: {}", req.recommendation_id, e);
+            return HttpResponse::InternalServerError().json(serde_json::json!({
+                "error": "Failed to record feedback"
+            }));
+        }
+    };
+    if !recommendation.sticker_urls.contains(&req.sticker_url) {
+        return HttpResponse::BadRequest().json(serde_json::json!({
+            "error": "Sticker was not part of this recommendation"
+        }));
+    }
+
+    let first_pick = match db.save_feedback(recommendation.id, user_id, &req.sticker_url, req.action).await {
+        Ok(first_pick) => first_pick,
+        Err(e) => {
+            log::error!("Failed to save feedback for recommendation {}: {}", recommendation.id, e);
+            return HttpResponse::InternalServerError().json(serde_json::json!({
+                "error": "Failed to record feedback"
+            }));
+        }
+    };
+    if first_pick {
+        if let Err(e) = db.update_sticker_metrics(user_id, &req.sticker_url).await {
+            log::error!("Failed to update sticker metrics: {}", e);
+        }
+        if let Err(e) = db.record_sticker_pick(&req.sticker_url).await {
+            log::error!("Failed to record sticker pick: {}", e);
+        }
+    }
+    if let Some(rating) = req.action.rating() {
+        if let Err(e) = db.rate_sticker(user_id, &req.sticker_url, rating).await {
+            log::error!("Failed to rate sticker: {}", e);
+        }
+    }
+
+    log::info!("User {} sent {} feedback for recommendation {}", user_id, req.action.as_str(), recommendation.id);
+    HttpResponse::Ok().json(serde_json::json!({
+        "recommendation_id": recommendation.id,
+        "action": req.action,
+    }))
+}
+
+#[derive(OpenApi)]
+#[openapi(paths(
+    find_sticker,
+    find_sticker_dashboard,
+    trending_dashboard,
+    sticker_feedback,
+))]
+pub struct ApiDoc;
+
 pub fn init_routes(cfg: &mut web::ServiceConfig) {
     cfg.service(find_sticker);
     cfg.service(find_sticker_dashboard);
     cfg.service(trending_dashboard);
+    cfg.service(sticker_feedback);
     
     
-}
\ No newline at end of file
+}
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::middleware::validation::json_config;
+    use crate::structs::database_structs::FeedbackAction;
+    use actix_web::http::StatusCode;
+    use actix_web::{App, HttpMessage};
+
+    // The database is unreachable, so feedback that passes validation answers 500
+    async fn feedback(body: serde_json::Value, authenticated: bool) -> StatusCode {
+        let app = actix_web::test::init_service(
+            App::new()
+                .app_data(json_config(4096))
+                .app_data(web::Data::new(DatabaseConnection::unreachable()))
+                .service(sticker_feedback),
+        )
+        .await;
+        let req = actix_web::test::TestRequest::post().uri("/feedback").set_json(body).to_request();
+        if authenticated {
+            req.extensions_mut().insert(AuthData::new(Uuid::new_v4().to_string(), "user".to_string()));
+        }
+        actix_web::test::call_service(&app, req).await.status()
+    }
+
+    fn body(action: &str, sticker_url: &str) -> serde_json::Value {
+        serde_json::json!({
+            "recommendation_id": Uuid::new_v4(),
+            "sticker_url": sticker_url,
+            "action": action,
+        })
+    }
+
+    #[actix_web::test]
+    async fn feedback_requires_a_user() {
+        assert_eq!(feedback(body("liked", "https://example.com/a.gif"), false).await, StatusCode::UNAUTHORIZED);
+    }
+
+    #[actix_web::test]
+    async fn feedback_rejects_bad_bodies() {
+        assert_eq!(feedback(body("loved", "https://example.com/a.gif"), true).await, StatusCode::BAD_REQUEST);
+        assert_eq!(feedback(body("clicked", ""), true).await, StatusCode::BAD_REQUEST);
+        assert_eq!(feedback(body("clicked", &"a".repeat(2049)), true).await, StatusCode::BAD_REQUEST);
+        assert_eq!(
+            feedback(serde_json::json!({ "sticker_url": "https://example.com/a.gif", "action": "clicked" }), true).await,
+            StatusCode::BAD_REQUEST
+        );
+    }
+
+    #[actix_web::test]
+    async fn every_action_reaches_the_recommendation_lookup() {
+        for action in ["shown", "clicked", "copied", "sent", "liked", "disliked"] {
+            assert_eq!(
+                feedback(body(action, "https://example.com/a.gif"), true).await,
+                StatusCode::INTERNAL_SERVER_ERROR,
+                "action {}",
+                action
+            );
+        }
+    }
+
+    #[test]
+    fn only_likes_and_dislikes_carry_a_rating() {
+        assert_eq!(FeedbackAction::Liked.rating(), Some(1));
+        assert_eq!(FeedbackAction::Disliked.rating(), Some(-1));
+        for action in [FeedbackAction::Shown, FeedbackAction::Clicked, FeedbackAction::Copied, FeedbackAction::Sent] {
+            assert_eq!(action.rating(), None);
+        }
+    }
+}
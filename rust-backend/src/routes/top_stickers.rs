@@ -1,42 +1,78 @@
-use actix_web::{post, web, HttpMessage, HttpRequest, HttpResponse, Responder};
-use crate::structs::database_structs::{DatabaseConnection, TopStickerRequest};
-use crate::middleware::auth::AuthData;
-use uuid::Uuid;
+use actix_web::{get, web, HttpResponse, Responder};
+use crate::structs::database_structs::{DatabaseConnection, TopSticker, TopStickerRequest, UserAnalytics};
+use crate::middleware::auth::AuthUser;
+use utoipa::OpenApi;
 
 
-#[post("/top-stickers")]
+#[utoipa::path(
+    get,
+    path = "/v1/user/top-stickers",
+    tag = "user",
+    params(TopStickerRequest),
+    responses((status = 200, description = "The caller's most picked stickers", body = Vec<TopSticker>))
+)]
+#[get("/top-stickers")]
 pub async fn get_top_stickers(
     db: web::Data<DatabaseConnection>,
-    req: HttpRequest,
-    payload: web::Json<TopStickerRequest>,
+    user: AuthUser,
+    payload: web::Query<TopStickerRequest>,
 ) -> impl Responder {
-    let extensions = req.extensions();
-    let auth_data = match extensions.get::<AuthData>() {
-        Some(data) => data.clone(),
-        None => {
-            log::error!("Auth data not found in request extensions");
-            return HttpResponse::Unauthorized().body("Auth data not found");
-        }
-    };
+    let user_id = user.id;
 
-    let user_id = match Uuid::parse_str(&auth_data.id) {
-        Ok(id) => id,
+    let limit = payload.limit.unwrap_or(4).clamp(1, 50);
+    match db.get_top_stickers(user_id, payload.window.since(), limit).await {
+        Ok(top_stickers) => HttpResponse::Ok().json(top_stickers),
         Err(e) => {
-            log::error!("Invalid user ID format: {}", e);
-            return HttpResponse::BadRequest().body("Invalid user ID format");
+            log::error!("Failed to fetch top stickers for user {}: {}", user_id, e);
+            HttpResponse::InternalServerError().body("Failed to fetch top stickers")
         }
-    };
+    }
+}
 
+#[utoipa::path(
+    get,
+    path = "/v1/user/analytics",
+    tag = "user",
+    params(TopStickerRequest),
+    responses((status = 200, description = "Usage summary for the window", body = UserAnalytics))
+)]
+#[get("/analytics")]
+pub async fn get_analytics(
+    db: web::Data<DatabaseConnection>,
+    user: AuthUser,
+    payload: web::Query<TopStickerRequest>,
+) -> impl Responder {
+    let user_id = user.id;
 
-    match db.get_top_stickers(user_id).await {
-        Ok(top_stickers) => HttpResponse::Ok().json(top_stickers),
+    let limit = payload.limit.unwrap_or(10).clamp(1, 50);
+    let since = payload.window.since();
+    let analytics = async {
+        let top_stickers = db.get_top_stickers(user_id, since, limit).await?;
+        let top_emotions = db.get_top_emotions(user_id, since, limit).await?;
+        let daily_activity = db.get_daily_activity(user_id, since).await?;
+        Ok::<_, sqlx::Error>(UserAnalytics {
+            window: payload.window,
+            total_interactions: daily_activity.iter().map(|day| day.count).sum(),
+            top_stickers,
+            top_emotions,
+            daily_activity,
+        })
+    };
+
+    match analytics.await {
+        Ok(analytics) => HttpResponse::Ok().json(analytics),
         Err(e) => {
-            log::error!("Failed to fetch top stickers for user {} (ID: {}): {}", payload.username, user_id, e);
-            HttpResponse::InternalServerError().body("Failed to fetch top stickers")
+            log::error!("Failed to build analytics for user {}: {}", user_id, e);
+            HttpResponse::InternalServerError().body("Failed to fetch analytics")
         }
     }
 }
 
+#[derive(OpenApi)]
+#[openapi(paths(get_top_stickers, get_analytics))]
+pub struct ApiDoc;
+
 pub fn init_routes(cfg: &mut web::ServiceConfig) {
     cfg.service(get_top_stickers);
-}
\ No newline at end of file
+    cfg.service(get_analytics);
+}
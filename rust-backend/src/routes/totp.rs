@@ -0,0 +1,435 @@
+use crate::configs::totp::TotpConfig;
+use crate::middleware::auth::{AuthConfig, AuthUser};
+use crate::middleware::totp::{
+    generate_recovery_codes, generate_secret, hash_recovery_code, looks_like_totp, otpauth_uri, verify_code,
+};
+use crate::routes::audit::{self, audit_event};
+use crate::routes::auth::{start_admin_session, start_user_session};
+use crate::structs::database_structs::{
+    AccountKind, DatabaseConnection, MfaChallenge, MfaLoginRequest, TotpCodeRequest, TotpState, UserStatus,
+};
+use actix_web::error::Error as ActixError;
+use actix_web::{get, post, web, HttpRequest, HttpResponse};
+use base64::engine::general_purpose::URL_SAFE_NO_PAD;
+use base64::Engine;
+use chrono::Utc;
+use rand::rngs::OsRng;
+use rand::RngCore;
+use serde_json::json;
+use uuid::Uuid;
+use crate::routes::openapi::{ErrorResponse, SessionResponse};
+use utoipa::OpenApi;
+
+fn internal(context: &'static str) -> impl Fn(sqlx::Error) -> ActixError {
+    move |e| {
+        log::error!("{}: {}", context, e);
+        actix_web::error::ErrorInternalServerError(json!({ "error": context }))
+    }
+}
+
+// Parks a login that passed its first factor until the second one is checked.
+// Returns the token the client sends back with the code.
+pub async fn start_mfa_challenge(
+    db: &DatabaseConnection,
+    totp_config: &TotpConfig,
+    challenge: MfaChallenge,
+) -> Result<String, ActixError> {
+    let mut bytes = [0u8; 32];
+    OsRng.fill_bytes(&mut bytes);
+    let token = URL_SAFE_NO_PAD.encode(bytes);
+    db.save_mfa_challenge(&token, &challenge, totp_config.challenge_ttl_secs)
+        .await
+        .map_err(|e| {
+            log::error!("Failed to save MFA challenge: {}", e);
+            actix_web::error::ErrorInternalServerError(json!({ "error": "Failed to start login" }))
+        })?;
+    Ok(token)
+}
+
+// Stores a fresh pending secret and returns (secret, otpauth URI)
+pub async fn begin_enrollment(
+    db: &DatabaseConnection,
+    totp_config: &TotpConfig,
+    kind: AccountKind,
+    id: Uuid,
+    account_name: &str,
+) -> Result<Option<(String, String)>, sqlx::Error> {
+    let secret = generate_secret();
+    if !db.set_pending_totp_secret(kind, id, &secret).await? {
+        return Ok(None);
+    }
+    let uri = otpauth_uri(&totp_config.issuer, account_name, &secret);
+    Ok(Some((secret, uri)))
+}
+
+// Checks a TOTP code (once per time step) or, if allowed, an unused recovery code.
+// Returns which one matched.
+async fn check_second_factor(
+    db: &DatabaseConnection,
+    kind: AccountKind,
+    id: Uuid,
+    state: &TotpState,
+    code: &str,
+    allow_recovery: bool,
+) -> Result<Option<&'static str>, sqlx::Error> {
+    let Some(secret) = state.totp_secret.as_deref() else {
+        return Ok(None);
+    };
+    if looks_like_totp(code) {
+        return match verify_code(secret, code, Utc::now().timestamp()) {
+            Some(step) if db.consume_totp_step(kind, id, step).await? => Ok(Some("totp")),
+            _ => Ok(None),
+        };
+    }
+    if allow_recovery && db.consume_recovery_code(kind, id, &hash_recovery_code(code)).await? {
+        return Ok(Some("recovery_code"));
+    }
+    Ok(None)
+}
+
+// Second step of a login: exchanges the MFA token and a code for a session token.
+// For admins enrolling on first login the code confirms the new secret instead.
+#[utoipa::path(
+    post,
+    path = "/v1/auth/login/mfa",
+    tag = "auth",
+    security(()),
+    request_body = MfaLoginRequest,
+    responses(
+        (status = 200, description = "Session token; admins enrolling also get `recovery_codes`", body = SessionResponse),
+        (status = 401, description = "Invalid code, or the login expired", body = ErrorResponse),
+        (status = 429, description = "Too many wrong codes", body = ErrorResponse),
+    )
+)]
+#[post("/auth/login/mfa")]
+async fn login_mfa(
+    db: web::Data<DatabaseConnection>,
+    auth_config: web::Data<AuthConfig>,
+    totp_config: web::Data<TotpConfig>,
+    http_req: HttpRequest,
+    req: web::Json<MfaLoginRequest>,
+) -> Result<HttpResponse, ActixError> {
+    let expired = || actix_web::error::ErrorUnauthorized(json!({ "error": "Login expired, please sign in again" }));
+    let challenge = db
+        .get_mfa_challenge(&req.mfa_token)
+        .await
+        .map_err(|e| {
+            log::error!("Failed to read MFA challenge: {}", e);
+            actix_web::error::ErrorInternalServerError(json!({ "error": "Login failed" }))
+        })?
+        .ok_or_else(expired)?;
+
+    let attempts = db
+        .count_mfa_attempt(&req.mfa_token, totp_config.challenge_ttl_secs)
+        .await
+        .map_err(|e| {
+            log::error!("Failed to count MFA attempt: {}", e);
+            actix_web::error::ErrorInternalServerError(json!({ "error": "Login failed" }))
+        })?;
+    if attempts > totp_config.max_attempts {
+        if let Err(e) = db.delete_mfa_challenge(&req.mfa_token).await {
+            log::error!("Failed to drop MFA challenge: {}", e);
+        }
+        return Err(actix_web::error::ErrorTooManyRequests(json!({
+            "error": "Too many attempts, please sign in again"
+        })));
+    }
+
+    let (kind, id) = (challenge.kind, challenge.id);
+    let state = db
+        .get_totp_state(kind, id)
+        .await
+        .map_err(internal("Failed to check code"))?
+        .filter(|state| state.totp_enabled != challenge.enrolling)
+        .ok_or_else(expired)?;
+
+    let mut recovery_codes = None;
+    let method = if challenge.enrolling {
+        let secret = state.totp_secret.as_deref().unwrap_or_default();
+        match verify_code(secret, &req.code, Utc::now().timestamp()) {
+            Some(step) => {
+                let (codes, hashes) = generate_recovery_codes();
+                if !db.enable_totp(kind, id, step, &hashes).await.map_err(internal("Failed to enable 2FA"))? {
+                    return Err(expired());
+                }
+                recovery_codes = Some(codes);
+                Some("totp_enrollment")
+            }
+            None => None,
+        }
+    } else {
+        check_second_factor(&db, kind, id, &state, &req.code, true)
+            .await
+            .map_err(internal("Failed to check code"))?
+    };
+
+    let Some(method) = method else {
+        log::warn!("MFA failed for {} {}", kind.as_str(), id);
+        audit::record(
+            &db,
+            audit_event(&http_req, "auth.mfa_failed")
+                .target(kind.as_str(), id)
+                .diff(json!({ "attempt": attempts })),
+        )
+        .await;
+        return Err(actix_web::error::ErrorUnauthorized(json!({ "error": "Invalid code" })));
+    };
+    if let Err(e) = db.delete_mfa_challenge(&req.mfa_token).await {
+        log::error!("Failed to drop MFA challenge: {}", e);
+    }
+
+    let (token, username, action) = match kind {
+        AccountKind::User => {
+            let user = db
+                .get_user_by_id(id)
+                .await
+                .map_err(internal("Failed to fetch user"))?
+                .filter(|user| UserStatus::parse(&user.status) == Some(UserStatus::Active))
+                .ok_or_else(|| actix_web::error::ErrorForbidden(json!({ "error": "Account is not active" })))?;
+            let token = start_user_session(&db, &auth_config, user.id).await?;
+            (token, user.username, "auth.login")
+        }
+        AccountKind::Admin => {
+            let admin = db
+                .get_admin_by_id(id)
+                .await
+                .map_err(internal("Failed to fetch admin"))?
+                .ok_or_else(expired)?;
+            let token = start_admin_session(&db, &auth_config, admin.id).await?;
+            (token, admin.username, "auth.admin_login")
+        }
+    };
+    audit::record(
+        &db,
+        audit_event(&http_req, action)
+            .actor(kind.as_str(), id)
+            .target(kind.as_str(), id)
+            .diff(json!({ "mfa": method, "provider": challenge.provider })),
+    )
+    .await;
+
+    let mut body = json!({ "token": token, "username": username });
+    if let Some(codes) = recovery_codes {
+        body["recovery_codes"] = json!(codes);
+    }
+    Ok(HttpResponse::Ok().json(body))
+}
+
+#[utoipa::path(
+    get,
+    path = "/v1/user/2fa",
+    tag = "user",
+    responses((status = 200, description = "`enabled`, `pending` and `recovery_codes_remaining`", body = Object))
+)]
+#[get("/2fa")]
+async fn get_2fa_status(
+    db: web::Data<DatabaseConnection>,
+    user: AuthUser,
+) -> Result<HttpResponse, ActixError> {
+    let user_id = user.id;
+    let state = db
+        .get_totp_state(AccountKind::User, user_id)
+        .await
+        .map_err(internal("Failed to fetch 2FA status"))?
+        .ok_or_else(|| actix_web::error::ErrorNotFound(json!({ "error": "User not found" })))?;
+    let remaining = db
+        .count_recovery_codes(AccountKind::User, user_id)
+        .await
+        .map_err(internal("Failed to fetch 2FA status"))?;
+    Ok(HttpResponse::Ok().json(json!({
+        "enabled": state.totp_enabled,
+        "pending": !state.totp_enabled && state.totp_secret.is_some(),
+        "recovery_codes_remaining": remaining,
+    })))
+}
+
+// Starts (or restarts) enrollment. 2FA is not on until /2fa/verify confirms a code.
+#[utoipa::path(
+    post,
+    path = "/v1/user/2fa/enroll",
+    tag = "user",
+    responses(
+        (status = 200, description = "`secret` and `otpauth_uri` for the authenticator app", body = Object),
+        (status = 409, description = "2FA is already enabled", body = ErrorResponse),
+    )
+)]
+#[post("/2fa/enroll")]
+async fn enroll_2fa(
+    db: web::Data<DatabaseConnection>,
+    totp_config: web::Data<TotpConfig>,
+    user: AuthUser,
+) -> Result<HttpResponse, ActixError> {
+    let user_id = user.id;
+    let user = db
+        .get_user_by_id(user_id)
+        .await
+        .map_err(internal("Failed to start enrollment"))?
+        .ok_or_else(|| actix_web::error::ErrorNotFound(json!({ "error": "User not found" })))?;
+    match begin_enrollment(&db, &totp_config, AccountKind::User, user_id, &user.username)
+        .await
+        .map_err(internal("Failed to start enrollment"))?
+    {
+        Some((secret, uri)) => Ok(HttpResponse::Ok().json(json!({ "secret": secret, "otpauth_uri": uri }))),
+        None => Err(actix_web::error::ErrorConflict(json!({ "error": "2FA is already enabled" }))),
+    }
+}
+
+#[utoipa::path(
+    post,
+    path = "/v1/user/2fa/verify",
+    tag = "user",
+    request_body = TotpCodeRequest,
+    responses(
+        (status = 200, description = "2FA enabled; `recovery_codes` are only shown once", body = Object),
+        (status = 400, description = "Invalid code, or enrollment not started", body = ErrorResponse),
+        (status = 409, description = "2FA is already enabled", body = ErrorResponse),
+    )
+)]
+#[post("/2fa/verify")]
+async fn verify_2fa(
+    db: web::Data<DatabaseConnection>,
+    user: AuthUser,
+    http_req: HttpRequest,
+    req: web::Json<TotpCodeRequest>,
+) -> Result<HttpResponse, ActixError> {
+    let user_id = user.id;
+    let state = db
+        .get_totp_state(AccountKind::User, user_id)
+        .await
+        .map_err(internal("Failed to enable 2FA"))?
+        .ok_or_else(|| actix_web::error::ErrorNotFound(json!({ "error": "User not found" })))?;
+    if state.totp_enabled {
+        return Err(actix_web::error::ErrorConflict(json!({ "error": "2FA is already enabled" })));
+    }
+    let Some(secret) = state.totp_secret.as_deref() else {
+        return Err(actix_web::error::ErrorBadRequest(json!({ "error": "Start enrollment first" })));
+    };
+    let Some(step) = verify_code(secret, &req.code, Utc::now().timestamp()) else {
+        return Err(actix_web::error::ErrorBadRequest(json!({ "error": "Invalid code" })));
+    };
+    let (codes, hashes) = generate_recovery_codes();
+    if !db
+        .enable_totp(AccountKind::User, user_id, step, &hashes)
+        .await
+        .map_err(internal("Failed to enable 2FA"))?
+    {
+        return Err(actix_web::error::ErrorConflict(json!({ "error": "2FA is already enabled" })));
+    }
+    audit::record(
+        &db,
+        audit_event(&http_req, "user.enable_2fa")
+            .actor("user", user_id)
+            .target("user", user_id),
+    )
+    .await;
+    Ok(HttpResponse::Ok().json(json!({ "enabled": true, "recovery_codes": codes })))
+}
+
+#[utoipa::path(
+    post,
+    path = "/v1/user/2fa/disable",
+    tag = "user",
+    request_body = TotpCodeRequest,
+    responses(
+        (status = 200, description = "2FA disabled", body = Object),
+        (status = 400, description = "Invalid code, or 2FA is not enabled", body = ErrorResponse),
+    )
+)]
+#[post("/2fa/disable")]
+async fn disable_2fa(
+    db: web::Data<DatabaseConnection>,
+    user: AuthUser,
+    http_req: HttpRequest,
+    req: web::Json<TotpCodeRequest>,
+) -> Result<HttpResponse, ActixError> {
+    let user_id = user.id;
+    let state = db
+        .get_totp_state(AccountKind::User, user_id)
+        .await
+        .map_err(internal("Failed to disable 2FA"))?
+        .filter(|state| state.totp_enabled)
+        .ok_or_else(|| actix_web::error::ErrorBadRequest(json!({ "error": "2FA is not enabled" })))?;
+    let method = check_second_factor(&db, AccountKind::User, user_id, &state, &req.code, true)
+        .await
+        .map_err(internal("Failed to disable 2FA"))?
+        .ok_or_else(|| actix_web::error::ErrorBadRequest(json!({ "error": "Invalid code" })))?;
+    db.reset_totp(AccountKind::User, user_id)
+        .await
+        .map_err(internal("Failed to disable 2FA"))?;
+    audit::record(
+        &db,
+        audit_event(&http_req, "user.disable_2fa")
+            .actor("user", user_id)
+            .target("user", user_id)
+            .diff(json!({ "mfa": method })),
+    )
+    .await;
+    Ok(HttpResponse::Ok().json(json!({ "enabled": false })))
+}
+
+// Replaces all recovery codes; needs a current TOTP code
+#[utoipa::path(
+    post,
+    path = "/v1/user/2fa/recovery-codes",
+    tag = "user",
+    request_body = TotpCodeRequest,
+    responses(
+        (status = 200, description = "New `recovery_codes`; the old ones stop working", body = Object),
+        (status = 400, description = "Invalid code, or 2FA is not enabled", body = ErrorResponse),
+    )
+)]
+#[post("/2fa/recovery-codes")]
+async fn regenerate_recovery_codes(
+    db: web::Data<DatabaseConnection>,
+    user: AuthUser,
+    http_req: HttpRequest,
+    req: web::Json<TotpCodeRequest>,
+) -> Result<HttpResponse, ActixError> {
+    let user_id = user.id;
+    let state = db
+        .get_totp_state(AccountKind::User, user_id)
+        .await
+        .map_err(internal("Failed to regenerate recovery codes"))?
+        .filter(|state| state.totp_enabled)
+        .ok_or_else(|| actix_web::error::ErrorBadRequest(json!({ "error": "2FA is not enabled" })))?;
+    check_second_factor(&db, AccountKind::User, user_id, &state, &req.code, false)
+        .await
+        .map_err(internal("Failed to regenerate recovery codes"))?
+        .ok_or_else(|| actix_web::error::ErrorBadRequest(json!({ "error": "Invalid code" })))?;
+    let (codes, hashes) = generate_recovery_codes();
+    db.replace_recovery_codes(AccountKind::User, user_id, &hashes)
+        .await
+        .map_err(internal("Failed to regenerate recovery codes"))?;
+    audit::record(
+        &db,
+        audit_event(&http_req, "user.regenerate_recovery_codes")
+            .actor("user", user_id)
+            .target("user", user_id),
+    )
+    .await;
+    Ok(HttpResponse::Ok().json(json!({ "recovery_codes": codes })))
+}
+
+#[derive(OpenApi)]
+#[openapi(paths(
+    login_mfa,
+    get_2fa_status,
+    enroll_2fa,
+    verify_2fa,
+    disable_2fa,
+    regenerate_recovery_codes,
+))]
+pub struct ApiDoc;
+
+pub fn init_routes(cfg: &mut web::ServiceConfig) {
+    cfg.service(login_mfa);
+}
+
+// Mounted under the authenticated /user scope
+pub fn init_user_routes(cfg: &mut web::ServiceConfig) {
+    cfg.service(get_2fa_status);
+    cfg.service(enroll_2fa);
+    cfg.service(verify_2fa);
+    cfg.service(disable_2fa);
+    cfg.service(regenerate_recovery_codes);
+}
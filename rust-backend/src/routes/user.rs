@@ -1,22 +1,49 @@
+use crate::configs::accounts::AccountConfig;
 use crate::structs::database_structs::{
-    DatabaseConnection, ManagementRequest, UpdateUsernameRequest,
+    DatabaseConnection, ExportQuery, ManagementRequest, UpdatePreferencesRequest,
+    UpdateUsernameRequest, UserExport, UserPreferences
 };
-use crate::middleware::auth::AuthData; // Import AuthData from the middleware module
+use crate::middleware::validation::ValidatedJson;
+use crate::middleware::auth::AuthUser;
+use crate::routes::audit::{self, audit_event};
 use actix_web::error::Error as ActixError;
-use actix_web::{delete, put, web, HttpResponse};
-use uuid::Uuid;
+use actix_web::http::header;
+use actix_web::{delete, get, post, put, web, HttpRequest, HttpResponse};
+use actix_web_httpauth::extractors::bearer::BearerAuth;
+use chrono::{Duration, Utc};
+use std::io::{Cursor, Write};
+use zip::write::SimpleFileOptions;
+use zip::ZipWriter;
+use crate::routes::openapi::{ErrorResponse, ValidationErrorResponse};
+use utoipa::OpenApi;
 
+#[utoipa::path(
+    put,
+    path = "/v1/user/update-username",
+    tag = "user",
+    request_body = UpdateUsernameRequest,
+    responses(
+        (status = 200, description = "The new username as `{\"username\": ...}`", body = Object),
+        (status = 400, description = "Invalid or taken username", body = ValidationErrorResponse),
+    )
+)]
 #[put("/update-username")]
 async fn update_username(
     db: web::Data<DatabaseConnection>,
-    auth_data: web::ReqData<AuthData>,
-    req: web::Json<UpdateUsernameRequest>,
+    user: AuthUser,
+    http_req: HttpRequest,
+    req: ValidatedJson<UpdateUsernameRequest>,
 ) -> Result<HttpResponse, ActixError> {
 println!("this is the request: {:?}", req);
-    let user_id = Uuid::parse_str(&auth_data.id).map_err(|_| {
-        log::error!("Invalid user ID in token");
-        actix_web::error::ErrorBadRequest("Invalid user ID")
-    })?;
+    let user_id = user.id;
+    let old_username = match db.get_user_by_id(user_id).await {
+        Ok(Some(user)) => user.username,
+        Ok(None) => return Err(actix_web::error::ErrorNotFound("User not found")),
+        Err(e) => {
+            log::error!("Failed to fetch user {}: {}", user_id, e);
+            return Err(actix_web::error::ErrorInternalServerError("Failed to update username"));
+        }
+    };
     match db
         .update_user(
             user_id,
@@ -27,7 +54,17 @@ println!("this is the request: {:?}", req);
         )
         .await
     {
-        Ok(user) => Ok(HttpResponse::Ok().json(serde_json::json!({ "username": user.username }))),
+        Ok(user) => {
+            audit::record(
+                &db,
+                audit_event(&http_req, "user.update_username")
+                    .actor("user", user_id)
+                    .target("user", user_id)
+                    .diff(serde_json::json!({ "username": audit::change(old_username, user.username.clone()) })),
+            )
+            .await;
+            Ok(HttpResponse::Ok().json(serde_json::json!({ "username": user.username })))
+        }
         Err(e) => {
             log::error!("Failed to update username for user {}: {}", user_id, e);
             Err(actix_web::error::ErrorBadRequest(
@@ -37,25 +74,293 @@ println!("this is the request: {:?}", req);
     }
 }
 
+// Same model as an admin delete: signed out and blocked now, erased once the restore window passes
+#[utoipa::path(
+    delete,
+    path = "/v1/user/delete",
+    tag = "user",
+    responses(
+        (status = 200, description = "Soft-deleted; `restorable_until` says when the account is erased", body = Object),
+        (status = 404, description = "User not found"),
+    )
+)]
 #[delete("/delete")]
 async fn delete_user(
     db: web::Data<DatabaseConnection>,
-    user_id: web::ReqData<String>,
+    account_config: web::Data<AccountConfig>,
+    user: AuthUser,
+    http_req: HttpRequest,
+) -> Result<HttpResponse, ActixError> {
+    let user_id = user.id;
+    match db.soft_delete_user(user_id, Some("Deleted by user")).await {
+        Ok(Some(previous_status)) => {
+            log::info!("User {} deleted their account", user_id);
+            audit::record(
+                &db,
+                audit_event(&http_req, "user.delete")
+                    .actor("user", user_id)
+                    .target("user", user_id)
+                    .diff(serde_json::json!({ "status": audit::change(previous_status, "deleted") })),
+            )
+            .await;
+            let purge_after = Utc::now() + Duration::days(account_config.restore_window_days);
+            Ok(HttpResponse::Ok().json(serde_json::json!({
+                "id": user_id,
+                "status": "deleted",
+                "restorable_until": purge_after,
+            })))
+        }
+        Ok(None) => Err(actix_web::error::ErrorNotFound("User not found")),
+        Err(e) => {
+            log::error!("Failed to delete user {}: {}", user_id, e);
+            Err(actix_web::error::ErrorInternalServerError("Failed to delete user"))
+        }
+    }
+}
+
+// Ends the session behind the bearer token. API keys can't call this; they revoke themselves instead.
+#[utoipa::path(
+    post,
+    path = "/v1/user/logout",
+    tag = "user",
+    responses(
+        (status = 204, description = "Session ended; the token no longer works"),
+        (status = 401, description = "Session already ended"),
+    )
+)]
+#[post("/logout")]
+async fn logout(
+    db: web::Data<DatabaseConnection>,
+    user: AuthUser,
+    credentials: BearerAuth,
+) -> Result<HttpResponse, ActixError> {
+    let user_id = user.id;
+    match db.delete_session(user_id, credentials.token()).await {
+        Ok(true) => Ok(HttpResponse::NoContent().finish()),
+        Ok(false) => Err(actix_web::error::ErrorUnauthorized("Invalid or expired session")),
+        Err(e) => {
+            log::error!("Failed to end session for user {}: {}", user_id, e);
+            Err(actix_web::error::ErrorInternalServerError("Failed to log out"))
+        }
+    }
+}
+
+#[utoipa::path(
+    get,
+    path = "/v1/user/export",
+    tag = "user",
+    params(ExportQuery),
+    responses(
+        (status = 200, description = "Everything stored about the caller, as JSON or a zip with one file per section",
+            content((UserExport = "application/json"), (Vec<u8> = "application/zip"))),
+        (status = 400, description = "format must be json or zip"),
+        (status = 429, description = "Export rate limit exceeded", body = ErrorResponse),
+    )
+)]
+#[get("/export")]
+async fn export_user_data(
+    db: web::Data<DatabaseConnection>,
+    user: AuthUser,
+    query: web::Query<ExportQuery>,
 ) -> Result<HttpResponse, ActixError> {
-    let user_id = Uuid::parse_str(&user_id).map_err(|_| {
-        log::error!("Invalid user ID in token");
-        actix_web::error::ErrorBadRequest("Invalid user ID")
-    })?;
-    db.delete_user(user_id)
+    let user_id = user.id;
+    let export = match db.export_user_data(user_id).await {
+        Ok(Some(export)) => export,
+        Ok(None) => return Err(actix_web::error::ErrorNotFound("User not found")),
+        Err(e) => {
+            log::error!("Failed to export data for user {}: {}", user_id, e);
+            return Err(actix_web::error::ErrorInternalServerError("Failed to export data"));
+        }
+    };
+    log::info!("User {} exported their data", user_id);
+
+    match query.format.as_deref().unwrap_or("json") {
+        "json" => Ok(HttpResponse::Ok()
+            .insert_header((
+                header::CONTENT_DISPOSITION,
+                "attachment; filename=\"sticker-export.json\"",
+            ))
+            .json(export)),
+        "zip" => {
+            let archive = build_export_zip(&export).map_err(|e| {
+                log::error!("Failed to build export archive for user {}: {}", user_id, e);
+                actix_web::error::ErrorInternalServerError("Failed to export data")
+            })?;
+            Ok(HttpResponse::Ok()
+                .content_type("application/zip")
+                .insert_header((
+                    header::CONTENT_DISPOSITION,
+                    "attachment; filename=\"sticker-export.zip\"",
+                ))
+                .body(archive))
+        }
+        _ => Err(actix_web::error::ErrorBadRequest("format must be json or zip")),
+    }
+}
+
+// One JSON file per section of the export
+fn build_export_zip(export: &UserExport) -> Result<Vec<u8>, Box<dyn std::error::Error>> {
+    let mut zip = ZipWriter::new(Cursor::new(Vec::new()));
+    let options = SimpleFileOptions::default();
+    let sections = [
+        ("profile.json", serde_json::to_vec_pretty(&export.profile)?),
+        ("history.json", serde_json::to_vec_pretty(&export.history)?),
+        ("sticker_metrics.json", serde_json::to_vec_pretty(&export.sticker_metrics)?),
+        ("sessions.json", serde_json::to_vec_pretty(&export.sessions)?),
+        ("favorites.json", serde_json::to_vec_pretty(&export.favorites)?),
+        ("collections.json", serde_json::to_vec_pretty(&export.collections)?),
+        ("feedback.json", serde_json::to_vec_pretty(&export.feedback)?),
+        ("api_keys.json", serde_json::to_vec_pretty(&export.api_keys)?),
+        ("identities.json", serde_json::to_vec_pretty(&export.identities)?),
+        ("audit_events.json", serde_json::to_vec_pretty(&export.audit_events)?),
+    ];
+    for (name, contents) in sections {
+        zip.start_file(name, options)?;
+        zip.write_all(&contents)?;
+    }
+    Ok(zip.finish()?.into_inner())
+}
+
+#[utoipa::path(
+    get,
+    path = "/v1/user/preferences",
+    tag = "user",
+    responses((status = 200, description = "Privacy preferences", body = UserPreferences))
+)]
+#[get("/preferences")]
+async fn get_preferences(
+    db: web::Data<DatabaseConnection>,
+    user: AuthUser,
+) -> Result<HttpResponse, ActixError> {
+    let user_id = user.id;
+    match db.get_preferences(user_id).await {
+        Ok(Some(preferences)) => Ok(HttpResponse::Ok().json(preferences)),
+        Ok(None) => Err(actix_web::error::ErrorNotFound("User not found")),
+        Err(e) => {
+            log::error!("Failed to fetch preferences for user {}: {}", user_id, e);
+            Err(actix_web::error::ErrorInternalServerError("Failed to fetch preferences"))
+        }
+    }
+}
+
+#[utoipa::path(
+    put,
+    path = "/v1/user/preferences",
+    tag = "user",
+    request_body = UpdatePreferencesRequest,
+    responses((status = 200, description = "Updated preferences", body = UserPreferences))
+)]
+#[put("/preferences")]
+async fn update_preferences(
+    db: web::Data<DatabaseConnection>,
+    user: AuthUser,
+    req: web::Json<UpdatePreferencesRequest>,
+) -> Result<HttpResponse, ActixError> {
+    let user_id = user.id;
+    db.update_preferences(user_id, req.store_input_text)
         .await
-        .map(|_| HttpResponse::Ok().body("User deleted"))
+        .map(|preferences| HttpResponse::Ok().json(preferences))
         .map_err(|e| {
-            log::error!("Failed to delete user {}: {}", user_id, e);
-            actix_web::error::ErrorInternalServerError("Failed to delete user")
+            log::error!("Failed to update preferences for user {}: {}", user_id, e);
+            actix_web::error::ErrorInternalServerError("Failed to update preferences")
         })
 }
 
+#[derive(OpenApi)]
+#[openapi(paths(
+    update_username,
+    delete_user,
+    logout,
+    export_user_data,
+    get_preferences,
+    update_preferences,
+))]
+pub struct ApiDoc;
+
 pub fn init_routes(cfg: &mut web::ServiceConfig) {
     cfg.service(update_username);
     cfg.service(delete_user);
-}
\ No newline at end of file
+    cfg.service(logout);
+    cfg.service(export_user_data);
+    cfg.service(get_preferences);
+    cfg.service(update_preferences);
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::structs::database_structs::{ApiKey, ExportProfile, OidcIdentity};
+    use chrono::Utc;
+    use std::io::Read;
+    use uuid::Uuid;
+
+    fn export() -> UserExport {
+        let now = Utc::now().naive_utc();
+        UserExport {
+            exported_at: Utc::now(),
+            profile: ExportProfile {
+                id: Uuid::new_v4(),
+                username: "alice".to_string(),
+                store_input_text: true,
+                totp_enabled: true,
+            },
+            history: vec![],
+            sticker_metrics: vec![],
+            sessions: vec![],
+            favorites: vec![],
+            collections: vec![],
+            feedback: vec![],
+            api_keys: vec![ApiKey {
+                id: Uuid::new_v4(),
+                name: "extension".to_string(),
+                prefix: "stk_abcd".to_string(),
+                scopes: vec!["sticker:find".to_string()],
+                created_at: now,
+                last_used_at: None,
+                expires_at: None,
+                revoked_at: None,
+            }],
+            identities: vec![OidcIdentity {
+                provider: "google".to_string(),
+                subject: "1234".to_string(),
+                email: Some("alice@example.com".to_string()),
+                created_at: now,
+                last_login_at: None,
+            }],
+            audit_events: vec![],
+        }
+    }
+
+    #[test]
+    fn zip_has_a_file_for_every_json_section() {
+        let export = export();
+        let json = serde_json::to_value(&export).unwrap();
+        let mut sections: Vec<String> = json
+            .as_object()
+            .unwrap()
+            .keys()
+            .filter(|key| *key != "exported_at")
+            .map(|key| format!("{}.json", key))
+            .collect();
+        sections.sort();
+
+        let archive = build_export_zip(&export).unwrap();
+        let mut zip = zip::ZipArchive::new(Cursor::new(archive)).unwrap();
+        let mut files: Vec<String> = zip.file_names().map(String::from).collect();
+        files.sort();
+        assert_eq!(files, sections);
+
+        let mut profile = String::new();
+        zip.by_name("profile.json").unwrap().read_to_string(&mut profile).unwrap();
+        let profile: serde_json::Value = serde_json::from_str(&profile).unwrap();
+        assert_eq!(profile["totp_enabled"], true);
+    }
+
+    #[test]
+    fn export_leaves_out_secrets() {
+        let json = serde_json::to_string(&export()).unwrap();
+        for field in ["password", "key_hash", "totp_secret", "code_hash", "token"] {
+            assert!(!json.contains(field), "export contains {}", field);
+        }
+    }
+}
@@ -2,8 +2,24 @@ use std::sync::Arc;
 use redis::Client;
 use sqlx::{Pool, Postgres, FromRow};
 use serde::{Deserialize, Serialize};
-use chrono::{NaiveDateTime, Utc, DateTime};
+use chrono::{NaiveDate, NaiveDateTime, Utc, DateTime};
 use uuid::Uuid;
+use utoipa::{IntoParams, ToSchema};
+use validator::Validate;
+use sticker_types::validation::{
+    normalized_username, sticker_url_lengths, trimmed, username_charset, USERNAME_MAX_CHARS, USERNAME_MIN_CHARS,
+};
+
+const MAX_COLLECTION_NAME_CHARS: u64 = 100;
+pub const MAX_COLLECTION_STICKERS: u64 = 1000; // a reorder lists them all in one body
+
+// API bodies live in the sticker-types crate so clients can share them
+pub use sticker_types::{
+    ClearHistoryRequest, DailyActivity, EmotionCount, Favorite, FavoriteRequest, FeedbackAction,
+    FeedbackRequest, HistoryItem, HistoryRequest, HistoryResponse, LoginRequest, MfaLoginRequest, RecommendRequest,
+    RecommendResponse, RegisterRequest, TopSticker, TopStickerRequest, TrendingRequest, TrendingResponse,
+    UpdatePreferencesRequest, UserAnalytics, UserPreferences,
+};
 
 
 #[derive(Clone)]
@@ -12,21 +28,124 @@ pub struct DatabaseConnection {
     pub redis: Arc<Client>,
 }
 
-#[derive(Debug, FromRow, Serialize)]
+// Never serialized directly; use UserDto for anything leaving the server
+#[derive(Debug, FromRow)]
 pub struct User {
     pub id: Uuid,
     pub username: String,
     pub password_hash: String,
+    pub status: String,
+    pub status_reason: Option<String>,
+    pub totp_enabled: bool,
+    pub email: Option<String>,
 }
 
-// #[derive(Debug, FromRow, Serialize)]
-// pub struct Admin {
-//     pub id: Uuid,
-//     pub username: String,
-//     pub password_hash: String,
-//     pub last_login: Option<NaiveDateTime>,
-//     pub failed_attempts: i32,
-// }
+// Stored as text in users.status
+#[derive(Debug, Clone, Copy, PartialEq, Serialize, Deserialize, ToSchema)]
+#[serde(rename_all = "lowercase")]
+pub enum UserStatus {
+    Active,
+    Suspended,
+    Deleted, // soft-deleted, restorable until the restore window ends
+}
+
+impl UserStatus {
+    pub fn as_str(&self) -> &'static str {
+        match self {
+            UserStatus::Active => "active",
+            UserStatus::Suspended => "suspended",
+            UserStatus::Deleted => "deleted",
+        }
+    }
+
+    pub fn parse(value: &str) -> Option<Self> {
+        match value {
+            "active" => Some(UserStatus::Active),
+            "suspended" => Some(UserStatus::Suspended),
+            "deleted" => Some(UserStatus::Deleted),
+            _ => None,
+        }
+    }
+}
+
+#[derive(Debug, FromRow, Serialize, ToSchema)]
+pub struct UserDto {
+    pub id: Uuid,
+    pub username: String,
+    pub status: String,
+}
+
+impl From<User> for UserDto {
+    fn from(user: User) -> Self {
+        UserDto {
+            id: user.id,
+            username: user.username,
+            status: user.status,
+        }
+    }
+}
+
+#[derive(Deserialize, IntoParams)]
+#[into_params(parameter_in = Query)]
+pub struct UserListQuery {
+    pub page: Option<i64>,
+    pub per_page: Option<i64>,
+    pub q: Option<String>,    // username prefix, case-insensitive
+    pub sort: Option<String>, // "username" or "-username"
+    pub status: Option<UserStatus>,
+}
+
+#[derive(Serialize, ToSchema)]
+pub struct UserListResponse {
+    pub users: Vec<UserDto>,
+    pub total: i64,
+    pub page: i64,
+    pub per_page: i64,
+}
+
+#[derive(Debug, FromRow, Serialize, ToSchema)]
+pub struct UserDetail {
+    pub id: Uuid,
+    pub username: String,
+    pub status: String,
+    pub status_reason: Option<String>,
+    pub status_changed_at: Option<NaiveDateTime>,
+    pub deleted_at: Option<NaiveDateTime>,
+    pub store_input_text: bool,
+    pub totp_enabled: bool,
+    pub session_count: i64,
+    pub active_session_count: i64,
+    pub interaction_count: i64,
+    pub last_interaction_at: Option<NaiveDateTime>,
+}
+
+#[derive(Debug, FromRow)]
+pub struct Admin {
+    pub id: Uuid,
+    pub username: String,
+    pub password_hash: String,
+    pub totp_enabled: bool,
+}
+
+#[derive(Deserialize, ToSchema)]
+pub struct StatusChangeRequest {
+    pub reason: Option<String>,
+}
+
+#[derive(Deserialize, ToSchema)]
+pub struct ImpersonateRequest {
+    pub reason: String, // required; kept in impersonation_log
+}
+
+#[derive(Debug, FromRow, Serialize, ToSchema)]
+pub struct ImpersonationRecord {
+    pub id: Uuid,
+    pub admin_id: Uuid,
+    pub user_id: Uuid,
+    pub reason: String,
+    pub started_at: NaiveDateTime,
+    pub expires_at: NaiveDateTime,
+}
 
 #[allow(dead_code)]
 #[derive(Debug, FromRow)]
@@ -38,10 +157,11 @@ pub struct Session {
     pub expires_at: NaiveDateTime,
 }
 
-#[derive(Debug, FromRow, Serialize)]
+#[derive(Debug, FromRow, Serialize, ToSchema)]
 pub struct Interaction {
     pub id: Uuid,
     pub user_id: Uuid,
+    pub recommendation_id: Option<Uuid>,
     pub input_text: String,
     pub detected_emotion: String,
     pub sticker_url: String,
@@ -50,38 +170,29 @@ pub struct Interaction {
 
 
 
-#[derive(Deserialize)]
-pub struct LoginRequest {
-    pub username: String,
-    pub password: String,
-}
-
-#[derive(Deserialize, Debug)]
-pub struct RegisterRequest {
-    pub username: String,
-    pub password: String,
-}
-
-#[derive(Deserialize, Debug)]
-pub struct RecommendRequest {
-    pub username: String,
-    pub input_text: String,
+#[derive(Serialize, Deserialize, Clone)]
+pub struct TrendingCacheEntry {
+    pub provider: String,
+    pub rating: String,
+    pub fetched_at: DateTime<Utc>,
+    pub sticker_urls: Vec<String>,
 }
 
-#[derive(Deserialize, Debug)]
-pub struct TrendingRequest {
-    pub username: String,
-}
-
-#[derive(Serialize, Clone)]
-pub struct TrendingResponse {
-    pub sticker_urls: Vec<String>, // Changed from sticker_url: String
+// Guard for the per-rating trending refresh lock; dropping it releases the lock
+pub struct TrendingRefreshLock {
+    pub redis: Arc<Client>,
+    pub key: String,
+    pub token: String,
 }
 
-#[derive(Serialize, Clone)]
-pub struct RecommendResponse {
+#[allow(dead_code)]
+#[derive(Debug, FromRow)]
+pub struct Recommendation {
+    pub id: Uuid,
+    pub user_id: Uuid,
     pub detected_emotion: String,
-    pub sticker_urls: Vec<String>, // Changed from sticker_url: String
+    pub sticker_urls: Vec<String>,
+    pub created_at: NaiveDateTime,
 }
 
 // #[derive(Clone)]
@@ -91,22 +202,73 @@ pub struct RecommendResponse {
 //     pub credentials: BearerAuth,
 // }
 
-#[derive(Debug, FromRow, Serialize)]
+#[derive(Debug, FromRow, Serialize, ToSchema)]
 pub struct StickerMetric {
     pub id: Uuid,
     pub user_id: Uuid,
     pub sticker_url: String,
     pub usage_count: i32,
     pub last_used: NaiveDateTime,
+    pub shown_count: i32,
+    pub last_shown: Option<NaiveDateTime>,
+    pub rating: i16,
 }
 
-// #[derive(Deserialize)]
-// pub struct FavoriteRequest {
-//     pub sticker_url: String,
-// }
+#[derive(Debug, FromRow)]
+pub struct Collection {
+    pub id: Uuid,
+    pub name: String,
+    pub created_at: NaiveDateTime,
+}
+
+#[derive(Debug, FromRow)]
+pub struct CollectionItem {
+    pub collection_id: Uuid,
+    pub sticker_url: String,
+}
+
+#[derive(Serialize, ToSchema)]
+pub struct CollectionResponse {
+    pub id: Uuid,
+    pub name: String,
+    pub created_at: NaiveDateTime,
+    pub sticker_urls: Vec<String>, // in the user's chosen order
+}
 
-#[derive(Deserialize, Debug)]
+#[derive(Debug, PartialEq)]
+pub enum CollectionItemAdded {
+    Added, // also when the sticker was already in the collection
+    Full,
+    NotFound,
+}
+
+#[derive(Deserialize, Validate, ToSchema)]
+pub struct CreateCollectionRequest {
+    #[serde(deserialize_with = "trimmed")]
+    #[validate(length(min = 1, max = MAX_COLLECTION_NAME_CHARS, message = "Must be 1-100 characters"))]
+    #[schema(min_length = 1, max_length = 100)]
+    pub name: String,
+}
+
+// Must list every sticker in the collection, in the new order
+#[derive(Deserialize, Validate, ToSchema)]
+pub struct ReorderCollectionRequest {
+    #[validate(
+        length(max = MAX_COLLECTION_STICKERS, message = "Must have at most 1000 stickers"),
+        custom(function = "sticker_url_lengths")
+    )]
+    #[schema(max_items = 1000)]
+    pub sticker_urls: Vec<String>,
+}
+
+#[derive(Deserialize, Debug, Validate, ToSchema)]
 pub struct UpdateUsernameRequest {
+    #[serde(deserialize_with = "normalized_username")]
+    #[validate(
+        length(min = USERNAME_MIN_CHARS, max = USERNAME_MAX_CHARS, message = "Must be 3-32 characters"),
+        custom(function = "username_charset")
+    )]
+    #[schema(min_length = 3, max_length = 32)]
     pub new_username: String,
 }
 
@@ -116,46 +278,363 @@ pub struct ManagementRequest {
     pub password: Option<String>,
 }
 
-#[derive(Serialize)]
-pub struct HistoryItem {
-    pub input_text: String,
-    pub detected_emotion: String,
-    pub sticker_url: Vec<String>,
-    pub created_at: DateTime<Utc>,
-}
-
-#[derive(Serialize)]
-pub struct HistoryResponse {
-    pub history: Vec<HistoryItem>,
-}
-
 #[derive(sqlx::FromRow)]
 pub struct DbHistoryItem {
+    pub id: Uuid,
     pub input_text: String,
     pub detected_emotion: String,
     pub sticker_url: String,
 	pub created_at: NaiveDateTime,
 }
 
-#[derive(Deserialize, Debug)]
-pub struct HistoryRequest {
-    pub username: String,
+// Filters for a single page of history, already validated by the route
+#[derive(Debug)]
+pub struct HistoryQuery {
+    pub cursor: Option<HistoryCursor>,
+    pub limit: i64,
+    pub emotion: Option<String>,
+    pub from: Option<NaiveDateTime>,
+    pub to: Option<NaiveDateTime>,
+    pub q: Option<String>,
+}
+
+// Keyset position in the (created_at DESC, id DESC) ordering, encoded as "<micros>_<id>"
+#[derive(Debug, Clone, Copy)]
+pub struct HistoryCursor {
+    pub created_at: NaiveDateTime,
+    pub id: Uuid,
+}
+
+impl HistoryCursor {
+    pub fn encode(&self) -> String {
+        format!("{}_{}", self.created_at.and_utc().timestamp_micros(), self.id)
+    }
+
+    pub fn decode(cursor: &str) -> Option<Self> {
+        let (micros, id) = cursor.split_once('_')?;
+        let created_at = DateTime::from_timestamp_micros(micros.parse().ok()?)?.naive_utc();
+        let id = Uuid::parse_str(id).ok()?;
+        Some(Self { created_at, id })
+    }
 }
 
 #[derive(sqlx::FromRow)]
 pub struct TopStickerItem {
-    pub input_text: String,
     pub sticker_url: String,
     pub usage_count: i64,
+    pub last_used: NaiveDateTime,
 }
-#[derive(Serialize)]
-pub struct TopSticker {
-    pub input_text: String,
-    pub sticker_url: String,
-    pub usage_count: u32,
+
+#[derive(Deserialize, IntoParams)]
+#[into_params(parameter_in = Query)]
+pub struct ExportQuery {
+    pub format: Option<String>, // "json" (default) or "zip"
 }
 
-#[derive(serde::Deserialize)]
-pub struct TopStickerRequest {
+#[derive(Debug, FromRow, Serialize, ToSchema)]
+pub struct ExportProfile {
+    pub id: Uuid,
     pub username: String,
-}
\ No newline at end of file
+    pub store_input_text: bool,
+    pub totp_enabled: bool, // whether 2FA is on; the secret and recovery codes are never exported
+}
+
+// Session metadata only; tokens are never exported
+#[derive(Debug, FromRow, Serialize, ToSchema)]
+pub struct ExportSession {
+    pub id: Uuid,
+    pub expires_at: NaiveDateTime,
+}
+
+#[derive(Debug, FromRow, Serialize, ToSchema)]
+pub struct ExportFeedback {
+    pub recommendation_id: Uuid,
+    pub sticker_url: String,
+    pub action: String,
+    pub created_at: NaiveDateTime,
+}
+
+#[derive(Serialize, ToSchema)]
+pub struct UserExport {
+    pub exported_at: DateTime<Utc>,
+    pub profile: ExportProfile,
+    pub history: Vec<Interaction>,
+    pub sticker_metrics: Vec<StickerMetric>,
+    pub sessions: Vec<ExportSession>,
+    pub favorites: Vec<Favorite>,
+    pub collections: Vec<CollectionResponse>,
+    pub feedback: Vec<ExportFeedback>,
+    pub api_keys: Vec<ApiKey>, // metadata only; key hashes are never exported
+    pub identities: Vec<OidcIdentity>,
+    pub audit_events: Vec<AuditEvent>, // events the user performed or that targeted them
+}
+
+#[derive(Deserialize, IntoParams)]
+#[into_params(parameter_in = Query)]
+pub struct AdminAnalyticsQuery {
+    pub days: Option<i64>,  // look-back window, default 30
+    pub limit: Option<i64>, // for top-N lists
+}
+
+#[derive(Debug, FromRow, Serialize, ToSchema)]
+pub struct ActivityDay {
+    pub day: NaiveDate,
+    pub active_users: i64,
+    pub recommendations: i64,
+    pub cache_hits: i64,
+    pub cache_misses: i64,
+    pub signups: i64,
+}
+
+#[derive(Debug, FromRow, Serialize, ToSchema)]
+pub struct WeeklyActiveUsers {
+    pub week_start: NaiveDate,
+    pub active_users: i64,
+}
+
+#[derive(Serialize, ToSchema)]
+pub struct ActivityResponse {
+    pub days: i64,
+    pub daily: Vec<ActivityDay>,
+    pub weekly_active_users: Vec<WeeklyActiveUsers>,
+    pub total_recommendations: i64,
+    pub total_signups: i64,
+    pub cache_hit_ratio: f64,
+}
+
+#[derive(Debug, FromRow, Serialize, ToSchema)]
+pub struct GlobalSticker {
+    pub sticker_url: String,
+    pub picks: i64,
+}
+
+#[derive(Debug, FromRow, Serialize, ToSchema)]
+pub struct UpstreamStat {
+    pub service: String,
+    pub requests: i64,
+    pub errors: i64,
+    pub error_rate: f64,
+}
+
+// Row for audit_events. diff holds changed fields as {"field": {"old": .., "new": ..}},
+// or extra context for events that don't change anything (e.g. a failed login).
+#[derive(Debug, FromRow, Serialize, ToSchema)]
+pub struct AuditEvent {
+    pub id: Uuid,
+    pub occurred_at: NaiveDateTime,
+    pub actor_type: String, // "user", "admin" or "anonymous"
+    pub actor_id: Option<Uuid>,
+    pub target_type: Option<String>,
+    pub target_id: Option<Uuid>,
+    pub action: String,
+    pub ip: Option<String>,
+    pub user_agent: Option<String>,
+    #[schema(value_type = Option<Object>)]
+    pub diff: Option<serde_json::Value>,
+}
+
+#[derive(Debug)]
+pub struct NewAuditEvent {
+    pub action: String,
+    pub actor_type: String,
+    pub actor_id: Option<Uuid>,
+    pub target_type: Option<String>,
+    pub target_id: Option<Uuid>,
+    pub ip: Option<String>,
+    pub user_agent: Option<String>,
+    pub diff: Option<serde_json::Value>,
+}
+
+impl NewAuditEvent {
+    pub fn actor(mut self, actor_type: &str, actor_id: Uuid) -> Self {
+        self.actor_type = actor_type.to_string();
+        self.actor_id = Some(actor_id);
+        self
+    }
+
+    pub fn target(mut self, target_type: &str, target_id: Uuid) -> Self {
+        self.target_type = Some(target_type.to_string());
+        self.target_id = Some(target_id);
+        self
+    }
+
+    pub fn diff(mut self, diff: serde_json::Value) -> Self {
+        self.diff = Some(diff);
+        self
+    }
+}
+
+#[derive(Deserialize, IntoParams)]
+#[into_params(parameter_in = Query)]
+pub struct AuditQuery {
+    pub actor_id: Option<Uuid>,
+    pub target_id: Option<Uuid>,
+    pub action: Option<String>,
+    pub from: Option<DateTime<Utc>>,
+    pub to: Option<DateTime<Utc>>,
+    pub page: Option<i64>,
+    pub per_page: Option<i64>,
+}
+
+#[derive(Debug, FromRow, Serialize, ToSchema)]
+pub struct ApiKey {
+    pub id: Uuid,
+    pub name: String,
+    pub prefix: String, // first characters of the key, for telling keys apart
+    pub scopes: Vec<String>,
+    pub created_at: NaiveDateTime,
+    pub last_used_at: Option<NaiveDateTime>,
+    pub expires_at: Option<NaiveDateTime>,
+    pub revoked_at: Option<NaiveDateTime>,
+}
+
+// What the auth middleware needs from a live key
+#[derive(Debug, FromRow)]
+pub struct ApiKeyAuth {
+    pub id: Uuid,
+    pub user_id: Uuid,
+    pub scopes: Vec<String>,
+}
+
+#[derive(Deserialize, ToSchema)]
+pub struct CreateApiKeyRequest {
+    pub name: String,
+    pub scopes: Vec<String>,
+    pub expires_in_days: Option<i64>, // None never expires
+}
+
+#[derive(Debug, FromRow, Serialize, ToSchema)]
+pub struct OidcIdentity {
+    pub provider: String,
+    pub subject: String,
+    pub email: Option<String>,
+    pub created_at: NaiveDateTime,
+    pub last_login_at: Option<NaiveDateTime>,
+}
+
+// Kept in Redis between the authorization redirect and the callback
+#[derive(Serialize, Deserialize)]
+pub struct OidcState {
+    pub provider: String,
+    pub code_verifier: String,
+    pub nonce: String,
+    pub link_user_id: Option<Uuid>, // set when a logged-in user is linking a provider
+    #[serde(default)]
+    pub link_session_id: Option<Uuid>, // the session that started the link, checked again on confirm
+}
+
+// A verified provider login waiting for the linking user to confirm it from the same session
+#[derive(Serialize, Deserialize)]
+pub struct PendingLink {
+    pub user_id: Uuid,
+    pub session_id: Uuid,
+    pub provider: String,
+    pub subject: String,
+    pub email: Option<String>,
+}
+
+#[derive(Deserialize, ToSchema)]
+pub struct ConfirmLinkRequest {
+    pub link_code: String, // from the #link_code=... the callback redirected to
+}
+
+#[derive(Deserialize, IntoParams)]
+#[into_params(parameter_in = Query)]
+pub struct OidcCallbackQuery {
+    pub code: Option<String>,
+    pub state: Option<String>,
+    pub error: Option<String>,
+    pub error_description: Option<String>,
+}
+
+#[derive(Debug, Deserialize)]
+pub struct IdTokenClaims {
+    pub sub: String,
+    pub nonce: Option<String>,
+    pub email: Option<String>,
+    pub preferred_username: Option<String>,
+}
+
+// Users and admins share the TOTP columns and the recovery code table
+#[derive(Debug, Clone, Copy, PartialEq, Serialize, Deserialize)]
+#[serde(rename_all = "lowercase")]
+pub enum AccountKind {
+    User,
+    Admin,
+}
+
+impl AccountKind {
+    pub fn table(self) -> &'static str {
+        match self {
+            AccountKind::User => "users",
+            AccountKind::Admin => "admins",
+        }
+    }
+
+    // Owner column in totp_recovery_codes
+    pub fn owner_column(self) -> &'static str {
+        match self {
+            AccountKind::User => "user_id",
+            AccountKind::Admin => "admin_id",
+        }
+    }
+
+    pub fn as_str(self) -> &'static str {
+        match self {
+            AccountKind::User => "user",
+            AccountKind::Admin => "admin",
+        }
+    }
+}
+
+#[derive(Debug, FromRow)]
+pub struct TotpState {
+    pub totp_secret: Option<String>, // set during enrollment, before totp_enabled
+    pub totp_enabled: bool,
+}
+
+// Kept in Redis between the password check and the second factor
+#[derive(Serialize, Deserialize)]
+pub struct MfaChallenge {
+    pub kind: AccountKind,
+    pub id: Uuid,
+    pub enrolling: bool, // admin without 2FA: the code confirms a new secret
+    pub provider: Option<String>, // OIDC provider when the first factor was an external login
+}
+
+#[derive(Deserialize, ToSchema)]
+pub struct TotpCodeRequest {
+    pub code: String,
+}
+
+#[derive(Debug, FromRow)]
+pub struct SigningKeyRow {
+    pub kid: String,
+    pub algorithm: String,
+    pub private_key: String,
+    pub public_jwk: serde_json::Value,
+    pub sign_from: NaiveDateTime,
+}
+
+#[derive(Deserialize, ToSchema)]
+pub struct ChangePasswordRequest {
+    pub current_password: Option<String>, // may be omitted by accounts created through OIDC
+    pub new_password: String,
+}
+
+#[derive(Deserialize, ToSchema)]
+pub struct UpdateEmailRequest {
+    pub email: Option<String>, // None removes the address
+    pub current_password: Option<String>,
+}
+
+#[derive(Deserialize, ToSchema)]
+pub struct ForgotPasswordRequest {
+    pub email: String,
+}
+
+#[derive(Deserialize, ToSchema)]
+pub struct ResetPasswordRequest {
+    pub token: String,
+    pub new_password: String,
+}
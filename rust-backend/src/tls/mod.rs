@@ -0,0 +1,273 @@
+use crate::configs::tls::TlsConfig;
+use notify::{RecursiveMode, Watcher};
+use rustls::crypto::{ring, CryptoProvider};
+use rustls::pki_types::pem::PemObject;
+use rustls::pki_types::{CertificateDer, PrivateKeyDer};
+use rustls::server::{ClientHello, ResolvesServerCert};
+use rustls::sign::CertifiedKey;
+use rustls::ServerConfig;
+use std::path::{Path, PathBuf};
+use std::sync::mpsc;
+use std::sync::{Arc, RwLock};
+use std::time::Duration;
+
+// Renewals tend to touch the cert and key separately; wait for the writes to settle
+const RELOAD_SETTLE: Duration = Duration::from_millis(500);
+
+// Hands every handshake the current certificate, which the watcher swaps on renewal
+#[derive(Debug)]
+pub struct ReloadingCert {
+    cert_path: PathBuf,
+    key_path: PathBuf,
+    provider: Arc<CryptoProvider>,
+    current: RwLock<Arc<CertifiedKey>>,
+}
+
+impl ReloadingCert {
+    fn load(cert_path: &Path, key_path: &Path, provider: &CryptoProvider) -> Result<CertifiedKey, String> {
+        let chain = CertificateDer::pem_file_iter(cert_path)
+            .and_then(|certs| certs.collect::<Result<Vec<_>, _>>())
+            .map_err(|e| format!("Failed to read certificates from {}: {}", cert_path.display(), e))?;
+        if chain.is_empty() {
+            return Err(format!("No certificates found in {}", cert_path.display()));
+        }
+        let key = PrivateKeyDer::from_pem_file(key_path)
+            .map_err(|e| format!("Failed to read private key from {}: {}", key_path.display(), e))?;
+        CertifiedKey::from_der(chain, key, provider)
+            .map_err(|e| format!("Certificate {} does not fit key {}: {}", cert_path.display(), key_path.display(), e))
+    }
+
+    // Ok(false) when the files still hold the certificate in service
+    pub fn reload(&self) -> Result<bool, String> {
+        let key = Self::load(&self.cert_path, &self.key_path, &self.provider)?;
+        let mut current = self.current.write().unwrap_or_else(|e| e.into_inner());
+        if current.cert == key.cert {
+            return Ok(false);
+        }
+        *current = Arc::new(key);
+        Ok(true)
+    }
+}
+
+impl ResolvesServerCert for ReloadingCert {
+    fn resolve(&self, _client_hello: ClientHello<'_>) -> Option<Arc<CertifiedKey>> {
+        Some(self.current.read().unwrap_or_else(|e| e.into_inner()).clone())
+    }
+}
+
+// Loads the certificate and starts watching it. Fails if the files can't be used at startup;
+// later failures are logged and the previous certificate stays in service.
+pub fn server_config(config: &TlsConfig) -> Result<ServerConfig, String> {
+    let (Some(cert_path), Some(key_path)) = (&config.cert_path, &config.key_path) else {
+        return Err("TLS_CERT_PATH and TLS_KEY_PATH are not set".to_string());
+    };
+    let provider = Arc::new(ring::default_provider());
+    let initial = ReloadingCert::load(cert_path, key_path, &provider)?;
+    let resolver = Arc::new(ReloadingCert {
+        cert_path: cert_path.clone(),
+        key_path: key_path.clone(),
+        provider: provider.clone(),
+        current: RwLock::new(Arc::new(initial)),
+    });
+    watch(resolver.clone())?;
+
+    // actix adds the h2 and http/1.1 ALPN protocols when binding
+    ServerConfig::builder_with_provider(provider)
+        .with_safe_default_protocol_versions()
+        .map_err(|e| format!("Failed to configure TLS: {}", e))
+        .map(|builder| builder.with_no_client_auth().with_cert_resolver(resolver))
+}
+
+// Watches the parent directories rather than the files, so renewals that swap files by
+// rename or symlink (certbot, Kubernetes secrets) are picked up too. Unrelated changes in
+// those directories only cost a re-read.
+fn watch(resolver: Arc<ReloadingCert>) -> Result<(), String> {
+    let (tx, rx) = mpsc::channel();
+    let mut watcher = notify::recommended_watcher(tx).map_err(|e| format!("Failed to watch certificates: {}", e))?;
+    let mut dirs = vec![parent_dir(&resolver.cert_path), parent_dir(&resolver.key_path)];
+    dirs.dedup();
+    for dir in &dirs {
+        watcher
+            .watch(dir, RecursiveMode::NonRecursive)
+            .map_err(|e| format!("Failed to watch {}: {}", dir.display(), e))?;
+    }
+
+    std::thread::spawn(move || {
+        // Owned by the thread so the watch lives as long as the server
+        let _watcher = watcher;
+        while let Ok(event) = rx.recv() {
+            if let Err(e) = event {
+                log::warn!("Certificate watcher error: {}", e);
+                continue;
+            }
+            settle(&rx, RELOAD_SETTLE);
+            match resolver.reload() {
+                Ok(false) => {}
+                Ok(true) => log::info!("Reloaded TLS certificate from {}", resolver.cert_path.display()),
+                Err(e) => log::error!("Keeping the current TLS certificate: {}", e),
+            }
+        }
+    });
+    Ok(())
+}
+
+// Swallows further events until none arrive for `quiet`, so a burst of writes costs one reload.
+// Returns how many events were swallowed.
+fn settle<T>(rx: &mpsc::Receiver<T>, quiet: Duration) -> usize {
+    let mut swallowed = 0;
+    while rx.recv_timeout(quiet).is_ok() {
+        swallowed += 1;
+    }
+    swallowed
+}
+
+fn parent_dir(path: &Path) -> PathBuf {
+    match path.parent() {
+        Some(parent) if !parent.as_os_str().is_empty() => parent.to_path_buf(),
+        _ => PathBuf::from("."),
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use std::fs;
+    use std::time::Instant;
+    use uuid::Uuid;
+
+    // Self-signed P-256 pairs for localhost, generated with openssl for these tests only
+    const CERT_A: &str = include_str!("testdata/a.crt");
+    const KEY_A: &str = include_str!("testdata/a.key");
+    const CERT_B: &str = include_str!("testdata/b.crt");
+    const KEY_B: &str = include_str!("testdata/b.key");
+
+    // A fresh directory holding cert.pem and key.pem, removed on drop
+    struct CertDir(PathBuf);
+
+    impl CertDir {
+        fn new(cert: &str, key: &str) -> Self {
+            let dir = std::env::temp_dir().join(format!("tls-test-{}", Uuid::new_v4()));
+            fs::create_dir_all(&dir).unwrap();
+            let dir = CertDir(dir);
+            dir.write(cert, key);
+            dir
+        }
+
+        fn write(&self, cert: &str, key: &str) {
+            fs::write(self.cert(), cert).unwrap();
+            fs::write(self.key(), key).unwrap();
+        }
+
+        fn cert(&self) -> PathBuf {
+            self.0.join("cert.pem")
+        }
+
+        fn key(&self) -> PathBuf {
+            self.0.join("key.pem")
+        }
+
+        fn resolver(&self) -> Arc<ReloadingCert> {
+            let provider = Arc::new(ring::default_provider());
+            let initial = ReloadingCert::load(&self.cert(), &self.key(), &provider).unwrap();
+            Arc::new(ReloadingCert {
+                cert_path: self.cert(),
+                key_path: self.key(),
+                provider,
+                current: RwLock::new(Arc::new(initial)),
+            })
+        }
+    }
+
+    impl Drop for CertDir {
+        fn drop(&mut self) {
+            let _ = fs::remove_dir_all(&self.0);
+        }
+    }
+
+    fn der(pem: &str) -> CertificateDer<'static> {
+        CertificateDer::from_pem_slice(pem.as_bytes()).unwrap()
+    }
+
+    fn serving(resolver: &ReloadingCert) -> CertificateDer<'static> {
+        resolver.current.read().unwrap().cert[0].clone()
+    }
+
+    #[test]
+    fn reload_swaps_in_a_renewed_certificate() {
+        let dir = CertDir::new(CERT_A, KEY_A);
+        let resolver = dir.resolver();
+        assert_eq!(serving(&resolver), der(CERT_A));
+        assert_eq!(resolver.reload(), Ok(false));
+
+        dir.write(CERT_B, KEY_B);
+        assert_eq!(resolver.reload(), Ok(true));
+        assert_eq!(serving(&resolver), der(CERT_B));
+        assert_eq!(resolver.reload(), Ok(false));
+    }
+
+    #[test]
+    fn reload_keeps_the_old_certificate_when_the_new_one_is_unusable() {
+        let dir = CertDir::new(CERT_A, KEY_A);
+        let resolver = dir.resolver();
+
+        // Halfway through a renewal: new certificate, old key
+        dir.write(CERT_B, KEY_A);
+        let err = resolver.reload().unwrap_err();
+        assert!(err.contains("does not fit key"), "{}", err);
+        assert_eq!(serving(&resolver), der(CERT_A));
+
+        dir.write("not a certificate", KEY_B);
+        let err = resolver.reload().unwrap_err();
+        assert!(err.contains("No certificates found"), "{}", err);
+        assert_eq!(serving(&resolver), der(CERT_A));
+
+        dir.write(CERT_B, "not a key");
+        let err = resolver.reload().unwrap_err();
+        assert!(err.contains("Failed to read private key"), "{}", err);
+        assert_eq!(serving(&resolver), der(CERT_A));
+
+        fs::remove_file(dir.cert()).unwrap();
+        assert!(resolver.reload().is_err());
+        assert_eq!(serving(&resolver), der(CERT_A));
+    }
+
+    #[test]
+    fn settle_waits_out_a_burst_of_events() {
+        let (tx, rx) = mpsc::channel();
+        let writer = std::thread::spawn(move || {
+            for _ in 0..3 {
+                tx.send(()).unwrap();
+                std::thread::sleep(Duration::from_millis(50));
+            }
+            // Stay connected, like the watcher, so only the quiet period ends the wait
+            std::thread::sleep(Duration::from_millis(500));
+        });
+        let started = Instant::now();
+        assert_eq!(settle(&rx, Duration::from_millis(200)), 3);
+        // The last event came ~100ms in, then 200ms of quiet
+        assert!(started.elapsed() >= Duration::from_millis(300));
+        writer.join().unwrap();
+
+        let (_tx, rx) = mpsc::channel::<()>();
+        assert_eq!(settle(&rx, Duration::from_millis(10)), 0);
+    }
+
+    #[test]
+    fn watcher_reloads_once_a_renewal_settles() {
+        let dir = CertDir::new(CERT_A, KEY_A);
+        let resolver = dir.resolver();
+        watch(resolver.clone()).unwrap();
+
+        // Certificate and key written separately, as renewals tend to
+        fs::write(dir.cert(), CERT_B).unwrap();
+        std::thread::sleep(Duration::from_millis(100));
+        assert_eq!(serving(&resolver), der(CERT_A), "reloaded before the writes settled");
+        fs::write(dir.key(), KEY_B).unwrap();
+
+        let deadline = Instant::now() + Duration::from_secs(10);
+        while serving(&resolver) != der(CERT_B) {
+            assert!(Instant::now() < deadline, "certificate was not reloaded");
+            std::thread::sleep(Duration::from_millis(50));
+        }
+    }
+}
@@ -0,0 +1,150 @@
+// Drives the real routes over HTTP through sticker-client, so the server and the client
+// can't drift apart unnoticed
+use actix_web::middleware::from_fn;
+use actix_web::{web, App, HttpServer};
+use reqwest::StatusCode;
+use rust_backend::configs::accounts::AccountConfig;
+use rust_backend::configs::api_keys::ApiKeyConfig;
+use rust_backend::configs::email::EmailConfig;
+use rust_backend::configs::limits::RequestLimits;
+use rust_backend::configs::oidc::OidcConfig;
+use rust_backend::configs::password::PasswordPolicy;
+use rust_backend::configs::rate_limit::RateLimitConfig;
+use rust_backend::configs::security_headers::SecurityHeadersConfig;
+use rust_backend::configs::totp::TotpConfig;
+use rust_backend::configs::trending::TrendingConfig;
+use rust_backend::middleware::auth::AuthConfig;
+use rust_backend::structs::database_structs::DatabaseConnection;
+use rust_backend::{email, middleware, routes};
+use sticker_client::types::{RegisterRequest, UpdatePreferencesRequest};
+use sticker_client::{Error, LoginOutcome, RetryPolicy, StickerClient};
+use std::sync::Arc;
+use uuid::Uuid;
+
+// The app as init_app builds it, on a free local port, with a client pointed at it
+fn serve(db: DatabaseConnection) -> StickerClient {
+    if std::env::var("JWT_SECRET").is_err() {
+        std::env::set_var("JWT_SECRET", "integration-test-secret");
+    }
+    let auth_config = AuthConfig::new();
+    let email_config = EmailConfig::new();
+    let email_sender = email::sender_from_config(&email_config);
+    let request_limits = RequestLimits::new();
+    let trending_config = TrendingConfig::new();
+    let account_config = AccountConfig::new();
+    let api_key_config = ApiKeyConfig::new();
+    let oidc_config = OidcConfig::new();
+    let totp_config = TotpConfig::new();
+    let rate_limit_config = RateLimitConfig::new();
+    let security_headers_config = SecurityHeadersConfig::new();
+    let password_policy = PasswordPolicy::new();
+
+    let server = HttpServer::new(move || {
+        App::new()
+            .wrap(from_fn(middleware::rate_limit::by_ip))
+            .wrap(from_fn(middleware::security_headers::set_headers))
+            .app_data(middleware::validation::json_config(request_limits.json_payload_bytes))
+            .app_data(web::Data::new(db.clone()))
+            .app_data(web::Data::new(db.redis.clone()))
+            .app_data(web::Data::new(auth_config.clone()))
+            .app_data(web::Data::new(trending_config.clone()))
+            .app_data(web::Data::new(account_config.clone()))
+            .app_data(web::Data::new(api_key_config.clone()))
+            .app_data(web::Data::new(oidc_config.clone()))
+            .app_data(web::Data::new(totp_config.clone()))
+            .app_data(web::Data::new(rate_limit_config.clone()))
+            .app_data(web::Data::new(security_headers_config.clone()))
+            .app_data(web::Data::new(password_policy.clone()))
+            .app_data(web::Data::new(email_config.clone()))
+            .app_data(web::Data::from(email_sender.clone()))
+            .configure(routes::init_api)
+    })
+    .workers(1)
+    .bind(("127.0.0.1", 0))
+    .expect("bind a free port");
+    let address = server.addrs()[0];
+    actix_web::rt::spawn(server.run());
+
+    StickerClient::new(&format!("http://{}", address))
+        .expect("valid base URL")
+        .with_retry(RetryPolicy::none())
+}
+
+// Every query fails, and Redis refuses connections so rate limiting lets everything through
+fn unreachable_db() -> DatabaseConnection {
+    let pool = sqlx::postgres::PgPoolOptions::new()
+        .acquire_timeout(std::time::Duration::from_millis(100))
+        .connect_lazy("postgres://unused@127.0.0.1:1/unused")
+        .expect("valid database URL");
+    let redis = Arc::new(redis::Client::open("redis://127.0.0.1:1").expect("valid Redis URL"));
+    DatabaseConnection { pool, redis }
+}
+
+fn api_error(result: Result<impl std::fmt::Debug, Error>) -> sticker_client::ApiError {
+    match result {
+        Err(Error::Api(e)) => e,
+        other => panic!("expected an API error, got {:?}", other),
+    }
+}
+
+#[actix_web::test]
+async fn errors_reach_the_client_in_its_own_types() {
+    let client = serve(unreachable_db());
+    client.health().await.unwrap();
+
+    // Validation happens before the database is needed
+    let error = api_error(
+        client
+            .register(&RegisterRequest {
+                username: "-x".to_string(),
+                password: "correct-Horse-battery".to_string(),
+                email: None,
+            })
+            .await,
+    );
+    assert_eq!(error.status, StatusCode::BAD_REQUEST);
+    assert!(error.fields.contains_key("username"));
+
+    assert!(client.preferences().await.unwrap_err().is_unauthorized());
+    let client = client.with_token("not-a-token");
+    assert!(client.preferences().await.unwrap_err().is_unauthorized());
+}
+
+// Needs a scratch Postgres: DATABASE_URL=... REDIS_URL=redis://unused cargo test -- --ignored
+#[actix_web::test]
+#[ignore = "needs DATABASE_URL"]
+async fn a_new_account_signs_in_and_keeps_its_settings() {
+    let db = DatabaseConnection::new().await.unwrap();
+    db.init_schema().await.unwrap();
+    let client = serve(db);
+
+    let username = format!("client-{}", &Uuid::new_v4().simple().to_string()[..8]);
+    let password = "correct-Horse-battery";
+    let session = client
+        .register(&RegisterRequest {
+            username: username.to_uppercase(),
+            password: password.to_string(),
+            email: None,
+        })
+        .await
+        .unwrap();
+    assert_eq!(session.username, username);
+    assert_eq!(client.token(), Some(session.token));
+
+    let updated = client
+        .update_preferences(&UpdatePreferencesRequest { store_input_text: false })
+        .await
+        .unwrap();
+    assert!(!updated.store_input_text);
+    client.add_favorite("https://media.example.com/cat.gif").await.unwrap();
+
+    // A fresh sign-in sees the same account
+    client.logout();
+    assert!(client.preferences().await.unwrap_err().is_unauthorized());
+    assert!(client.login(&username, "wrong-Password-1").await.unwrap_err().is_unauthorized());
+    assert!(matches!(client.login(&username, password).await.unwrap(), LoginOutcome::LoggedIn(_)));
+    assert!(!client.preferences().await.unwrap().store_input_text);
+    let favorites = client.favorites().await.unwrap();
+    assert_eq!(favorites.len(), 1);
+    assert_eq!(favorites[0].sticker_url, "https://media.example.com/cat.gif");
+}
@@ -8,6 +8,7 @@ import ProtectedRoute from './components/ProtectedRoute';
 // Lazy load pages and components
 const Login = lazy(() => import('./pages/Login'));
 const Signup = lazy(() => import('./pages/Signup'));
+const OidcCallback = lazy(() => import('./pages/OidcCallback'));
 const Welcome = lazy(() => import('./pages/Welcome'));
 const Profile = lazy(() => import('./components/Profile'));
 const Recommend = lazy(() => import('./components/Recommend'));
@@ -37,6 +38,7 @@ export default function App() {
           <Routes location={location} key={location.pathname}>
           <Route path="/login" element={!token ? <Login /> : <Navigate to="/welcome" />} />
           <Route path="/signup" element={!token ? <Signup /> : <Navigate to="/welcome" />} />
+          <Route path="/oidc/callback" element={<OidcCallback />} />
           <Route path="/welcome" element={token ? <ProtectedRoute><Welcome /> </ProtectedRoute>: <Navigate to="/login" />} />
           <Route path="/home" element={<Navigate to={token ? '/welcome' : '/login'} />} />
           <Route path="/profile" element={<ProtectedRoute><Profile /></ProtectedRoute>} />
@@ -12,49 +12,57 @@ const API_BASE_URL =import.meta.env.VITE_API_URL;
 const History = () => {
 
     const updateUI = useCallback(() => {}, []);
-    const [history, setHistory] = useState([]);
+    const [entries, setEntries] = useState([]);
+    const [nextCursor, setNextCursor] = useState(null);
     const [loading, setLoading] = useState(true);
+    const [loadingMore, setLoadingMore] = useState(false);
     const [error, setError] = useState(null);
-    
-    useEffect(() => {
-        const fetchHistory = async () => {
-            try {
-                const userToken = localStorage.getItem('user_token');
-                const username = localStorage.getItem('username');
-                
-                if (!userToken || !username) {
-                    throw new Error('User not authenticated');
-                }
-                
-                const res = await fetch(`${API_BASE_URL}/v1/user/history`, {
-                    method: 'POST',
-                    headers: {
-                        'Content-Type': 'application/json',
-                        'Authorization': `Bearer ${userToken}`
-                    },
-                    body: JSON.stringify({ username: username })
-                });
-                
-                const data = await res.json();
-                
-                if (res.ok) {
-                    // Process the history data
-                    const processedHistory = processHistory(data.history || []);
-                    setHistory(processedHistory);
-                } else {
-                    setError(data.error || 'Failed to fetch history');
+
+    // Fetches one page of history; with a cursor the page is appended to what is already shown
+    const fetchHistory = useCallback(async (cursor) => {
+        try {
+            const userToken = localStorage.getItem('user_token');
+            const username = localStorage.getItem('username');
+
+            if (!userToken || !username) {
+                throw new Error('User not authenticated');
+            }
+
+            const params = new URLSearchParams({ limit: '100' });
+            if (cursor) {
+                params.set('cursor', cursor);
+            }
+            const res = await fetch(`${API_BASE_URL}/v1/user/history?${params}`, {
+                headers: {
+                    'Authorization': `Bearer ${userToken}`
                 }
-            } catch (err) {
-                setError('An error occurred while fetching history');
-                console.error('History fetch error:', err);
-            } finally {
-                setLoading(false);
+            });
+
+            const data = await res.json();
+
+            if (res.ok) {
+                const page = data.history || [];
+                setEntries(prev => (cursor ? [...prev, ...page] : page));
+                setNextCursor(data.next_cursor || null);
+            } else {
+                setError(data.error || 'Failed to fetch history');
             }
-        };
-        
-        fetchHistory();
+        } catch (err) {
+            setError('An error occurred while fetching history');
+            console.error('History fetch error:', err);
+        }
     }, []);
-    
+
+    useEffect(() => {
+        fetchHistory(null).finally(() => setLoading(false));
+    }, [fetchHistory]);
+
+    const loadMore = async () => {
+        setLoadingMore(true);
+        await fetchHistory(nextCursor);
+        setLoadingMore(false);
+    };
+
     // Process history data to group by input_text, filter GIF URLs, and sort by newest first
     const processHistory = (historyItems) => {
         // Sort items by created_at descending (newest first)
@@ -93,7 +101,9 @@ const History = () => {
             items: groupedHistory[inputText]
         }));
     };
-    
+
+    const history = processHistory(entries);
+
     return (
         <div className="history-bg">
             <SidePanel updateUI={updateUI} />
@@ -152,6 +162,15 @@ const History = () => {
                                     </div>
                                 </motion.div>
                             ))}
+                            {nextCursor && (
+                                <button
+                                    className="history-load-more"
+                                    onClick={loadMore}
+                                    disabled={loadingMore}
+                                >
+                                    {loadingMore ? 'Loading...' : 'Load more'}
+                                </button>
+                            )}
                         </div>
                     )}
                 </motion.div>
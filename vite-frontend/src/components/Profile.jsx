@@ -65,12 +65,13 @@ const Profile = () => {
             const data = await res.json();
 
             if (res.ok) {
-                localStorage.setItem('username', newUsername); // Update local storage
-                setUsername(newUsername); // Update state
+                localStorage.setItem('username', data.username); // Server returns the normalized name
+                setUsername(data.username); // Update state
                 setMessage(''); // Clear message on success
                 setIsEditingUsername(false); // Exit edit mode
             } else {
-                setMessage(data.error || 'Failed to update username.');
+                const fieldErrors = data.fields ? Object.values(data.fields).flat().join('; ') : '';
+                setMessage(fieldErrors ? `${data.error}: ${fieldErrors}` : data.error || 'Failed to update username.');
             }
         } catch (err) {
             setMessage(`Network error: ${err.message}`);
@@ -72,14 +72,9 @@ const Recommend = () => {
             }
 
             const res = await fetch(`${API_BASE_URL}/v1/sticker/dashboard-trending`, {
-                method: 'POST',
                 headers: {
-                    'Content-Type': 'application/json',
                     'Authorization': `Bearer ${userToken}`
-                },
-                body: JSON.stringify({
-                    username: username
-                })
+                }
             });
 
             const data = await res.json();
@@ -14,6 +14,7 @@ const API_BASE_URL = import.meta.env.VITE_API_URL;
 const SearchPage = () => {
     const [searchQuery, setSearchQuery] = useState('');
     const [results, setResults] = useState([]);
+    const [recommendationId, setRecommendationId] = useState(null);
     const [loading, setLoading] = useState(false);
     const [isSearchBarActive, setIsSearchBarActive] = useState(false);
     const [error, setError] = useState(null);
@@ -43,7 +44,6 @@ const SearchPage = () => {
                         'Authorization': `Bearer ${userToken}`,
                     },
                     body: JSON.stringify({
-                        username: username,
                         input_text: query,
                     }),
                 });
@@ -52,6 +52,7 @@ const SearchPage = () => {
 
                 if (res.ok) {
                     setResults(data.sticker_urls || []);
+                    setRecommendationId(data.recommendation_id || null);
                 } else {
                     setError(data.error || 'Search failed');
                 }
@@ -66,6 +67,23 @@ const SearchPage = () => {
         []
     );
 
+    const sendFeedback = useCallback((stickerUrl, action) => {
+        const userToken = localStorage.getItem('user_token');
+        if (!recommendationId || !userToken) return;
+        fetch(`${API_BASE_URL}/v1/sticker/feedback`, {
+            method: 'POST',
+            headers: {
+                'Content-Type': 'application/json',
+                'Authorization': `Bearer ${userToken}`,
+            },
+            body: JSON.stringify({
+                recommendation_id: recommendationId,
+                sticker_url: stickerUrl,
+                action,
+            }),
+        }).catch((err) => console.error('Feedback error:', err));
+    }, [recommendationId]);
+
     const handleSearch = useCallback((e) => {
         e.preventDefault();
         debouncedSearch(searchQuery);
@@ -178,6 +196,7 @@ const SearchPage = () => {
                                         initial={{ opacity: 0, y: 20 }}
                                         animate={{ opacity: 1, y: 0 }}
                                         transition={{ delay: index * 0.1 }}
+                                        onClick={() => sendFeedback(url, 'clicked')}
                                     >
                                         <div className="result-image">
                                             <img src={url} alt="Search result" loading='lazy' />
@@ -1,6 +1,6 @@
 // File: src/pages/Login.jsx
-import React, { useState } from 'react';
-import { useNavigate, Link } from 'react-router-dom';
+import React, { useEffect, useState } from 'react';
+import { useNavigate, useLocation, Link } from 'react-router-dom';
 import { motion } from 'framer-motion';
 import './Auth.css';
 import { useTheme } from '../context/ThemeContext';
@@ -11,9 +11,21 @@ const Login = () => {
     const [username, setUsername] = useState('');
     const [password, setPassword] = useState('');
     const [msg, setMsg] = useState('');
+    const [providers, setProviders] = useState([]);
+    const location = useLocation();
+    // Set when the password (or an external login) was accepted and a 2FA code is needed
+    const [mfaToken, setMfaToken] = useState(location.state?.mfaToken || '');
+    const [code, setCode] = useState('');
     const navigate = useNavigate();
     const API_BASE_URL = import.meta.env.VITE_API_URL;
 
+    useEffect(() => {
+        fetch(`${API_BASE_URL}/v1/auth/oidc/providers`)
+            .then(res => (res.ok ? res.json() : { providers: [] }))
+            .then(data => setProviders(data.providers || []))
+            .catch(() => setProviders([]));
+    }, [API_BASE_URL]);
+
     const handleSubmit = async e => {
         e.preventDefault();
         setMsg('Logging in...');
@@ -24,7 +36,10 @@ const Login = () => {
                 body: JSON.stringify({ username, password })
             });
             const data = await res.json();
-            if (res.ok) {
+            if (res.ok && data.mfa_required) {
+                setMfaToken(data.mfa_token);
+                setMsg('');
+            } else if (res.ok) {
                 localStorage.setItem('user_token', data.token);
                 localStorage.setItem('username', data.username);
                 navigate('/welcome');
@@ -36,6 +51,30 @@ const Login = () => {
         }
     };
 
+    const handleMfaSubmit = async e => {
+        e.preventDefault();
+        setMsg('Checking code...');
+        try {
+            const res = await fetch(`${API_BASE_URL}/v1/auth/login/mfa`, {
+                method: 'POST',
+                headers: { 'Content-Type': 'application/json' },
+                body: JSON.stringify({ mfa_token: mfaToken, code })
+            });
+            const data = await res.json();
+            if (res.ok) {
+                localStorage.setItem('user_token', data.token);
+                localStorage.setItem('username', data.username);
+                navigate('/welcome');
+            } else {
+                // Expired or out of attempts: start over from the password step
+                if (data.error !== 'Invalid code') setMfaToken('');
+                setMsg(data.error || 'Invalid code');
+            }
+        } catch (err) {
+            setMsg(err.message);
+        }
+    };
+
     return (
         <div className="welcome-bg">
             <ThemeToggle />
@@ -57,30 +96,58 @@ const Login = () => {
                 >
                     Login
                 </motion.h2>
-                <motion.form
-                    onSubmit={handleSubmit}
-                    initial={{ y: 20, opacity: 0 }}
-                    animate={{ y: 0, opacity: 1 }}
-                    transition={{ delay: 0.4 }}
-                >
-                    <input
-                        type="text"
-                        placeholder="Username"
-                        name='username'
-                        autoComplete="username"
-                        value={username}
-                        onChange={e => setUsername(e.target.value)}
-                        required
-                    />
-                    <input
-                        type="password"
-                        placeholder="Password"
-                        value={password}
-                        onChange={e => setPassword(e.target.value)}
-                        required
-                    />
-                    <button type="submit" className='button-auth'>Login</button>
-                </motion.form>
+                {mfaToken ? (
+                    <motion.form
+                        onSubmit={handleMfaSubmit}
+                        initial={{ y: 20, opacity: 0 }}
+                        animate={{ y: 0, opacity: 1 }}
+                        transition={{ delay: 0.4 }}
+                    >
+                        <input
+                            type="text"
+                            placeholder="Authenticator or recovery code"
+                            autoComplete="one-time-code"
+                            value={code}
+                            onChange={e => setCode(e.target.value)}
+                            required
+                        />
+                        <button type="submit" className='button-auth'>Verify</button>
+                    </motion.form>
+                ) : (
+                    <motion.form
+                        onSubmit={handleSubmit}
+                        initial={{ y: 20, opacity: 0 }}
+                        animate={{ y: 0, opacity: 1 }}
+                        transition={{ delay: 0.4 }}
+                    >
+                        <input
+                            type="text"
+                            placeholder="Username"
+                            name='username'
+                            autoComplete="username"
+                            value={username}
+                            onChange={e => setUsername(e.target.value)}
+                            required
+                        />
+                        <input
+                            type="password"
+                            placeholder="Password"
+                            value={password}
+                            onChange={e => setPassword(e.target.value)}
+                            required
+                        />
+                        <button type="submit" className='button-auth'>Login</button>
+                    </motion.form>
+                )}
+                {providers.map(name => (
+                    <a
+                        key={name}
+                        href={`${API_BASE_URL}/v1/auth/oidc/${name}/login`}
+                        className="button-auth"
+                    >
+                        Continue with {name}
+                    </a>
+                ))}
                 <motion.p
                     initial={{ opacity: 0 }}
                     animate={{ opacity: 1 }}
@@ -0,0 +1,36 @@
+// File: src/pages/OidcCallback.jsx
+import React, { useEffect, useState } from 'react';
+import { useNavigate, Link } from 'react-router-dom';
+import './Auth.css';
+
+// The backend redirects here with the login result in the URL fragment
+const OidcCallback = () => {
+    const [msg, setMsg] = useState('Signing in...');
+    const navigate = useNavigate();
+
+    useEffect(() => {
+        const params = new URLSearchParams(window.location.hash.slice(1));
+        window.history.replaceState(null, '', window.location.pathname);
+        if (params.get('token')) {
+            localStorage.setItem('user_token', params.get('token'));
+            localStorage.setItem('username', params.get('username'));
+            navigate('/welcome');
+        } else if (params.get('mfa_token')) {
+            navigate('/login', { state: { mfaToken: params.get('mfa_token') } });
+        } else if (params.get('linked')) {
+            navigate('/profile');
+        } else {
+            setMsg(params.get('error') || 'Login failed');
+        }
+    }, [navigate]);
+
+    return (
+        <div className="welcome-bg">
+            <div className="auth-container">
+                <div className="msg">{msg}</div>
+                <Link to="/login" className="link-animate">Back to login</Link>
+            </div>
+        </div>
+    );
+};
+export default OidcCallback;
@@ -9,6 +9,7 @@ import ThemeToggle from '../components/ThemeToggle';
 const SignUp = () => {
     const [username, setUsername] = useState('');
     const [password, setPassword] = useState('');
+    const [email, setEmail] = useState('');
     const [msg, setMsg] = useState('');
     const [theme, setTheme] = useState('dark'); // Default theme
     const navigate = useNavigate();
@@ -46,7 +47,7 @@ const SignUp = () => {
             const res = await fetch(`${API_BASE_URL}/v1/auth/register/user`, {
                 method: 'POST',
                 headers: { 'Content-Type': 'application/json' },
-                body: JSON.stringify({ username, password })
+                body: JSON.stringify({ username, password, email: email || undefined })
             });
             const data = await res.json();
             if (res.ok) {
@@ -54,7 +55,11 @@ const SignUp = () => {
                 localStorage.setItem('username', data.username);
                 navigate('/welcome'); // Redirect to welcome page
             } else {
-                setMsg(data.error || 'Signup failed');
+                // Policy failures list every rule the password breaks; validation failures are per field
+                const problems = data.fields
+                    ? Object.entries(data.fields).map(([field, errors]) => `${field}: ${errors.join(', ')}`)
+                    : data.details || [];
+                setMsg(problems.length ? `${data.error}: ${problems.join('; ')}` : data.error || 'Signup failed');
             }
         } catch (err) {
             setMsg(err.message);
@@ -94,6 +99,14 @@ const SignUp = () => {
                         onChange={e => setUsername(e.target.value)}
                         required
                     />
+                    <input
+                        type="email"
+                        placeholder="Email (optional, for password reset)"
+                        name='email'
+                        value={email}
+                        autoComplete="email"
+                        onChange={e => setEmail(e.target.value)}
+                    />
                     <input
                         type="password"
                         placeholder="Password"